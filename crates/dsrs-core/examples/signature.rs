@@ -3,7 +3,9 @@ use schemars::{JsonSchema, Schema};
 use serde::{Deserialize, Serialize};
 
 use dsrs_core::{
-    primatives::{ChatHistory, History, Signature, ToolCallSet, ToolCalls, ToolSet, Tools},
+    primatives::{
+        ChatHistory, History, Signature, SignatureSchema, ToolCallSet, ToolCalls, ToolSet, Tools,
+    },
     providers::models::{AvailableTool, Message, ToolCall},
 };
 
@@ -57,6 +59,12 @@ pub struct EnhancedSignature {
     instructions: String,
 }
 
+impl Default for EnhancedSignature {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EnhancedSignature {
     pub fn new() -> Self {
         Self {
@@ -125,6 +133,12 @@ pub struct ExplicitPromptSignature {
     instructions: String,
 }
 
+impl Default for ExplicitPromptSignature {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ExplicitPromptSignature {
     pub fn new() -> Self {
         Self {
@@ -141,7 +155,7 @@ impl ExplicitPromptSignature {
     }
 
     // Convert prompt outputs to full outputs
-    fn from_prompt_outputs(
+    fn outputs_from_prompt(
         &self,
         prompt_outputs: PromptOutputs,
         tool_calls: Option<Vec<ToolCall>>,
@@ -223,7 +237,82 @@ impl Signature for ExplicitPromptSignature {
             answer: regular.answer,
             confidence: regular.confidence,
         };
-        self.from_prompt_outputs(prompt_outputs, calls)
+        self.outputs_from_prompt(prompt_outputs, calls)
+    }
+}
+
+/// Derive approach: special fields are marked on the inputs and outputs, and
+/// `#[derive(Signature)]` generates the extraction, filtering and injection above
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+pub struct DerivedInputs {
+    pub query: String,
+    pub context: String,
+    #[signature(history)]
+    pub history: Option<ChatHistory>,
+    #[signature(tools)]
+    pub tools: Option<ToolSet>,
+}
+
+/// Arguments of the search tool offered in `main`
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+pub struct SearchArgs {
+    /// What to search for
+    pub query: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize)]
+pub struct DerivedOutputs {
+    pub answer: String,
+    pub confidence: f64,
+    #[signature(tool_calls)]
+    pub tool_calls: Option<ToolCallSet>,
+}
+
+/// Question answering with derived special field handling
+#[derive(dsrs_core::primatives::Signature)]
+#[signature(name = "DerivedQA", inputs = DerivedInputs, outputs = DerivedOutputs)]
+pub struct DerivedSignature {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+fn main() {
+    let enhanced_inputs = EnhancedSignature::prompt_input_schema();
+    let explicit_outputs = ExplicitPromptSignature::prompt_output_schema();
+
+    println!(
+        "{} prompt input schema:\n{}",
+        EnhancedSignature::new().name(),
+        serde_json::to_string_pretty(&enhanced_inputs).unwrap()
+    );
+    println!(
+        "{} prompt output schema:\n{}",
+        ExplicitPromptSignature::new().name(),
+        serde_json::to_string_pretty(&explicit_outputs).unwrap()
+    );
+
+    let derived = DerivedSignature {
+        instructions: "Answer the user's question based on the provided context.".to_string(),
+    };
+    println!(
+        "{} prompt input schema:\n{}",
+        derived.name(),
+        serde_json::to_string_pretty(&DerivedSignature::prompt_input_schema()).unwrap()
+    );
+
+    let inputs = DerivedInputs {
+        query: "What's new in Rust?".to_string(),
+        context: String::new(),
+        history: None,
+        tools: Some(
+            ToolSet::builder()
+                .tool::<SearchArgs>("search", "Search the web")
+                .build(),
+        ),
+    };
+    for tool in derived.extract_tools(&inputs).unwrap_or_default() {
+        println!("Tool offered to the model: {} ({})", tool.name, tool.desc);
     }
 }
 
@@ -238,7 +327,7 @@ mod tests {
         let schema_json = serde_json::to_value(&schema).unwrap();
 
         // The schema should not contain 'history' or 'tools' fields due to schemars(skip)
-        if let Some(properties) = schema_json.get("object").and_then(|o| o.get("properties")) {
+        if let Some(properties) = schema_json.get("properties") {
             assert!(!properties.as_object().unwrap().contains_key("history"));
             assert!(!properties.as_object().unwrap().contains_key("tools"));
             assert!(properties.as_object().unwrap().contains_key("query"));
@@ -257,7 +346,7 @@ mod tests {
         let output_json = serde_json::to_value(&output_schema).unwrap();
 
         // Input schema should only have query and context
-        if let Some(input_props) = input_json.get("object").and_then(|o| o.get("properties")) {
+        if let Some(input_props) = input_json.get("properties") {
             let props = input_props.as_object().unwrap();
             assert_eq!(props.len(), 2);
             assert!(props.contains_key("query"));
@@ -265,7 +354,7 @@ mod tests {
         }
 
         // Output schema should only have answer and confidence
-        if let Some(output_props) = output_json.get("object").and_then(|o| o.get("properties")) {
+        if let Some(output_props) = output_json.get("properties") {
             let props = output_props.as_object().unwrap();
             assert_eq!(props.len(), 2);
             assert!(props.contains_key("answer"));
@@ -1,24 +1,164 @@
-use super::traits::{Adapter, AdapterConfig};
+use super::traits::{Adapter, AdapterConfig, FieldUpdate};
 use super::utils::*;
 use crate::primatives::Signature;
 use anyhow::{Result, anyhow};
 use lazy_static::lazy_static;
 use regex::Regex;
 use schemars::Schema;
+use serde::de::DeserializeOwned;
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
 
 lazy_static! {
-    static ref FIELD_HEADER_PATTERN: Regex = Regex::new(r"\[\[ ## (\w+) ## \]\]").unwrap();
+    static ref FIELD_HEADER_PATTERN: Regex =
+        Regex::new(r"\[\[ ## ((?:\d+\. )?\w+) ## \]\]").unwrap();
+    static ref LIST_MARKER_PATTERN: Regex = Regex::new(r"^(?:[-*•]|\d+[.)])\s+").unwrap();
 }
 
 pub struct ChatAdapter {
     config: AdapterConfig,
+    numbered_fields: bool,
+    split_lists: bool,
+    bulleted_lists: bool,
 }
 
 impl ChatAdapter {
     pub fn new(config: AdapterConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            numbered_fields: false,
+            split_lists: true,
+            bulleted_lists: false,
+        }
+    }
+
+    /// Number output field headers (`[[ ## 1. answer ## ]]`) so models don't mix up similar names
+    pub fn with_numbered_fields(mut self, enabled: bool) -> Self {
+        self.numbered_fields = enabled;
+        self
+    }
+
+    /// Read array fields that aren't valid JSON as one item per line, dropping list
+    /// markers like `- ` or `1. `. On by default
+    pub fn with_list_splitting(mut self, enabled: bool) -> Self {
+        self.split_lists = enabled;
+        self
+    }
+
+    /// Show array values in prompts and demos as bulleted lists instead of JSON
+    pub fn with_bulleted_lists(mut self, enabled: bool) -> Self {
+        self.bulleted_lists = enabled;
+        self
+    }
+
+    fn format_field_value(&self, value: &JsonValue, field_info: &FieldInfo) -> String {
+        match value {
+            JsonValue::Array(items) if self.bulleted_lists => items
+                .iter()
+                .map(|item| match item {
+                    JsonValue::String(text) => format!("- {}", text),
+                    other => format!("- {}", other),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            other => format_field_value(other, field_info),
+        }
+    }
+
+    // Header label for the output field at `index` (zero-based)
+    fn output_label(&self, index: usize, name: &str) -> String {
+        if self.numbered_fields {
+            format!("{}. {}", index + 1, name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    // Parse each `[[ ## field ## ]]` section, sending it to `on_partial_output` if `report`.
+    // Final updates come from `Adapter::generate` once the outputs are validated
+    fn parse_fields<T: DeserializeOwned>(
+        &self,
+        completion: &str,
+        schema: &Schema,
+        report: bool,
+    ) -> Result<T> {
+        let fields = extract_fields(schema).unwrap_or_default();
+        let mut sections: Vec<(Option<String>, Vec<String>)> = vec![(None, Vec::new())];
+
+        for line in completion.lines() {
+            if let Some(captures) = FIELD_HEADER_PATTERN.captures(line.trim()) {
+                let header = strip_field_number(captures.get(1).unwrap().as_str()).to_string();
+                let remaining = line[captures.get(0).unwrap().end()..].trim().to_string();
+
+                sections.push((
+                    Some(header),
+                    if remaining.is_empty() {
+                        Vec::new()
+                    } else {
+                        vec![remaining]
+                    },
+                ));
+            } else {
+                sections.last_mut().unwrap().1.push(line.to_string());
+            }
+        }
+
+        let sections: Vec<(String, String)> = sections
+            .into_iter()
+            .filter_map(|(k, v)| k.map(|key| (key, v.join("\n").trim().to_string())))
+            .collect();
+
+        let on_partial_output = self.config.on_partial_output.as_ref().filter(|_| report);
+
+        // Build JSON object from sections, in the order they appear in the completion
+        let mut json_obj = serde_json::Map::new();
+        for (key, value) in sections {
+            if key == "completed" {
+                continue;
+            }
+
+            // Try to parse as JSON, otherwise use as string. Array fields that don't hold
+            // a JSON array are read as a list, one item per line
+            let parsed = match fields.get(&key) {
+                Some(info) if self.split_lists && info.type_name == "Array" => {
+                    match serde_json::from_str::<JsonValue>(&value) {
+                        Ok(JsonValue::Array(items)) => JsonValue::Array(items),
+                        _ => list_items(&value, info.item_type.as_deref().unwrap_or("Unknown")),
+                    }
+                }
+                _ => serde_json::from_str::<JsonValue>(&value)
+                    .unwrap_or_else(|_| JsonValue::String(value.to_string())),
+            };
+            if let Some(callback) = on_partial_output {
+                callback(FieldUpdate {
+                    field_name: key.clone(),
+                    value: parsed.clone(),
+                    is_final: false,
+                });
+            }
+            json_obj.insert(key, parsed);
+        }
+
+        serde_json::from_value(JsonValue::Object(json_obj))
+            .map_err(|e| anyhow!("Failed to deserialize output: {}", e))
+    }
+}
+
+// One item per non-empty line, without its list marker
+fn list_items(text: &str, item_type: &str) -> JsonValue {
+    let items = text
+        .lines()
+        .map(|line| LIST_MARKER_PATTERN.replace(line.trim(), ""))
+        .filter(|item| !item.is_empty())
+        .map(|item| field_value(&item, item_type))
+        .collect();
+    JsonValue::Array(items)
+}
+
+// Strip an optional `1. ` numbering prefix from a parsed header
+fn strip_field_number(header: &str) -> &str {
+    match header.split_once(". ") {
+        Some((number, name)) if number.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => header,
     }
 }
 
@@ -31,10 +171,10 @@ impl<S: Signature> Adapter<S> for ChatAdapter {
         let fields = extract_fields(schema).unwrap_or_default();
 
         let descriptions: Vec<String> = fields
-            .iter()
-            .map(|(name, info)| {
+            .values()
+            .map(|info| {
                 let desc = info.description.as_deref().unwrap_or("No description");
-                format!("- {}: {}", name, desc)
+                format!("- {}: {}", info.display_name(), desc)
             })
             .collect();
 
@@ -52,13 +192,14 @@ impl<S: Signature> Adapter<S> for ChatAdapter {
         // Format input fields
         let input_fields = extract_fields(input_schema).unwrap_or_default();
         for (name, info) in &input_fields {
-            parts.push(format!("[[ ## {} ## ]]\n{}", name, info.type_name));
+            parts.push(format!("[[ ## {} ## ]]\n{}", name, info.type_description()));
         }
 
         // Format output fields
         let output_fields = extract_fields(output_schema).unwrap_or_default();
-        for (name, info) in &output_fields {
-            parts.push(format!("[[ ## {} ## ]]\n{}", name, info.type_name));
+        for (index, (name, info)) in output_fields.iter().enumerate() {
+            let label = self.output_label(index, name);
+            parts.push(format!("[[ ## {} ## ]]\n{}", label, info.type_description()));
         }
 
         parts.push("[[ ## completed ## ]]".to_string());
@@ -79,30 +220,35 @@ impl<S: Signature> Adapter<S> for ChatAdapter {
         )
     }
 
-    fn format_user_message_content(&self, inputs: &S::Inputs, schema: &Schema) -> String {
-        let fields = extract_fields(schema).unwrap_or_default();
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        let fields = extract_fields(input_schema).unwrap_or_default();
         let json_value = serde_json::to_value(inputs).unwrap_or(JsonValue::Null);
 
         let mut parts = Vec::new();
 
         if let JsonValue::Object(map) = json_value {
-            for (name, _info) in &fields {
+            for (name, field_info) in &fields {
                 if let Some(value) = map.get(name) {
-                    let formatted = format_value(value);
+                    let formatted = self.format_field_value(value, field_info);
                     parts.push(format!("[[ ## {} ## ]]\n{}", name, formatted));
                 }
             }
         }
 
         // Add output requirements
-        let output_schema = schemars::schema_for!(S::Outputs);
-        let output_fields = extract_fields(&output_schema).unwrap_or_default();
+        let output_fields = extract_fields(output_schema).unwrap_or_default();
         let mut output_req =
             "Respond with the corresponding output fields, starting with the field ".to_string();
 
         let field_names: Vec<String> = output_fields
             .keys()
-            .map(|name| format!("`[[ ## {} ## ]]`", name))
+            .enumerate()
+            .map(|(index, name)| format!("`[[ ## {} ## ]]`", self.output_label(index, name)))
             .collect();
 
         output_req.push_str(&field_names.join(", then "));
@@ -123,10 +269,11 @@ impl<S: Signature> Adapter<S> for ChatAdapter {
         let mut parts = Vec::new();
 
         if let JsonValue::Object(map) = json_value {
-            for (name, _info) in &fields {
+            for (index, (name, field_info)) in fields.iter().enumerate() {
                 if let Some(value) = map.get(name) {
-                    let formatted = format_value(value);
-                    parts.push(format!("[[ ## {} ## ]]\n{}", name, formatted));
+                    let formatted = self.format_field_value(value, field_info);
+                    let label = self.output_label(index, name);
+                    parts.push(format!("[[ ## {} ## ]]\n{}", label, formatted));
                 }
             }
         }
@@ -136,44 +283,11 @@ impl<S: Signature> Adapter<S> for ChatAdapter {
         parts.join("\n\n")
     }
 
-    fn parse(&self, completion: &str, _schema: &Schema) -> Result<S::Outputs> {
-        let mut sections: Vec<(Option<String>, Vec<String>)> = vec![(None, Vec::new())];
-
-        for line in completion.lines() {
-            if let Some(captures) = FIELD_HEADER_PATTERN.captures(line.trim()) {
-                let header = captures.get(1).unwrap().as_str().to_string();
-                let remaining = line[captures.get(0).unwrap().end()..].trim().to_string();
-
-                sections.push((
-                    Some(header),
-                    if remaining.is_empty() {
-                        Vec::new()
-                    } else {
-                        vec![remaining]
-                    },
-                ));
-            } else {
-                sections.last_mut().unwrap().1.push(line.to_string());
-            }
-        }
-
-        let sections: HashMap<String, String> = sections
-            .into_iter()
-            .filter_map(|(k, v)| k.map(|key| (key, v.join("\n").trim().to_string())))
-            .collect();
-
-        // Build JSON object from sections
-        let mut json_obj = serde_json::Map::new();
-        for (key, value) in sections {
-            if key != "completed" {
-                // Try to parse as JSON, otherwise use as string
-                let parsed = serde_json::from_str::<JsonValue>(&value)
-                    .unwrap_or_else(|_| JsonValue::String(value.to_string()));
-                json_obj.insert(key, parsed);
-            }
-        }
+    fn parse(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        self.parse_fields(completion, schema, true)
+    }
 
-        serde_json::from_value(JsonValue::Object(json_obj))
-            .map_err(|e| anyhow!("Failed to deserialize output: {}", e))
+    fn parse_without_updates(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        self.parse_fields(completion, schema, false)
     }
 }
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use super::traits::{ContextTruncationStrategy, TokenCountFn, count_message_tokens};
+use crate::providers::models::Message;
+
+// What `ContextWindowManager::trim_to_fit` drops first. The first system message and the
+// last message, the current inputs in an adapter's prompt, are always kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrimStrategy {
+    // Messages in order, oldest first
+    #[default]
+    TrimOldestMessages,
+    // The messages just before the last one, where adapters put demos, nearest first
+    TrimDemoMessages,
+    // Whole turns, from a user message up to the next, starting just after the system
+    // message: the history in an adapter's prompt, then the demos after it
+    TrimHistoryFirst,
+}
+
+// The adapter's truncation when a manager is set on `AdapterConfig::context_window`, where
+// the adapter knows which messages are demos and which are history. Adapters only ever
+// drop whole history turns and demos, so trimming the oldest messages and trimming history
+// first come to the same thing there
+impl From<TrimStrategy> for ContextTruncationStrategy {
+    fn from(strategy: TrimStrategy) -> Self {
+        match strategy {
+            TrimStrategy::TrimOldestMessages | TrimStrategy::TrimHistoryFirst => {
+                ContextTruncationStrategy::TruncateOldestFirst
+            }
+            TrimStrategy::TrimDemoMessages => ContextTruncationStrategy::TruncateDemos,
+        }
+    }
+}
+
+// Estimates the token count of messages and trims them to a model's context window
+#[derive(Clone)]
+pub struct ContextWindowManager {
+    max_tokens: usize,
+    count_tokens: TokenCountFn,
+    strategy: TrimStrategy,
+}
+
+impl ContextWindowManager {
+    pub fn new(
+        max_tokens: usize,
+        count_tokens: impl Fn(&str) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        ContextWindowManager {
+            max_tokens,
+            count_tokens: Arc::new(count_tokens),
+            strategy: TrimStrategy::default(),
+        }
+    }
+
+    // Strategy used by adapters when the manager is set on `AdapterConfig`
+    pub fn with_strategy(mut self, strategy: TrimStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    pub fn strategy(&self) -> TrimStrategy {
+        self.strategy
+    }
+
+    pub(crate) fn count_fn(&self) -> &TokenCountFn {
+        &self.count_tokens
+    }
+
+    // Tokens in the text of `messages`; images aren't counted
+    pub fn estimate_tokens(&self, messages: &[Message]) -> usize {
+        count_message_tokens(messages, Some(self.count_tokens.as_ref()))
+    }
+
+    pub fn fits_in_window(&self, messages: &[Message]) -> bool {
+        self.estimate_tokens(messages) <= self.max_tokens
+    }
+
+    // Drop messages by `strategy` until `messages` fit, returning how many were dropped.
+    // Tool results go with the assistant message that called the tools. May still not fit
+    // once only the protected messages are left
+    pub fn trim_to_fit(&self, messages: &mut Vec<Message>, strategy: TrimStrategy) -> usize {
+        let before = messages.len();
+        while !self.fits_in_window(messages) {
+            let first = usize::from(matches!(messages.first(), Some(Message::System { .. })));
+            let last = messages.len().saturating_sub(1);
+            if first >= last {
+                break;
+            }
+            let (start, end) = match strategy {
+                TrimStrategy::TrimOldestMessages => (first, turn_end(messages, first, last)),
+                TrimStrategy::TrimHistoryFirst => {
+                    let mut end = first + 1;
+                    while end < last && !matches!(messages[end], Message::User { .. }) {
+                        end += 1;
+                    }
+                    (first, end)
+                }
+                TrimStrategy::TrimDemoMessages => {
+                    let mut start = last - 1;
+                    while start > first && messages[start].is_tool_call_response() {
+                        start -= 1;
+                    }
+                    (start, last)
+                }
+            };
+            messages.drain(start..end);
+        }
+        before - messages.len()
+    }
+}
+
+// One past the message at `start` and the tool results following it, before `limit`
+fn turn_end(messages: &[Message], start: usize, limit: usize) -> usize {
+    let mut end = start + 1;
+    while end < limit && messages[end].is_tool_call_response() {
+        end += 1;
+    }
+    end
+}
+
+impl std::fmt::Debug for ContextWindowManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextWindowManager")
+            .field("max_tokens", &self.max_tokens)
+            .field("count_tokens", &"Fn(&str) -> usize")
+            .field("strategy", &self.strategy)
+            .finish()
+    }
+}
@@ -0,0 +1,178 @@
+use super::traits::{Adapter, AdapterConfig};
+use crate::primatives::Signature;
+use anyhow::Result;
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value as JsonValue, json};
+use std::sync::{Arc, Mutex};
+
+const RATIONALE_FIELD: &str = "rationale";
+
+const RATIONALE_INSTRUCTION: &str = "Start with the `rationale` field, reasoning step by step, \
+    before any of the output fields above.";
+
+/// Output of [`RationaleSignature`]: just the reasoning, ignoring every other field
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Rationale {
+    pub rationale: String,
+}
+
+/// Signature the inner adapter parses the rationale with, so any adapter's own field
+/// format works for it
+pub struct RationaleSignature;
+
+impl Signature for RationaleSignature {
+    type Inputs = Rationale;
+    type Outputs = Rationale;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        ""
+    }
+
+    fn name(&self) -> &str {
+        "Rationale"
+    }
+
+    fn desc(&self) -> &str {
+        "Step-by-step reasoning"
+    }
+}
+
+/// Wraps another adapter and asks the model to reason in a `rationale` field before the
+/// outputs, like DSPy's `ChainOfThought`
+pub struct ChainOfThoughtAdapter<A> {
+    inner: A,
+    last_rationale: Arc<Mutex<Option<String>>>,
+}
+
+impl<A> ChainOfThoughtAdapter<A> {
+    pub fn new(inner: A) -> Self {
+        Self {
+            inner,
+            last_rationale: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Rationale of the most recently parsed completion, if it had one
+    pub fn last_rationale(&self) -> Option<String> {
+        self.last_rationale.lock().unwrap().clone()
+    }
+
+    // Shared with `ChainOfThought`, which only holds the adapter boxed
+    pub(crate) fn rationale_slot(&self) -> Arc<Mutex<Option<String>>> {
+        self.last_rationale.clone()
+    }
+}
+
+/// `schema` with a required `rationale: String` property ahead of its other properties
+pub fn with_rationale(schema: &Schema) -> Schema {
+    let mut schema_json = schema.as_value().clone();
+    let Some(object) = schema_json.as_object_mut() else {
+        return schema.clone();
+    };
+
+    // Ordered fields sort first, so the lowest order keeps the rationale ahead of them too
+    let mut properties = serde_json::Map::new();
+    properties.insert(
+        RATIONALE_FIELD.to_string(),
+        json!({
+            "type": "string",
+            "description": "Think step by step in order to produce the outputs",
+            "x-field-order": i64::MIN,
+        }),
+    );
+    if let Some(JsonValue::Object(existing)) = object.remove("properties") {
+        properties.extend(
+            existing
+                .into_iter()
+                .filter(|(name, _)| name != RATIONALE_FIELD),
+        );
+    }
+    object.insert("properties".to_string(), JsonValue::Object(properties));
+
+    let mut required = vec![JsonValue::from(RATIONALE_FIELD)];
+    if let Some(JsonValue::Array(existing)) = object.remove("required") {
+        required.extend(existing.into_iter().filter(|name| name != RATIONALE_FIELD));
+    }
+    object.insert("required".to_string(), JsonValue::Array(required));
+
+    Schema::try_from(schema_json).unwrap_or_else(|_| schema.clone())
+}
+
+impl<S, A> Adapter<S> for ChainOfThoughtAdapter<A>
+where
+    S: Signature,
+    A: Adapter<S> + Adapter<RationaleSignature>,
+{
+    fn config(&self) -> &AdapterConfig {
+        <A as Adapter<S>>::config(&self.inner)
+    }
+
+    fn format_field_description(&self, schema: &Schema) -> String {
+        <A as Adapter<S>>::format_field_description(&self.inner, schema)
+    }
+
+    fn format_field_structure(&self, input_schema: &Schema, output_schema: &Schema) -> String {
+        <A as Adapter<S>>::format_field_structure(
+            &self.inner,
+            input_schema,
+            &with_rationale(output_schema),
+        )
+    }
+
+    fn format_task_description(&self, instructions: &str) -> String {
+        <A as Adapter<S>>::format_task_description(&self.inner, instructions)
+    }
+
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        let content = <A as Adapter<S>>::format_user_message_content(
+            &self.inner,
+            inputs,
+            input_schema,
+            output_schema,
+        );
+        format!("{}\n\n{}", content, RATIONALE_INSTRUCTION)
+    }
+
+    fn prepend_context(&self, content: String, context: &str) -> String {
+        <A as Adapter<S>>::prepend_context(&self.inner, content, context)
+    }
+
+    fn format_assistant_message_content(&self, outputs: &S::Outputs, schema: &Schema) -> String {
+        <A as Adapter<S>>::format_assistant_message_content(&self.inner, outputs, schema)
+    }
+
+    fn parse(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        // The full schema, so the rationale ends where the first output field begins. Fields
+        // are reported once, by the parse of the outputs below
+        let rationale = <A as Adapter<RationaleSignature>>::parse_without_updates(
+            &self.inner,
+            completion,
+            &with_rationale(schema),
+        )
+        .ok()
+        .map(|parsed| parsed.rationale);
+
+        // Reasoning may quote field markers, so it is removed before the outputs are parsed
+        let remaining = match &rationale {
+            Some(rationale) if !rationale.is_empty() => {
+                completion.replacen(rationale.as_str(), "", 1)
+            }
+            _ => completion.to_string(),
+        };
+        *self.last_rationale.lock().unwrap() = rationale;
+
+        <A as Adapter<S>>::parse(&self.inner, &remaining, schema)
+    }
+}
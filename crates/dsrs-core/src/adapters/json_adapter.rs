@@ -30,11 +30,13 @@ impl<S: Signature> Adapter<S> for JsonAdapter {
         // Similar to ChatAdapter but formatted for JSON mode
         let fields = extract_fields(schema).unwrap_or_default();
 
+        // Nested object fields are listed, indented, under their parent
         let descriptions: Vec<String> = fields
-            .iter()
-            .map(|(name, info)| {
+            .values()
+            .flat_map(|info| {
                 let desc = info.description.as_deref().unwrap_or("No description");
-                format!("- {}: {} ({})", name, desc, info.type_name)
+                let line = format!("- {}: {} ({})", info.display_name(), desc, info.type_name);
+                std::iter::once(line).chain(info.children_description(0))
             })
             .collect();
 
@@ -42,14 +44,15 @@ impl<S: Signature> Adapter<S> for JsonAdapter {
     }
 
     fn format_field_structure(&self, input_schema: &Schema, output_schema: &Schema) -> String {
-        let mut parts = Vec::new();
-        parts.push("All interactions will be structured in the following way:".to_string());
-        parts.push("".to_string());
-        parts.push("Input fields:".to_string());
-        parts.push(<JsonAdapter as Adapter<S>>::format_field_description(self, input_schema));
-        parts.push("".to_string());
-        parts.push("Output will be a JSON object with the following fields:".to_string());
-        parts.push(<JsonAdapter as Adapter<S>>::format_field_description(self, output_schema));
+        let parts = [
+            "All interactions will be structured in the following way:".to_string(),
+            "".to_string(),
+            "Input fields:".to_string(),
+            <JsonAdapter as Adapter<S>>::format_field_description(self, input_schema),
+            "".to_string(),
+            "Output will be a JSON object with the following fields:".to_string(),
+            <JsonAdapter as Adapter<S>>::format_field_description(self, output_schema),
+        ];
 
         parts.join("\n")
     }
@@ -58,14 +61,19 @@ impl<S: Signature> Adapter<S> for JsonAdapter {
         format!("Your task: {}", instructions)
     }
 
-    fn format_user_message_content(&self, inputs: &S::Inputs, schema: &Schema) -> String {
-        let fields = extract_fields(schema).unwrap_or_default();
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        let fields = extract_fields(input_schema).unwrap_or_default();
         let json_value = serde_json::to_value(inputs).unwrap_or(JsonValue::Null);
 
         let mut parts = Vec::new();
 
         if let JsonValue::Object(map) = json_value {
-            for (name, _info) in &fields {
+            for name in fields.keys() {
                 if let Some(value) = map.get(name) {
                     let formatted = format_value(value);
                     parts.push(format!("{}: {}", name, formatted));
@@ -74,8 +82,7 @@ impl<S: Signature> Adapter<S> for JsonAdapter {
         }
 
         // Add JSON output requirement
-        let output_schema = schemars::schema_for!(S::Outputs);
-        let output_fields = extract_fields(&output_schema).unwrap_or_default();
+        let output_fields = extract_fields(output_schema).unwrap_or_default();
         let field_names: Vec<&str> = output_fields.keys().map(|s| s.as_str()).collect();
 
         parts.push(format!(
@@ -86,6 +93,10 @@ impl<S: Signature> Adapter<S> for JsonAdapter {
         parts.join("\n")
     }
 
+    fn prepend_context(&self, content: String, context: &str) -> String {
+        format!("context: {}\n{}", context, content)
+    }
+
     fn format_assistant_message_content(&self, outputs: &S::Outputs, _schema: &Schema) -> String {
         serde_json::to_string_pretty(outputs).unwrap_or_else(|_| "{}".to_string())
     }
@@ -98,6 +109,9 @@ impl<S: Signature> Adapter<S> for JsonAdapter {
             completion
         };
 
-        serde_json::from_str(json_str).map_err(|e| anyhow!("Failed to parse JSON response: {}", e))
+        serde_json::from_str(json_str)
+            // Models often get the JSON almost right, so try again with the usual slips fixed
+            .or_else(|e| serde_json::from_str(&json_repair(completion)).map_err(|_| e))
+            .map_err(|e| anyhow!("Failed to parse JSON response: {}", e))
     }
 }
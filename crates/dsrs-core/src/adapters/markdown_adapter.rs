@@ -0,0 +1,192 @@
+use super::traits::{Adapter, AdapterConfig};
+use super::utils::*;
+use crate::primatives::Signature;
+use anyhow::{Result, anyhow};
+use schemars::Schema;
+use serde_json::Value as JsonValue;
+
+pub struct MarkdownAdapter {
+    config: AdapterConfig,
+}
+
+impl MarkdownAdapter {
+    pub fn new(config: AdapterConfig) -> Self {
+        Self { config }
+    }
+}
+
+// `## name` on its own line; other heading levels never delimit fields
+fn field_header(line: &str) -> Option<&str> {
+    let name = line.trim_end().strip_prefix("## ")?.trim();
+    (!name.is_empty()).then_some(name)
+}
+
+// Opening or closing marker of a fenced code block
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// Split `markdown` into the sections under `## name` headers whose name passes
+/// `is_field`. Headers inside fenced code blocks and headers for unknown names are kept
+/// as content, so values may themselves contain markdown.
+pub fn parse_markdown_sections(
+    markdown: &str,
+    is_field: impl Fn(&str) -> bool,
+) -> Vec<(String, String)> {
+    let mut sections: Vec<(String, Vec<&str>)> = Vec::new();
+    let mut in_fence = false;
+
+    for line in markdown.lines() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        } else if !in_fence
+            && let Some(name) = field_header(line).filter(|name| is_field(name))
+        {
+            sections.push((name.to_string(), Vec::new()));
+            continue;
+        }
+
+        // Text before the first header is preamble and dropped
+        if let Some((_, lines)) = sections.last_mut() {
+            lines.push(line);
+        }
+    }
+
+    sections
+        .into_iter()
+        .map(|(name, lines)| (name, lines.join("\n").trim().to_string()))
+        .collect()
+}
+
+// Models often fence structured values, e.g. ```json ... ```, so unwrap a value that is a
+// single code block before reading it as JSON
+fn unfence(text: &str) -> &str {
+    let Some(body) = text.strip_prefix("```") else {
+        return text;
+    };
+    match (body.find('\n'), body.strip_suffix("```")) {
+        (Some(start), Some(_)) if start < body.len() - 3 => body[start..body.len() - 3].trim(),
+        _ => text,
+    }
+}
+
+impl<S: Signature> Adapter<S> for MarkdownAdapter {
+    fn config(&self) -> &AdapterConfig {
+        &self.config
+    }
+
+    fn format_field_description(&self, schema: &Schema) -> String {
+        let fields = extract_fields(schema).unwrap_or_default();
+
+        let descriptions: Vec<String> = fields
+            .values()
+            .map(|info| {
+                let desc = info.description.as_deref().unwrap_or("No description");
+                format!("- {}: {} ({})", info.display_name(), desc, info.type_name)
+            })
+            .collect();
+
+        descriptions.join("\n")
+    }
+
+    fn format_field_structure(&self, input_schema: &Schema, output_schema: &Schema) -> String {
+        let mut sections = Vec::new();
+
+        let input_fields = extract_fields(input_schema).unwrap_or_default();
+        for (name, info) in &input_fields {
+            sections.push(format!("## {}\n{}", name, info.type_description()));
+        }
+
+        let output_fields = extract_fields(output_schema).unwrap_or_default();
+        for (name, info) in &output_fields {
+            sections.push(format!("## {}\n{}", name, info.type_description()));
+        }
+
+        // A four-backtick fence so the example can show fenced values without closing early
+        format!(
+            "All interactions will be structured in the following way, with the appropriate values filled in.\n\n\
+             ````markdown\n{}\n````\n\n\
+             Start each field with its `## field_name` header on its own line. Values may use any \
+             other markdown, including `###` and deeper headers.",
+            sections.join("\n\n")
+        )
+    }
+
+    fn format_task_description(&self, instructions: &str) -> String {
+        format!("Your task: {}", instructions)
+    }
+
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        let fields = extract_fields(input_schema).unwrap_or_default();
+        let json_value = serde_json::to_value(inputs).unwrap_or(JsonValue::Null);
+
+        let mut parts = Vec::new();
+
+        if let JsonValue::Object(map) = json_value {
+            for name in fields.keys() {
+                if let Some(value) = map.get(name) {
+                    parts.push(format!("## {}\n{}", name, format_value(value)));
+                }
+            }
+        }
+
+        // Add output requirements
+        let output_fields = extract_fields(output_schema).unwrap_or_default();
+        let field_names: Vec<String> = output_fields
+            .keys()
+            .map(|name| format!("`## {}`", name))
+            .collect();
+
+        parts.push(format!(
+            "Respond with the corresponding output fields under their headers: {}.",
+            field_names.join(", then ")
+        ));
+
+        parts.join("\n\n")
+    }
+
+    fn prepend_context(&self, content: String, context: &str) -> String {
+        format!("## context\n{}\n\n{}", context, content)
+    }
+
+    fn format_assistant_message_content(&self, outputs: &S::Outputs, schema: &Schema) -> String {
+        let fields = extract_fields(schema).unwrap_or_default();
+        let json_value = serde_json::to_value(outputs).unwrap_or(JsonValue::Null);
+
+        let mut parts = Vec::new();
+
+        if let JsonValue::Object(map) = json_value {
+            for name in fields.keys() {
+                if let Some(value) = map.get(name) {
+                    parts.push(format!("## {}\n{}", name, format_value(value)));
+                }
+            }
+        }
+
+        parts.join("\n\n")
+    }
+
+    fn parse(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        let fields = extract_fields(schema).unwrap_or_default();
+
+        let mut json_obj = serde_json::Map::new();
+        for (name, text) in parse_markdown_sections(completion, |name| fields.contains_key(name)) {
+            let type_name = &fields[&name].type_name;
+            let text = if type_name == "String" {
+                text.as_str()
+            } else {
+                unfence(&text)
+            };
+            json_obj.insert(name, field_value(text, type_name));
+        }
+
+        serde_json::from_value(JsonValue::Object(json_obj))
+            .map_err(|e| anyhow!("Failed to deserialize output: {}", e))
+    }
+}
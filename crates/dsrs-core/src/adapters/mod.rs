@@ -1,5 +1,12 @@
 pub mod chat_adapter;
+pub mod context_window;
+pub mod cot_adapter;
 pub mod json_adapter;
+pub mod markdown_adapter;
+pub mod pot_adapter;
 pub mod schema_parser;
+pub mod structured_output_adapter;
 pub mod traits;
 pub mod utils;
+pub mod xml_adapter;
+pub mod yaml_adapter;
@@ -0,0 +1,165 @@
+use super::chat_adapter::ChatAdapter;
+use super::traits::{Adapter, AdapterConfig};
+use super::utils::extract_fields;
+use crate::primatives::Signature;
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use regex::Regex;
+use schemars::Schema;
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    static ref CODE_BLOCK_PATTERN: Regex =
+        Regex::new(r"(?s)```(python|py)?[ \t]*\n(.*?)```").unwrap();
+}
+
+const PROGRAM_INSTRUCTION: &str = "Don't answer directly. Instead, write Python code that \
+    solves the task in a single ```python fenced block. Running it must print the response \
+    described above, each output field under its marker; with a single output field, printing \
+    just its value is enough.";
+
+/// Runs the code a `ProgramOfThoughtAdapter` gets from the model, returning what it printed.
+///
+/// The code is written by a model and must be treated as untrusted: implementations should
+/// run it in a sandbox, such as a container, a WASM runtime or a subprocess with no network
+/// or file system access and a timeout, never directly on the host
+pub trait CodeInterpreter: Send + Sync {
+    fn execute(&self, code: &str) -> Result<String>;
+}
+
+impl<F> CodeInterpreter for F
+where
+    F: Fn(&str) -> Result<String> + Send + Sync,
+{
+    fn execute(&self, code: &str) -> Result<String> {
+        self(code)
+    }
+}
+
+/// An interpreter that runs nothing and returns the code itself as the result, for
+/// inspecting the programs a model writes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoOpInterpreter;
+
+impl CodeInterpreter for NoOpInterpreter {
+    fn execute(&self, code: &str) -> Result<String> {
+        Ok(code.to_string())
+    }
+}
+
+/// Wraps `ChatAdapter` and asks the model for a Python program instead of the outputs. The
+/// program is run with a `CodeInterpreter`, and what it prints is parsed as the outputs
+pub struct ProgramOfThoughtAdapter<I: CodeInterpreter = NoOpInterpreter> {
+    inner: ChatAdapter,
+    interpreter: I,
+    last_code: Arc<Mutex<Option<String>>>,
+}
+
+impl ProgramOfThoughtAdapter {
+    /// A `ChatAdapter` with `config`, returning programs unrun with `NoOpInterpreter`
+    pub fn new(config: AdapterConfig) -> Self {
+        Self::with_interpreter(ChatAdapter::new(config), NoOpInterpreter)
+    }
+}
+
+impl<I: CodeInterpreter> ProgramOfThoughtAdapter<I> {
+    pub fn with_interpreter(inner: ChatAdapter, interpreter: I) -> Self {
+        Self {
+            inner,
+            interpreter,
+            last_code: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn inner(&self) -> &ChatAdapter {
+        &self.inner
+    }
+
+    pub fn interpreter(&self) -> &I {
+        &self.interpreter
+    }
+
+    /// Code of the most recently parsed completion, if it had any
+    pub fn last_code(&self) -> Option<String> {
+        self.last_code.lock().unwrap().clone()
+    }
+}
+
+// The first Python code block in `completion`, or the first untagged one without one
+fn extract_code(completion: &str) -> Option<String> {
+    let blocks: Vec<_> = CODE_BLOCK_PATTERN.captures_iter(completion).collect();
+    blocks
+        .iter()
+        .find(|block| block.get(1).is_some())
+        .or_else(|| blocks.first())
+        .map(|block| block[2].trim().to_string())
+}
+
+impl<S, I> Adapter<S> for ProgramOfThoughtAdapter<I>
+where
+    S: Signature,
+    I: CodeInterpreter,
+{
+    fn config(&self) -> &AdapterConfig {
+        <ChatAdapter as Adapter<S>>::config(&self.inner)
+    }
+
+    fn format_field_description(&self, schema: &Schema) -> String {
+        <ChatAdapter as Adapter<S>>::format_field_description(&self.inner, schema)
+    }
+
+    fn format_field_structure(&self, input_schema: &Schema, output_schema: &Schema) -> String {
+        <ChatAdapter as Adapter<S>>::format_field_structure(
+            &self.inner,
+            input_schema,
+            output_schema,
+        )
+    }
+
+    fn format_task_description(&self, instructions: &str) -> String {
+        <ChatAdapter as Adapter<S>>::format_task_description(&self.inner, instructions)
+    }
+
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        let content = <ChatAdapter as Adapter<S>>::format_user_message_content(
+            &self.inner,
+            inputs,
+            input_schema,
+            output_schema,
+        );
+        format!("{}\n\n{}", content, PROGRAM_INSTRUCTION)
+    }
+
+    fn prepend_context(&self, content: String, context: &str) -> String {
+        <ChatAdapter as Adapter<S>>::prepend_context(&self.inner, content, context)
+    }
+
+    fn format_assistant_message_content(&self, outputs: &S::Outputs, schema: &Schema) -> String {
+        <ChatAdapter as Adapter<S>>::format_assistant_message_content(&self.inner, outputs, schema)
+    }
+
+    fn parse(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        let code = extract_code(completion);
+        *self.last_code.lock().unwrap() = code.clone();
+        let code = code.ok_or_else(|| anyhow!("No ```python code block in the completion"))?;
+        let result = self
+            .interpreter
+            .execute(&code)
+            .map_err(|e| anyhow!("Failed to execute the generated code: {}", e))?;
+
+        // A lone field's bare value is read as if it came under the field's marker
+        let fields = extract_fields(schema).unwrap_or_default();
+        let result = match fields.keys().next() {
+            Some(name) if fields.len() == 1 && !result.contains("[[ ## ") => {
+                format!("[[ ## {} ## ]]\n{}", name, result.trim())
+            }
+            _ => result,
+        };
+        <ChatAdapter as Adapter<S>>::parse(&self.inner, &result, schema)
+    }
+}
@@ -1,7 +1,7 @@
 use anyhow::{Result, anyhow};
-use schemars::Schema;
+use schemars::{JsonSchema, Schema};
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use indexmap::IndexMap;
 use crate::primatives::Signature;
 
 #[derive(Debug, Clone)]
@@ -10,44 +10,117 @@ pub struct FieldInfo {
     pub type_name: String,
     pub description: Option<String>,
     pub required: bool,
+    /// Fields of a nested object, resolved through `$ref` where needed
+    pub children: Option<IndexMap<String, FieldInfo>>,
+    /// Type name of an array's items
+    pub item_type: Option<String>,
+    /// Values an enum field is restricted to
+    pub allowed_values: Option<Vec<String>>,
+}
+
+impl FieldInfo {
+    /// The name, with an enum field's allowed values: `sentiment (one of: positive, negative)`
+    pub fn display_name(&self) -> String {
+        match &self.allowed_values {
+            Some(values) => format!("{} (one of: {})", self.name, values.join(", ")),
+            None => self.name.clone(),
+        }
+    }
+
+    /// The type name followed by the nested fields, indented by depth, for prompts
+    pub fn type_description(&self) -> String {
+        let mut lines = vec![self.type_name.clone()];
+        lines.extend(self.children_description(0));
+        lines.join("\n")
+    }
+
+    /// One `- name (Type): description` line per nested field, indented below `depth`;
+    /// enum fields show their allowed values in place of the type
+    pub fn children_description(&self, depth: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        for child in self.children.iter().flat_map(|children| children.values()) {
+            let indent = "  ".repeat(depth + 1);
+            let label = match &child.allowed_values {
+                Some(_) => child.display_name(),
+                None => format!("{} ({})", child.name, child.type_name),
+            };
+            match &child.description {
+                Some(desc) => lines.push(format!("{}- {}: {}", indent, label, desc)),
+                None => lines.push(format!("{}- {}", indent, label)),
+            }
+            lines.extend(child.children_description(depth + 1));
+        }
+        lines
+    }
 }
 
 /// Convert a Schema to JSON and extract field information
-pub fn extract_fields_from_schema(schema: &Schema) -> Result<HashMap<String, FieldInfo>> {
+pub fn extract_fields_from_schema(schema: &Schema) -> Result<IndexMap<String, FieldInfo>> {
     // Convert schema to JSON for runtime navigation
     let schema_json = serde_json::to_value(schema)
         .map_err(|e| anyhow!("Failed to serialize schema to JSON: {}", e))?;
     
-    extract_fields_from_json(&schema_json)
+    let mut fields = extract_fields_from_json(&schema_json)?;
+    sort_fields_by_order_extension(&mut fields, &schema_json);
+    Ok(fields)
+}
+
+/// Sort fields by their `x-field-order` extension (set with `#[dsrs(field_order = N)]`)
+/// Fields without the extension keep their schema order and come after the ordered ones
+pub fn sort_fields_by_order_extension(fields: &mut IndexMap<String, FieldInfo>, schema_json: &JsonValue) {
+
+    let order_of = |name: &str| {
+        schema_json
+            .get("properties")
+            .and_then(|p| p.get(name))
+            .and_then(|field| field.get("x-field-order"))
+            .and_then(|order| order.as_i64())
+    };
+
+    // Stable sort, so unordered fields (and ties) keep their relative order
+    fields.sort_by_cached_key(|name, _| match order_of(name) {
+        Some(order) => (0, order),
+        None => (1, 0),
+    });
 }
 
 /// Extract field information from a JSON schema representation
-pub fn extract_fields_from_json(schema_json: &JsonValue) -> Result<HashMap<String, FieldInfo>> {
-    let mut fields = HashMap::new();
+/// Nested objects are followed through `$ref`s into the root's `$defs`/`definitions`
+pub fn extract_fields_from_json(schema_json: &JsonValue) -> Result<IndexMap<String, FieldInfo>> {
+    extract_object_fields(schema_json, schema_json, &mut Vec::new())
+}
+
+// Fields of `object_json`; `visiting` holds the refs being expanded, so recursive types stop
+fn extract_object_fields(
+    object_json: &JsonValue,
+    root: &JsonValue,
+    visiting: &mut Vec<String>,
+) -> Result<IndexMap<String, FieldInfo>> {
+    let mut fields = IndexMap::new();
     
-    // Navigate the JSON schema structure
-    if let Some(object_def) = schema_json.get("object") {
-        if let Some(properties) = object_def.get("properties").and_then(|p| p.as_object()) {
-            // Get required fields
-            let required_fields: Vec<String> = object_def
-                .get("required")
-                .and_then(|r| r.as_array())
-                .map(|arr| {
-                    arr.iter()
-                        .filter_map(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .collect()
-                })
-                .unwrap_or_default();
-            
-            for (field_name, field_schema) in properties {
-                let field_info = extract_field_info_from_json(
-                    field_name, 
-                    field_schema, 
-                    required_fields.contains(field_name)
-                )?;
-                fields.insert(field_name.clone(), field_info);
-            }
+    // Navigate the JSON schema structure (properties live at the root of the schema)
+    if let Some(properties) = object_json.get("properties").and_then(|p| p.as_object()) {
+        // Get required fields
+        let required_fields: Vec<String> = object_json
+            .get("required")
+            .and_then(|r| r.as_array())
+            .map(|arr| {
+                arr.iter()
+                    .filter_map(|v| v.as_str())
+                    .map(|s| s.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for (field_name, field_schema) in properties {
+            let field_info = extract_field_info_from_json(
+                field_name,
+                field_schema,
+                required_fields.contains(field_name),
+                root,
+                visiting,
+            )?;
+            fields.insert(field_name.clone(), field_info);
         }
     }
     
@@ -55,48 +128,227 @@ pub fn extract_fields_from_json(schema_json: &JsonValue) -> Result<HashMap<Strin
 }
 
 /// Extract information for a single field from JSON schema
-fn extract_field_info_from_json(name: &str, field_json: &JsonValue, required: bool) -> Result<FieldInfo> {
-    let type_name = extract_type_name_from_json(field_json);
+fn extract_field_info_from_json(
+    name: &str,
+    field_json: &JsonValue,
+    required: bool,
+    root: &JsonValue,
+    visiting: &mut Vec<String>,
+) -> Result<FieldInfo> {
+    let reference = field_json.get("$ref").and_then(|r| r.as_str());
+    let resolved = reference.and_then(|r| resolve_ref(root, r)).unwrap_or(field_json);
+
+    // `Option<T>` is an `anyOf` of `T` and null: describe `T`, as an optional field
+    let inner = nullable_inner(resolved);
+    let inner_ref = inner.and_then(|i| i.get("$ref")).and_then(|r| r.as_str());
+    let nested_ref = inner_ref.or(reference);
+    let nested = match (inner, inner_ref) {
+        (Some(inner), Some(r)) => resolve_ref(root, r).unwrap_or(inner),
+        (Some(inner), None) => inner,
+        (None, _) => resolved,
+    };
+    let type_name = extract_type_name_from_json(nested);
+    let item_type = nested.get("items").map(|items| {
+        let items = match items.get("$ref").and_then(|r| r.as_str()) {
+            Some(r) => resolve_ref(root, r).unwrap_or(items),
+            None => items,
+        };
+        extract_type_name_from_json(items)
+    });
+    let allowed_values = extract_allowed_values(nested);
+    let required = required && inner.is_none() && !allows_null_type(resolved);
     let description = field_json
         .get("description")
+        .or_else(|| nested.get("description"))
         .and_then(|d| d.as_str())
         .map(|s| s.to_string());
-    
+
+    let recursive = nested_ref.is_some_and(|r| visiting.iter().any(|v| v == r));
+    let children = if nested.get("properties").is_none() || recursive {
+        None
+    } else {
+        visiting.extend(nested_ref.map(str::to_string));
+        let children = extract_object_fields(nested, root, visiting);
+        if nested_ref.is_some() {
+            visiting.pop();
+        }
+        let mut children = children?;
+        sort_fields_by_order_extension(&mut children, nested);
+        Some(children)
+    };
+
     Ok(FieldInfo {
         name: name.to_string(),
         type_name,
         description,
         required,
+        children,
+        item_type,
+        allowed_values,
     })
 }
 
+// Definition a local `#/$defs/Name` or `#/definitions/Name` reference points to, following
+// definitions that are themselves references
+fn resolve_ref<'a>(root: &'a JsonValue, reference: &str) -> Option<&'a JsonValue> {
+    let mut target = root.pointer(reference.strip_prefix('#')?)?;
+    for _ in 0..MAX_REF_CHAIN {
+        match target.get("$ref").and_then(|r| r.as_str()) {
+            Some(next) => target = root.pointer(next.strip_prefix('#')?)?,
+            None => return Some(target),
+        }
+    }
+    None
+}
+
+const MAX_REF_CHAIN: usize = 16;
+
+/// Copy of `schema_json` with local `$ref`s replaced by their definitions, for consumers
+/// such as tool input schemas that don't follow references. Recursive references are kept,
+/// along with the definitions they need
+pub fn inline_refs(schema_json: &JsonValue) -> JsonValue {
+    // The root is always being expanded, so `#` references stay as they are
+    let mut recursive = false;
+    let mut visiting = vec!["#".to_string()];
+    let mut inlined = inline_value(schema_json, schema_json, &mut visiting, &mut recursive);
+    if !recursive && let JsonValue::Object(map) = &mut inlined {
+        map.remove("$defs");
+        map.remove("definitions");
+    }
+    inlined
+}
+
+fn inline_value(
+    value: &JsonValue,
+    root: &JsonValue,
+    visiting: &mut Vec<String>,
+    recursive: &mut bool,
+) -> JsonValue {
+    match value {
+        JsonValue::Object(map) => {
+            if let Some(reference) = map.get("$ref").and_then(|r| r.as_str()) {
+                if visiting.iter().any(|v| v == reference) {
+                    *recursive = true;
+                    return value.clone();
+                }
+                if let Some(target) = resolve_ref(root, reference) {
+                    visiting.push(reference.to_string());
+                    let mut inlined = inline_value(target, root, visiting, recursive);
+                    visiting.pop();
+                    // Keywords next to the `$ref`, such as a description, take precedence
+                    if let JsonValue::Object(inlined_map) = &mut inlined {
+                        for (key, sibling) in map.iter().filter(|(key, _)| *key != "$ref") {
+                            inlined_map.insert(key.clone(), inline_value(sibling, root, visiting, recursive));
+                        }
+                    }
+                    return inlined;
+                }
+            }
+            JsonValue::Object(
+                map.iter()
+                    .map(|(key, child)| (key.clone(), inline_value(child, root, visiting, recursive)))
+                    .collect(),
+            )
+        }
+        JsonValue::Array(items) => JsonValue::Array(
+            items
+                .iter()
+                .map(|item| inline_value(item, root, visiting, recursive))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Self-contained JSON schema for a tool's arguments, for `AvailableTool::input_schema_json`
+pub fn tool_input_schema<T: JsonSchema>() -> JsonValue {
+    let schema = serde_json::to_value(schemars::schema_for!(T)).unwrap_or(JsonValue::Null);
+    inline_refs(&schema)
+}
+
+/// Set `additionalProperties: false` on every object in `schema`, as providers with strict
+/// tool schemas (Groq, OpenAI's strict mode) require
+pub fn forbid_additional_properties(schema: &mut JsonValue) {
+    match schema {
+        JsonValue::Object(map) => {
+            if map.get("type").and_then(JsonValue::as_str) == Some("object") {
+                map.insert("additionalProperties".to_string(), JsonValue::Bool(false));
+            }
+            map.values_mut().for_each(forbid_additional_properties);
+        }
+        JsonValue::Array(items) => items.iter_mut().for_each(forbid_additional_properties),
+        _ => {}
+    }
+}
+
+// Values of an `enum` array, or of `const` branches (schemars' form for documented variants)
+fn extract_allowed_values(field_json: &JsonValue) -> Option<Vec<String>> {
+    let label = |value: &JsonValue| match value {
+        JsonValue::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    if let Some(values) = field_json.get("enum").and_then(|e| e.as_array()) {
+        return Some(values.iter().map(label).collect());
+    }
+    const_branches(field_json).map(|values| values.into_iter().map(label).collect())
+}
+
+// The `const` of every `oneOf`/`anyOf` branch, if all branches are constants
+fn const_branches(field_json: &JsonValue) -> Option<Vec<&JsonValue>> {
+    let branches = field_json
+        .get("oneOf")
+        .or_else(|| field_json.get("anyOf"))?
+        .as_array()?;
+    branches.iter().map(|branch| branch.get("const")).collect()
+}
+
+// Whether a `type` array includes null, as in `["string", "null"]`
+fn allows_null_type(field_json: &JsonValue) -> bool {
+    field_json
+        .get("type")
+        .and_then(|t| t.as_array())
+        .is_some_and(|types| types.iter().any(|t| t.as_str() == Some("null")))
+}
+
+// The single non-null branch of an `anyOf`/`oneOf`
+fn nullable_inner(field_json: &JsonValue) -> Option<&JsonValue> {
+    let branches = field_json
+        .get("anyOf")
+        .or_else(|| field_json.get("oneOf"))?
+        .as_array()?;
+    let mut non_null = branches
+        .iter()
+        .filter(|branch| branch.get("type").and_then(|t| t.as_str()) != Some("null"));
+    let inner = non_null.next()?;
+    non_null.next().is_none().then_some(inner)
+}
+
 /// Extract type name from JSON schema field
 fn extract_type_name_from_json(field_json: &JsonValue) -> String {
     // Check for direct type field
     if let Some(type_value) = field_json.get("type") {
         if let Some(type_str) = type_value.as_str() {
-            return match type_str {
-                "string" => "String".to_string(),
-                "number" => "Number".to_string(),
-                "integer" => "Integer".to_string(),
-                "boolean" => "Boolean".to_string(),
-                "array" => "Array".to_string(),
-                "object" => "Object".to_string(),
-                "null" => "Null".to_string(),
-                _ => type_str.to_string(),
-            };
+            return type_label(type_str);
         }
-        // Handle array of types
+        // Handle array of types; null only marks the field optional
         if let Some(type_array) = type_value.as_array() {
             let types: Vec<String> = type_array
                 .iter()
                 .filter_map(|v| v.as_str())
-                .map(|s| s.to_string())
+                .filter(|t| *t != "null")
+                .map(type_label)
                 .collect();
             return types.join(" | ");
         }
     }
     
+    // Enums of documented string variants are `oneOf` string constants
+    if let Some(values) = const_branches(field_json)
+        && values.iter().all(|value| value.is_string())
+    {
+        return "String".to_string();
+    }
+
     // Check for anyOf, oneOf, allOf
     if field_json.get("anyOf").is_some() {
         return "AnyOf".to_string();
@@ -116,6 +368,19 @@ fn extract_type_name_from_json(field_json: &JsonValue) -> String {
     "Unknown".to_string()
 }
 
+fn type_label(type_str: &str) -> String {
+    match type_str {
+        "string" => "String".to_string(),
+        "number" => "Number".to_string(),
+        "integer" => "Integer".to_string(),
+        "boolean" => "Boolean".to_string(),
+        "array" => "Array".to_string(),
+        "object" => "Object".to_string(),
+        "null" => "Null".to_string(),
+        _ => type_str.to_string(),
+    }
+}
+
 /// Get a simplified field list for display purposes
 pub fn get_field_names_from_schema(schema: &Schema) -> Result<Vec<String>> {
     let fields = extract_fields_from_schema(schema)?;
@@ -130,7 +395,7 @@ pub fn get_field_descriptions_from_schema(schema: &Schema) -> Result<Vec<String>
         .values()
         .map(|info| {
             let desc = info.description.as_deref().unwrap_or("No description");
-            format!("- {}: {} ({})", info.name, desc, info.type_name)
+            format!("- {}: {} ({})", info.display_name(), desc, info.type_name)
         })
         .collect();
     
@@ -140,13 +405,13 @@ pub fn get_field_descriptions_from_schema(schema: &Schema) -> Result<Vec<String>
 // NEW: Functions that work with signature-filtered schemas
 
 /// Extract fields from a signature's prompt input schema (excludes special fields)
-pub fn extract_prompt_input_fields<S: Signature>() -> Result<HashMap<String, FieldInfo>> {
+pub fn extract_prompt_input_fields<S: Signature>() -> Result<IndexMap<String, FieldInfo>> {
     let schema = S::prompt_input_schema();
     extract_fields_from_schema(&schema)
 }
 
 /// Extract fields from a signature's prompt output schema (excludes special fields)  
-pub fn extract_prompt_output_fields<S: Signature>() -> Result<HashMap<String, FieldInfo>> {
+pub fn extract_prompt_output_fields<S: Signature>() -> Result<IndexMap<String, FieldInfo>> {
     let schema = S::prompt_output_schema();
     extract_fields_from_schema(&schema)
 }
@@ -171,7 +436,7 @@ pub fn get_prompt_input_descriptions<S: Signature>() -> Result<Vec<String>> {
         .values()
         .map(|info| {
             let desc = info.description.as_deref().unwrap_or("No description");
-            format!("- {}: {} ({})", info.name, desc, info.type_name)
+            format!("- {}: {} ({})", info.display_name(), desc, info.type_name)
         })
         .collect();
     
@@ -186,7 +451,7 @@ pub fn get_prompt_output_descriptions<S: Signature>() -> Result<Vec<String>> {
         .values()
         .map(|info| {
             let desc = info.description.as_deref().unwrap_or("No description");
-            format!("- {}: {} ({})", info.name, desc, info.type_name)
+            format!("- {}: {} ({})", info.display_name(), desc, info.type_name)
         })
         .collect();
     
@@ -218,4 +483,194 @@ mod tests {
         assert_eq!(fields["name"].type_name, "String");
         assert_eq!(fields["age"].type_name, "Integer");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_optional_fields_unwrap_to_inner_type() {
+        let schema = schemars::schema_for!(Metadata);
+        let fields = extract_fields_from_schema(&schema).unwrap();
+
+        let location = &fields["location"];
+        assert_eq!(location.type_name, "Object");
+        assert!(!location.required);
+        assert!(fields["author"].required);
+
+        let schema = schemars::schema_for!(TestStruct);
+        let fields = extract_fields_from_schema(&schema).unwrap();
+        assert_eq!(fields["email"].type_name, "String");
+        assert!(!fields["email"].required);
+    }
+
+    #[derive(crate::primatives::SignatureSchema, Serialize, Deserialize)]
+    struct OrderedStruct {
+        notes: String,
+        #[dsrs(field_order = 2)]
+        answer: String,
+        /// How sure the model is
+        #[dsrs(field_order = 1)]
+        confidence: f32,
+        extra: Option<String>,
+    }
+
+    #[test]
+    fn test_fields_sorted_by_field_order() {
+        let schema = schemars::schema_for!(OrderedStruct);
+        let fields = extract_fields_from_schema(&schema).unwrap();
+
+        let names: Vec<&str> = fields.keys().map(|k| k.as_str()).collect();
+        assert_eq!(names, vec!["confidence", "answer", "notes", "extra"]);
+        assert_eq!(fields["confidence"].description.as_deref(), Some("How sure the model is"));
+        assert_eq!(schema.get("title").and_then(|t| t.as_str()), Some("OrderedStruct"));
+    }
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    struct Metadata {
+        /// Who wrote the post
+        author: String,
+        tags: Vec<String>,
+        location: Option<Location>,
+    }
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    struct Location {
+        city: String,
+    }
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    struct Post {
+        title: String,
+        metadata: Metadata,
+    }
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    struct TreeNode {
+        label: String,
+        children: Vec<TreeNode>,
+        parent: Option<Box<TreeNode>>,
+    }
+
+    #[test]
+    fn test_nested_fields_follow_refs() {
+        let schema = schemars::schema_for!(Post);
+        let fields = extract_fields_from_schema(&schema).unwrap();
+
+        assert!(fields["title"].children.is_none());
+        let metadata = &fields["metadata"];
+        assert_eq!(metadata.type_name, "Object");
+        let children = metadata.children.as_ref().unwrap();
+        let names: Vec<&str> = children.keys().map(|k| k.as_str()).collect();
+        assert_eq!(names, vec!["author", "tags", "location"]);
+        assert_eq!(children["author"].description.as_deref(), Some("Who wrote the post"));
+        assert_eq!(children["tags"].item_type.as_deref(), Some("String"));
+        let location = children["location"].children.as_ref().unwrap();
+        assert_eq!(location["city"].type_name, "String");
+
+        assert_eq!(
+            metadata.type_description(),
+            "Object\n  - author (String): Who wrote the post\n  - tags (Array)\n  - location (Object)\n    - city (String)"
+        );
+    }
+
+    #[test]
+    fn test_recursive_refs_stop_expanding() {
+        let schema = schemars::schema_for!(TreeNode);
+        let fields = extract_fields_from_schema(&schema).unwrap();
+
+        let parent = fields["parent"].children.as_ref().unwrap();
+        assert!(parent.contains_key("label"));
+        assert!(parent["parent"].children.is_none());
+    }
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum Sentiment {
+        Positive,
+        Negative,
+        Neutral,
+    }
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    enum Priority {
+        /// Needs attention today
+        High,
+        /// Can wait
+        Low,
+    }
+
+    #[derive(JsonSchema, Serialize, Deserialize)]
+    struct Classification {
+        sentiment: Sentiment,
+        priority: Option<Priority>,
+    }
+
+    #[test]
+    fn test_enum_fields_list_allowed_values() {
+        let schema = schemars::schema_for!(Classification);
+        let fields = extract_fields_from_schema(&schema).unwrap();
+
+        let sentiment = &fields["sentiment"];
+        assert_eq!(sentiment.type_name, "String");
+        assert_eq!(
+            sentiment.display_name(),
+            "sentiment (one of: positive, negative, neutral)"
+        );
+        // Documented variants become `oneOf` constants
+        let priority = &fields["priority"];
+        assert_eq!(priority.type_name, "String");
+        assert_eq!(
+            priority.allowed_values,
+            Some(vec!["High".to_string(), "Low".to_string()])
+        );
+        assert!(fields["priority"].children.is_none());
+    }
+
+    #[test]
+    fn test_resolves_definitions_refs() {
+        let schema_json = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "owner": {"$ref": "#/definitions/Owner", "description": "Who owns it"}
+            },
+            "definitions": {
+                "Owner": {"$ref": "#/definitions/Person"},
+                "Person": {
+                    "type": "object",
+                    "properties": {"name": {"type": "string"}},
+                    "required": ["name"]
+                }
+            }
+        });
+
+        let fields = extract_fields_from_json(&schema_json).unwrap();
+
+        let owner = &fields["owner"];
+        assert_eq!(owner.type_name, "Object");
+        assert_eq!(owner.description.as_deref(), Some("Who owns it"));
+        let children = owner.children.as_ref().unwrap();
+        assert_eq!(children["name"].type_name, "String");
+        assert!(children["name"].required);
+    }
+
+    #[test]
+    fn test_tool_input_schema_inlines_refs() {
+        let schema = tool_input_schema::<Post>();
+
+        assert!(!schema.to_string().contains("$ref"));
+        assert!(schema.get("$defs").is_none());
+        assert_eq!(
+            schema.pointer("/properties/metadata/properties/author/type"),
+            Some(&serde_json::json!("string"))
+        );
+        assert!(schema.pointer("/properties/metadata/properties/location/anyOf/0/properties/city").is_some());
+    }
+
+    #[test]
+    fn test_inline_refs_keeps_recursive_refs() {
+        let schema = tool_input_schema::<TreeNode>();
+
+        assert!(schema.to_string().contains("$ref"));
+        assert_eq!(
+            schema.pointer("/properties/label/type"),
+            Some(&serde_json::json!("string"))
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use super::json_adapter::JsonAdapter;
+use super::traits::{Adapter, AdapterConfig};
+use crate::primatives::Signature;
+use crate::providers::models::ResponseFormat;
+use anyhow::{Result, anyhow};
+use schemars::Schema;
+use serde_json::Value as JsonValue;
+
+// OpenAI limits schema names to 64 characters
+const MAX_SCHEMA_NAME_LEN: usize = 64;
+
+/// JSON adapter that, with `use_native_function_calling`, sends the output schema as a
+/// strict `json_schema` response format so the provider guarantees a matching object.
+/// Without it, it behaves like `JsonAdapter`
+pub struct StructuredOutputAdapter {
+    json: JsonAdapter,
+    config: AdapterConfig,
+}
+
+impl StructuredOutputAdapter {
+    pub fn new(config: AdapterConfig) -> Self {
+        Self {
+            json: JsonAdapter::new(config.clone()),
+            config,
+        }
+    }
+}
+
+/// Rewrite a schemars schema into the subset strict structured outputs accept: every
+/// object closed to extra properties with all of its properties required, and no
+/// `format` on numbers
+pub fn strict_schema(schema: &JsonValue) -> JsonValue {
+    let mut schema = schema.clone();
+    if let JsonValue::Object(map) = &mut schema {
+        map.remove("$schema");
+    }
+    make_strict(&mut schema);
+    schema
+}
+
+fn make_strict(schema: &mut JsonValue) {
+    match schema {
+        JsonValue::Object(map) => {
+            let numeric = matches!(
+                map.get("type").and_then(JsonValue::as_str),
+                Some("number" | "integer")
+            );
+            if numeric {
+                map.remove("format");
+            }
+            if let Some(JsonValue::Object(properties)) = map.get("properties") {
+                let required = properties.keys().cloned().map(JsonValue::String).collect();
+                map.insert("required".to_string(), JsonValue::Array(required));
+                map.insert("additionalProperties".to_string(), JsonValue::Bool(false));
+            }
+            map.values_mut().for_each(make_strict);
+        }
+        JsonValue::Array(items) => items.iter_mut().for_each(make_strict),
+        _ => {}
+    }
+}
+
+// The schema title (the output type's name) limited to the characters OpenAI allows
+fn schema_name(schema: &JsonValue) -> String {
+    let name: String = schema
+        .get("title")
+        .and_then(JsonValue::as_str)
+        .unwrap_or("outputs")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .take(MAX_SCHEMA_NAME_LEN)
+        .collect();
+    if name.is_empty() {
+        "outputs".to_string()
+    } else {
+        name
+    }
+}
+
+impl<S: Signature> Adapter<S> for StructuredOutputAdapter {
+    fn config(&self) -> &AdapterConfig {
+        &self.config
+    }
+
+    fn format_field_description(&self, schema: &Schema) -> String {
+        <JsonAdapter as Adapter<S>>::format_field_description(&self.json, schema)
+    }
+
+    fn format_field_structure(&self, input_schema: &Schema, output_schema: &Schema) -> String {
+        <JsonAdapter as Adapter<S>>::format_field_structure(&self.json, input_schema, output_schema)
+    }
+
+    fn format_task_description(&self, instructions: &str) -> String {
+        <JsonAdapter as Adapter<S>>::format_task_description(&self.json, instructions)
+    }
+
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        <JsonAdapter as Adapter<S>>::format_user_message_content(
+            &self.json,
+            inputs,
+            input_schema,
+            output_schema,
+        )
+    }
+
+    fn prepend_context(&self, content: String, context: &str) -> String {
+        <JsonAdapter as Adapter<S>>::prepend_context(&self.json, content, context)
+    }
+
+    fn format_assistant_message_content(&self, outputs: &S::Outputs, schema: &Schema) -> String {
+        <JsonAdapter as Adapter<S>>::format_assistant_message_content(&self.json, outputs, schema)
+    }
+
+    fn parse(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        if !self.config.use_native_function_calling {
+            return <JsonAdapter as Adapter<S>>::parse(&self.json, completion, schema);
+        }
+
+        // The response format guarantees the whole completion is the object
+        serde_json::from_str(completion)
+            .map_err(|e| anyhow!("Failed to parse structured output: {}", e))
+    }
+
+    fn response_format(&self, output_schema: &Schema) -> Option<ResponseFormat> {
+        if !self.config.use_native_function_calling {
+            return None;
+        }
+
+        let schema = output_schema.as_value();
+        Some(ResponseFormat::JsonSchema {
+            name: schema_name(schema),
+            description: schema
+                .get("description")
+                .and_then(JsonValue::as_str)
+                .map(str::to_string),
+            schema: strict_schema(schema),
+            strict: true,
+        })
+    }
+}
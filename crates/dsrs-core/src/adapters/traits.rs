@@ -1,13 +1,32 @@
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
+use futures::StreamExt;
+use rand::Rng;
+use rand::seq::{IndexedRandom, SliceRandom};
 use schemars::{JsonSchema, Schema};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
 
+use super::context_window::ContextWindowManager;
+use super::utils::SystemMessageNormalizer;
 use crate::{
-    primatives::Signature,
-    providers::models::{ContentTypes, Message},
-    providers::{CompletionConfig, CompletionProvider},
+    evaluation::EvaluationMetric,
+    primatives::{
+        Signature, SignatureValidationError, ToolCallSet, ValidationErrors, validate_json_schema,
+        validate_schema,
+    },
+    providers::models::{
+        CompletionResponse, ContentTypes, FinishReason, Message, ResponseFormat, ToolCall,
+        UsageStats,
+    },
+    providers::{
+        CompletionConfig, ErasedCompletionProvider, RetryConfig, StreamChunk, retry_with_backoff,
+    },
+    tools::ToolExecutor,
 };
 
 // Represents a demo/example for few-shot learning
@@ -45,11 +64,162 @@ where
     }
 }
 
+impl<I, O> Demo<I, O>
+where
+    I: JsonSchema + Serialize + DeserializeOwned,
+    O: JsonSchema + Serialize + DeserializeOwned,
+{
+    /// Read demos from a JSONL file with one `{"inputs": ..., "outputs": ...}` object per line
+    pub fn load_jsonl(path: &Path) -> Result<Vec<Demo<I, O>>> {
+        let file = File::open(path)
+            .map_err(|e| anyhow!("Failed to open demos file {}: {}", path.display(), e))?;
+
+        let mut demos = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| anyhow!("Failed to read line {}: {}", index + 1, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let demo = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("Failed to parse demo on line {}: {}", index + 1, e))?;
+            demos.push(demo);
+        }
+        Ok(demos)
+    }
+
+    /// Write demos in the format read by `load_jsonl`, replacing the file's contents
+    pub fn save_jsonl(demos: &[Demo<I, O>], path: &Path) -> Result<()> {
+        let file = File::create(path)
+            .map_err(|e| anyhow!("Failed to create demos file {}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+
+        for (index, demo) in demos.iter().enumerate() {
+            let line = serde_json::to_string(demo)
+                .map_err(|e| anyhow!("Failed to serialize demo {}: {}", index, e))?;
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// How good the demo is by `metric`, with its outputs as both the expected and the
+    /// actual outputs, so only reference-free metrics give a meaningful score
+    pub fn score<S>(&self, metric: &dyn EvaluationMetric<S>) -> f64
+    where
+        S: Signature<Inputs = I, Outputs = O>,
+    {
+        metric.score(&self.inputs, &self.outputs, &self.outputs)
+    }
+
+    /// Keep the demos whose `score` is at least `threshold`, in order
+    pub fn filter_by_score<S>(
+        demos: Vec<Demo<I, O>>,
+        threshold: f64,
+        metric: &dyn EvaluationMetric<S>,
+    ) -> Vec<Demo<I, O>>
+    where
+        S: Signature<Inputs = I, Outputs = O>,
+    {
+        demos
+            .into_iter()
+            .filter(|demo| demo.score(metric) >= threshold)
+            .collect()
+    }
+
+    pub fn shuffle(demos: &mut [Demo<I, O>], rng: &mut impl Rng) {
+        demos.shuffle(rng);
+    }
+
+    /// `n` distinct demos in random order, or all of them if there are fewer
+    pub fn sample<'a>(
+        demos: &'a [Demo<I, O>],
+        n: usize,
+        rng: &mut impl Rng,
+    ) -> Vec<&'a Demo<I, O>> {
+        demos.choose_multiple(rng, n).collect()
+    }
+}
+
+// A single output field parsed from a completion
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldUpdate {
+    pub field_name: String,
+    pub value: serde_json::Value,
+    // False as the adapter parses each field, true once the outputs have also deserialized,
+    // passed validation and gone through `post_generate`
+    pub is_final: bool,
+}
+
+// Called for each output field as it is parsed, e.g. to drive a progressive UI
+pub type PartialOutputCallback = Arc<dyn Fn(FieldUpdate) + Send + Sync>;
+
+// Edits the assembled messages of each request before it is sent
+pub type PreGenerateHook = Arc<dyn Fn(&mut Vec<Message>) + Send + Sync>;
+
+// Edits the outputs, as JSON, before `generate` returns them
+pub type PostGenerateHook = Arc<dyn Fn(&mut serde_json::Value) + Send + Sync>;
+
+// Token count of a piece of text, for enforcing `max_context_tokens`
+pub type TokenCountFn = Arc<dyn Fn(&str) -> usize + Send + Sync>;
+
+// What to drop from a prompt over `max_context_tokens`. Demos and history turns are dropped
+// oldest first; the system message and the current inputs are always kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContextTruncationStrategy {
+    // Only drop demos
+    TruncateDemos,
+    // Only drop history turns
+    TruncateHistory,
+    // Drop messages in prompt order: the history, which comes first, then the demos
+    #[default]
+    TruncateOldestFirst,
+}
+
+// A generation in progress: the conversation and config that `Adapter::generate_step`
+// carries from one completion to the next
+pub struct Generation {
+    pub messages: Arc<tokio::sync::RwLock<Vec<Message>>>,
+    pub config: CompletionConfig,
+    // Completions that were truncated or rejected so far
+    pub rejected: usize,
+    // Requests sent, including retries of transient failures
+    pub requests: usize,
+    // Summed over every completion
+    pub usage: Option<UsageStats>,
+}
+
 // Configuration for adapters
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct AdapterConfig {
     pub use_native_function_calling: bool,
+    // Attempts at a usable completion; truncated and unparseable responses are retried
     pub max_retries: usize,
+    // Backoff for transient provider errors such as rate limits, within each attempt
+    pub retry: RetryConfig,
+    // Emit the formatted messages, raw completions and parse errors as `tracing` debug events
+    pub debug_mode: bool,
+    pub on_partial_output: Option<PartialOutputCallback>,
+    // Grow `max_tokens` by `MAX_TOKENS_EXPANSION_FACTOR` when a retry follows a truncated response
+    pub auto_expand_max_tokens: bool,
+    // Allow responses to be served from a `CachedProvider`; retries after a parse error
+    // always bypass the cache
+    pub enable_cache: bool,
+    // Token budget for the prompt, enforced by dropping demos and history turns
+    pub max_context_tokens: Option<usize>,
+    // Defaults to counting whitespace-separated words
+    pub token_count_fn: Option<TokenCountFn>,
+    pub context_truncation: ContextTruncationStrategy,
+    // Overrides `max_context_tokens`, `token_count_fn` and `context_truncation` when set
+    pub context_window: Option<ContextWindowManager>,
+    // On a parse or validation failure, retry with the rejected response and the error in
+    // the conversation rather than resending the same messages
+    pub use_correction_prompt: bool,
+    // Placed before and after the generated system message, e.g. for organization policies
+    pub system_prompt_prefix: Option<String>,
+    pub system_prompt_suffix: Option<String>,
+    // Run by the default `Adapter::pre_generate` and `Adapter::post_generate`
+    pub pre_generate: Option<PreGenerateHook>,
+    pub post_generate: Option<PostGenerateHook>,
 }
 
 impl Default for AdapterConfig {
@@ -57,11 +227,284 @@ impl Default for AdapterConfig {
         Self {
             use_native_function_calling: false,
             max_retries: 3,
+            retry: RetryConfig::default(),
+            debug_mode: false,
+            on_partial_output: None,
+            auto_expand_max_tokens: false,
+            enable_cache: true,
+            max_context_tokens: None,
+            token_count_fn: None,
+            context_truncation: ContextTruncationStrategy::default(),
+            context_window: None,
+            use_correction_prompt: true,
+            system_prompt_prefix: None,
+            system_prompt_suffix: None,
+            pre_generate: None,
+            post_generate: None,
+        }
+    }
+}
+
+impl AdapterConfig {
+    pub fn with_native_function_calling(mut self, enabled: bool) -> Self {
+        self.use_native_function_calling = enabled;
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_debug_mode(mut self, enabled: bool) -> Self {
+        self.debug_mode = enabled;
+        self
+    }
+
+    pub fn with_on_partial_output(
+        mut self,
+        callback: impl Fn(FieldUpdate) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_partial_output = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn with_auto_expand_max_tokens(mut self, enabled: bool) -> Self {
+        self.auto_expand_max_tokens = enabled;
+        self
+    }
+
+    pub fn with_cache(mut self, enabled: bool) -> Self {
+        self.enable_cache = enabled;
+        self
+    }
+
+    pub fn with_max_context_tokens(mut self, max_context_tokens: usize) -> Self {
+        self.max_context_tokens = Some(max_context_tokens);
+        self
+    }
+
+    pub fn with_token_count_fn(
+        mut self,
+        token_count_fn: impl Fn(&str) -> usize + Send + Sync + 'static,
+    ) -> Self {
+        self.token_count_fn = Some(Arc::new(token_count_fn));
+        self
+    }
+
+    pub fn with_context_truncation(mut self, strategy: ContextTruncationStrategy) -> Self {
+        self.context_truncation = strategy;
+        self
+    }
+
+    pub fn with_context_window(mut self, context_window: ContextWindowManager) -> Self {
+        self.context_window = Some(context_window);
+        self
+    }
+
+    pub fn with_correction_prompt(mut self, enabled: bool) -> Self {
+        self.use_correction_prompt = enabled;
+        self
+    }
+
+    pub fn with_system_prompt_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.system_prompt_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn with_system_prompt_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.system_prompt_suffix = Some(suffix.into());
+        self
+    }
+
+    pub fn with_pre_generate(
+        mut self,
+        hook: impl Fn(&mut Vec<Message>) + Send + Sync + 'static,
+    ) -> Self {
+        self.pre_generate = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn with_post_generate(
+        mut self,
+        hook: impl Fn(&mut serde_json::Value) + Send + Sync + 'static,
+    ) -> Self {
+        self.post_generate = Some(Arc::new(hook));
+        self
+    }
+}
+
+impl std::fmt::Debug for AdapterConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdapterConfig")
+            .field("use_native_function_calling", &self.use_native_function_calling)
+            .field("max_retries", &self.max_retries)
+            .field("retry", &self.retry)
+            .field("debug_mode", &self.debug_mode)
+            .field(
+                "on_partial_output",
+                &self.on_partial_output.as_ref().map(|_| "Fn(FieldUpdate)"),
+            )
+            .field("auto_expand_max_tokens", &self.auto_expand_max_tokens)
+            .field("enable_cache", &self.enable_cache)
+            .field("max_context_tokens", &self.max_context_tokens)
+            .field(
+                "token_count_fn",
+                &self.token_count_fn.as_ref().map(|_| "Fn(&str) -> usize"),
+            )
+            .field("context_truncation", &self.context_truncation)
+            .field("context_window", &self.context_window)
+            .field("use_correction_prompt", &self.use_correction_prompt)
+            .field("system_prompt_prefix", &self.system_prompt_prefix)
+            .field("system_prompt_suffix", &self.system_prompt_suffix)
+            .field(
+                "pre_generate",
+                &self.pre_generate.as_ref().map(|_| "Fn(&mut Vec<Message>)"),
+            )
+            .field(
+                "post_generate",
+                &self.post_generate.as_ref().map(|_| "Fn(&mut Value)"),
+            )
+            .finish()
+    }
+}
+
+// Number of characters of each message shown by `Adapter::debug_format_messages`
+const DEBUG_PREVIEW_CHARS: usize = 200;
+
+// Growth applied to `max_tokens` after a truncated response when `auto_expand_max_tokens` is set
+const MAX_TOKENS_EXPANSION_FACTOR: f32 = 1.5;
+
+const TRUNCATION_RETRY_MESSAGE: &str =
+    "Your previous response was cut off due to length. Please provide a more concise answer.";
+
+const VALIDATION_RETRY_MESSAGE: &str =
+    "Your previous response was invalid. Please answer again, correcting these errors:";
+
+const PARSE_RETRY_MESSAGE: &str = "Please try again following the format.";
+
+// What to tell the model about outputs that failed `validate_outputs`
+fn validation_feedback(error: &anyhow::Error) -> String {
+    format!("{} {}", VALIDATION_RETRY_MESSAGE, error)
+}
+
+// What to tell the model about a completion that failed to parse
+fn parse_feedback(error: &anyhow::Error) -> String {
+    format!(
+        "Your previous response was invalid: {}. {}",
+        error, PARSE_RETRY_MESSAGE
+    )
+}
+
+// Show the model its rejected response followed by what was wrong with it
+async fn push_correction(
+    messages: &tokio::sync::RwLock<Vec<Message>>,
+    response: String,
+    feedback: String,
+) {
+    let mut conversation = messages.write().await;
+    conversation.push(Message::assistant(Some(response), None));
+    conversation.push(Message::user(feedback));
+}
+
+// Tokens in the text of `messages`, by `count` or else by whitespace-separated words. The
+// one estimate shared by context budgets, history trimming and rate limiting
+pub(crate) fn count_message_tokens(
+    messages: &[Message],
+    count: Option<&dyn Fn(&str) -> usize>,
+) -> usize {
+    let count_text = |text: &str| match count {
+        Some(count) => count(text),
+        None => text.split_whitespace().count(),
+    };
+    // Images aren't counted
+    let content_tokens = |content: &ContentTypes| content.as_text().map_or(0, count_text);
+    messages
+        .iter()
+        .map(|message| match message {
+            Message::System { content } => content_tokens(content),
+            Message::User { content } => content.iter().map(content_tokens).sum(),
+            Message::Assistant { content, .. } => content.as_ref().map_or(0, content_tokens),
+            Message::Tool { content, .. } => content_tokens(content),
+        })
+        .sum()
+}
+
+// Send each output field of `outputs` to `on_partial_output` as final
+fn report_final_outputs<T: Serialize>(config: &AdapterConfig, outputs: &T, output_schema: &Schema) {
+    let Some(callback) = &config.on_partial_output else {
+        return;
+    };
+    let Ok(serde_json::Value::Object(values)) = serde_json::to_value(outputs) else {
+        return;
+    };
+    let fields = output_schema
+        .get("properties")
+        .and_then(serde_json::Value::as_object);
+    for (name, value) in values {
+        if fields.is_some_and(|fields| fields.contains_key(&name)) {
+            callback(FieldUpdate {
+                field_name: name,
+                value,
+                is_final: true,
+            });
         }
     }
 }
 
-// Core adapter trait - generic over signature types
+// `Signature::validate`'s errors as one error. Outputs capturing tool calls are fine when
+// the tools come from the config rather than the inputs
+fn check_signature<S: Signature>(signature: &S, config: &CompletionConfig) -> Result<()> {
+    let Err(errors) = signature.validate() else {
+        return Ok(());
+    };
+    let errors: Vec<String> = errors
+        .into_iter()
+        .filter(|error| {
+            config.tools.is_none() || *error != SignatureValidationError::ToolCallsWithoutTools
+        })
+        .map(|error| error.to_string())
+        .collect();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "Invalid signature {}: {}",
+        signature.name(),
+        errors.join("; ")
+    ))
+}
+
+// `schema` with the descriptions of its top-level fields replaced where `description` gives one
+pub(crate) fn with_field_descriptions(
+    schema: &Schema,
+    description: impl Fn(&str) -> Option<String>,
+) -> Schema {
+    let mut schema = schema.clone();
+    let properties = schema
+        .get_mut("properties")
+        .and_then(serde_json::Value::as_object_mut);
+    for (name, property) in properties.into_iter().flatten() {
+        if let (Some(text), Some(property)) = (description(name), property.as_object_mut()) {
+            property.insert("description".to_string(), serde_json::Value::String(text));
+        }
+    }
+    schema
+}
+
+// Next `max_tokens` after a truncated response, never beyond what the provider accepts
+fn expand_max_tokens(max_tokens: u32, limit: Option<u32>) -> u32 {
+    let expanded = (max_tokens as f32 * MAX_TOKENS_EXPANSION_FACTOR).ceil() as u32;
+    limit.map_or(expanded, |limit| expanded.min(limit))
+}
+
+// Core adapter trait - generic over signature types. Providers are taken as trait objects
+// so adapters themselves can be boxed, e.g. `Box<dyn Adapter<S>>` in `Predict`
 #[async_trait]
 pub trait Adapter<S: Signature>: Send + Sync {
     fn config(&self) -> &AdapterConfig;
@@ -70,22 +513,88 @@ pub trait Adapter<S: Signature>: Send + Sync {
     fn format_field_description(&self, schema: &Schema) -> String;
     fn format_field_structure(&self, input_schema: &Schema, output_schema: &Schema) -> String;
     fn format_task_description(&self, instructions: &str) -> String;
-    fn format_user_message_content(&self, inputs: &S::Inputs, schema: &Schema) -> String;
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String;
     fn format_assistant_message_content(&self, outputs: &S::Outputs, schema: &Schema) -> String;
 
+    // The user message content with `context` ahead of it, marked like the adapter's
+    // input fields
+    fn prepend_context(&self, content: String, context: &str) -> String {
+        format!("[[ ## context ## ]]\n{}\n\n{}", context, content)
+    }
+
     // Parse the completion back to the output type
     fn parse(&self, completion: &str, schema: &Schema) -> Result<S::Outputs>;
 
-    // Core functionality with default implementations
-    async fn generate(
+    // Like `parse`, without sending fields to `on_partial_output`, for parsing a completion
+    // that is not the outputs themselves, like a wrapping adapter's extra fields
+    fn parse_without_updates(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        self.parse(completion, schema)
+    }
+
+    // Native response format to request for the outputs; overrides the one in the base config
+    fn response_format(&self, _output_schema: &Schema) -> Option<ResponseFormat> {
+        None
+    }
+
+    // Check the inputs before any request is made: required fields and property types
+    // against the input schema, then the signature's input validators. Fails with
+    // `ValidationErrors`; override for custom checks
+    fn validate_inputs(&self, signature: &S, inputs: &S::Inputs) -> Result<()> {
+        let value = serde_json::to_value(inputs)?;
+        let mut errors = validate_schema(&value, &schemars::schema_for!(S::Inputs));
+        errors.extend(signature.input_validators().run_all(inputs));
+        Ok(ValidationErrors::new(errors).into_result()?)
+    }
+
+    // Check parsed outputs against the output schema, including constraints from `schemars`
+    // attributes such as patterns and ranges, then the signature's output validators.
+    // `generate` retries failures with the errors shown to the model
+    fn validate_outputs(&self, signature: &S, outputs: &S::Outputs) -> Result<()> {
+        let value = serde_json::to_value(outputs)?;
+        let mut errors = validate_json_schema(&value, &schemars::schema_for!(S::Outputs));
+        errors.extend(signature.output_validators().run_all(outputs));
+        Ok(ValidationErrors::new(errors).into_result()?)
+    }
+
+    // Called with the messages of each request before it is sent; runs the config's
+    // `pre_generate` hook by default
+    fn pre_generate(&self, messages: &mut Vec<Message>) {
+        if let Some(hook) = &self.config().pre_generate {
+            hook(messages);
+        }
+    }
+
+    // Called with the outputs before `generate` returns them; runs the config's
+    // `post_generate` hook on their JSON by default
+    fn post_generate(&self, outputs: &mut S::Outputs) -> Result<()> {
+        if let Some(hook) = &self.config().post_generate {
+            let mut value = serde_json::to_value(&*outputs)?;
+            hook(&mut value);
+            *outputs = serde_json::from_value(value)
+                .map_err(|e| anyhow!("post_generate produced invalid outputs: {}", e))?;
+        }
+        Ok(())
+    }
+
+    // Messages and completion config for a request, with special fields resolved and
+    // `context`, if any, ahead of the inputs
+    #[allow(clippy::too_many_arguments)]
+    fn prepare_request(
         &self,
-        provider: &impl CompletionProvider,
         base_config: CompletionConfig,
         signature: &S,
         instructions: &str,
         demos: &[Demo<S::Inputs, S::Outputs>],
         inputs: &S::Inputs,
-    ) -> Result<S::Outputs> {
+        context: Option<&str>,
+    ) -> Result<(Vec<Message>, CompletionConfig)> {
+        self.validate_inputs(signature, inputs)?;
+
         // Extract special fields from inputs
         let history = signature.extract_history(inputs);
         let tools = signature.extract_tools(inputs);
@@ -94,86 +603,169 @@ pub trait Adapter<S: Signature>: Send + Sync {
         let filtered_inputs = signature.filter_special_fields(inputs);
 
         // Get filtered schemas for prompt formatting
-        let input_schema = S::prompt_input_schema();
-        let output_schema = S::prompt_output_schema();
+        let input_schema = signature.input_schema();
+        let output_schema = signature.output_schema();
 
-        // Format messages using filtered inputs and schemas
-        let mut messages = self.format_messages_filtered(
-            signature,
-            instructions,
-            demos,
-            &filtered_inputs,
-            &input_schema,
-            &output_schema,
-        )?;
+        let history = history.unwrap_or_default();
+        let (budget, count_fn, truncation) = match &self.config().context_window {
+            Some(window) => (
+                Some(window.max_tokens()),
+                Some(window.count_fn()),
+                window.strategy().into(),
+            ),
+            None => (
+                self.config().max_context_tokens,
+                self.config().token_count_fn.as_ref(),
+                self.config().context_truncation,
+            ),
+        };
+        let (mut demo_start, mut history_start) = (0, 0);
+        let mut messages = loop {
+            // Format messages using filtered inputs and schemas
+            let mut messages = self.format_messages_filtered(
+                signature,
+                &base_config,
+                instructions,
+                &demos[demo_start..],
+                &filtered_inputs,
+                &input_schema,
+                &output_schema,
+                context,
+            )?;
 
-        // Prepend history if present (insert after system message)
-        if let Some(hist) = history {
+            // Prepend history if present (insert after system message)
+            let hist = history[history_start..].iter().cloned();
             if !messages.is_empty() {
                 messages.splice(1..1, hist);
             } else {
                 messages.extend(hist);
             }
-        }
+
+            let Some(budget) = budget else {
+                break messages;
+            };
+            let used = count_message_tokens(&messages, count_fn.map(|count| count.as_ref() as _));
+            if used <= budget {
+                break messages;
+            }
+
+            let can_drop_history = history_start < history.len()
+                && truncation != ContextTruncationStrategy::TruncateDemos;
+            let can_drop_demo = demo_start < demos.len()
+                && truncation != ContextTruncationStrategy::TruncateHistory;
+            if can_drop_history {
+                // Whole turns, from a user message up to the next, so the history still
+                // starts with a user message
+                history_start += 1;
+                while history
+                    .get(history_start)
+                    .is_some_and(|message| !matches!(message, Message::User { .. }))
+                {
+                    history_start += 1;
+                }
+            } else if can_drop_demo {
+                demo_start += 1;
+            } else {
+                tracing::warn!(
+                    tokens = used,
+                    budget,
+                    "Prompt exceeds max_context_tokens with nothing left to truncate"
+                );
+                break messages;
+            }
+        };
 
         // Build enhanced config with tools
         let config = CompletionConfig {
-            model: base_config.model,
             tools: tools.or(base_config.tools),
+            skip_cache: base_config.skip_cache || !self.config().enable_cache,
+            response_format: self
+                .response_format(&output_schema)
+                .or(base_config.response_format),
+            ..base_config
         };
 
-        let all_messages = std::sync::Arc::new(tokio::sync::RwLock::new(messages));
-
-        // Try with retries
-        for attempt in 0..self.config().max_retries {
-            match provider
-                .complete(all_messages.clone(), config.clone())
-                .await
-            {
-                Ok(response) => {
-                    if let Message::Assistant {
-                        content: Some(ContentTypes::Text(text)),
-                        tool_calls,
-                    } = response
-                    {
-                        // Parse regular outputs
-                        match self.parse(&text, &output_schema) {
-                            Ok(mut outputs) => {
-                                // Handle tool calls if present
-                                if let Some(calls) = tool_calls {
-                                    signature.inject_tool_calls(&mut outputs, calls.clone())?;
-                                    // Use signature's merge function for final result
-                                    return signature.merge_special_outputs(outputs, Some(calls));
-                                } else {
-                                    return signature.merge_special_outputs(outputs, None);
-                                }
-                            }
-                            Err(e) if attempt < self.config().max_retries - 1 => {
-                                eprintln!("Parse error on attempt {}: {}", attempt + 1, e);
-                                continue;
-                            }
-                            Err(e) => return Err(e),
-                        }
-                    } else if let Message::Assistant {
-                        content: None,
-                        tool_calls: Some(calls),
-                    } = response
-                    {
-                        // Handle tool-only responses
-                        let mut outputs = serde_json::from_value(serde_json::json!({}))?;
-                        signature.inject_tool_calls(&mut outputs, calls.clone())?;
-                        return signature.merge_special_outputs(outputs, Some(calls));
-                    } else {
-                        return Err(anyhow!(
-                            "Expected assistant message with text content or tool calls"
-                        ));
-                    }
-                }
-                Err(e) if attempt < self.config().max_retries - 1 => {
-                    eprintln!("Provider error on attempt {}: {}", attempt + 1, e);
-                    continue;
-                }
-                Err(e) => return Err(e.into()),
+        self.pre_generate(&mut messages);
+        Ok((messages, config))
+    }
+
+    // Core functionality with default implementations
+    async fn generate(
+        &self,
+        provider: &dyn ErasedCompletionProvider,
+        base_config: CompletionConfig,
+        signature: &S,
+        instructions: &str,
+        demos: &[Demo<S::Inputs, S::Outputs>],
+        inputs: &S::Inputs,
+    ) -> Result<S::Outputs> {
+        self.generate_with_context(
+            provider,
+            base_config,
+            signature,
+            instructions,
+            demos,
+            inputs,
+            None,
+        )
+        .await
+    }
+
+    // Like `generate`, with `context`, e.g. retrieved documents, shown ahead of the inputs
+    // as formatted by `prepend_context`
+    #[allow(clippy::too_many_arguments)]
+    async fn generate_with_context(
+        &self,
+        provider: &dyn ErasedCompletionProvider,
+        base_config: CompletionConfig,
+        signature: &S,
+        instructions: &str,
+        demos: &[Demo<S::Inputs, S::Outputs>],
+        inputs: &S::Inputs,
+        context: Option<&str>,
+    ) -> Result<S::Outputs> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "dsrs.generate",
+            llm.model = %base_config.model,
+            llm.retries = tracing::field::Empty,
+        );
+        let generation = self.generate_with_stats(
+            provider,
+            base_config,
+            signature,
+            instructions,
+            demos,
+            inputs,
+            context,
+        );
+        #[cfg(feature = "tracing")]
+        let generation = tracing::Instrument::instrument(generation, span);
+        let (outputs, _) = generation.await?;
+        Ok(outputs)
+    }
+
+    // Like `generate_with_context`, but also returns the final completion. Its usage covers
+    // every attempt, so retries are included in the token counts
+    #[allow(clippy::too_many_arguments)]
+    async fn generate_with_stats(
+        &self,
+        provider: &dyn ErasedCompletionProvider,
+        base_config: CompletionConfig,
+        signature: &S,
+        instructions: &str,
+        demos: &[Demo<S::Inputs, S::Outputs>],
+        inputs: &S::Inputs,
+        context: Option<&str>,
+    ) -> Result<(S::Outputs, CompletionResponse)> {
+        let output_schema = signature.output_schema();
+        let mut generation =
+            self.start_generation(base_config, signature, instructions, demos, inputs, context)?;
+
+        for _ in 0..self.config().max_retries {
+            let step = self.generate_step(provider, signature, &output_schema, &mut generation, None);
+            if let Some(result) = step.await? {
+                return Ok(result);
             }
         }
 
@@ -183,9 +775,292 @@ pub trait Adapter<S: Signature>: Send + Sync {
         ))
     }
 
+    // The first request of a generation, once the signature has been checked
+    fn start_generation(
+        &self,
+        base_config: CompletionConfig,
+        signature: &S,
+        instructions: &str,
+        demos: &[Demo<S::Inputs, S::Outputs>],
+        inputs: &S::Inputs,
+        context: Option<&str>,
+    ) -> Result<Generation> {
+        check_signature(signature, &base_config)?;
+        let (messages, config) =
+            self.prepare_request(base_config, signature, instructions, demos, inputs, context)?;
+        Ok(Generation {
+            messages: Arc::new(tokio::sync::RwLock::new(messages)),
+            config,
+            rejected: 0,
+            requests: 0,
+            usage: None,
+        })
+    }
+
+    // One completion of `generation`, with transient failures retried with backoff. Tool
+    // calls are run by `executor`, if given, and their results added to the conversation.
+    // Anything else is finished with `finish_outputs`; while `max_retries` allows, a
+    // truncated or rejected completion is added with a nudge or correction instead.
+    // `None` when another completion is needed
+    async fn generate_step(
+        &self,
+        provider: &dyn ErasedCompletionProvider,
+        signature: &S,
+        output_schema: &Schema,
+        generation: &mut Generation,
+        executor: Option<&dyn ToolExecutor>,
+    ) -> Result<Option<(S::Outputs, CompletionResponse)>> {
+        let attempt = generation.rejected + 1;
+        if self.config().debug_mode {
+            let formatted = self.debug_format_messages(&generation.messages.read().await);
+            tracing::debug!(attempt, "Sending messages:\n{}", formatted);
+        }
+
+        let response = retry_with_backoff(&self.config().retry, || {
+            generation.requests += 1;
+            provider.complete_erased(generation.messages.clone(), generation.config.clone())
+        })
+        .await;
+        // Transient and parse retries so far, for the `generate` span
+        tracing::Span::current().record("llm.retries", generation.requests - 1);
+        // Already retried with backoff if transient
+        let response = response?;
+        if let Some(usage) = response.usage {
+            generation.usage = Some(generation.usage.unwrap_or_default() + usage);
+        }
+        let stats = CompletionResponse {
+            usage: generation.usage,
+            ..response.clone()
+        };
+        let Message::Assistant {
+            content,
+            tool_calls,
+        } = response.message
+        else {
+            return Err(anyhow!(
+                "Expected assistant message with text content or tool calls"
+            ));
+        };
+
+        if let (Some(executor), Some(calls)) = (executor, &tool_calls)
+            && !calls.is_empty()
+        {
+            let results = ToolCallSet {
+                calls: calls.clone(),
+            }
+            .execute_and_to_messages(executor)
+            .await;
+            let mut conversation = generation.messages.write().await;
+            conversation.push(Message::Assistant {
+                content,
+                tool_calls,
+            });
+            conversation.extend(results);
+            return Ok(None);
+        }
+
+        let can_retry = attempt < self.config().max_retries;
+        // Truncated output rarely parses, so retry with a nudge while attempts remain
+        if stats.finish_reason == FinishReason::Length {
+            tracing::warn!("Response truncated by max_tokens limit");
+            if can_retry {
+                generation.rejected += 1;
+                {
+                    let mut conversation = generation.messages.write().await;
+                    conversation.push(Message::Assistant {
+                        content,
+                        tool_calls,
+                    });
+                    conversation.push(Message::user(TRUNCATION_RETRY_MESSAGE));
+                }
+                if self.config().auto_expand_max_tokens {
+                    generation.config.max_tokens = generation.config.max_tokens.map(|max_tokens| {
+                        expand_max_tokens(max_tokens, provider.max_context_tokens_erased())
+                    });
+                }
+                return Ok(None);
+            }
+        }
+
+        let text = match content {
+            Some(ContentTypes::Text(text)) => text,
+            None if tool_calls.is_some() => String::new(),
+            _ => {
+                return Err(anyhow!(
+                    "Expected assistant message with text content or tool calls"
+                ));
+            }
+        };
+        if self.config().debug_mode {
+            tracing::debug!(attempt, "Raw completion:\n{}", text);
+        }
+
+        let calls = tool_calls.unwrap_or_default();
+        match self.finish_outputs(signature, &text, calls, output_schema) {
+            Ok(outputs) => Ok(Some((outputs, stats))),
+            Err(e) if can_retry => {
+                generation.rejected += 1;
+                let invalid = e.is::<ValidationErrors>();
+                if self.config().debug_mode {
+                    let kind = if invalid { "Validation" } else { "Parse" };
+                    tracing::debug!(attempt, "{} error: {}", kind, e);
+                }
+                if self.config().use_correction_prompt {
+                    let feedback = if invalid {
+                        validation_feedback(&e)
+                    } else {
+                        parse_feedback(&e)
+                    };
+                    push_correction(&generation.messages, text, feedback).await;
+                }
+                // A cached response would be rejected the same way
+                generation.config.skip_cache = true;
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Like `generate`, but streams the completion and passes each text token to `on_token`
+    // as it arrives. There are no retries, since tokens have already been shown to the caller
+    #[allow(clippy::too_many_arguments)]
+    async fn generate_streaming(
+        &self,
+        provider: &dyn ErasedCompletionProvider,
+        base_config: CompletionConfig,
+        signature: &S,
+        instructions: &str,
+        demos: &[Demo<S::Inputs, S::Outputs>],
+        inputs: &S::Inputs,
+        on_token: &(dyn for<'t> Fn(&'t str) + Send + Sync),
+    ) -> Result<S::Outputs> {
+        let output_schema = signature.output_schema();
+        let generation =
+            self.start_generation(base_config, signature, instructions, demos, inputs, None)?;
+
+        let mut text = String::new();
+        let mut calls = Vec::new();
+        let mut chunks = provider.stream_erased(generation.messages, generation.config);
+        while let Some(chunk) = chunks.next().await {
+            match chunk? {
+                StreamChunk::Text(token) => {
+                    on_token(&token);
+                    text.push_str(&token);
+                }
+                StreamChunk::ToolCall(call) => calls.push(call),
+            }
+        }
+
+        if self.config().debug_mode {
+            tracing::debug!("Raw streamed completion:\n{}", text);
+        }
+
+        self.finish_outputs(signature, &text, calls, &output_schema)
+    }
+
+    // Like `generate` for each of `inputs`, with the first attempts sent together through
+    // `complete_many`. Inputs whose first completion fails, is truncated or doesn't parse
+    // are retried on their own with `generate`. Results are in input order
+    async fn generate_batch(
+        &self,
+        provider: &dyn ErasedCompletionProvider,
+        base_config: CompletionConfig,
+        signature: &S,
+        instructions: &str,
+        demos: &[Demo<S::Inputs, S::Outputs>],
+        inputs: &[S::Inputs],
+    ) -> Vec<Result<S::Outputs>> {
+        let output_schema = signature.output_schema();
+        let mut results: Vec<Option<Result<S::Outputs>>> = Vec::new();
+        let mut requests = Vec::new();
+        for input in inputs {
+            let config = base_config.clone();
+            match self.start_generation(config, signature, instructions, demos, input, None) {
+                Ok(generation) => {
+                    requests.push((generation.messages, generation.config));
+                    results.push(None);
+                }
+                Err(e) => results.push(Some(Err(e))),
+            }
+        }
+
+        let mut responses = provider.complete_many_erased(requests).await.into_iter();
+        for result in results.iter_mut().filter(|result| result.is_none()) {
+            *result = match responses.next() {
+                Some(Ok(response)) if response.finish_reason != FinishReason::Length => {
+                    match response.message {
+                        Message::Assistant {
+                            content,
+                            tool_calls,
+                        } => {
+                            let text = content.as_ref().and_then(ContentTypes::as_text);
+                            self.finish_outputs(
+                                signature,
+                                text.unwrap_or_default(),
+                                tool_calls.unwrap_or_default(),
+                                &output_schema,
+                            )
+                            .ok()
+                            .map(Ok)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+        }
+
+        let retries = inputs
+            .iter()
+            .zip(&results)
+            .filter(|(_, result)| result.is_none())
+            .map(|(input, _)| {
+                self.generate(provider, base_config.clone(), signature, instructions, demos, input)
+            });
+        let mut retried = futures::future::join_all(retries).await.into_iter();
+        results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| retried.next().expect("a retry for every missing result"))
+            })
+            .collect()
+    }
+
+    // The outputs for a completion's text and tool calls, validated and passed through
+    // `post_generate`
+    fn finish_outputs(
+        &self,
+        signature: &S,
+        text: &str,
+        calls: Vec<ToolCall>,
+        output_schema: &Schema,
+    ) -> Result<S::Outputs> {
+        // Tool-only responses carry no text to parse
+        let tool_only = text.is_empty() && !calls.is_empty();
+        let mut outputs = if tool_only {
+            serde_json::from_value(serde_json::json!({}))?
+        } else {
+            let outputs = self.parse(text, output_schema)?;
+            self.validate_outputs(signature, &outputs)?;
+            outputs
+        };
+        let mut outputs = if calls.is_empty() {
+            signature.merge_special_outputs(outputs, None)?
+        } else {
+            signature.inject_tool_calls(&mut outputs, calls.clone())?;
+            signature.merge_special_outputs(outputs, Some(calls))?
+        };
+        self.post_generate(&mut outputs)?;
+        if !tool_only {
+            report_final_outputs(self.config(), &outputs, output_schema);
+        }
+        Ok(outputs)
+    }
+
     // Original format_messages for backward compatibility
     fn format_messages(
         &self,
+        config: &CompletionConfig,
         instructions: &str,
         demos: &[Demo<S::Inputs, S::Outputs>],
         inputs: &S::Inputs,
@@ -195,57 +1070,134 @@ pub trait Adapter<S: Signature>: Send + Sync {
         let output_schema = self.get_output_schema();
 
         self.format_messages_with_schemas(
+            config,
             instructions,
             demos,
             inputs,
             &input_schema,
             &output_schema,
+            None,
+        )
+    }
+
+    // Like `format_messages`, with `context` ahead of the inputs in the user message
+    fn format_messages_with_context(
+        &self,
+        config: &CompletionConfig,
+        instructions: &str,
+        demos: &[Demo<S::Inputs, S::Outputs>],
+        inputs: &S::Inputs,
+        context: Option<&str>,
+    ) -> Result<Vec<Message>> {
+        let input_schema = self.get_input_schema();
+        let output_schema = self.get_output_schema();
+
+        self.format_messages_with_schemas(
+            config,
+            instructions,
+            demos,
+            inputs,
+            &input_schema,
+            &output_schema,
+            context,
         )
     }
 
     // New format_messages_filtered that uses signature-provided schemas
+    #[allow(clippy::too_many_arguments)]
     fn format_messages_filtered(
         &self,
-        _signature: &S,
+        signature: &S,
+        config: &CompletionConfig,
         instructions: &str,
         demos: &[Demo<S::Inputs, S::Outputs>],
         inputs: &S::Inputs,
         input_schema: &Schema,
         output_schema: &Schema,
+        context: Option<&str>,
     ) -> Result<Vec<Message>> {
-        self.format_messages_with_schemas(instructions, demos, inputs, input_schema, output_schema)
+        let input_schema = with_field_descriptions(input_schema, |field| {
+            signature.description_for_field(field, true)
+        });
+        let output_schema = with_field_descriptions(output_schema, |field| {
+            signature.description_for_field(field, false)
+        });
+        self.format_messages_with_schemas(
+            config,
+            instructions,
+            demos,
+            inputs,
+            &input_schema,
+            &output_schema,
+            context,
+        )
     }
 
     // Common implementation for both message formatting approaches
+    #[allow(clippy::too_many_arguments)]
     fn format_messages_with_schemas(
         &self,
+        config: &CompletionConfig,
         instructions: &str,
         demos: &[Demo<S::Inputs, S::Outputs>],
         inputs: &S::Inputs,
         input_schema: &Schema,
         output_schema: &Schema,
+        context: Option<&str>,
     ) -> Result<Vec<Message>> {
         let mut messages = Vec::new();
 
         // System message
-        let system_content = format!(
-            "{}\n{}\n{}",
-            self.format_field_description(input_schema),
-            self.format_field_structure(input_schema, output_schema),
-            self.format_task_description(instructions)
-        );
-        messages.push(Message::system(system_content));
+        messages.push(Message::system(self.format_system_message(
+            config,
+            instructions,
+            input_schema,
+            output_schema,
+        )));
 
         // Add few-shot examples
         messages.extend(self.format_demos_with_schemas(demos, input_schema, output_schema)?);
 
         // Add current input
-        let user_content = self.format_user_message_content(inputs, input_schema);
+        let mut user_content =
+            self.format_user_message_content(inputs, input_schema, output_schema);
+        if let Some(context) = context {
+            user_content = self.prepend_context(user_content, context);
+        }
         messages.push(Message::user(user_content));
 
         Ok(messages)
     }
 
+    // System message content; it never depends on the inputs, so it doubles as a stable cache key
+    fn format_system_message(
+        &self,
+        config: &CompletionConfig,
+        instructions: &str,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        let system_content = format!(
+            "{}\n{}\n{}",
+            self.format_field_description(input_schema),
+            self.format_field_structure(input_schema, output_schema),
+            self.format_task_description(instructions)
+        );
+        let adapter_config = self.config();
+        let system_content = [
+            adapter_config.system_prompt_prefix.as_deref(),
+            Some(system_content.as_str()),
+            adapter_config.system_prompt_suffix.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n");
+        // Global policies from the completion config apply regardless of adapter
+        let system_content = config.apply_system_injections(system_content);
+        SystemMessageNormalizer::normalize(&system_content)
+    }
+
     fn format_demos(&self, demos: &[Demo<S::Inputs, S::Outputs>]) -> Result<Vec<Message>> {
         let input_schema = self.get_input_schema();
         let output_schema = self.get_output_schema();
@@ -262,7 +1214,7 @@ pub trait Adapter<S: Signature>: Send + Sync {
 
         for demo in demos {
             messages.push(Message::user(
-                self.format_user_message_content(&demo.inputs, input_schema),
+                self.format_user_message_content(&demo.inputs, input_schema, output_schema),
             ));
             messages.push(Message::assistant(
                 Some(self.format_assistant_message_content(&demo.outputs, output_schema)),
@@ -273,6 +1225,51 @@ pub trait Adapter<S: Signature>: Send + Sync {
         Ok(messages)
     }
 
+    // Human-readable listing of messages: role plus a truncated content preview
+    fn debug_format_messages(&self, messages: &[Message]) -> String {
+        let preview = |content: &ContentTypes| match content {
+            ContentTypes::Text(text) if text.chars().count() > DEBUG_PREVIEW_CHARS => {
+                format!("{}...", text.chars().take(DEBUG_PREVIEW_CHARS).collect::<String>())
+            }
+            ContentTypes::Text(text) => text.clone(),
+            ContentTypes::Image { url, .. } => format!("<image {}>", url),
+            ContentTypes::ImageBytes {
+                data, media_type, ..
+            } => format!("<{} image, {} bytes>", media_type, data.len()),
+        };
+
+        messages
+            .iter()
+            .enumerate()
+            .map(|(index, message)| match message {
+                Message::System { content } => format!("[{}] system: {}", index, preview(content)),
+                Message::User { content } => {
+                    let parts: Vec<String> = content.iter().map(preview).collect();
+                    format!("[{}] user: {}", index, parts.join(" "))
+                }
+                Message::Assistant {
+                    content,
+                    tool_calls,
+                } => {
+                    let text = content.as_ref().map(preview).unwrap_or_default();
+                    let calls = tool_calls
+                        .as_ref()
+                        .map(|calls| {
+                            let names: Vec<&str> = calls.iter().map(|c| c.name.as_str()).collect();
+                            format!(" [tool calls: {}]", names.join(", "))
+                        })
+                        .unwrap_or_default();
+                    format!("[{}] assistant: {}{}", index, text, calls)
+                }
+                Message::Tool {
+                    content,
+                    tool_call_id,
+                } => format!("[{}] tool ({}): {}", index, tool_call_id, preview(content)),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     // Helper methods to get schemas
     fn get_input_schema(&self) -> Schema {
         schemars::schema_for!(S::Inputs)
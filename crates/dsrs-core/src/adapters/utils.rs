@@ -19,6 +19,14 @@ pub fn parse_value<T: serde::de::DeserializeOwned>(value_str: &str) -> Result<T>
     }
 }
 
+/// Read a delimited field's text as JSON, except `String` fields which are taken as-is
+pub fn field_value(text: &str, type_name: &str) -> JsonValue {
+    if type_name == "String" {
+        return JsonValue::String(text.to_string());
+    }
+    serde_json::from_str(text).unwrap_or_else(|_| JsonValue::String(text.to_string()))
+}
+
 /// Format a value for display
 pub fn format_value<T: Serialize>(value: &T) -> String {
     match serde_json::to_value(value) {
@@ -27,3 +35,187 @@ pub fn format_value<T: Serialize>(value: &T) -> String {
         Err(_) => "error".to_string(),
     }
 }
+
+/// Format a field's value for a prompt by its schema type: booleans, numbers and strings
+/// appear bare, arrays one item per line and objects as indented JSON
+pub fn format_field_value(value: &JsonValue, field_info: &FieldInfo) -> String {
+    match (field_info.type_name.as_str(), value) {
+        ("String", JsonValue::String(text)) => text.clone(),
+        ("Boolean" | "Number" | "Integer", JsonValue::Bool(_) | JsonValue::Number(_)) => {
+            value.to_string()
+        }
+        // Empty arrays stay `[]` so they read back as arrays
+        ("Array", JsonValue::Array(items)) if !items.is_empty() => items
+            .iter()
+            .map(|item| match item {
+                JsonValue::String(text) => text.clone(),
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ("Object", JsonValue::Object(_)) => {
+            serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+        }
+        _ => format_value(value),
+    }
+}
+
+/// Heuristically fix almost-valid JSON from a model: strips markdown code fences and any
+/// text around the outermost object, turns single-quoted strings into double-quoted ones,
+/// quotes bare keys and drops trailing commas
+pub fn json_repair(text: &str) -> String {
+    let text = strip_code_fence(text);
+    let text = match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text,
+    };
+
+    let mut repaired = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                repaired.push('"');
+                while let Some(c2) = chars.next() {
+                    match c2 {
+                        '\\' => match chars.next() {
+                            // `\'` isn't a valid JSON escape
+                            Some('\'') => repaired.push('\''),
+                            Some(escaped) => {
+                                repaired.push('\\');
+                                repaired.push(escaped);
+                            }
+                            None => break,
+                        },
+                        _ if c2 == c => break,
+                        // A double quote inside a single-quoted string
+                        '"' => repaired.push_str("\\\""),
+                        _ => repaired.push(c2),
+                    }
+                }
+                repaired.push('"');
+            }
+            '}' | ']' => {
+                let trimmed = repaired.trim_end();
+                if trimmed.ends_with(',') {
+                    repaired.truncate(trimmed.len() - 1);
+                }
+                repaired.push(c);
+            }
+            c if (c.is_alphabetic() || c == '_' || c == '$')
+                && matches!(repaired.trim_end().chars().last(), Some('{' | ',')) =>
+            {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_alphanumeric() || next == '_' || next == '$') {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                let rest = chars.clone().find(|c| !c.is_whitespace());
+                if rest == Some(':') {
+                    repaired.push('"');
+                    repaired.push_str(&word);
+                    repaired.push('"');
+                } else {
+                    // A value such as `true` in an array
+                    repaired.push_str(&word);
+                }
+            }
+            _ => repaired.push(c),
+        }
+    }
+    repaired
+}
+
+// The contents of the first ```-fenced block, or the whole text if there is none
+fn strip_code_fence(text: &str) -> &str {
+    let Some(start) = text.find("```") else {
+        return text;
+    };
+    let body = &text[start + 3..];
+    // Skip the language tag, e.g. ```json
+    let body = body.find('\n').map_or(body, |newline| &body[newline + 1..]);
+    body.find("```").map_or(body, |end| &body[..end])
+}
+
+/// Normalizes system message whitespace so identical prompts serialize to identical bytes,
+/// which keeps provider-side prompt caching effective
+pub struct SystemMessageNormalizer;
+
+impl SystemMessageNormalizer {
+    /// Strip trailing whitespace per line, collapse runs of blank lines and end with one newline
+    pub fn normalize(content: &str) -> String {
+        let mut normalized = String::with_capacity(content.len() + 1);
+        let mut previous_blank = false;
+
+        for line in content.trim().lines() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                if previous_blank {
+                    continue;
+                }
+                previous_blank = true;
+            } else {
+                previous_blank = false;
+            }
+            normalized.push_str(line);
+            normalized.push('\n');
+        }
+
+        normalized
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_system_message() {
+        let content = "  Rules:  \n\n\n\n- be concise \t\n- be kind\n\n";
+        assert_eq!(
+            SystemMessageNormalizer::normalize(content),
+            "Rules:\n\n- be concise\n- be kind\n"
+        );
+    }
+
+    #[test]
+    fn test_format_field_value_by_type() {
+        #[derive(schemars::JsonSchema)]
+        #[allow(dead_code)]
+        struct Fields {
+            done: bool,
+            score: f64,
+            note: String,
+            meta: std::collections::BTreeMap<String, u32>,
+        }
+        let fields = extract_fields(&schemars::schema_for!(Fields)).unwrap();
+        let format = |name: &str, value: JsonValue| format_field_value(&value, &fields[name]);
+
+        assert_eq!(format("done", serde_json::json!(true)), "true");
+        assert_eq!(format("score", serde_json::json!(0.5)), "0.5");
+        assert_eq!(format("note", serde_json::json!("say \"hi\"")), "say \"hi\"");
+        assert_eq!(format("meta", serde_json::json!({"a": 1})), "{\n  \"a\": 1\n}");
+    }
+
+    #[test]
+    fn test_json_repair_fixes_common_mistakes() {
+        let completion = "Here you go:\n```json\n{answer: 'It\\'s \"Paris\"', tags: ['a', true,],}\n```";
+
+        let repaired: JsonValue = serde_json::from_str(&json_repair(completion)).unwrap();
+
+        assert_eq!(
+            repaired,
+            serde_json::json!({"answer": "It's \"Paris\"", "tags": ["a", true]})
+        );
+    }
+
+    #[test]
+    fn test_json_repair_leaves_valid_json_and_string_contents_alone() {
+        let json = r#"{"note": "a, b} {c: 'd'}", "n": [1, 2]}"#;
+
+        assert_eq!(json_repair(json), json);
+    }
+}
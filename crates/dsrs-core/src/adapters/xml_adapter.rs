@@ -0,0 +1,264 @@
+use super::traits::{Adapter, AdapterConfig};
+use super::utils::*;
+use crate::primatives::Signature;
+use anyhow::{Result, anyhow};
+use schemars::Schema;
+use serde_json::Value as JsonValue;
+
+pub struct XmlAdapter {
+    config: AdapterConfig,
+}
+
+impl XmlAdapter {
+    pub fn new(config: AdapterConfig) -> Self {
+        Self { config }
+    }
+}
+
+/// Escape the characters that would otherwise be read as markup
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Decode a single entity body (without `&` and `;`), e.g. `amp` or `#x27`
+fn decode_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let code = match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => entity.strip_prefix('#')?.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+const CDATA_START: &str = "<![CDATA[";
+const CDATA_END: &str = "]]>";
+
+/// Scan `xml` for `<tag>...</tag>` elements whose tag passes `is_field` and return each
+/// tag with its text, with entities decoded and CDATA sections taken verbatim. Other tags,
+/// e.g. a wrapping `<response>`, are skipped, and markup nested inside an element is kept
+/// as text, so values may themselves contain XML.
+pub fn parse_xml_elements(xml: &str, is_field: impl Fn(&str) -> bool) -> Vec<(String, String)> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let Some((tag, after_open)) = open_tag(rest).filter(|(tag, _)| is_field(tag)) else {
+            rest = &rest[1..];
+            continue;
+        };
+        match element_text(after_open, tag) {
+            Some((text, after_close)) => {
+                elements.push((tag.to_string(), text));
+                rest = after_close;
+            }
+            // An unclosed tag takes the remaining text, e.g. a completion cut off mid-field
+            None => {
+                elements.push((tag.to_string(), decode(after_open).trim().to_string()));
+                break;
+            }
+        }
+    }
+
+    elements
+}
+
+// `<name>` at the start of `text`, returning the name and what follows the tag
+fn open_tag(text: &str) -> Option<(&str, &str)> {
+    let end = text.find('>')?;
+    let name = &text[1..end];
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.');
+    valid.then(|| (name, &text[end + 1..]))
+}
+
+// The decoded text up to the `</tag>` closing this element, and what follows it
+fn element_text<'a>(text: &'a str, tag: &str) -> Option<(String, &'a str)> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut depth = 0;
+    let mut index = 0;
+
+    while index < text.len() {
+        let rest = &text[index..];
+        if rest.starts_with(CDATA_START) {
+            let end = rest.find(CDATA_END).map_or(rest.len(), |end| end + CDATA_END.len());
+            index += end;
+        } else if rest.starts_with(&close) {
+            if depth == 0 {
+                let raw = &text[..index];
+                return Some((decode(raw).trim().to_string(), &rest[close.len()..]));
+            }
+            depth -= 1;
+            index += close.len();
+        } else if rest.starts_with(&open) {
+            depth += 1;
+            index += open.len();
+        } else {
+            index += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    None
+}
+
+// Resolve entities outside of CDATA sections and unwrap the sections themselves
+fn decode(raw: &str) -> String {
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix(CDATA_START) {
+            let end = after.find(CDATA_END).unwrap_or(after.len());
+            decoded.push_str(&after[..end]);
+            rest = after.get(end + CDATA_END.len()..).unwrap_or("");
+        } else if rest.starts_with('&')
+            && let Some(end) = rest.find(';')
+            && let Some(c) = decode_entity(&rest[1..end])
+        {
+            decoded.push(c);
+            rest = &rest[end + 1..];
+        } else {
+            let c = rest.chars().next().unwrap();
+            decoded.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    decoded
+}
+
+impl<S: Signature> Adapter<S> for XmlAdapter {
+    fn config(&self) -> &AdapterConfig {
+        &self.config
+    }
+
+    fn format_field_description(&self, schema: &Schema) -> String {
+        let fields = extract_fields(schema).unwrap_or_default();
+
+        let descriptions: Vec<String> = fields
+            .values()
+            .map(|info| {
+                let desc = info.description.as_deref().unwrap_or("No description");
+                format!("- {}: {} ({})", info.display_name(), desc, info.type_name)
+            })
+            .collect();
+
+        descriptions.join("\n")
+    }
+
+    fn format_field_structure(&self, input_schema: &Schema, output_schema: &Schema) -> String {
+        let mut parts = Vec::new();
+        parts.push("All interactions will be structured in the following way, with the appropriate values filled in.".to_string());
+
+        let input_fields = extract_fields(input_schema).unwrap_or_default();
+        for (name, info) in &input_fields {
+            parts.push(format!("<{0}>\n{1}\n</{0}>", name, info.type_description()));
+        }
+
+        let output_fields = extract_fields(output_schema).unwrap_or_default();
+        for (name, info) in &output_fields {
+            parts.push(format!("<{0}>\n{1}\n</{0}>", name, info.type_description()));
+        }
+
+        parts.push(
+            "Escape `<`, `>` and `&` inside values, or wrap the value in a CDATA section."
+                .to_string(),
+        );
+
+        parts.join("\n\n")
+    }
+
+    fn format_task_description(&self, instructions: &str) -> String {
+        format!("Your task: {}", instructions)
+    }
+
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        let fields = extract_fields(input_schema).unwrap_or_default();
+        let json_value = serde_json::to_value(inputs).unwrap_or(JsonValue::Null);
+
+        let mut parts = Vec::new();
+
+        if let JsonValue::Object(map) = json_value {
+            for name in fields.keys() {
+                if let Some(value) = map.get(name) {
+                    let formatted = escape_xml(&format_value(value));
+                    parts.push(format!("<{0}>\n{1}\n</{0}>", name, formatted));
+                }
+            }
+        }
+
+        // Add output requirements
+        let output_fields = extract_fields(output_schema).unwrap_or_default();
+        let field_names: Vec<String> = output_fields
+            .keys()
+            .map(|name| format!("`<{}>`", name))
+            .collect();
+
+        parts.push(format!(
+            "Respond with each output field wrapped in its XML tag: {}.",
+            field_names.join(", then ")
+        ));
+
+        parts.join("\n\n")
+    }
+
+    fn prepend_context(&self, content: String, context: &str) -> String {
+        format!("<context>\n{}\n</context>\n\n{}", escape_xml(context), content)
+    }
+
+    fn format_assistant_message_content(&self, outputs: &S::Outputs, schema: &Schema) -> String {
+        let fields = extract_fields(schema).unwrap_or_default();
+        let json_value = serde_json::to_value(outputs).unwrap_or(JsonValue::Null);
+
+        let mut parts = Vec::new();
+
+        if let JsonValue::Object(map) = json_value {
+            for name in fields.keys() {
+                if let Some(value) = map.get(name) {
+                    let formatted = escape_xml(&format_value(value));
+                    parts.push(format!("<{0}>\n{1}\n</{0}>", name, formatted));
+                }
+            }
+        }
+
+        parts.join("\n\n")
+    }
+
+    fn parse(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        let fields = extract_fields(schema).unwrap_or_default();
+
+        let mut json_obj = serde_json::Map::new();
+        for (tag, text) in parse_xml_elements(completion, |tag| fields.contains_key(tag)) {
+            let value = field_value(&text, &fields[&tag].type_name);
+            json_obj.insert(tag, value);
+        }
+
+        serde_json::from_value(JsonValue::Object(json_obj))
+            .map_err(|e| anyhow!("Failed to deserialize output: {}", e))
+    }
+}
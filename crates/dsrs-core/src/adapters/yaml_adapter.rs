@@ -0,0 +1,166 @@
+use super::traits::{Adapter, AdapterConfig};
+use super::utils::*;
+use crate::primatives::Signature;
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use regex::Regex;
+use schemars::Schema;
+use serde_json::Value as JsonValue;
+
+lazy_static! {
+    static ref YAML_FENCE_PATTERN: Regex =
+        Regex::new(r"(?s)```(?:ya?ml)?[ \t]*\n(.*?)```").unwrap();
+}
+
+pub struct YamlAdapter {
+    config: AdapterConfig,
+}
+
+impl YamlAdapter {
+    pub fn new(config: AdapterConfig) -> Self {
+        Self { config }
+    }
+}
+
+// The completion as a YAML mapping, either as a whole or from the first fenced block
+fn yaml_mapping(completion: &str) -> Result<serde_yaml::Mapping> {
+    if let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::from_str(completion) {
+        return Ok(mapping);
+    }
+
+    let block = YAML_FENCE_PATTERN
+        .captures(completion)
+        .and_then(|captures| captures.get(1))
+        .ok_or_else(|| anyhow!("Failed to parse YAML response: no YAML mapping found"))?;
+
+    match serde_yaml::from_str(block.as_str()) {
+        Ok(serde_yaml::Value::Mapping(mapping)) => Ok(mapping),
+        Ok(_) => Err(anyhow!("Failed to parse YAML response: expected a mapping")),
+        Err(e) => Err(anyhow!("Failed to parse YAML response: {}", e)),
+    }
+}
+
+// Undo YAML's implicit typing where it disagrees with the schema: `answer: 42` or
+// `answer: no` is still a string field, and YAML 1.1 booleans like `yes` still answer a
+// boolean field
+fn coerce(value: JsonValue, type_name: &str) -> JsonValue {
+    match (type_name, value) {
+        ("String", JsonValue::Number(n)) => JsonValue::String(n.to_string()),
+        ("String", JsonValue::Bool(b)) => JsonValue::String(b.to_string()),
+        ("Boolean", JsonValue::String(s)) => match s.to_ascii_lowercase().as_str() {
+            "yes" | "y" | "on" | "true" => JsonValue::Bool(true),
+            "no" | "n" | "off" | "false" => JsonValue::Bool(false),
+            _ => JsonValue::String(s),
+        },
+        (_, value) => value,
+    }
+}
+
+impl<S: Signature> Adapter<S> for YamlAdapter {
+    fn config(&self) -> &AdapterConfig {
+        &self.config
+    }
+
+    fn format_field_description(&self, schema: &Schema) -> String {
+        let fields = extract_fields(schema).unwrap_or_default();
+
+        // Nested object fields are listed, indented, under their parent
+        let descriptions: Vec<String> = fields
+            .values()
+            .flat_map(|info| {
+                let desc = info.description.as_deref().unwrap_or("No description");
+                let line = format!("- {}: {} ({})", info.display_name(), desc, info.type_name);
+                std::iter::once(line).chain(info.children_description(0))
+            })
+            .collect();
+
+        descriptions.join("\n")
+    }
+
+    fn format_field_structure(&self, input_schema: &Schema, output_schema: &Schema) -> String {
+        let output_fields = extract_fields(output_schema).unwrap_or_default();
+        let example: Vec<String> = output_fields
+            .iter()
+            .map(|(name, info)| format!("{}: <{}>", name, info.type_name))
+            .collect();
+
+        let parts = [
+            "All interactions will be structured in the following way:".to_string(),
+            "".to_string(),
+            "Input fields:".to_string(),
+            <YamlAdapter as Adapter<S>>::format_field_description(self, input_schema),
+            "".to_string(),
+            "Respond in YAML: a single mapping with the following fields:".to_string(),
+            <YamlAdapter as Adapter<S>>::format_field_description(self, output_schema),
+            "".to_string(),
+            format!("```yaml\n{}\n```", example.join("\n")),
+            "".to_string(),
+            "Use `|` block scalars for multi-line text, and quote strings that could be read as numbers or booleans.".to_string(),
+        ];
+
+        parts.join("\n")
+    }
+
+    fn format_task_description(&self, instructions: &str) -> String {
+        format!("Your task: {}", instructions)
+    }
+
+    fn format_user_message_content(
+        &self,
+        inputs: &S::Inputs,
+        input_schema: &Schema,
+        output_schema: &Schema,
+    ) -> String {
+        let fields = extract_fields(input_schema).unwrap_or_default();
+        let json_value = serde_json::to_value(inputs).unwrap_or(JsonValue::Null);
+
+        let mut parts = Vec::new();
+
+        if let JsonValue::Object(map) = json_value {
+            for name in fields.keys() {
+                if let Some(value) = map.get(name) {
+                    let formatted = format_value(value);
+                    parts.push(format!("{}: {}", name, formatted));
+                }
+            }
+        }
+
+        // Add YAML output requirement
+        let output_fields = extract_fields(output_schema).unwrap_or_default();
+        let field_names: Vec<&str> = output_fields.keys().map(|s| s.as_str()).collect();
+
+        parts.push(format!(
+            "\nRespond in YAML with a mapping containing these fields: {}",
+            field_names.join(", ")
+        ));
+
+        parts.join("\n")
+    }
+
+    fn prepend_context(&self, content: String, context: &str) -> String {
+        format!("context: {}\n{}", context, content)
+    }
+
+    fn format_assistant_message_content(&self, outputs: &S::Outputs, _schema: &Schema) -> String {
+        serde_yaml::to_string(outputs).unwrap_or_else(|_| "{}".to_string())
+    }
+
+    fn parse(&self, completion: &str, schema: &Schema) -> Result<S::Outputs> {
+        let fields = extract_fields(schema).unwrap_or_default();
+        let mapping = yaml_mapping(completion)?;
+
+        let mut json_obj = match serde_json::to_value(mapping) {
+            Ok(JsonValue::Object(map)) => map,
+            Ok(_) => serde_json::Map::new(),
+            Err(e) => return Err(anyhow!("Failed to convert YAML response: {}", e)),
+        };
+        for (name, info) in &fields {
+            if let Some(value) = json_obj.remove(name) {
+                json_obj.insert(name.clone(), coerce(value, &info.type_name));
+            }
+        }
+
+        serde_json::from_value(JsonValue::Object(json_obj))
+            .map_err(|e| anyhow!("Failed to deserialize output: {}", e))
+    }
+}
@@ -0,0 +1,329 @@
+use anyhow::Result;
+use futures::{StreamExt, stream};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+use super::{EvaluationMetric, LabeledDataset, mean, to_json};
+use crate::primatives::{Module, Signature};
+
+type SigInputs<M> = <<M as Module>::Sig as Signature>::Inputs;
+type SigOutputs<M> = <<M as Module>::Sig as Signature>::Outputs;
+
+type ProgressFn = Box<dyn Fn(usize, usize) + Send + Sync>;
+
+/// Examples run at once unless set with `with_concurrency`
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// The results of a `BatchEvaluator` run
+#[derive(Clone, Debug)]
+pub struct EvaluationReport<I, O> {
+    /// Score of each example, in dataset order; examples the module failed on score 0.0
+    pub scores: Vec<f64>,
+    pub mean_score: f64,
+    /// Population standard deviation of `scores`
+    pub std_dev: f64,
+    /// Inputs, expected and actual outputs of the examples scoring below the pass threshold
+    pub failing_examples: Vec<(I, O, O)>,
+    /// Fraction of examples with each output field equal to the expected one; examples the
+    /// module failed on count as a miss for every field
+    pub per_field_scores: HashMap<String, f64>,
+    /// Inputs and error of the examples the module failed on
+    pub errors: Vec<(I, String)>,
+}
+
+/// Runs every example of a dataset through a module and scores the outputs with a metric.
+/// Up to `DEFAULT_CONCURRENCY` examples run through `Module::aforward` at once, a new one
+/// starting as soon as another finishes
+pub struct BatchEvaluator<M: Module, E: EvaluationMetric<M::Sig>> {
+    module: M,
+    metric: E,
+    dataset: LabeledDataset<SigInputs<M>, SigOutputs<M>>,
+    concurrency: usize,
+    batched: bool,
+    pass_threshold: f64,
+    on_progress: Option<ProgressFn>,
+}
+
+impl<M: Module, E: EvaluationMetric<M::Sig>> BatchEvaluator<M, E> {
+    pub fn new(module: M, metric: E, dataset: LabeledDataset<SigInputs<M>, SigOutputs<M>>) -> Self {
+        BatchEvaluator {
+            module,
+            metric,
+            dataset,
+            concurrency: DEFAULT_CONCURRENCY,
+            batched: false,
+            pass_threshold: 1.0,
+            on_progress: None,
+        }
+    }
+
+    /// Most examples running at once, or examples per batch when batched
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Send examples through `Module::aforward_batch` in batches of the concurrency, so a
+    /// `Predict` makes one `complete_many` call per batch. Each batch waits for the one
+    /// before it to finish entirely
+    pub fn with_batching(mut self, enabled: bool) -> Self {
+        self.batched = enabled;
+        self
+    }
+
+    /// Examples scoring below this are reported as failing; defaults to 1.0
+    pub fn with_pass_threshold(mut self, pass_threshold: f64) -> Self {
+        self.pass_threshold = pass_threshold;
+        self
+    }
+
+    /// Called with the number of finished examples and the total as each example finishes,
+    /// or as each batch finishes when batched
+    pub fn with_progress(
+        mut self,
+        on_progress: impl Fn(usize, usize) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    pub fn module(&self) -> &M {
+        &self.module
+    }
+
+    pub fn dataset(&self) -> &LabeledDataset<SigInputs<M>, SigOutputs<M>> {
+        &self.dataset
+    }
+}
+
+impl<M: Module, E: EvaluationMetric<M::Sig>> BatchEvaluator<M, E>
+where
+    SigOutputs<M>: Clone,
+{
+    pub async fn run(&self) -> EvaluationReport<SigInputs<M>, SigOutputs<M>> {
+        let total = self.dataset.len();
+        let results = if self.batched {
+            self.run_batched().await
+        } else {
+            self.run_concurrently().await
+        };
+
+        let mut report = EvaluationReport {
+            scores: Vec::with_capacity(total),
+            mean_score: 0.0,
+            std_dev: 0.0,
+            failing_examples: Vec::new(),
+            per_field_scores: HashMap::new(),
+            errors: Vec::new(),
+        };
+        let mut field_matches: HashMap<String, (usize, usize)> = HashMap::new();
+        for (example, result) in self.dataset.examples().iter().zip(results) {
+            let expected = &example.expected_outputs;
+            let actual_json = result.as_ref().map(to_json).unwrap_or_default();
+            if let JsonValue::Object(fields) = to_json(expected) {
+                for (name, value) in fields {
+                    let (matches, count) = field_matches.entry(name.clone()).or_default();
+                    *matches += usize::from(actual_json.get(&name) == Some(&value));
+                    *count += 1;
+                }
+            }
+
+            let actual = match result {
+                Ok(actual) => actual,
+                Err(e) => {
+                    report.scores.push(0.0);
+                    report.errors.push((example.inputs.clone(), e.to_string()));
+                    continue;
+                }
+            };
+            let score = self.metric.score(&example.inputs, expected, &actual);
+            report.scores.push(score);
+            if score < self.pass_threshold {
+                report
+                    .failing_examples
+                    .push((example.inputs.clone(), expected.clone(), actual));
+            }
+        }
+
+        report.mean_score = mean(&report.scores);
+        report.std_dev = std_dev(&report.scores, report.mean_score);
+        report.per_field_scores = field_matches
+            .into_iter()
+            .map(|(name, (matches, count))| (name, matches as f64 / count as f64))
+            .collect();
+        report
+    }
+
+    // Results in dataset order, running up to `concurrency` examples at a time
+    async fn run_concurrently(&self) -> Vec<Result<SigOutputs<M>>> {
+        let total = self.dataset.len();
+        let mut finished = stream::iter(self.dataset.examples().iter().enumerate())
+            .map(|(index, example)| async move {
+                (index, self.module.aforward(example.inputs.clone()).await)
+            })
+            .buffer_unordered(self.concurrency);
+
+        let mut results: Vec<Option<Result<SigOutputs<M>>>> = (0..total).map(|_| None).collect();
+        let mut done = 0;
+        while let Some((index, result)) = finished.next().await {
+            results[index] = Some(result);
+            done += 1;
+            self.report_progress(done, total);
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("every example runs"))
+            .collect()
+    }
+
+    // Results in dataset order, one `aforward_batch` call per `concurrency` examples
+    async fn run_batched(&self) -> Vec<Result<SigOutputs<M>>> {
+        let total = self.dataset.len();
+        let mut results = Vec::with_capacity(total);
+        for batch in self.dataset.examples().chunks(self.concurrency) {
+            let inputs = batch.iter().map(|example| example.inputs.clone()).collect();
+            results.extend(self.module.aforward_batch(inputs).await);
+            self.report_progress(results.len(), total);
+        }
+        results
+    }
+
+    fn report_progress(&self, done: usize, total: usize) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(done, total);
+        }
+    }
+}
+
+fn std_dev(scores: &[f64], average: f64) -> f64 {
+    let squared: Vec<f64> = scores
+        .iter()
+        .map(|score| (score - average).powi(2))
+        .collect();
+    mean(&squared).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::{ExactMatchMetric, LabeledExample};
+    use crate::test_support::{CapitalInputs, CapitalOutputs, CapitalSignature, LookupModule};
+    use async_trait::async_trait;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // Answers correctly, after a second for France and a tenth of one otherwise
+    struct SlowLookup;
+
+    #[async_trait]
+    impl Module for SlowLookup {
+        type Sig = CapitalSignature;
+
+        async fn aforward(&self, inputs: CapitalInputs) -> Result<CapitalOutputs> {
+            let delay = if inputs.country == "France" { 1000 } else { 100 };
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            Ok(CapitalOutputs {
+                capital: format!("Capital of {}", inputs.country),
+                continent: String::new(),
+            })
+        }
+    }
+
+    fn example(
+        country: &str,
+        capital: &str,
+        continent: &str,
+    ) -> LabeledExample<CapitalInputs, CapitalOutputs> {
+        LabeledExample::new(
+            CapitalInputs {
+                country: country.to_string(),
+            },
+            CapitalOutputs {
+                capital: capital.to_string(),
+                continent: continent.to_string(),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_run_scores_examples_and_collects_failures() {
+        let dataset = LabeledDataset::new(vec![
+            example("France", "Paris", "Europe"),
+            example("Japan", "Tokyo", "Asia"),
+            example("Peru", "Lima", "South America"),
+            example("France", "Paris", "Europe"),
+        ]);
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let sink = progress.clone();
+        let evaluator = BatchEvaluator::new(LookupModule, ExactMatchMetric, dataset)
+            .with_concurrency(2)
+            .with_progress(move |done, total| sink.lock().unwrap().push((done, total)));
+
+        let report = evaluator.run().await;
+
+        assert_eq!(report.scores, vec![1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(report.mean_score, 0.5);
+        assert_eq!(report.std_dev, 0.5);
+        assert_eq!(report.failing_examples.len(), 1);
+        assert_eq!(report.failing_examples[0].2.continent, "Europe");
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0.country, "Peru");
+        // The failed example misses every field
+        assert_eq!(report.per_field_scores["capital"], 0.75);
+        assert_eq!(report.per_field_scores["continent"], 0.5);
+        assert_eq!(
+            *progress.lock().unwrap(),
+            vec![(1, 4), (2, 4), (3, 4), (4, 4)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_example_does_not_hold_up_the_others() {
+        let dataset = LabeledDataset::new(
+            ["France", "Japan", "Peru", "Chile"]
+                .into_iter()
+                .map(|country| example(country, &format!("Capital of {}", country), ""))
+                .collect(),
+        );
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let sink = progress.clone();
+        let started = tokio::time::Instant::now();
+        let evaluator = BatchEvaluator::new(SlowLookup, ExactMatchMetric, dataset)
+            .with_concurrency(2)
+            .with_progress(move |done, _| sink.lock().unwrap().push((done, started.elapsed())));
+
+        let report = evaluator.run().await;
+
+        assert_eq!(report.scores, vec![1.0; 4]);
+        // The other three run one after another beside France
+        assert_eq!(
+            *progress.lock().unwrap(),
+            vec![
+                (1, Duration::from_millis(100)),
+                (2, Duration::from_millis(200)),
+                (3, Duration::from_millis(300)),
+                (4, Duration::from_millis(1000)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batching_reports_progress_per_batch() {
+        let dataset = LabeledDataset::new(vec![
+            example("France", "Paris", "Europe"),
+            example("Japan", "Tokyo", "Asia"),
+            example("France", "Paris", "Europe"),
+        ]);
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let sink = progress.clone();
+        let evaluator = BatchEvaluator::new(LookupModule, ExactMatchMetric, dataset)
+            .with_concurrency(2)
+            .with_batching(true)
+            .with_progress(move |done, total| sink.lock().unwrap().push((done, total)));
+
+        let report = evaluator.run().await;
+
+        assert_eq!(report.scores, vec![1.0, 0.0, 1.0]);
+        assert_eq!(*progress.lock().unwrap(), vec![(2, 3), (3, 3)]);
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{Result, anyhow};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::adapters::traits::Demo;
+
+/// Inputs paired with the outputs a module should produce for them, for training and
+/// evaluation. Unlike a `Demo`, it's a ground-truth label rather than a prompt example
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LabeledExample<I, O> {
+    pub inputs: I,
+    pub expected_outputs: O,
+}
+
+impl<I, O> LabeledExample<I, O> {
+    pub fn new(inputs: I, expected_outputs: O) -> Self {
+        LabeledExample {
+            inputs,
+            expected_outputs,
+        }
+    }
+}
+
+impl<I, O> LabeledExample<I, O>
+where
+    I: JsonSchema + Serialize,
+    O: JsonSchema + DeserializeOwned,
+{
+    /// Use the example as a few-shot demo, with the expected outputs as the demo's outputs
+    pub fn into_demo(self) -> Demo<I, O> {
+        Demo {
+            inputs: self.inputs,
+            outputs: self.expected_outputs,
+        }
+    }
+}
+
+/// An ordered collection of `LabeledExample`s
+#[derive(Clone, Debug, PartialEq)]
+pub struct LabeledDataset<I, O> {
+    examples: Vec<LabeledExample<I, O>>,
+}
+
+impl<I, O> LabeledDataset<I, O> {
+    pub fn new(examples: Vec<LabeledExample<I, O>>) -> Self {
+        LabeledDataset { examples }
+    }
+
+    pub fn examples(&self) -> &[LabeledExample<I, O>] {
+        &self.examples
+    }
+
+    pub fn into_examples(self) -> Vec<LabeledExample<I, O>> {
+        self.examples
+    }
+
+    pub fn len(&self) -> usize {
+        self.examples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.examples.is_empty()
+    }
+
+    /// The first `ratio` of the examples (rounded, clamped to 0..=1) and the rest, in
+    /// order; shuffle beforehand for a random split
+    pub fn train_test_split(self, ratio: f64) -> (LabeledDataset<I, O>, LabeledDataset<I, O>) {
+        let train_len = (self.examples.len() as f64 * ratio.clamp(0.0, 1.0)).round() as usize;
+        let mut train = self.examples;
+        let test = train.split_off(train_len);
+        (LabeledDataset::new(train), LabeledDataset::new(test))
+    }
+}
+
+impl<I, O> LabeledDataset<I, O>
+where
+    I: Serialize + DeserializeOwned,
+    O: Serialize + DeserializeOwned,
+{
+    /// Read a JSONL file with one `{"inputs": ..., "expected_outputs": ...}` object per line
+    pub fn load_jsonl(path: &Path) -> Result<LabeledDataset<I, O>> {
+        let file = File::open(path)
+            .map_err(|e| anyhow!("Failed to open dataset file {}: {}", path.display(), e))?;
+
+        let mut examples = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| anyhow!("Failed to read line {}: {}", index + 1, e))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let example = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("Failed to parse example on line {}: {}", index + 1, e))?;
+            examples.push(example);
+        }
+        Ok(LabeledDataset::new(examples))
+    }
+
+    /// Write the examples in the format read by `load_jsonl`, replacing the file's contents
+    pub fn save_jsonl(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)
+            .map_err(|e| anyhow!("Failed to create dataset file {}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+
+        for (index, example) in self.examples.iter().enumerate() {
+            let line = serde_json::to_string(example)
+                .map_err(|e| anyhow!("Failed to serialize example {}: {}", index, e))?;
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<I, O> From<Vec<LabeledExample<I, O>>> for LabeledDataset<I, O> {
+    fn from(examples: Vec<LabeledExample<I, O>>) -> Self {
+        LabeledDataset::new(examples)
+    }
+}
+
+impl<I, O> IntoIterator for LabeledDataset<I, O> {
+    type Item = LabeledExample<I, O>;
+    type IntoIter = std::vec::IntoIter<LabeledExample<I, O>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.examples.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset(count: u32) -> LabeledDataset<String, u32> {
+        (0..count)
+            .map(|index| LabeledExample::new(format!("{} squared", index), index * index))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    #[test]
+    fn test_train_test_split_keeps_order() {
+        let (train, test) = dataset(10).train_test_split(0.75);
+
+        assert_eq!(train.len(), 8);
+        assert_eq!(test.examples()[0].inputs, "8 squared");
+        assert_eq!(test.len(), 2);
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("dsrs-labeled-dataset-{}.jsonl", std::process::id()));
+        let original = dataset(3);
+
+        original.save_jsonl(&path).unwrap();
+        let loaded: LabeledDataset<String, u32> = LabeledDataset::load_jsonl(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, original);
+        assert_eq!(loaded.into_iter().last().unwrap().expected_outputs, 4);
+    }
+}
@@ -0,0 +1,279 @@
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+use crate::modules::JudgeModule;
+use crate::primatives::Signature;
+use crate::providers::CompletionProvider;
+
+pub mod batch;
+pub mod dataset;
+
+pub use batch::{BatchEvaluator, EvaluationReport};
+pub use dataset::{LabeledDataset, LabeledExample};
+
+/// Scores a module's outputs against the expected outputs, from 0.0 (wrong) to 1.0 (right)
+pub trait EvaluationMetric<S: Signature>: Send + Sync {
+    fn score(&self, inputs: &S::Inputs, expected: &S::Outputs, actual: &S::Outputs) -> f64;
+}
+
+/// 1.0 when the outputs serialize to equal JSON, 0.0 otherwise
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExactMatchMetric;
+
+impl<S: Signature> EvaluationMetric<S> for ExactMatchMetric {
+    fn score(&self, _inputs: &S::Inputs, expected: &S::Outputs, actual: &S::Outputs) -> f64 {
+        if to_json(expected) == to_json(actual) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Token-overlap F1 of the string fields, averaged over the expected string fields, as in
+/// SQuAD-style answer scoring. Tokens are lowercase alphanumeric runs
+#[derive(Clone, Copy, Debug, Default)]
+pub struct F1Metric;
+
+impl<S: Signature> EvaluationMetric<S> for F1Metric {
+    fn score(&self, _inputs: &S::Inputs, expected: &S::Outputs, actual: &S::Outputs) -> f64 {
+        let expected = to_json(expected);
+        let actual = to_json(actual);
+        let scores: Vec<f64> = string_fields(&expected)
+            .map(|(name, expected)| {
+                let actual = actual.get(name).and_then(|v| v.as_str()).unwrap_or("");
+                token_f1(expected, actual)
+            })
+            .collect();
+        mean(&scores)
+    }
+}
+
+/// Fraction of expected fields found in the actual outputs: string fields must contain
+/// the expected text (ignoring case and surrounding whitespace), other fields must be equal
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ContainsMetric;
+
+impl<S: Signature> EvaluationMetric<S> for ContainsMetric {
+    fn score(&self, _inputs: &S::Inputs, expected: &S::Outputs, actual: &S::Outputs) -> f64 {
+        let expected = to_json(expected);
+        let actual = to_json(actual);
+        let Some(fields) = expected.as_object() else {
+            return if expected == actual { 1.0 } else { 0.0 };
+        };
+        let scores: Vec<f64> = fields
+            .iter()
+            .map(|(name, expected)| {
+                let found = match (expected, actual.get(name)) {
+                    (JsonValue::String(expected), Some(JsonValue::String(actual))) => actual
+                        .to_lowercase()
+                        .contains(&expected.trim().to_lowercase()),
+                    (expected, actual) => actual == Some(expected),
+                };
+                if found { 1.0 } else { 0.0 }
+            })
+            .collect();
+        mean(&scores)
+    }
+}
+
+/// Weighted average of several metrics
+pub struct CompositeMetric<S: Signature> {
+    metrics: Vec<(Box<dyn EvaluationMetric<S>>, f64)>,
+}
+
+impl<S: Signature> CompositeMetric<S> {
+    pub fn new() -> Self {
+        CompositeMetric {
+            metrics: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, metric: impl EvaluationMetric<S> + 'static, weight: f64) -> Self {
+        self.metrics.push((Box::new(metric), weight));
+        self
+    }
+}
+
+impl<S: Signature> Default for CompositeMetric<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Signature> EvaluationMetric<S> for CompositeMetric<S> {
+    fn score(&self, inputs: &S::Inputs, expected: &S::Outputs, actual: &S::Outputs) -> f64 {
+        let total_weight: f64 = self.metrics.iter().map(|(_, weight)| weight).sum();
+        if total_weight <= 0.0 {
+            return 0.0;
+        }
+        let weighted: f64 = self
+            .metrics
+            .iter()
+            .map(|(metric, weight)| metric.score(inputs, expected, actual) * weight)
+            .sum();
+        weighted / total_weight
+    }
+}
+
+/// Scores with a `JudgeModule`. `EvaluationMetric::score` is synchronous, so this blocks on
+/// the judge and needs a multi-threaded Tokio runtime; a failed judgement scores 0.0
+pub struct LLMJudgeMetric<S: Signature, P: CompletionProvider> {
+    judge: JudgeModule<S, P>,
+}
+
+impl<S: Signature, P: CompletionProvider> LLMJudgeMetric<S, P> {
+    pub fn new(judge: JudgeModule<S, P>) -> Self {
+        LLMJudgeMetric { judge }
+    }
+
+    pub fn judge(&self) -> &JudgeModule<S, P> {
+        &self.judge
+    }
+}
+
+impl<S: Signature, P: CompletionProvider> EvaluationMetric<S> for LLMJudgeMetric<S, P> {
+    fn score(&self, inputs: &S::Inputs, expected: &S::Outputs, actual: &S::Outputs) -> f64 {
+        let evaluation = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(self.judge.evaluate(inputs, expected, actual))
+        });
+        evaluation.unwrap_or_else(|e| {
+            tracing::warn!("LLM judge failed, scoring 0: {}", e);
+            0.0
+        })
+    }
+}
+
+fn to_json(value: &impl Serialize) -> JsonValue {
+    serde_json::to_value(value).unwrap_or(JsonValue::Null)
+}
+
+fn string_fields(value: &JsonValue) -> impl Iterator<Item = (&str, &str)> {
+    value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| Some((name.as_str(), value.as_str()?)))
+}
+
+pub(crate) fn tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn token_f1(expected: &str, actual: &str) -> f64 {
+    let expected = tokens(expected);
+    let actual = tokens(actual);
+    if expected.is_empty() || actual.is_empty() {
+        return if expected == actual { 1.0 } else { 0.0 };
+    }
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for token in &expected {
+        *counts.entry(token).or_default() += 1;
+    }
+    let overlap = actual
+        .iter()
+        .filter(|token| match counts.get_mut(token.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        })
+        .count();
+    if overlap == 0 {
+        return 0.0;
+    }
+
+    let precision = overlap as f64 / actual.len() as f64;
+    let recall = overlap as f64 / expected.len() as f64;
+    2.0 * precision * recall / (precision + recall)
+}
+
+pub(crate) fn mean(scores: &[f64]) -> f64 {
+    if scores.is_empty() {
+        0.0
+    } else {
+        scores.iter().sum::<f64>() / scores.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{QaInputs, QaOutputs, QaSignature};
+
+    fn score(
+        metric: &dyn EvaluationMetric<QaSignature>,
+        expected: (&str, u32),
+        actual: (&str, u32),
+    ) -> f64 {
+        let inputs = QaInputs {
+            question: "When did it open?".to_string(),
+        };
+        let outputs = |(answer, year): (&str, u32)| QaOutputs {
+            answer: answer.to_string(),
+            year,
+        };
+        metric.score(&inputs, &outputs(expected), &outputs(actual))
+    }
+
+    #[test]
+    fn test_exact_match() {
+        assert_eq!(
+            score(&ExactMatchMetric, ("Paris", 1889), ("Paris", 1889)),
+            1.0
+        );
+        assert_eq!(
+            score(&ExactMatchMetric, ("Paris", 1889), ("paris", 1889)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_f1_token_overlap() {
+        assert_eq!(
+            score(&F1Metric, ("The Eiffel Tower", 0), ("the eiffel tower", 0)),
+            1.0
+        );
+        // Two of three expected tokens, in a four-token answer
+        let partial = score(
+            &F1Metric,
+            ("The Eiffel Tower", 0),
+            ("Eiffel Tower in Paris", 0),
+        );
+        assert!((partial - 4.0 / 7.0).abs() < 1e-9);
+        assert_eq!(score(&F1Metric, ("Paris", 0), ("London", 0)), 0.0);
+    }
+
+    #[test]
+    fn test_contains() {
+        assert_eq!(
+            score(&ContainsMetric, ("Paris", 1889), ("It is in paris.", 1889)),
+            1.0
+        );
+        assert_eq!(
+            score(&ContainsMetric, ("Paris", 1889), ("It is in Paris.", 1890)),
+            0.5
+        );
+    }
+
+    #[test]
+    fn test_composite_weights_metrics() {
+        let metric = CompositeMetric::new()
+            .with(ExactMatchMetric, 1.0)
+            .with(ContainsMetric, 3.0);
+
+        let combined = score(&metric, ("Paris", 1889), ("In Paris", 1889));
+        assert_eq!(combined, 0.75);
+        assert_eq!(
+            score(&CompositeMetric::new(), ("Paris", 1889), ("Paris", 1889)),
+            0.0
+        );
+    }
+}
@@ -1,4 +1,15 @@
+// Lets code generated by dsrs-macros refer to `::dsrs_core` from inside this crate too
+extern crate self as dsrs_core;
+
 pub mod adapters;
+pub mod evaluation;
+pub mod modules;
+pub mod optimizers;
 pub mod predict;
 pub mod primatives;
 pub mod providers;
+pub mod retrieval;
+pub mod tools;
+
+#[cfg(test)]
+pub(crate) mod test_support;
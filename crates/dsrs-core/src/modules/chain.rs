@@ -0,0 +1,105 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::marker::PhantomData;
+
+use crate::primatives::{Module, ModuleParameter, Signature};
+
+type Connector<A, B> = Box<
+    dyn Fn(<<A as Module>::Sig as Signature>::Outputs) -> <<B as Module>::Sig as Signature>::Inputs
+        + Send
+        + Sync,
+>;
+
+/// Signature of a `ChainModule`: the first module's inputs and the second module's outputs
+pub struct ChainSignature<A: Signature, B: Signature>(PhantomData<fn() -> (A, B)>);
+
+impl<A: Signature, B: Signature> Signature for ChainSignature<A, B> {
+    type Inputs = A::Inputs;
+    type Outputs = B::Outputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        ""
+    }
+
+    fn name(&self) -> &str {
+        "Chain"
+    }
+
+    fn desc(&self) -> &str {
+        ""
+    }
+}
+
+/// Runs `first`, maps its outputs to `second`'s inputs with `connector`, then runs `second`,
+/// e.g. to summarize a document before answering a question about it
+pub struct ChainModule<M1: Module, M2: Module> {
+    first: M1,
+    second: M2,
+    connector: Connector<M1, M2>,
+}
+
+impl<M1: Module, M2: Module> ChainModule<M1, M2> {
+    pub fn new(
+        first: M1,
+        second: M2,
+        connector: impl Fn(<M1::Sig as Signature>::Outputs) -> <M2::Sig as Signature>::Inputs
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        ChainModule {
+            first,
+            second,
+            connector: Box::new(connector),
+        }
+    }
+
+    pub fn first(&self) -> &M1 {
+        &self.first
+    }
+
+    pub fn second(&self) -> &M2 {
+        &self.second
+    }
+}
+
+#[async_trait]
+impl<M1: Module, M2: Module> Module for ChainModule<M1, M2> {
+    type Sig = ChainSignature<M1::Sig, M2::Sig>;
+
+    async fn aforward(
+        &self,
+        inputs: <M1::Sig as Signature>::Inputs,
+    ) -> Result<<M2::Sig as Signature>::Outputs> {
+        let intermediate = self.first.aforward(inputs).await?;
+        self.second.aforward((self.connector)(intermediate)).await
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        let mut parameters = self.first.parameters();
+        parameters.extend(self.second.parameters());
+        parameters
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        let mut parameters = self.first.parameters_mut();
+        parameters.extend(self.second.parameters_mut());
+        parameters
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        let first = self
+            .first
+            .named_parameters()
+            .into_iter()
+            .map(|(name, parameter)| (format!("first.{}", name), parameter));
+        let second = self
+            .second
+            .named_parameters()
+            .into_iter()
+            .map(|(name, parameter)| (format!("second.{}", name), parameter));
+        first.chain(second).collect()
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+
+use crate::adapters::chat_adapter::ChatAdapter;
+use crate::adapters::cot_adapter::{ChainOfThoughtAdapter, RationaleSignature};
+use crate::adapters::traits::{Adapter, AdapterConfig};
+use crate::predict::Predict;
+use crate::primatives::{Module, ModuleParameter, Signature};
+use crate::providers::CompletionProvider;
+
+/// `Predict` that has the model reason step by step before answering, like DSPy's
+/// `ChainOfThought`. The rationale is kept out of the outputs, so the signature stays as is
+pub struct ChainOfThought<S: Signature, P: CompletionProvider> {
+    predict: Predict<S, P>,
+    last_rationale: Arc<Mutex<Option<String>>>,
+}
+
+impl<S: Signature, P: CompletionProvider> ChainOfThought<S, P> {
+    pub fn new(signature: S, lm: P) -> Self {
+        Self::with_adapter(signature, lm, ChatAdapter::new(AdapterConfig::default()))
+    }
+
+    /// Like `new`, but with the rationale and outputs formatted by `adapter`
+    pub fn with_adapter<A>(signature: S, lm: P, adapter: A) -> Self
+    where
+        A: Adapter<S> + Adapter<RationaleSignature> + 'static,
+    {
+        let adapter = ChainOfThoughtAdapter::new(adapter);
+        let last_rationale = adapter.rationale_slot();
+        ChainOfThought {
+            predict: Predict::with_adapter(signature, lm, adapter),
+            last_rationale,
+        }
+    }
+
+    /// The wrapped `Predict`, e.g. to set its config, demos or instructions
+    pub fn predict(&self) -> &Predict<S, P> {
+        &self.predict
+    }
+
+    pub fn predict_mut(&mut self) -> &mut Predict<S, P> {
+        &mut self.predict
+    }
+
+    /// Rationale behind the most recent outputs, for debugging
+    pub fn last_rationale(&self) -> Option<String> {
+        self.last_rationale.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl<S: Signature, P: CompletionProvider> Module for ChainOfThought<S, P> {
+    type Sig = S;
+
+    async fn aforward(&self, inputs: S::Inputs) -> Result<S::Outputs> {
+        self.predict.aforward(inputs).await
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        self.predict.parameters()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        self.predict.parameters_mut()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        self.predict
+            .named_parameters()
+            .into_iter()
+            .map(|(name, parameter)| (format!("predict.{}", name), parameter))
+            .collect()
+    }
+}
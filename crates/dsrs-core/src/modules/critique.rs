@@ -0,0 +1,142 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::predict::Predict;
+use crate::primatives::{Module, ModuleParameter, Signature};
+use crate::providers::CompletionProvider;
+
+type CritiqueInputsFn<D, C> = Box<
+    dyn Fn(&<D as Signature>::Inputs, &<D as Signature>::Outputs) -> <C as Signature>::Inputs
+        + Send
+        + Sync,
+>;
+
+type ReviseInputsFn<D, C, R> = Box<
+    dyn Fn(
+            &<D as Signature>::Inputs,
+            &<D as Signature>::Outputs,
+            &<C as Signature>::Outputs,
+        ) -> <R as Signature>::Inputs
+        + Send
+        + Sync,
+>;
+
+/// Drafts outputs with `draft`, then up to `max_revisions` times has `critique` review the
+/// latest outputs and `revise` rewrite them with the critique in hand. The closures build
+/// the critique and revise inputs from the original inputs and the outputs so far
+pub struct CritiqueAndRevise<D, C, R, P>
+where
+    D: Signature,
+    C: Signature,
+    R: Signature<Outputs = D::Outputs>,
+    P: CompletionProvider,
+{
+    draft: Predict<D, P>,
+    critique: Predict<C, P>,
+    revise: Predict<R, P>,
+    critique_inputs: CritiqueInputsFn<D, C>,
+    revise_inputs: ReviseInputsFn<D, C, R>,
+    max_revisions: usize,
+}
+
+impl<D, C, R, P> CritiqueAndRevise<D, C, R, P>
+where
+    D: Signature,
+    C: Signature,
+    R: Signature<Outputs = D::Outputs>,
+    P: CompletionProvider,
+{
+    /// Revises once; see `with_max_revisions`
+    pub fn new(
+        draft: Predict<D, P>,
+        critique: Predict<C, P>,
+        revise: Predict<R, P>,
+        critique_inputs: impl Fn(&D::Inputs, &D::Outputs) -> C::Inputs + Send + Sync + 'static,
+        revise_inputs: impl Fn(&D::Inputs, &D::Outputs, &C::Outputs) -> R::Inputs
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        CritiqueAndRevise {
+            draft,
+            critique,
+            revise,
+            critique_inputs: Box::new(critique_inputs),
+            revise_inputs: Box::new(revise_inputs),
+            max_revisions: 1,
+        }
+    }
+
+    /// Critique and revise rounds after the draft; 0 returns the draft as is
+    pub fn with_max_revisions(mut self, max_revisions: usize) -> Self {
+        self.max_revisions = max_revisions;
+        self
+    }
+
+    pub fn draft(&self) -> &Predict<D, P> {
+        &self.draft
+    }
+
+    pub fn critique(&self) -> &Predict<C, P> {
+        &self.critique
+    }
+
+    pub fn revise(&self) -> &Predict<R, P> {
+        &self.revise
+    }
+}
+
+#[async_trait]
+impl<D, C, R, P> Module for CritiqueAndRevise<D, C, R, P>
+where
+    D: Signature,
+    C: Signature,
+    R: Signature<Outputs = D::Outputs>,
+    P: CompletionProvider,
+{
+    type Sig = D;
+
+    async fn aforward(&self, inputs: D::Inputs) -> Result<D::Outputs> {
+        let mut outputs = self.draft.aforward(inputs.clone()).await?;
+        for _ in 0..self.max_revisions {
+            let critique = self
+                .critique
+                .aforward((self.critique_inputs)(&inputs, &outputs))
+                .await?;
+            outputs = self
+                .revise
+                .aforward((self.revise_inputs)(&inputs, &outputs, &critique))
+                .await?;
+        }
+        Ok(outputs)
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        let mut parameters = self.draft.parameters();
+        parameters.extend(self.critique.parameters());
+        parameters.extend(self.revise.parameters());
+        parameters
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        let mut parameters = self.draft.parameters_mut();
+        parameters.extend(self.critique.parameters_mut());
+        parameters.extend(self.revise.parameters_mut());
+        parameters
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        [
+            ("draft", self.draft.named_parameters()),
+            ("critique", self.critique.named_parameters()),
+            ("revise", self.revise.named_parameters()),
+        ]
+        .into_iter()
+        .flat_map(|(prefix, parameters)| {
+            parameters
+                .into_iter()
+                .map(move |(name, parameter)| (format!("{}.{}", prefix, name), parameter))
+        })
+        .collect()
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::marker::PhantomData;
+
+use crate::predict::Predict;
+use crate::primatives::{Module, ModuleParameter, Signature};
+use crate::providers::CompletionProvider;
+
+/// What the judge is shown
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct JudgeInputs {
+    /// The inputs the output was produced for
+    pub input: JsonValue,
+    /// A reference output to compare against
+    pub expected: JsonValue,
+    /// The output to score
+    pub actual: JsonValue,
+    /// What a good output looks like
+    pub criteria: String,
+}
+
+/// The judge's verdict
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct JudgeOutputs {
+    /// From 0.0 (fails the criteria) to 1.0 (fully meets them)
+    pub score: f64,
+    /// Why the output got this score
+    pub explanation: String,
+}
+
+/// Signature of the `JudgeModule`'s `Predict`
+pub struct JudgeSignature;
+
+impl Signature for JudgeSignature {
+    type Inputs = JudgeInputs;
+    type Outputs = JudgeOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Score how well the actual output meets the criteria for the given input, using the \
+         expected output as a reference. Give a score from 0.0 to 1.0 and explain it."
+    }
+
+    fn name(&self) -> &str {
+        "Judge"
+    }
+
+    fn desc(&self) -> &str {
+        "LLM-as-a-judge scoring"
+    }
+}
+
+/// LLM-as-a-judge: has a model score a module's actual outputs for `S` against the expected
+/// ones by `criteria`, for open-ended outputs that exact metrics can't compare
+pub struct JudgeModule<S: Signature, P: CompletionProvider> {
+    predict: Predict<JudgeSignature, P>,
+    criteria: String,
+    _signature: PhantomData<fn() -> S>,
+}
+
+impl<S: Signature, P: CompletionProvider> JudgeModule<S, P> {
+    pub fn new(lm: P, criteria: impl Into<String>) -> Self {
+        Self::with_predict(Predict::new(JudgeSignature, lm), criteria)
+    }
+
+    /// Like `new`, with a configured `Predict`, e.g. with another adapter or demos
+    pub fn with_predict(predict: Predict<JudgeSignature, P>, criteria: impl Into<String>) -> Self {
+        JudgeModule {
+            predict,
+            criteria: criteria.into(),
+            _signature: PhantomData,
+        }
+    }
+
+    pub fn predict(&self) -> &Predict<JudgeSignature, P> {
+        &self.predict
+    }
+
+    pub fn criteria(&self) -> &str {
+        &self.criteria
+    }
+
+    /// The judge's score and explanation for `actual`
+    pub async fn judge(
+        &self,
+        inputs: &S::Inputs,
+        expected: &S::Outputs,
+        actual: &S::Outputs,
+    ) -> Result<JudgeOutputs> {
+        let inputs = JudgeInputs {
+            input: to_json(inputs)?,
+            expected: to_json(expected)?,
+            actual: to_json(actual)?,
+            criteria: self.criteria.clone(),
+        };
+        self.predict.aforward(inputs).await
+    }
+
+    /// The judge's score for `actual`, clamped to 0.0..=1.0
+    pub async fn evaluate(
+        &self,
+        inputs: &S::Inputs,
+        expected: &S::Outputs,
+        actual: &S::Outputs,
+    ) -> Result<f64> {
+        let verdict = self.judge(inputs, expected, actual).await?;
+        Ok(verdict.score.clamp(0.0, 1.0))
+    }
+}
+
+#[async_trait]
+impl<S: Signature, P: CompletionProvider> Module for JudgeModule<S, P> {
+    type Sig = JudgeSignature;
+
+    async fn aforward(&self, inputs: JudgeInputs) -> Result<JudgeOutputs> {
+        self.predict.aforward(inputs).await
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        self.predict.parameters()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        self.predict.parameters_mut()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        self.predict
+            .named_parameters()
+            .into_iter()
+            .map(|(name, parameter)| (format!("predict.{}", name), parameter))
+            .collect()
+    }
+}
+
+fn to_json(value: &impl Serialize) -> Result<JsonValue> {
+    serde_json::to_value(value).map_err(|e| anyhow!("Failed to serialize judged value: {}", e))
+}
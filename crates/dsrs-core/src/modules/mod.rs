@@ -0,0 +1,17 @@
+pub mod chain;
+pub mod cot;
+pub mod critique;
+pub mod judge;
+pub mod parallel;
+pub mod rag;
+pub mod react;
+pub mod self_consistency;
+
+pub use chain::{ChainModule, ChainSignature};
+pub use cot::ChainOfThought;
+pub use critique::CritiqueAndRevise;
+pub use judge::{JudgeInputs, JudgeModule, JudgeOutputs, JudgeSignature};
+pub use parallel::{ParallelModule, ParallelModuleWithMerge};
+pub use rag::{Document, RAGModule, Retriever, format_documents};
+pub use react::ReActModule;
+pub use self_consistency::{SelfConsistency, majority_vote_strings};
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::join_all;
+
+use crate::primatives::{Module, ModuleParameter, Signature};
+
+type Merge<M> = Box<
+    dyn Fn(
+            Vec<<<M as Module>::Sig as Signature>::Outputs>,
+        ) -> <<M as Module>::Sig as Signature>::Outputs
+        + Send
+        + Sync,
+>;
+
+/// Runs several modules of the same type concurrently on clones of one input
+pub struct ParallelModule<M: Module> {
+    modules: Vec<M>,
+}
+
+impl<M: Module> ParallelModule<M> {
+    pub fn new(modules: Vec<M>) -> Self {
+        ParallelModule { modules }
+    }
+
+    pub fn modules(&self) -> &[M] {
+        &self.modules
+    }
+
+    pub fn modules_mut(&mut self) -> &mut [M] {
+        &mut self.modules
+    }
+
+    /// Outputs of every module, in module order. Fails with the first error if any module fails
+    pub async fn aforward(
+        &self,
+        inputs: <M::Sig as Signature>::Inputs,
+    ) -> Result<Vec<<M::Sig as Signature>::Outputs>> {
+        join_all(
+            self.modules
+                .iter()
+                .map(|module| module.aforward(inputs.clone())),
+        )
+        .await
+        .into_iter()
+        .collect()
+    }
+
+    /// Combine the outputs into one with `merge`, e.g. a majority vote, giving a `Module`
+    pub fn with_merge(
+        self,
+        merge: impl Fn(Vec<<M::Sig as Signature>::Outputs>) -> <M::Sig as Signature>::Outputs
+        + Send
+        + Sync
+        + 'static,
+    ) -> ParallelModuleWithMerge<M> {
+        ParallelModuleWithMerge::new(self.modules, merge)
+    }
+}
+
+/// `ParallelModule` whose outputs are merged into one, for self-consistency style decoding
+pub struct ParallelModuleWithMerge<M: Module> {
+    parallel: ParallelModule<M>,
+    merge: Merge<M>,
+}
+
+impl<M: Module> ParallelModuleWithMerge<M> {
+    pub fn new(
+        modules: Vec<M>,
+        merge: impl Fn(Vec<<M::Sig as Signature>::Outputs>) -> <M::Sig as Signature>::Outputs
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        ParallelModuleWithMerge {
+            parallel: ParallelModule::new(modules),
+            merge: Box::new(merge),
+        }
+    }
+
+    pub fn modules(&self) -> &[M] {
+        self.parallel.modules()
+    }
+}
+
+#[async_trait]
+impl<M: Module> Module for ParallelModuleWithMerge<M> {
+    type Sig = M::Sig;
+
+    async fn aforward(
+        &self,
+        inputs: <M::Sig as Signature>::Inputs,
+    ) -> Result<<M::Sig as Signature>::Outputs> {
+        let outputs = self.parallel.aforward(inputs).await?;
+        Ok((self.merge)(outputs))
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        self.parallel
+            .modules
+            .iter()
+            .flat_map(|module| module.parameters())
+            .collect()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        self.parallel
+            .modules
+            .iter_mut()
+            .flat_map(|module| module.parameters_mut())
+            .collect()
+    }
+
+    /// Parameters prefixed by module index, e.g. `0.demos`
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        self.parallel
+            .modules
+            .iter()
+            .enumerate()
+            .flat_map(|(index, module)| {
+                module
+                    .named_parameters()
+                    .into_iter()
+                    .map(move |(name, parameter)| (format!("{}.{}", index, name), parameter))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::predict::Predict;
+use crate::primatives::{Module, ModuleParameter, Signature};
+use crate::providers::CompletionProvider;
+
+/// A passage found by a `Retriever`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub id: String,
+    pub text: String,
+    /// Relevance to the query, higher is better, if the retriever ranks by score
+    pub score: Option<f32>,
+}
+
+impl Document {
+    pub fn new(id: impl Into<String>, text: impl Into<String>) -> Self {
+        Document {
+            id: id.into(),
+            text: text.into(),
+            score: None,
+        }
+    }
+
+    pub fn with_score(mut self, score: f32) -> Self {
+        self.score = Some(score);
+        self
+    }
+}
+
+/// Finds the documents most relevant to a query, e.g. from a search index or vector store
+#[async_trait]
+pub trait Retriever: Send + Sync {
+    /// At most `top_k` documents, most relevant first
+    async fn retrieve(&self, query: &str, top_k: usize) -> Result<Vec<Document>>;
+}
+
+/// The documents' texts, numbered and separated by blank lines, for a context input field
+pub fn format_documents(documents: &[Document]) -> String {
+    documents
+        .iter()
+        .enumerate()
+        .map(|(index, document)| format!("[{}] {}", index + 1, document.text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+type QueryFn<S> = Box<dyn Fn(&<S as Signature>::Inputs) -> String + Send + Sync>;
+type InjectContextFn<S> = Box<dyn Fn(&mut <S as Signature>::Inputs, Vec<Document>) + Send + Sync>;
+
+/// Retrieval-augmented generation: looks up documents for a query taken from the inputs,
+/// puts them into the inputs with `inject_context`, then runs the `Predict`
+pub struct RAGModule<S: Signature, P: CompletionProvider, R: Retriever> {
+    predict: Predict<S, P>,
+    retriever: R,
+    top_k: usize,
+    query: QueryFn<S>,
+    inject_context: InjectContextFn<S>,
+}
+
+impl<S: Signature, P: CompletionProvider, R: Retriever> RAGModule<S, P, R> {
+    /// Retrieves 3 documents per call; see `with_top_k`
+    pub fn new(
+        predict: Predict<S, P>,
+        retriever: R,
+        query: impl Fn(&S::Inputs) -> String + Send + Sync + 'static,
+        inject_context: impl Fn(&mut S::Inputs, Vec<Document>) + Send + Sync + 'static,
+    ) -> Self {
+        RAGModule {
+            predict,
+            retriever,
+            top_k: 3,
+            query: Box::new(query),
+            inject_context: Box::new(inject_context),
+        }
+    }
+
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    pub fn predict(&self) -> &Predict<S, P> {
+        &self.predict
+    }
+
+    pub fn predict_mut(&mut self) -> &mut Predict<S, P> {
+        &mut self.predict
+    }
+
+    pub fn retriever(&self) -> &R {
+        &self.retriever
+    }
+}
+
+#[async_trait]
+impl<S: Signature, P: CompletionProvider, R: Retriever> Module for RAGModule<S, P, R> {
+    type Sig = S;
+
+    async fn aforward(&self, mut inputs: S::Inputs) -> Result<S::Outputs> {
+        let query = (self.query)(&inputs);
+        let documents = self.retriever.retrieve(&query, self.top_k).await?;
+        (self.inject_context)(&mut inputs, documents);
+        self.predict.aforward(inputs).await
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        self.predict.parameters()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        self.predict.parameters_mut()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        self.predict
+            .named_parameters()
+            .into_iter()
+            .map(|(name, parameter)| (format!("predict.{}", name), parameter))
+            .collect()
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+
+use crate::predict::Predict;
+use crate::primatives::{Module, ModuleParameter, Signature};
+use crate::providers::CompletionProvider;
+use crate::tools::{FunctionRegistry, ToolExecutor};
+
+const DEFAULT_MAX_STEPS: usize = 5;
+
+/// Reason+Act loop, like DSPy's `ReAct`: while the model asks for tools, their calls are
+/// run by the executor and the results sent back as `Message::Tool`, until the model
+/// answers or `max_steps` completions have been made. Each completion is one
+/// `Adapter::generate_step` of the `Predict`'s adapter, so the answer is parsed, validated
+/// and retried as in `Adapter::generate`.
+///
+/// Without an executor, tool calls are injected into the outputs as `Predict` does
+pub struct ReActModule<S: Signature, P: CompletionProvider> {
+    predict: Predict<S, P>,
+    executor: Option<Box<dyn ToolExecutor>>,
+    max_steps: usize,
+}
+
+impl<S: Signature, P: CompletionProvider> ReActModule<S, P> {
+    pub fn new(signature: S, lm: P) -> Self {
+        Self::from_predict(Predict::new(signature, lm))
+    }
+
+    /// Like `new`, but with the adapter, config, demos and instructions of `predict`
+    pub fn from_predict(predict: Predict<S, P>) -> Self {
+        ReActModule {
+            predict,
+            executor: None,
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Run tool calls with `executor`, such as a `FunctionRegistry`
+    pub fn with_executor(mut self, executor: impl ToolExecutor + 'static) -> Self {
+        self.executor = Some(Box::new(executor));
+        self
+    }
+
+    /// Offer `registry`'s tools to the model and run their calls with it
+    pub fn with_registry(mut self, registry: FunctionRegistry) -> Self {
+        let mut config = self.predict.config().clone();
+        config.tools = Some(registry.tools());
+        self.predict.set_config(config);
+        self.with_executor(registry)
+    }
+
+    /// Most completions to request before giving up. Defaults to 5
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    pub fn predict(&self) -> &Predict<S, P> {
+        &self.predict
+    }
+
+    pub fn predict_mut(&mut self) -> &mut Predict<S, P> {
+        &mut self.predict
+    }
+}
+
+#[async_trait]
+impl<S: Signature, P: CompletionProvider> Module for ReActModule<S, P> {
+    type Sig = S;
+
+    async fn aforward(&self, inputs: S::Inputs) -> Result<S::Outputs> {
+        let predict = &self.predict;
+        let adapter = predict.adapter();
+        let signature = predict.signature();
+        let output_schema = signature.output_schema();
+        let mut generation = adapter.start_generation(
+            predict.config().clone(),
+            signature,
+            predict.instructions(),
+            predict.demos(),
+            &inputs,
+            None,
+        )?;
+
+        for _ in 0..self.max_steps {
+            let step = adapter.generate_step(
+                predict.lm(),
+                signature,
+                &output_schema,
+                &mut generation,
+                self.executor.as_deref(),
+            );
+            if let Some((outputs, _)) = step.await? {
+                return Ok(outputs);
+            }
+        }
+
+        Err(anyhow!("No final answer after {} steps", self.max_steps))
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        self.predict.parameters()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        self.predict.parameters_mut()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        self.predict
+            .named_parameters()
+            .into_iter()
+            .map(|(name, parameter)| (format!("predict.{}", name), parameter))
+            .collect()
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::join_all;
+use std::collections::HashMap;
+
+use crate::evaluation::tokens;
+use crate::predict::Predict;
+use crate::primatives::{Module, ModuleParameter, Signature};
+use crate::providers::CompletionProvider;
+
+/// Temperature used for sampling when the `Predict`'s config leaves it unset
+pub const DEFAULT_SAMPLING_TEMPERATURE: f32 = 0.7;
+
+type VoteFn<S> =
+    Box<dyn Fn(Vec<<S as Signature>::Outputs>) -> <S as Signature>::Outputs + Send + Sync>;
+
+/// Self-consistency decoding: samples `n` outputs for the same inputs concurrently and
+/// picks one with `vote_fn`, e.g. `majority_vote_strings` on the answer field. Sampling
+/// needs a temperature above 0, so an unset temperature becomes
+/// `DEFAULT_SAMPLING_TEMPERATURE`
+pub struct SelfConsistency<S: Signature, P: CompletionProvider> {
+    predict: Predict<S, P>,
+    n: usize,
+    vote_fn: VoteFn<S>,
+}
+
+impl<S: Signature, P: CompletionProvider> SelfConsistency<S, P> {
+    pub fn new(
+        mut predict: Predict<S, P>,
+        n: usize,
+        vote_fn: impl Fn(Vec<S::Outputs>) -> S::Outputs + Send + Sync + 'static,
+    ) -> Self {
+        if predict.config().temperature.is_none() {
+            let mut config = predict.config().clone();
+            config.temperature = Some(DEFAULT_SAMPLING_TEMPERATURE);
+            predict.set_config(config);
+        }
+        SelfConsistency {
+            predict,
+            n,
+            vote_fn: Box::new(vote_fn),
+        }
+    }
+
+    pub fn predict(&self) -> &Predict<S, P> {
+        &self.predict
+    }
+
+    pub fn predict_mut(&mut self) -> &mut Predict<S, P> {
+        &mut self.predict
+    }
+
+    pub fn n(&self) -> usize {
+        self.n
+    }
+}
+
+#[async_trait]
+impl<S: Signature, P: CompletionProvider> Module for SelfConsistency<S, P> {
+    type Sig = S;
+
+    /// Votes among the samples that succeeded; fails only if every sample fails
+    async fn aforward(&self, inputs: S::Inputs) -> Result<S::Outputs> {
+        let predict = &self.predict;
+        let mut config = predict.config().clone();
+        // Each sample must reach the model, not a cached copy of the first
+        config.skip_cache = true;
+        let samples = (0..self.n.max(1)).map(|_| {
+            predict.adapter().generate(
+                predict.lm(),
+                config.clone(),
+                predict.signature(),
+                predict.instructions(),
+                predict.demos(),
+                &inputs,
+            )
+        });
+
+        let mut outputs = Vec::new();
+        let mut first_error = None;
+        for result in join_all(samples).await {
+            match result {
+                Ok(sample) => outputs.push(sample),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if outputs.is_empty() => Err(e),
+            _ => Ok((self.vote_fn)(outputs)),
+        }
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        self.predict.parameters()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        self.predict.parameters_mut()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        self.predict
+            .named_parameters()
+            .into_iter()
+            .map(|(name, parameter)| (format!("predict.{}", name), parameter))
+            .collect()
+    }
+}
+
+/// The output whose `field` is most common, comparing fields by their lowercase
+/// alphanumeric tokens so `"Paris."` and `"paris"` count as one answer. Ties go to the
+/// earliest output. Panics if `outputs` is empty, which `SelfConsistency` never passes
+pub fn majority_vote_strings<T>(outputs: Vec<T>, field: impl Fn(&T) -> &str) -> T {
+    let keys: Vec<Vec<String>> = outputs.iter().map(|output| tokens(field(output))).collect();
+    let mut counts: HashMap<&[String], usize> = HashMap::new();
+    for key in &keys {
+        *counts.entry(key.as_slice()).or_default() += 1;
+    }
+    let best = counts.values().copied().max().unwrap_or_default();
+    let winner = keys
+        .iter()
+        .position(|key| counts[key.as_slice()] == best)
+        .expect("majority_vote_strings needs at least one output");
+    outputs
+        .into_iter()
+        .nth(winner)
+        .expect("winner is an index into outputs")
+}
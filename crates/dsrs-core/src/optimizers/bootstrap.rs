@@ -0,0 +1,140 @@
+use anyhow::{Result, anyhow};
+
+use crate::adapters::traits::Demo;
+use crate::evaluation::EvaluationMetric;
+use crate::primatives::{Module, Signature};
+
+type Metric<S> = Box<dyn EvaluationMetric<S>>;
+
+// Adapts a metric that only looks at the prediction
+struct PredictionMetric<F>(F);
+
+impl<S: Signature, F> EvaluationMetric<S> for PredictionMetric<F>
+where
+    F: Fn(&S::Inputs, &S::Outputs) -> f64 + Send + Sync,
+{
+    fn score(&self, inputs: &S::Inputs, _expected: &S::Outputs, actual: &S::Outputs) -> f64 {
+        (self.0)(inputs, actual)
+    }
+}
+
+/// Few-shot optimizer in the style of DSPy's `BootstrapFewShot`: runs a module over a
+/// training set and keeps the predictions that pass a metric as its demos
+pub struct BootstrapFewShot<S: Signature> {
+    metric: Metric<S>,
+    trainset: Vec<(S::Inputs, S::Outputs)>,
+    max_demos: usize,
+    threshold: f64,
+}
+
+impl<S: Signature> BootstrapFewShot<S> {
+    /// `metric` scores a prediction for its inputs; `trainset` pairs inputs with their
+    /// expected outputs
+    pub fn new(
+        metric: impl Fn(&S::Inputs, &S::Outputs) -> f64 + Send + Sync + 'static,
+        trainset: Vec<(S::Inputs, S::Outputs)>,
+        max_demos: usize,
+    ) -> Self {
+        Self::with_metric(PredictionMetric(metric), trainset, max_demos)
+    }
+
+    /// Like `new`, but scoring each prediction against its training example's outputs
+    pub fn with_metric(
+        metric: impl EvaluationMetric<S> + 'static,
+        trainset: Vec<(S::Inputs, S::Outputs)>,
+        max_demos: usize,
+    ) -> Self {
+        BootstrapFewShot {
+            metric: Box::new(metric),
+            trainset,
+            max_demos,
+            threshold: 1.0,
+        }
+    }
+
+    /// Lowest metric score a prediction needs to become a demo. Defaults to 1.0
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Bootstrap demos for `module` and set them on its `demos` parameters.
+    ///
+    /// Each training input is run through the module; predictions scoring at least the
+    /// threshold become demos, best first. Slots left over are filled with the training
+    /// set's own examples. Fails if every prediction fails, or if the module has no
+    /// demos parameter for this signature
+    pub async fn compile<M: Module<Sig = S>>(&self, module: &mut M) -> Result<()>
+    where
+        S::Outputs: Clone,
+    {
+        let mut candidates = Vec::new();
+        let mut bootstrapped = vec![false; self.trainset.len()];
+        let mut failures = 0;
+        let mut last_error = None;
+
+        for (index, (inputs, expected)) in self.trainset.iter().enumerate() {
+            match module.aforward(inputs.clone()).await {
+                Ok(outputs) => {
+                    let score = self.metric.score(inputs, expected, &outputs);
+                    if score >= self.threshold {
+                        candidates.push((score, index, outputs));
+                    }
+                }
+                Err(error) => {
+                    failures += 1;
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        // Every example failing, rather than merely scoring low, points at the setup
+        if failures == self.trainset.len()
+            && let Some(error) = last_error
+        {
+            return Err(anyhow!("Every bootstrap prediction failed: {}", error));
+        }
+
+        // Stable sort, so ties keep training set order
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.truncate(self.max_demos);
+
+        let mut demos: Vec<Demo<S::Inputs, S::Outputs>> = candidates
+            .into_iter()
+            .map(|(_, index, outputs)| {
+                bootstrapped[index] = true;
+                Demo {
+                    inputs: self.trainset[index].0.clone(),
+                    outputs,
+                }
+            })
+            .collect();
+
+        let labeled = self
+            .trainset
+            .iter()
+            .zip(&bootstrapped)
+            .filter(|(_, used)| !**used)
+            .map(|((inputs, outputs), _)| Demo {
+                inputs: inputs.clone(),
+                outputs: outputs.clone(),
+            });
+        let remaining = self.max_demos.saturating_sub(demos.len());
+        demos.extend(labeled.take(remaining));
+
+        let mut updated = false;
+        for parameter in module.parameters_mut() {
+            if let Some(slot) = parameter
+                .as_any_mut()
+                .downcast_mut::<Vec<Demo<S::Inputs, S::Outputs>>>()
+            {
+                *slot = demos.clone();
+                updated = true;
+            }
+        }
+        if !updated {
+            return Err(anyhow!("Module has no demos parameter to bootstrap"));
+        }
+        Ok(())
+    }
+}
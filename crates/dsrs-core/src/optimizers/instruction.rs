@@ -0,0 +1,246 @@
+use anyhow::{Result, anyhow};
+use futures::future::join_all;
+use rand::seq::index;
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::traits::{OptimizationResult, PromptOptimizer};
+use crate::evaluation::{EvaluationMetric, LabeledDataset, LabeledExample, mean};
+use crate::modules::self_consistency::DEFAULT_SAMPLING_TEMPERATURE;
+use crate::predict::Predict;
+use crate::primatives::{Module, Signature};
+use crate::providers::CompletionProvider;
+
+/// What the proposer is shown about the task
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ProposeInstructionInputs {
+    /// Name and description of the task
+    pub task: String,
+    /// The task's input fields and their descriptions
+    pub input_fields: String,
+    /// The task's output fields and their descriptions
+    pub output_fields: String,
+    /// The instruction to improve on
+    pub current_instruction: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct ProposeInstructionOutputs {
+    /// A new instruction for the task
+    pub instruction: String,
+}
+
+/// Signature of the `InstructionOptimizer`'s proposer
+pub struct ProposeInstructionSignature;
+
+impl Signature for ProposeInstructionSignature {
+    type Inputs = ProposeInstructionInputs;
+    type Outputs = ProposeInstructionOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Write an instruction that will make a language model perform the task better than \
+         the current instruction does. Keep it self-contained and refer to the fields by name."
+    }
+
+    fn name(&self) -> &str {
+        "ProposeInstruction"
+    }
+
+    fn desc(&self) -> &str {
+        "Instruction proposal"
+    }
+}
+
+/// Instruction search: each round has a proposer model write `num_candidates` variants of
+/// the best instruction so far, scores them and the best one on a random mini-batch of
+/// the training set, and keeps the highest scoring. Proposals are sampled at
+/// `DEFAULT_SAMPLING_TEMPERATURE` unless the proposer's config sets one
+pub struct InstructionOptimizer<S: Signature, L: CompletionProvider> {
+    proposer: Predict<ProposeInstructionSignature, L>,
+    metric: Box<dyn EvaluationMetric<S>>,
+    num_candidates: usize,
+    minibatch_size: usize,
+    rounds: usize,
+}
+
+impl<S: Signature, L: CompletionProvider> InstructionOptimizer<S, L> {
+    /// 5 candidates on 10 examples, for one round
+    pub fn new(lm: L, metric: impl EvaluationMetric<S> + 'static) -> Self {
+        Self::with_proposer(Predict::new(ProposeInstructionSignature, lm), metric)
+    }
+
+    /// Like `new`, with a configured proposer, e.g. with another model or adapter
+    pub fn with_proposer(
+        proposer: Predict<ProposeInstructionSignature, L>,
+        metric: impl EvaluationMetric<S> + 'static,
+    ) -> Self {
+        InstructionOptimizer {
+            proposer,
+            metric: Box::new(metric),
+            num_candidates: 5,
+            minibatch_size: 10,
+            rounds: 1,
+        }
+    }
+
+    pub fn with_num_candidates(mut self, num_candidates: usize) -> Self {
+        self.num_candidates = num_candidates;
+        self
+    }
+
+    pub fn with_minibatch_size(mut self, minibatch_size: usize) -> Self {
+        self.minibatch_size = minibatch_size.max(1);
+        self
+    }
+
+    pub fn with_rounds(mut self, rounds: usize) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    pub fn proposer(&self) -> &Predict<ProposeInstructionSignature, L> {
+        &self.proposer
+    }
+
+    // Up to `minibatch_size` distinct examples, in training set order
+    fn minibatch<'a>(
+        &self,
+        train_set: &'a LabeledDataset<S::Inputs, S::Outputs>,
+    ) -> Vec<&'a LabeledExample<S::Inputs, S::Outputs>> {
+        let examples = train_set.examples();
+        if examples.len() <= self.minibatch_size {
+            return examples.iter().collect();
+        }
+        let mut indices =
+            index::sample(&mut rand::rng(), examples.len(), self.minibatch_size).into_vec();
+        indices.sort_unstable();
+        indices.into_iter().map(|index| &examples[index]).collect()
+    }
+
+    // Candidate instructions improving on `current`; fails only if every proposal fails
+    async fn propose(&self, signature: &S, current: &str) -> Result<Vec<String>> {
+        let inputs = ProposeInstructionInputs {
+            task: format!("{}: {}", signature.name(), signature.desc()),
+            input_fields: describe_fields(&signature.input_schema()),
+            output_fields: describe_fields(&signature.output_schema()),
+            current_instruction: current.to_string(),
+        };
+        let mut config = self.proposer.config().clone();
+        config
+            .temperature
+            .get_or_insert(DEFAULT_SAMPLING_TEMPERATURE);
+        // Each proposal must reach the model, not a cached copy of the first
+        config.skip_cache = true;
+        let proposals = (0..self.num_candidates).map(|_| {
+            self.proposer.adapter().generate(
+                self.proposer.lm(),
+                config.clone(),
+                self.proposer.signature(),
+                self.proposer.instructions(),
+                self.proposer.demos(),
+                &inputs,
+            )
+        });
+
+        let mut candidates = Vec::new();
+        let mut first_error = None;
+        for result in join_all(proposals).await {
+            match result {
+                Ok(proposal) => {
+                    let instruction = proposal.instruction.trim().to_string();
+                    if !instruction.is_empty() && !candidates.contains(&instruction) {
+                        candidates.push(instruction);
+                    }
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if candidates.is_empty() => Err(anyhow!("Every proposal failed: {}", e)),
+            _ => Ok(candidates),
+        }
+    }
+
+    // Mean metric score of `module` on `examples`; failed predictions score 0.0
+    async fn evaluate<P: CompletionProvider>(
+        &self,
+        module: &Predict<S, P>,
+        examples: &[&LabeledExample<S::Inputs, S::Outputs>],
+    ) -> f64 {
+        let predictions = examples
+            .iter()
+            .map(|example| module.aforward(example.inputs.clone()));
+        let scores: Vec<f64> = join_all(predictions)
+            .await
+            .into_iter()
+            .zip(examples)
+            .map(|(result, example)| match result {
+                Ok(actual) => {
+                    self.metric
+                        .score(&example.inputs, &example.expected_outputs, &actual)
+                }
+                Err(_) => 0.0,
+            })
+            .collect();
+        mean(&scores)
+    }
+}
+
+impl<S: Signature, L: CompletionProvider> PromptOptimizer<S> for InstructionOptimizer<S, L> {
+    /// Leaves the winning instruction on `module`. The starting instruction competes in
+    /// every round, so it stays unless a candidate beats it
+    async fn optimize<P: CompletionProvider>(
+        &mut self,
+        module: &mut Predict<S, P>,
+        train_set: &LabeledDataset<S::Inputs, S::Outputs>,
+    ) -> Result<OptimizationResult> {
+        if train_set.is_empty() {
+            return Err(anyhow!(
+                "Cannot optimize instructions on an empty training set"
+            ));
+        }
+        let mut instruction = module.instructions().to_string();
+        let mut score = 0.0;
+        for _ in 0..self.rounds {
+            let minibatch = self.minibatch(train_set);
+            let candidates = self.propose(module.signature(), &instruction).await?;
+            score = self.evaluate(module, &minibatch).await;
+            for candidate in candidates {
+                module.set_instructions(candidate.clone());
+                let candidate_score = self.evaluate(module, &minibatch).await;
+                if candidate_score > score {
+                    (instruction, score) = (candidate, candidate_score);
+                }
+            }
+            module.set_instructions(instruction.clone());
+        }
+
+        Ok(OptimizationResult {
+            score,
+            iterations: self.rounds,
+            instruction,
+        })
+    }
+}
+
+// "name: description" for each property of an object schema, one per line
+fn describe_fields(schema: &Schema) -> String {
+    let Some(properties) = schema.get("properties").and_then(JsonValue::as_object) else {
+        return String::new();
+    };
+    properties
+        .iter()
+        .map(
+            |(name, property)| match property.get("description").and_then(JsonValue::as_str) {
+                Some(description) => format!("{}: {}", name, description),
+                None => name.clone(),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
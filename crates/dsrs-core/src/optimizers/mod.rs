@@ -0,0 +1,10 @@
+pub mod bootstrap;
+pub mod instruction;
+pub mod traits;
+
+pub use bootstrap::BootstrapFewShot;
+pub use instruction::{
+    InstructionOptimizer, ProposeInstructionInputs, ProposeInstructionOutputs,
+    ProposeInstructionSignature,
+};
+pub use traits::{OptimizationResult, PromptOptimizer};
@@ -0,0 +1,27 @@
+use anyhow::Result;
+use std::future::Future;
+
+use crate::evaluation::LabeledDataset;
+use crate::predict::Predict;
+use crate::primatives::Signature;
+use crate::providers::CompletionProvider;
+
+/// What a `PromptOptimizer` settled on
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptimizationResult {
+    /// Metric score of the winning instruction on the last examples it was scored on
+    pub score: f64,
+    /// Optimization rounds run
+    pub iterations: usize,
+    /// The instruction left on the `Predict`
+    pub instruction: String,
+}
+
+/// Tunes a `Predict`'s prompt against a training set, leaving the best prompt found on it
+pub trait PromptOptimizer<S: Signature> {
+    fn optimize<P: CompletionProvider>(
+        &mut self,
+        module: &mut Predict<S, P>,
+        train_set: &LabeledDataset<S::Inputs, S::Outputs>,
+    ) -> impl Future<Output = Result<OptimizationResult>> + Send;
+}
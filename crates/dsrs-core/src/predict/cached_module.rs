@@ -0,0 +1,149 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::primatives::{Module, ModuleParameter, Signature};
+
+type Outputs<M> = <<M as Module>::Sig as Signature>::Outputs;
+type Entry<M> = (Instant, Outputs<M>);
+
+/// Hit/miss counters for a `CachedModule`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Wraps a module and memoizes its outputs, keyed by a hash of the serialized inputs
+pub struct CachedModule<M: Module> {
+    inner: M,
+    cache: Mutex<LruCache<[u8; 32], Entry<M>>>,
+    ttl: Option<Duration>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<M: Module> CachedModule<M>
+where
+    Outputs<M>: Clone,
+{
+    /// Cache up to `capacity` distinct inputs (at least one), evicting the least recently used
+    pub fn new(inner: M, capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            inner,
+            cache: Mutex::new(LruCache::new(capacity)),
+            ttl: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Treat entries older than `ttl` as misses
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lookup(&self, key: &[u8; 32]) -> Option<Outputs<M>> {
+        let mut cache = self.cache.lock().unwrap();
+        let (inserted_at, outputs) = cache.get(key)?;
+
+        // Stale entries are evicted when read
+        if self.ttl.is_some_and(|ttl| inserted_at.elapsed() > ttl) {
+            cache.pop(key);
+            return None;
+        }
+        Some(outputs.clone())
+    }
+}
+
+#[async_trait]
+impl<M: Module> Module for CachedModule<M>
+where
+    Outputs<M>: Clone,
+{
+    type Sig = M::Sig;
+
+    async fn aforward(&self, inputs: <Self::Sig as Signature>::Inputs) -> Result<Outputs<M>> {
+        // Inputs that fail to serialize can't be keyed, so they bypass the cache
+        let key: Option<[u8; 32]> = serde_json::to_vec(&inputs)
+            .ok()
+            .map(|bytes| blake3::hash(&bytes).into());
+
+        if let Some(outputs) = key.as_ref().and_then(|key| self.lookup(key)) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(outputs);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        // Errors are returned without being cached, so the next call retries
+        let outputs = self.inner.aforward(inputs).await?;
+        if let Some(key) = key {
+            self.cache
+                .lock()
+                .unwrap()
+                .put(key, (Instant::now(), outputs.clone()));
+        }
+        Ok(outputs)
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        self.inner.parameters()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        self.inner.parameters_mut()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        self.inner.named_parameters()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{CountingEcho, text as echo};
+
+    #[tokio::test]
+    async fn test_inner_called_once_for_identical_inputs() {
+        let cached = CachedModule::new(CountingEcho::default(), 8);
+
+        let first = cached.aforward(echo("hello")).await.unwrap();
+        let second = cached.aforward(echo("hello")).await.unwrap();
+        cached.aforward(echo("world")).await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(cached.inner().calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cached.stats(), CacheStats { hits: 1, misses: 2 });
+    }
+
+    #[tokio::test]
+    async fn test_stale_entries_are_refetched() {
+        let cached =
+            CachedModule::new(CountingEcho::default(), 8).with_ttl(Duration::from_millis(10));
+
+        cached.aforward(echo("hello")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cached.aforward(echo("hello")).await.unwrap();
+
+        assert_eq!(cached.inner().calls.load(Ordering::SeqCst), 2);
+        assert_eq!(cached.stats(), CacheStats { hits: 0, misses: 2 });
+    }
+}
@@ -0,0 +1,229 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::adapters::traits::Demo;
+use crate::providers::embedding::EmbeddingProvider;
+
+/// Chooses which demos to include in the prompt for a given input
+#[async_trait]
+pub trait DemoSelector<I, O>: Send + Sync
+where
+    I: JsonSchema + Serialize + Send + Sync,
+    O: JsonSchema + DeserializeOwned + Send + Sync,
+{
+    async fn select<'a>(
+        &self,
+        all: &'a [Demo<I, O>],
+        inputs: &I,
+        n: usize,
+    ) -> Result<Vec<&'a Demo<I, O>>>;
+}
+
+/// Cosine similarity of two vectors, or 0.0 if either has zero magnitude
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+// Demo inputs are embedded through their JSON representation
+fn embedding_text<I: Serialize>(inputs: &I) -> Result<String> {
+    serde_json::to_string(inputs).map_err(|e| anyhow!("Failed to serialize demo inputs: {}", e))
+}
+
+/// Selects the demos whose inputs are most similar to the current inputs.
+///
+/// Embeddings are precomputed per demo index, so `select` must be given the same demos
+/// (in the same order) that were passed to `precompute`.
+pub struct EmbeddingSimilaritySelector<E: EmbeddingProvider> {
+    embedder: E,
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl<E: EmbeddingProvider> EmbeddingSimilaritySelector<E> {
+    /// Embed the inputs of every demo up front
+    pub async fn precompute<I, O>(demos: &[Demo<I, O>], embedder: E) -> Result<Self>
+    where
+        I: JsonSchema + Serialize,
+        O: JsonSchema + DeserializeOwned,
+    {
+        let texts = demos
+            .iter()
+            .map(|demo| embedding_text(&demo.inputs))
+            .collect::<Result<Vec<_>>>()?;
+        let embeddings = if texts.is_empty() {
+            Vec::new()
+        } else {
+            embedder.embed(texts).await?
+        };
+
+        if embeddings.len() != demos.len() {
+            return Err(anyhow!(
+                "Expected {} demo embeddings, got {}",
+                demos.len(),
+                embeddings.len()
+            ));
+        }
+
+        Ok(Self {
+            embedder,
+            embeddings,
+        })
+    }
+
+    /// Replace the embedding at `idx`, or append it when `idx` is one past the last demo
+    pub fn update_demo_embedding(&mut self, idx: usize, embedding: Vec<f32>) -> Result<()> {
+        match idx.cmp(&self.embeddings.len()) {
+            std::cmp::Ordering::Less => self.embeddings[idx] = embedding,
+            std::cmp::Ordering::Equal => self.embeddings.push(embedding),
+            std::cmp::Ordering::Greater => {
+                return Err(anyhow!(
+                    "Demo index {} is out of range for {} embeddings",
+                    idx,
+                    self.embeddings.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<I, O, E> DemoSelector<I, O> for EmbeddingSimilaritySelector<E>
+where
+    I: JsonSchema + Serialize + Send + Sync,
+    O: JsonSchema + DeserializeOwned + Send + Sync,
+    E: EmbeddingProvider,
+{
+    async fn select<'a>(
+        &self,
+        all: &'a [Demo<I, O>],
+        inputs: &I,
+        n: usize,
+    ) -> Result<Vec<&'a Demo<I, O>>> {
+        if n == 0 || all.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = self
+            .embedder
+            .embed(vec![embedding_text(inputs)?])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Embedding provider returned no embedding for the inputs"))?;
+
+        // Demos without a precomputed embedding are never selected
+        let mut scored: Vec<(f32, &'a Demo<I, O>)> = all
+            .iter()
+            .zip(&self.embeddings)
+            .map(|(demo, embedding)| (cosine_similarity(&query, embedding), demo))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        Ok(scored.into_iter().take(n).map(|(_, demo)| demo).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ProviderError;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(JsonSchema, Serialize, Deserialize, Clone)]
+    struct Query {
+        q: String,
+    }
+
+    #[derive(JsonSchema, Serialize, Deserialize, Clone)]
+    struct Reply {
+        a: String,
+    }
+
+    // Looks up fixed vectors for known texts
+    struct MockEmbedder(HashMap<String, Vec<f32>>);
+
+    #[async_trait]
+    impl EmbeddingProvider for MockEmbedder {
+        async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+            Ok(texts
+                .iter()
+                .map(|text| self.0.get(text).cloned().unwrap_or(vec![0.0, 0.0]))
+                .collect())
+        }
+    }
+
+    fn demo(q: &str) -> Demo<Query, Reply> {
+        Demo {
+            inputs: Query { q: q.to_string() },
+            outputs: Reply {
+                a: format!("answer to {}", q),
+            },
+        }
+    }
+
+    fn key(q: &str) -> String {
+        serde_json::to_string(&Query { q: q.to_string() }).unwrap()
+    }
+
+    fn embedder() -> MockEmbedder {
+        MockEmbedder(HashMap::from([
+            (key("cats"), vec![1.0, 0.0]),
+            (key("dogs"), vec![0.8, 0.6]),
+            (key("stocks"), vec![0.0, 1.0]),
+            (key("kittens"), vec![0.9, 0.1]),
+        ]))
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!((cosine_similarity(&[1.0, 0.0], &[0.8, 0.6]) - 0.8).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_selects_most_similar_demos() {
+        let demos = vec![demo("stocks"), demo("dogs"), demo("cats")];
+        let selector = EmbeddingSimilaritySelector::precompute(&demos, embedder())
+            .await
+            .unwrap();
+
+        let selected = selector
+            .select(&demos, &Query { q: "kittens".to_string() }, 2)
+            .await
+            .unwrap();
+
+        let picked: Vec<&str> = selected.iter().map(|d| d.inputs.q.as_str()).collect();
+        assert_eq!(picked, vec!["cats", "dogs"]);
+    }
+
+    #[tokio::test]
+    async fn test_update_demo_embedding() {
+        let mut demos = vec![demo("cats")];
+        let mut selector = EmbeddingSimilaritySelector::precompute(&demos, embedder())
+            .await
+            .unwrap();
+
+        demos.push(demo("stocks"));
+        selector.update_demo_embedding(1, vec![0.95, 0.05]).unwrap();
+        assert!(selector.update_demo_embedding(5, vec![1.0, 0.0]).is_err());
+
+        let selected = selector
+            .select(&demos, &Query { q: "kittens".to_string() }, 1)
+            .await
+            .unwrap();
+        assert_eq!(selected[0].inputs.q, "stocks");
+    }
+}
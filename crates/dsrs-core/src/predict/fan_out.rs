@@ -0,0 +1,167 @@
+use anyhow::{Result, anyhow};
+use futures::future::{BoxFuture, join_all};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+use crate::primatives::{Module, Signature};
+
+/// Type-erased branch of a fan-out, hiding the concrete output type of the module
+trait FanOutBranch<I>: Send + Sync {
+    fn run<'a>(&'a self, inputs: I) -> BoxFuture<'a, Result<JsonValue>>;
+}
+
+struct ModuleBranch<M>(M);
+
+impl<I, M> FanOutBranch<I> for ModuleBranch<M>
+where
+    M: Module + Send + Sync,
+    M::Sig: Signature<Inputs = I>,
+    I: Send + 'static,
+{
+    fn run<'a>(&'a self, inputs: I) -> BoxFuture<'a, Result<JsonValue>> {
+        Box::pin(async move {
+            let outputs = self.0.aforward(inputs).await?;
+            serde_json::to_value(outputs).map_err(|e| anyhow!("Failed to serialize output: {}", e))
+        })
+    }
+}
+
+/// Runs several named modules sharing the same input type concurrently.
+///
+/// Unlike running one module N times, every branch may produce a different output type,
+/// so results are returned as `serde_json::Value` keyed by branch name.
+pub struct FanOut<I> {
+    branches: Vec<(String, Box<dyn FanOutBranch<I>>)>,
+}
+
+impl<I: Clone + Send + 'static> FanOut<I> {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> FanOutBuilder<I> {
+        FanOutBuilder {
+            branches: Vec::new(),
+        }
+    }
+
+    /// Names of the registered branches, in registration order
+    pub fn names(&self) -> Vec<&str> {
+        self.branches.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Run every branch concurrently on a clone of `inputs`
+    pub async fn aforward(&self, inputs: I) -> FanOutResults {
+        let runs = self.branches.iter().map(|(name, branch)| {
+            let inputs = inputs.clone();
+            async move { (name.clone(), branch.run(inputs).await) }
+        });
+
+        FanOutResults {
+            results: join_all(runs).await.into_iter().collect(),
+        }
+    }
+
+    /// Run only the branch registered under `name`, returning `None` if there is no such branch
+    pub async fn run_one(&self, name: &str, inputs: I) -> Option<Result<JsonValue>> {
+        let (_, branch) = self.branches.iter().find(|(n, _)| n == name)?;
+        Some(branch.run(inputs).await)
+    }
+}
+
+/// Results of `FanOut::aforward`, one per branch
+#[derive(Debug)]
+pub struct FanOutResults {
+    results: HashMap<String, Result<JsonValue>>,
+}
+
+impl FanOutResults {
+    /// The result of the branch registered under `name`, or `None` if there is no such branch
+    pub fn select(&self, name: &str) -> Option<&Result<JsonValue>> {
+        self.results.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    /// The results keyed by branch name
+    pub fn into_map(self) -> HashMap<String, Result<JsonValue>> {
+        self.results
+    }
+}
+
+pub struct FanOutBuilder<I> {
+    branches: Vec<(String, Box<dyn FanOutBranch<I>>)>,
+}
+
+impl<I: Clone + Send + 'static> FanOutBuilder<I> {
+    /// Register a module under `name`; registering the same name twice replaces the earlier module
+    pub fn add<M>(mut self, name: &str, module: M) -> Self
+    where
+        M: Module + Send + Sync + 'static,
+        M::Sig: Signature<Inputs = I>,
+    {
+        let branch: Box<dyn FanOutBranch<I>> = Box::new(ModuleBranch(module));
+        match self.branches.iter_mut().find(|(n, _)| n == name) {
+            Some(existing) => existing.1 = branch,
+            None => self.branches.push((name.to_string(), branch)),
+        }
+        self
+    }
+
+    pub fn build(self) -> FanOut<I> {
+        FanOut {
+            branches: self.branches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{Measurer, Summarizer, TextInputs, assert_send};
+
+    fn inputs() -> TextInputs {
+        TextInputs {
+            text: "the quick brown fox".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_named_results() {
+        let fan_out = FanOut::new()
+            .add("summary", Summarizer)
+            .add("length", Measurer)
+            .build();
+
+        let results = fan_out.aforward(inputs()).await;
+
+        assert_eq!(results.len(), 2);
+        let summary = results.select("summary").unwrap().as_ref().unwrap();
+        assert_eq!(summary["summary"], "the quick");
+        let length = results.select("length").unwrap().as_ref().unwrap();
+        assert_eq!(length["length"], 19);
+        assert!(results.select("missing").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_run_one() {
+        let fan_out = FanOut::new()
+            .add("summary", Summarizer)
+            .add("length", Measurer)
+            .build();
+
+        let length = fan_out.run_one("length", inputs()).await.unwrap().unwrap();
+        assert_eq!(length["length"], 19);
+        assert!(fan_out.run_one("missing", inputs()).await.is_none());
+    }
+
+    #[test]
+    fn test_fan_out_futures_are_send() {
+        let fan_out = FanOut::new().add("summary", Summarizer).build();
+        assert_send(fan_out.aforward(inputs()));
+        assert_send(fan_out.run_one("summary", inputs()));
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::{Result, anyhow};
+use futures::future::join_all;
+use serde_json::Value as JsonValue;
+
+use crate::primatives::ErasedModule;
+
+/// Handle to a node in an `ExecutionGraph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// Maps the output of an edge's source node to (part of) the input of its target node
+pub type EdgeTransform = Box<dyn Fn(JsonValue) -> JsonValue + Send + Sync>;
+
+struct Node {
+    name: String,
+    module: Box<dyn ErasedModule>,
+}
+
+struct Edge {
+    from: NodeId,
+    to: NodeId,
+    transform: EdgeTransform,
+}
+
+/// A DAG of modules exchanging JSON values.
+///
+/// The input node receives the graph inputs. Every other node runs once all of its
+/// predecessors have finished; nodes whose predecessors finish together run concurrently.
+/// A node with several incoming edges receives the transformed outputs merged into one
+/// JSON object (later edges win on key conflicts). Nodes not reachable from the input
+/// node are not run, and a reachable node with an edge from one of them is an error,
+/// since it could never receive all of its inputs.
+#[derive(Default)]
+pub struct ExecutionGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    input: Option<NodeId>,
+    output: Option<NodeId>,
+}
+
+impl ExecutionGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, name: &str, module: Box<dyn ErasedModule>) -> NodeId {
+        self.nodes.push(Node {
+            name: name.to_string(),
+            module,
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Connect two nodes, rejecting unknown nodes and edges that would create a cycle
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId, transform: EdgeTransform) -> Result<()> {
+        self.check_node(from)?;
+        self.check_node(to)?;
+
+        if from == to || self.reaches(to, from) {
+            return Err(anyhow!(
+                "Edge {} -> {} would create a cycle",
+                self.nodes[from.0].name,
+                self.nodes[to.0].name
+            ));
+        }
+
+        self.edges.push(Edge {
+            from,
+            to,
+            transform,
+        });
+        Ok(())
+    }
+
+    pub fn set_input_node(&mut self, id: NodeId) -> Result<()> {
+        self.check_node(id)?;
+        self.input = Some(id);
+        Ok(())
+    }
+
+    pub fn set_output_node(&mut self, id: NodeId) -> Result<()> {
+        self.check_node(id)?;
+        self.output = Some(id);
+        Ok(())
+    }
+
+    pub fn node_name(&self, id: NodeId) -> Option<&str> {
+        self.nodes.get(id.0).map(|node| node.name.as_str())
+    }
+
+    /// Run the graph and return the output node's result
+    pub async fn execute(&self, inputs: JsonValue) -> Result<JsonValue> {
+        let input = self.input.ok_or_else(|| anyhow!("No input node set"))?;
+        let output = self.output.ok_or_else(|| anyhow!("No output node set"))?;
+        if self.edges.iter().any(|edge| edge.to == input) {
+            return Err(anyhow!("Input node {} has incoming edges", self.nodes[input.0].name));
+        }
+
+        let reachable: Vec<bool> = (0..self.nodes.len())
+            .map(|idx| idx == input.0 || self.reaches(input, NodeId(idx)))
+            .collect();
+        if !reachable[output.0] {
+            return Err(anyhow!(
+                "Output node {} is not reachable from the input node",
+                self.nodes[output.0].name
+            ));
+        }
+
+        if let Some(edge) = self
+            .edges
+            .iter()
+            .find(|edge| reachable[edge.to.0] && !reachable[edge.from.0])
+        {
+            return Err(anyhow!(
+                "Node {} waits on {}, which is not reachable from the input node",
+                self.nodes[edge.to.0].name,
+                self.nodes[edge.from.0].name
+            ));
+        }
+
+        let mut results: Vec<Option<JsonValue>> = vec![None; self.nodes.len()];
+        let mut pending = Some(inputs);
+
+        loop {
+            let ready: Vec<usize> = (0..self.nodes.len())
+                .filter(|&idx| reachable[idx] && results[idx].is_none())
+                .filter(|&idx| {
+                    self.edges
+                        .iter()
+                        .filter(|edge| edge.to.0 == idx)
+                        .all(|edge| results[edge.from.0].is_some())
+                })
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+
+            let mut wave = Vec::with_capacity(ready.len());
+            for &idx in &ready {
+                let node_inputs = if idx == input.0 {
+                    pending.take().unwrap_or(JsonValue::Null)
+                } else {
+                    self.node_inputs(idx, &results)?
+                };
+                wave.push(self.nodes[idx].module.aforward_json(node_inputs));
+            }
+
+            for (idx, result) in ready.into_iter().zip(join_all(wave).await) {
+                let value = result
+                    .map_err(|e| anyhow!("Node {} failed: {}", self.nodes[idx].name, e))?;
+                results[idx] = Some(value);
+            }
+        }
+
+        results[output.0]
+            .take()
+            .ok_or_else(|| anyhow!("Output node {} did not run", self.nodes[output.0].name))
+    }
+
+    fn check_node(&self, id: NodeId) -> Result<()> {
+        if id.0 < self.nodes.len() {
+            Ok(())
+        } else {
+            Err(anyhow!("Unknown node id {:?}", id))
+        }
+    }
+
+    // Whether there is a path of existing edges from `from` to `to`
+    fn reaches(&self, from: NodeId, to: NodeId) -> bool {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![from];
+
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            if std::mem::replace(&mut visited[node.0], true) {
+                continue;
+            }
+            stack.extend(
+                self.edges
+                    .iter()
+                    .filter(|edge| edge.from == node)
+                    .map(|edge| edge.to),
+            );
+        }
+        false
+    }
+
+    fn node_inputs(&self, idx: usize, results: &[Option<JsonValue>]) -> Result<JsonValue> {
+        let mut incoming: Vec<JsonValue> = self
+            .edges
+            .iter()
+            .filter(|edge| edge.to.0 == idx)
+            .map(|edge| (edge.transform)(results[edge.from.0].clone().unwrap_or_default()))
+            .collect();
+        if incoming.len() == 1 {
+            return Ok(incoming.remove(0));
+        }
+
+        let mut merged = serde_json::Map::new();
+        for value in incoming {
+            match value {
+                JsonValue::Object(map) => merged.extend(map),
+                _ => {
+                    return Err(anyhow!(
+                        "Node {} has several inputs, so each must be a JSON object",
+                        self.nodes[idx].name
+                    ));
+                }
+            }
+        }
+        Ok(JsonValue::Object(merged))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{Step, assert_send};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    fn step(
+        name: &'static str,
+        op: fn(i64) -> i64,
+        log: &Arc<Mutex<Vec<&'static str>>>,
+    ) -> Box<dyn ErasedModule> {
+        Box::new(Step {
+            name,
+            op,
+            log: log.clone(),
+        })
+    }
+
+    fn identity() -> EdgeTransform {
+        Box::new(|value| value)
+    }
+
+    #[test]
+    fn test_execute_future_is_send() {
+        let graph = ExecutionGraph::new();
+        assert_send(graph.execute(json!({"value": 1})));
+    }
+
+    #[tokio::test]
+    async fn test_three_node_chain_runs_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut graph = ExecutionGraph::new();
+        let double = graph.add_node("double", step("double", |x| x * 2, &log));
+        let add_one = graph.add_node("add_one", step("add_one", |x| x + 1, &log));
+        let square = graph.add_node("square", step("square", |x| x * x, &log));
+
+        graph.add_edge(double, add_one, identity()).unwrap();
+        graph.add_edge(add_one, square, identity()).unwrap();
+        graph.set_input_node(double).unwrap();
+        graph.set_output_node(square).unwrap();
+
+        let result = graph.execute(json!({"value": 3})).await.unwrap();
+
+        assert_eq!(result, json!({"value": 49}));
+        assert_eq!(*log.lock().unwrap(), vec!["double", "add_one", "square"]);
+    }
+
+    #[tokio::test]
+    async fn test_fan_in_merges_transformed_outputs() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut graph = ExecutionGraph::new();
+        let source = graph.add_node("source", step("source", |x| x, &log));
+        let left = graph.add_node("left", step("left", |x| x + 1, &log));
+        let sink = graph.add_node("sink", step("sink", |x| x * 10, &log));
+
+        graph.add_edge(source, left, identity()).unwrap();
+        graph.add_edge(left, sink, identity()).unwrap();
+        // The direct edge only contributes an unrelated key, so `value` comes from `left`
+        graph
+            .add_edge(source, sink, Box::new(|value| json!({"source": value["value"]})))
+            .unwrap();
+        graph.set_input_node(source).unwrap();
+        graph.set_output_node(sink).unwrap();
+
+        let result = graph.execute(json!({"value": 4})).await.unwrap();
+
+        assert_eq!(result, json!({"value": 50}));
+        assert_eq!(*log.lock().unwrap(), vec!["source", "left", "sink"]);
+    }
+
+    #[tokio::test]
+    async fn test_edge_from_unreachable_node_rejected() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut graph = ExecutionGraph::new();
+        let source = graph.add_node("source", step("source", |x| x, &log));
+        let middle = graph.add_node("middle", step("middle", |x| x, &log));
+        let sink = graph.add_node("sink", step("sink", |x| x, &log));
+        let orphan = graph.add_node("orphan", step("orphan", |x| x, &log));
+
+        graph.add_edge(source, middle, identity()).unwrap();
+        graph.add_edge(middle, sink, identity()).unwrap();
+        graph.add_edge(orphan, middle, identity()).unwrap();
+        graph.set_input_node(source).unwrap();
+        graph.set_output_node(sink).unwrap();
+
+        let error = graph.execute(json!({"value": 1})).await.unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Node middle waits on orphan, which is not reachable from the input node"
+        );
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cycles_rejected_at_add_edge() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut graph = ExecutionGraph::new();
+        let a = graph.add_node("a", step("a", |x| x, &log));
+        let b = graph.add_node("b", step("b", |x| x, &log));
+        let c = graph.add_node("c", step("c", |x| x, &log));
+
+        graph.add_edge(a, b, identity()).unwrap();
+        graph.add_edge(b, c, identity()).unwrap();
+
+        assert!(graph.add_edge(c, a, identity()).is_err());
+        assert!(graph.add_edge(b, b, identity()).is_err());
+    }
+}
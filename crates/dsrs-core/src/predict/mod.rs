@@ -1 +1,12 @@
+#[allow(clippy::module_inception)]
 pub mod predict;
+pub mod cached_module;
+pub mod demo_selector;
+pub mod fan_out;
+pub mod graph;
+
+pub use cached_module::{CacheStats, CachedModule};
+pub use demo_selector::{DemoSelector, EmbeddingSimilaritySelector};
+pub use fan_out::{FanOut, FanOutBuilder, FanOutResults};
+pub use graph::{EdgeTransform, ExecutionGraph, NodeId};
+pub use predict::{Predict, PredictBuilder};
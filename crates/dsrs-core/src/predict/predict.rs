@@ -1,23 +1,230 @@
-use crate::primatives::{Module, Signature};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+
+use crate::adapters::chat_adapter::ChatAdapter;
+use crate::adapters::traits::{Adapter, AdapterConfig, Demo, with_field_descriptions};
+use crate::primatives::{Module, ModuleParameter, Parameter, Signature};
 use crate::providers::CompletionProvider;
+use crate::providers::models::CompletionConfig;
+
+type Demos<S> = Vec<Demo<<S as Signature>::Inputs, <S as Signature>::Outputs>>;
 
-struct Predict<S: Signature, P: CompletionProvider> {
-    _marker: std::marker::PhantomData<S>,
+/// The basic module: formats the signature with an adapter, calls the language model and
+/// parses its completion into the signature's outputs
+pub struct Predict<S: Signature, P: CompletionProvider> {
+    signature: S,
     lm: P,
+    adapter: Box<dyn Adapter<S>>,
+    config: CompletionConfig,
+    demos: Parameter<Demos<S>>,
+    instructions: Parameter<String>,
+}
+
+impl<S: Signature, P: CompletionProvider> Predict<S, P> {
+    /// A `Predict` using `ChatAdapter`, the default config and the signature's instructions
+    pub fn new(signature: S, lm: P) -> Self {
+        Self::with_adapter(signature, lm, ChatAdapter::new(AdapterConfig::default()))
+    }
+
+    /// Like `new`, but formatting and parsing with `adapter`
+    pub fn with_adapter(signature: S, lm: P, adapter: impl Adapter<S> + 'static) -> Self {
+        let instructions = signature.get_instructions().to_string();
+        Predict {
+            signature,
+            lm,
+            adapter: Box::new(adapter),
+            config: CompletionConfig::default(),
+            demos: Parameter::new("demos", Vec::new()),
+            instructions: Parameter::new("instructions", instructions),
+        }
+    }
+
+    pub fn builder() -> PredictBuilder<S, P> {
+        PredictBuilder {
+            signature: None,
+            lm: None,
+            adapter: None,
+            config: CompletionConfig::default(),
+            demos: Vec::new(),
+            instructions: None,
+        }
+    }
+
+    pub fn signature(&self) -> &S {
+        &self.signature
+    }
+
+    pub fn lm(&self) -> &P {
+        &self.lm
+    }
+
+    pub fn adapter(&self) -> &dyn Adapter<S> {
+        self.adapter.as_ref()
+    }
+
+    pub fn config(&self) -> &CompletionConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: CompletionConfig) {
+        self.config = config;
+    }
+
+    pub fn demos(&self) -> &[Demo<S::Inputs, S::Outputs>] {
+        self.demos.get()
+    }
+
+    pub fn set_demos(&mut self, demos: Demos<S>) {
+        self.demos.set(demos);
+    }
+
+    pub fn instructions(&self) -> &str {
+        self.instructions.get()
+    }
+
+    pub fn set_instructions(&mut self, instructions: impl Into<String>) {
+        self.instructions.set(instructions.into());
+    }
+
+    /// The system message every request currently starts with; it depends only on the
+    /// signature, instructions and config, never on the inputs
+    pub fn precompute_system_message(&self) -> String {
+        let input_schema = with_field_descriptions(&self.signature.input_schema(), |field| {
+            self.signature.description_for_field(field, true)
+        });
+        let output_schema = with_field_descriptions(&self.signature.output_schema(), |field| {
+            self.signature.description_for_field(field, false)
+        });
+        self.adapter.format_system_message(
+            &self.config,
+            self.instructions.get(),
+            &input_schema,
+            &output_schema,
+        )
+    }
+
+    /// Like `aforward`, with `context`, e.g. retrieved passages, shown ahead of the inputs
+    pub async fn aforward_with_context(
+        &self,
+        inputs: S::Inputs,
+        context: &str,
+    ) -> Result<S::Outputs> {
+        self.adapter
+            .generate_with_context(
+                &self.lm,
+                self.config.clone(),
+                &self.signature,
+                self.instructions.get(),
+                self.demos.get(),
+                &inputs,
+                Some(context),
+            )
+            .await
+    }
 }
 
-// impl<S: Signature, P: CompletionProvider> Module for Predict<S, P> {
-//     type Sig = S;
-
-//     fn aforward(
-//         &self,
-//         inputs: <<Self as Module>::Sig as Signature>::Inputs,
-//     ) -> impl Future<Output = <<Self as Module>::Sig as Signature>::Outputs> {
-//         self.lm.complete(messages, config)
-//     }
-
-//     fn parameters(&self) -> &[impl Module] {
-//         let empty: &[impl Module] = &[];
-//         empty
-//     }
-// }
+#[async_trait]
+impl<S: Signature, P: CompletionProvider> Module for Predict<S, P> {
+    type Sig = S;
+
+    async fn aforward(&self, inputs: S::Inputs) -> Result<S::Outputs> {
+        self.adapter
+            .generate(
+                &self.lm,
+                self.config.clone(),
+                &self.signature,
+                self.instructions.get(),
+                self.demos.get(),
+                &inputs,
+            )
+            .await
+    }
+
+    async fn aforward_batch(&self, inputs: Vec<S::Inputs>) -> Vec<Result<S::Outputs>> {
+        self.adapter
+            .generate_batch(
+                &self.lm,
+                self.config.clone(),
+                &self.signature,
+                self.instructions.get(),
+                self.demos.get(),
+                &inputs,
+            )
+            .await
+    }
+
+    /// `demos` (a `Vec<Demo<S::Inputs, S::Outputs>>`) and `instructions` (a `String`)
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        vec![&self.demos, &self.instructions]
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        vec![&mut self.demos, &mut self.instructions]
+    }
+}
+
+pub struct PredictBuilder<S: Signature, P: CompletionProvider> {
+    signature: Option<S>,
+    lm: Option<P>,
+    adapter: Option<Box<dyn Adapter<S>>>,
+    config: CompletionConfig,
+    demos: Demos<S>,
+    instructions: Option<String>,
+}
+
+impl<S: Signature, P: CompletionProvider> PredictBuilder<S, P> {
+    pub fn signature(mut self, signature: S) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    pub fn lm(mut self, lm: P) -> Self {
+        self.lm = Some(lm);
+        self
+    }
+
+    /// Defaults to `ChatAdapter`
+    pub fn adapter(mut self, adapter: impl Adapter<S> + 'static) -> Self {
+        self.adapter = Some(Box::new(adapter));
+        self
+    }
+
+    pub fn config(mut self, config: CompletionConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn demos(mut self, demos: Demos<S>) -> Self {
+        self.demos = demos;
+        self
+    }
+
+    /// Defaults to the signature's own instructions
+    pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Predict<S, P>> {
+        let signature = self
+            .signature
+            .ok_or_else(|| anyhow!("Predict requires a signature"))?;
+        let lm = self
+            .lm
+            .ok_or_else(|| anyhow!("Predict requires a language model"))?;
+        let instructions = self
+            .instructions
+            .unwrap_or_else(|| signature.get_instructions().to_string());
+
+        Ok(Predict {
+            signature,
+            lm,
+            adapter: self
+                .adapter
+                .unwrap_or_else(|| Box::new(ChatAdapter::new(AdapterConfig::default()))),
+            config: self.config,
+            demos: Parameter::new("demos", self.demos),
+            instructions: Parameter::new("instructions", instructions),
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use schemars::Schema;
+
+use super::signature::{Signature, SignatureValidationError};
+use super::validation::ValidationChain;
+use crate::providers::models::{AvailableTool, Message, ToolCall};
+
+/// Two signatures as one pipeline step: `sig1`'s inputs and `sig2`'s outputs, with
+/// `connector` mapping `sig1`'s outputs to `sig2`'s inputs. Input fields and their special
+/// fields come from `sig1`, output fields and their special fields from `sig2`
+pub struct ComposedSignature<S1, S2, F> {
+    sig1: S1,
+    sig2: S2,
+    connector: F,
+    instructions: String,
+    name: String,
+    desc: String,
+}
+
+/// Compose `sig1` and `sig2`; the instructions are both signatures' instructions in order
+pub fn compose_signatures<S1, S2, F>(
+    sig1: S1,
+    sig2: S2,
+    connector: F,
+) -> ComposedSignature<S1, S2, F>
+where
+    S1: Signature,
+    S2: Signature,
+    F: Fn(S1::Outputs) -> S2::Inputs + Send + Sync,
+{
+    let instructions = [sig1.get_instructions(), sig2.get_instructions()]
+        .into_iter()
+        .filter(|instructions| !instructions.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let name = format!("{} -> {}", sig1.name(), sig2.name());
+    let desc = format!("{}, then {}", sig1.desc(), sig2.desc());
+    ComposedSignature {
+        sig1,
+        sig2,
+        connector,
+        instructions,
+        name,
+        desc,
+    }
+}
+
+impl<S1, S2, F> ComposedSignature<S1, S2, F>
+where
+    S1: Signature,
+    S2: Signature,
+    F: Fn(S1::Outputs) -> S2::Inputs + Send + Sync,
+{
+    pub fn first(&self) -> &S1 {
+        &self.sig1
+    }
+
+    pub fn second(&self) -> &S2 {
+        &self.sig2
+    }
+
+    /// `sig2`'s inputs for `sig1`'s outputs
+    pub fn connect(&self, outputs: S1::Outputs) -> S2::Inputs {
+        (self.connector)(outputs)
+    }
+}
+
+impl<S1, S2, F> Signature for ComposedSignature<S1, S2, F>
+where
+    S1: Signature,
+    S2: Signature,
+    F: Fn(S1::Outputs) -> S2::Inputs + Send + Sync,
+{
+    type Inputs = S1::Inputs;
+    type Outputs = S2::Outputs;
+
+    fn set_instructions(&mut self, instructions: String) {
+        self.instructions = instructions;
+    }
+
+    fn get_instructions(&self) -> &str {
+        &self.instructions
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn desc(&self) -> &str {
+        &self.desc
+    }
+
+    fn prompt_input_schema() -> Schema {
+        S1::prompt_input_schema()
+    }
+
+    fn prompt_output_schema() -> Schema {
+        S2::prompt_output_schema()
+    }
+
+    fn input_schema(&self) -> Schema {
+        self.sig1.input_schema()
+    }
+
+    fn output_schema(&self) -> Schema {
+        self.sig2.output_schema()
+    }
+
+    fn description_for_field(&self, field_name: &str, is_input: bool) -> Option<String> {
+        if is_input {
+            self.sig1.description_for_field(field_name, true)
+        } else {
+            self.sig2.description_for_field(field_name, false)
+        }
+    }
+
+    fn extract_history(&self, inputs: &Self::Inputs) -> Option<Vec<Message>> {
+        self.sig1.extract_history(inputs)
+    }
+
+    fn extract_tools(&self, inputs: &Self::Inputs) -> Option<Vec<AvailableTool>> {
+        self.sig1.extract_tools(inputs)
+    }
+
+    fn inject_tool_calls(&self, outputs: &mut Self::Outputs, calls: Vec<ToolCall>) -> Result<()> {
+        self.sig2.inject_tool_calls(outputs, calls)
+    }
+
+    fn filter_special_fields(&self, inputs: &Self::Inputs) -> Self::Inputs {
+        self.sig1.filter_special_fields(inputs)
+    }
+
+    fn input_validators(&self) -> ValidationChain<Self::Inputs> {
+        self.sig1.input_validators()
+    }
+
+    fn output_validators(&self) -> ValidationChain<Self::Outputs> {
+        self.sig2.output_validators()
+    }
+
+    fn validate(&self) -> Result<(), Vec<SignatureValidationError>> {
+        let errors: Vec<SignatureValidationError> = [self.sig1.validate(), self.sig2.validate()]
+            .into_iter()
+            .filter_map(Result::err)
+            .flatten()
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn merge_special_outputs(
+        &self,
+        regular: Self::Outputs,
+        calls: Option<Vec<ToolCall>>,
+    ) -> Result<Self::Outputs> {
+        self.sig2.merge_special_outputs(regular, calls)
+    }
+}
@@ -0,0 +1,134 @@
+use schemars::Schema;
+use serde_json::Value as JsonValue;
+
+use super::signature::{Signature, SignatureValidationError};
+use super::validation::{ValidationChain, validate_json_schema, validate_schema};
+
+/// A signature whose fields are only known at runtime, e.g. loaded from a config file.
+/// Inputs and outputs are JSON objects described by raw JSON Schemas, which the adapters
+/// format prompts and parse completions with just as they do for derived schemas
+#[derive(Clone, Debug)]
+pub struct DynamicSignature {
+    name: String,
+    desc: String,
+    instructions: String,
+    input_schema: JsonValue,
+    output_schema: JsonValue,
+}
+
+impl DynamicSignature {
+    /// Both schemas should be object schemas with `properties`; `validate` reports any that
+    /// aren't before a request is made
+    pub fn new(
+        name: impl Into<String>,
+        instructions: impl Into<String>,
+        input_schema: JsonValue,
+        output_schema: JsonValue,
+    ) -> Self {
+        DynamicSignature {
+            name: name.into(),
+            desc: String::new(),
+            instructions: instructions.into(),
+            input_schema,
+            output_schema,
+        }
+    }
+
+    pub fn with_desc(mut self, desc: impl Into<String>) -> Self {
+        self.desc = desc.into();
+        self
+    }
+
+    pub fn input_schema_json(&self) -> &JsonValue {
+        &self.input_schema
+    }
+
+    pub fn output_schema_json(&self) -> &JsonValue {
+        &self.output_schema
+    }
+}
+
+// An object schema's `Schema`; anything else becomes the schema accepting every value
+fn to_schema(value: &JsonValue) -> Schema {
+    Schema::try_from(value.clone()).unwrap_or_default()
+}
+
+fn has_properties(schema: &JsonValue) -> bool {
+    schema.get("properties").is_some_and(JsonValue::is_object)
+}
+
+impl Signature for DynamicSignature {
+    type Inputs = JsonValue;
+    type Outputs = JsonValue;
+
+    fn set_instructions(&mut self, instructions: String) {
+        self.instructions = instructions;
+    }
+
+    fn get_instructions(&self) -> &str {
+        &self.instructions
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn desc(&self) -> &str {
+        &self.desc
+    }
+
+    fn input_schema(&self) -> Schema {
+        to_schema(&self.input_schema)
+    }
+
+    fn output_schema(&self) -> Schema {
+        to_schema(&self.output_schema)
+    }
+
+    /// The input schema's required fields and property types, reporting the first mismatch
+    fn input_validators(&self) -> ValidationChain<JsonValue> {
+        let schema = self.input_schema();
+        let mut chain = ValidationChain::new();
+        chain.add(move |inputs: &JsonValue| {
+            match validate_schema(inputs, &schema).into_iter().next() {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        });
+        chain
+    }
+
+    /// Every constraint of the output schema, reporting the first violation
+    fn output_validators(&self) -> ValidationChain<JsonValue> {
+        let schema = self.output_schema();
+        let mut chain = ValidationChain::new();
+        chain.add(move |outputs: &JsonValue| {
+            match validate_json_schema(outputs, &schema).into_iter().next() {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        });
+        chain
+    }
+
+    fn validate(&self) -> Result<(), Vec<SignatureValidationError>> {
+        let errors: Vec<SignatureValidationError> = [
+            ("input", &self.input_schema),
+            ("output", &self.output_schema),
+        ]
+        .into_iter()
+        .filter(|(_, schema)| !has_properties(schema))
+        .map(|(kind, _)| {
+            SignatureValidationError::Custom(format!(
+                "the {} schema must be an object schema with properties",
+                kind
+            ))
+        })
+        .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
@@ -1,7 +1,18 @@
+pub mod compose;
+pub mod dynamic;
 pub mod module;
 pub mod signature;
+pub mod spec;
 pub mod specials;
+pub mod state;
+pub mod validation;
 
-pub use module::Module;
-pub use signature::Signature;
+pub use compose::{ComposedSignature, compose_signatures};
+pub use dynamic::DynamicSignature;
+pub use module::{ErasedModule, Module, ModuleParameter, Parameter};
+pub use signature::{Signature, SignatureFields, SignatureValidationError, check_special_fields};
+pub use spec::SignatureSpec;
+pub use dsrs_macros::{Signature, SignatureSchema};
 pub use specials::*;
+pub use state::{ModuleStateV1, ParameterValue};
+pub use validation::{ValidationChain, ValidationError, ValidationErrors, Validator, validate_json_schema, validate_schema};
@@ -1,22 +1,265 @@
 use super::signature::Signature;
-use std::future::Future;
+use super::state::{ModuleStateV1, ParameterValue};
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use std::any::Any;
+use std::path::Path;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
 
-pub trait Module {
+// Object safe, so modules can be boxed as `Box<dyn Module<Sig = S>>` and composed at runtime
+#[async_trait]
+pub trait Module: Send + Sync {
     type Sig: Signature;
 
     fn forward(
         &self,
         inputs: <<Self as Module>::Sig as Signature>::Inputs,
-    ) -> <<Self as Module>::Sig as Signature>::Outputs {
+    ) -> Result<<<Self as Module>::Sig as Signature>::Outputs> {
         tokio::task::block_in_place(|| {
             tokio::runtime::Handle::current().block_on(self.aforward(inputs))
         })
     }
 
-    fn aforward(
+    async fn aforward(
         &self,
         inputs: <<Self as Module>::Sig as Signature>::Inputs,
-    ) -> impl Future<Output = <<Self as Module>::Sig as Signature>::Outputs>;
+    ) -> Result<<<Self as Module>::Sig as Signature>::Outputs>;
 
-    fn parameters(&self) -> &[impl Module];
+    /// `aforward` for each of `inputs`, in order. The default runs them concurrently;
+    /// `Predict` sends them to its provider in one `complete_many` call
+    async fn aforward_batch(
+        &self,
+        inputs: Vec<<<Self as Module>::Sig as Signature>::Inputs>,
+    ) -> Vec<Result<<<Self as Module>::Sig as Signature>::Outputs>> {
+        futures::future::join_all(inputs.into_iter().map(|inputs| self.aforward(inputs))).await
+    }
+
+    /// Learnable parameters, such as a `Predict`'s demos and instructions, including those
+    /// of sub-modules
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        Vec::new()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        Vec::new()
+    }
+
+    /// Parameters keyed by their path, e.g. `predict.demos` for a sub-module's demos.
+    /// Modules with sub-modules override this to prefix their parameters
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        self.parameters()
+            .into_iter()
+            .map(|parameter| (parameter.name().to_string(), parameter))
+            .collect()
+    }
+
+    // Checkpointing of `named_parameters` - modules with other learned state override these
+    fn state_version(&self) -> u32 {
+        1
+    }
+
+    fn serialize_state(&self) -> Result<ModuleStateV1> {
+        let parameters = self
+            .named_parameters()
+            .into_iter()
+            .map(|(name, parameter)| Ok((name, parameter.to_value()?)))
+            .collect::<Result<_>>()?;
+        Ok(ModuleStateV1::new(parameters))
+    }
+
+    // `parameters_mut` must list the parameters in the same order as `named_parameters`
+    fn deserialize_state(&mut self, state: ModuleStateV1) -> Result<()> {
+        if state.schema_version > self.state_version() {
+            return Err(anyhow!(
+                "State version {} is newer than supported version {}",
+                state.schema_version,
+                self.state_version()
+            ));
+        }
+        let names: Vec<String> = self
+            .named_parameters()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        let mut saved = state.parameters;
+        for (name, parameter) in names.into_iter().zip(self.parameters_mut()) {
+            let value = saved
+                .remove(&name)
+                .ok_or_else(|| anyhow!("Saved state has no parameter {}", name))?;
+            parameter
+                .set_value(value)
+                .map_err(|e| anyhow!("Failed to restore parameter {}: {}", name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Short type name recorded by `save` and checked by `load`, e.g. `Predict`
+    fn module_type(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    /// Write the learned parameters, such as demos and instructions, to a JSON file with
+    /// `version`, `module_type` and `parameters` fields
+    fn save(&self, path: &Path) -> Result<()> {
+        let mut state = self.serialize_state()?;
+        state.module_type = Some(self.module_type().to_string());
+        state.save(path)
+    }
+
+    /// Restore parameters written by `save`, failing if they came from another type of module
+    fn load(&mut self, path: &Path) -> Result<()> {
+        let state = ModuleStateV1::load(path)?;
+        match state.module_type.as_deref() {
+            Some(module_type) if module_type != self.module_type() => Err(anyhow!(
+                "Saved state in {} is for a {} module, not {}",
+                path.display(),
+                module_type,
+                self.module_type()
+            )),
+            _ => self.deserialize_state(state),
+        }
+    }
+}
+
+#[async_trait]
+impl<M: Module + ?Sized> Module for Box<M> {
+    type Sig = M::Sig;
+
+    async fn aforward(
+        &self,
+        inputs: <Self::Sig as Signature>::Inputs,
+    ) -> Result<<Self::Sig as Signature>::Outputs> {
+        (**self).aforward(inputs).await
+    }
+
+    async fn aforward_batch(
+        &self,
+        inputs: Vec<<Self::Sig as Signature>::Inputs>,
+    ) -> Vec<Result<<Self::Sig as Signature>::Outputs>> {
+        (**self).aforward_batch(inputs).await
+    }
+
+    fn parameters(&self) -> Vec<&dyn ModuleParameter> {
+        (**self).parameters()
+    }
+
+    fn parameters_mut(&mut self) -> Vec<&mut dyn ModuleParameter> {
+        (**self).parameters_mut()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, &dyn ModuleParameter)> {
+        (**self).named_parameters()
+    }
+
+    fn state_version(&self) -> u32 {
+        (**self).state_version()
+    }
+
+    fn serialize_state(&self) -> Result<ModuleStateV1> {
+        (**self).serialize_state()
+    }
+
+    fn deserialize_state(&mut self, state: ModuleStateV1) -> Result<()> {
+        (**self).deserialize_state(state)
+    }
+
+    fn module_type(&self) -> &'static str {
+        (**self).module_type()
+    }
+}
+
+/// Object-safe handle on a learnable parameter, downcast through `as_any` to its value
+pub trait ModuleParameter: Send + Sync {
+    fn name(&self) -> &str;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// The value as checkpointed by `Module::serialize_state`
+    fn to_value(&self) -> Result<ParameterValue>;
+    fn set_value(&mut self, value: ParameterValue) -> Result<()>;
+}
+
+/// A named parameter value; `as_any` exposes the value itself
+#[derive(Clone, Debug)]
+pub struct Parameter<T> {
+    name: &'static str,
+    value: T,
+}
+
+impl<T> Parameter<T> {
+    pub fn new(name: &'static str, value: T) -> Self {
+        Parameter { name, value }
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn set(&mut self, value: T) {
+        self.value = value;
+    }
+}
+
+// Strings are checkpointed as instructions and anything else as demos
+impl<T> ModuleParameter for Parameter<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        &self.value
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        &mut self.value
+    }
+
+    fn to_value(&self) -> Result<ParameterValue> {
+        match serde_json::to_value(&self.value) {
+            Ok(JsonValue::String(instructions)) => Ok(ParameterValue::Instructions(instructions)),
+            Ok(demos) => Ok(ParameterValue::Demos(demos)),
+            Err(e) => Err(anyhow!("Failed to serialize parameter {}: {}", self.name, e)),
+        }
+    }
+
+    fn set_value(&mut self, value: ParameterValue) -> Result<()> {
+        let json = match value {
+            ParameterValue::Instructions(instructions) => JsonValue::String(instructions),
+            ParameterValue::Demos(demos) => demos,
+        };
+        self.value = serde_json::from_value(json)
+            .map_err(|e| anyhow!("Failed to deserialize parameter {}: {}", self.name, e))?;
+        Ok(())
+    }
+}
+
+/// Object-safe view of a module that exchanges JSON values, for wiring modules together at runtime
+pub trait ErasedModule: Send + Sync {
+    fn aforward_json<'a>(&'a self, inputs: JsonValue) -> BoxFuture<'a, Result<JsonValue>>;
+}
+
+impl<M> ErasedModule for M
+where
+    M: Module + Send + Sync,
+    <M::Sig as Signature>::Inputs: DeserializeOwned,
+{
+    fn aforward_json<'a>(&'a self, inputs: JsonValue) -> BoxFuture<'a, Result<JsonValue>> {
+        Box::pin(async move {
+            let inputs = serde_json::from_value(inputs)
+                .map_err(|e| anyhow!("Failed to deserialize module inputs: {}", e))?;
+            let outputs = self.aforward(inputs).await?;
+            serde_json::to_value(outputs).map_err(|e| anyhow!("Failed to serialize output: {}", e))
+        })
+    }
 }
@@ -1,10 +1,12 @@
 use anyhow::Result;
 use schemars::Schema;
+use thiserror::Error;
 use crate::providers::models::{Message, ToolCall, AvailableTool};
+use super::validation::ValidationChain;
 
 pub trait Signature: Send + Sync {
-    type Inputs: schemars::JsonSchema + serde::Serialize + Send + Sync + Clone;
-    type Outputs: schemars::JsonSchema + serde::de::DeserializeOwned + serde::Serialize + Send + Sync;
+    type Inputs: schemars::JsonSchema + serde::Serialize + serde::de::DeserializeOwned + Send + Sync + Clone + 'static;
+    type Outputs: schemars::JsonSchema + serde::de::DeserializeOwned + serde::Serialize + Send + Sync + 'static;
 
     fn set_instructions(&mut self, instructions: String);
     fn get_instructions(&self) -> &str;
@@ -23,6 +25,22 @@ pub trait Signature: Send + Sync {
         schemars::schema_for!(Self::Outputs)
     }
 
+    // Schemas the adapters format prompts and parse completions with. Defaults to the prompt
+    // schemas; override when the schemas are only known at runtime
+    fn input_schema(&self) -> Schema {
+        Self::prompt_input_schema()
+    }
+
+    fn output_schema(&self) -> Schema {
+        Self::prompt_output_schema()
+    }
+
+    // Description to show for a field in place of the one from its schema, e.g. to tailor
+    // prompts per user at runtime. `is_input` tells input fields from output fields
+    fn description_for_field(&self, _field_name: &str, _is_input: bool) -> Option<String> {
+        None
+    }
+
     // Special field extraction methods - default implementations return None
     fn extract_history(&self, _inputs: &Self::Inputs) -> Option<Vec<Message>> {
         None
@@ -44,9 +62,79 @@ pub trait Signature: Send + Sync {
         inputs.clone()
     }
     
+    // Validators for inputs and outputs - default chains are empty
+    fn input_validators(&self) -> ValidationChain<Self::Inputs> {
+        ValidationChain::new()
+    }
+
+    fn output_validators(&self) -> ValidationChain<Self::Outputs> {
+        ValidationChain::new()
+    }
+
+    // Structural checks on the signature's configuration, run by `Adapter::generate` before
+    // any request. The default accepts everything; derived signatures check their special fields
+    fn validate(&self) -> Result<(), Vec<SignatureValidationError>> {
+        Ok(())
+    }
+
     // Merge regular outputs with tool call results
     // Default implementation returns the regular outputs unchanged
     fn merge_special_outputs(&self, regular: Self::Outputs, _calls: Option<Vec<ToolCall>>) -> Result<Self::Outputs> {
         Ok(regular)
     }
 }
+
+/// An inconsistency in how a signature is configured
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SignatureValidationError {
+    #[error("outputs capture tool calls, but no tools are offered to the model")]
+    ToolCallsWithoutTools,
+    #[error("inputs offer tools, but the outputs have no field for the tool calls")]
+    ToolsWithoutToolCalls,
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// Check that inputs offering tools and outputs capturing tool calls come together, as
+/// `#[derive(Signature)]` does in `validate`
+pub fn check_special_fields<I: SignatureFields, O: SignatureFields>()
+-> Result<(), Vec<SignatureValidationError>> {
+    let mut errors = Vec::new();
+    if O::HAS_TOOL_CALLS && !I::HAS_TOOLS {
+        errors.push(SignatureValidationError::ToolCallsWithoutTools);
+    }
+    if I::HAS_TOOLS && !O::HAS_TOOL_CALLS {
+        errors.push(SignatureValidationError::ToolsWithoutToolCalls);
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors) }
+}
+
+/// Special fields of a signature's inputs or outputs, marked with `#[signature(history)]`,
+/// `#[signature(tools)]` or `#[signature(tool_calls)]` and implemented by
+/// `#[derive(SignatureSchema)]`
+pub trait SignatureFields {
+    /// Whether a field is marked `#[signature(tools)]`
+    const HAS_TOOLS: bool = false;
+    /// Whether a field is marked `#[signature(tool_calls)]`
+    const HAS_TOOL_CALLS: bool = false;
+
+    fn extract_history(&self) -> Option<Vec<Message>> {
+        None
+    }
+
+    fn extract_tools(&self) -> Option<Vec<AvailableTool>> {
+        None
+    }
+
+    fn inject_tool_calls(&mut self, _calls: Vec<ToolCall>) -> Result<()> {
+        Ok(())
+    }
+
+    // Copy for prompt generation, with optional special fields emptied
+    fn filter_special_fields(&self) -> Self
+    where
+        Self: Clone,
+    {
+        self.clone()
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::dynamic::DynamicSignature;
+use super::signature::Signature;
+
+/// A snapshot of a signature's configuration as plain data, for inspecting, storing or
+/// documenting it. The schemas are the ones adapters format prompts with, so special fields
+/// such as history and tools are left out
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SignatureSpec {
+    pub name: String,
+    pub description: String,
+    pub instructions: String,
+    pub input_schema: JsonValue,
+    pub output_schema: JsonValue,
+}
+
+impl SignatureSpec {
+    pub fn from_signature<S: Signature>(sig: &S) -> SignatureSpec {
+        SignatureSpec {
+            name: sig.name().to_string(),
+            description: sig.desc().to_string(),
+            instructions: sig.get_instructions().to_string(),
+            input_schema: sig.input_schema().to_value(),
+            output_schema: sig.output_schema().to_value(),
+        }
+    }
+
+    /// Pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a signature spec always serializes")
+    }
+
+    pub fn from_json(s: &str) -> Result<SignatureSpec> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+/// A runtime signature with the spec's fields, e.g. to run a stored signature
+impl From<SignatureSpec> for DynamicSignature {
+    fn from(spec: SignatureSpec) -> Self {
+        DynamicSignature::new(
+            spec.name,
+            spec.instructions,
+            spec.input_schema,
+            spec.output_schema,
+        )
+        .with_desc(spec.description)
+    }
+}
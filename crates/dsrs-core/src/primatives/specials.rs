@@ -1,5 +1,9 @@
 use anyhow::Result;
-use crate::providers::models::{Message, ToolCall, AvailableTool};
+use futures::future::join_all;
+use schemars::JsonSchema;
+use crate::adapters::traits::count_message_tokens;
+use crate::providers::models::{AvailableTool, Message, ToolCall};
+use crate::tools::{ToolExecutionResult, ToolExecutionResults, ToolExecutor};
 
 /// Marker trait for special fields that require custom handling in signatures
 pub trait SpecialField: Send + Sync {}
@@ -34,22 +38,178 @@ pub trait ToolCalls: SpecialField {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ChatHistory {
     pub messages: Vec<Message>,
+    /// Applied by `to_messages`, so long conversations can be trimmed automatically
+    #[serde(default)]
+    pub truncation: TruncationMode,
+}
+
+/// How `ChatHistory::to_messages` trims the conversation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum TruncationMode {
+    /// Keep every message
+    #[default]
+    None,
+    /// Keep the last n turns, as `truncate_to_last_n_turns`
+    LastNTurns(usize),
+    /// Keep the most recent turns within this many whitespace-separated words, as
+    /// `truncate_to_token_budget`
+    TokenBudget(usize),
+}
+
+impl ChatHistory {
+    pub fn new(messages: Vec<Message>) -> Self {
+        ChatHistory {
+            messages,
+            truncation: TruncationMode::None,
+        }
+    }
+
+    pub fn with_truncation(mut self, truncation: TruncationMode) -> Self {
+        self.truncation = truncation;
+        self
+    }
+
+    /// The last `n` turns. A turn starts at a user message and includes the replies and
+    /// tool results after it; anything before the first user message belongs to the first turn
+    pub fn truncate_to_last_n_turns(&self, n: usize) -> ChatHistory {
+        let starts = self.turn_starts();
+        let messages = if n == 0 {
+            Vec::new()
+        } else {
+            let keep_from = starts.get(starts.len().saturating_sub(n)).copied();
+            self.messages[keep_from.unwrap_or(0)..].to_vec()
+        };
+        ChatHistory {
+            messages,
+            truncation: self.truncation,
+        }
+    }
+
+    /// The most recent whole turns whose text totals at most `budget` by `count_fn`.
+    /// Turns are never split, so if the last turn alone is over budget (say, one very
+    /// long message) the result is empty
+    pub fn truncate_to_token_budget(
+        &self,
+        budget: usize,
+        count_fn: impl Fn(&str) -> usize,
+    ) -> ChatHistory {
+        self.truncate_to_budget(budget, Some(&count_fn))
+    }
+
+    // `truncate_to_token_budget`, counting whitespace-separated words without `count_fn`
+    fn truncate_to_budget(
+        &self,
+        budget: usize,
+        count_fn: Option<&dyn Fn(&str) -> usize>,
+    ) -> ChatHistory {
+        let mut keep_from = self.messages.len();
+        let mut used = 0;
+        let mut end = self.messages.len();
+        for &start in self.turn_starts().iter().rev() {
+            let turn = count_message_tokens(&self.messages[start..end], count_fn);
+            if used + turn > budget {
+                break;
+            }
+            used += turn;
+            keep_from = start;
+            end = start;
+        }
+        ChatHistory {
+            messages: self.messages[keep_from..].to_vec(),
+            truncation: self.truncation,
+        }
+    }
+
+    // Index of the first message of each turn
+    fn turn_starts(&self) -> Vec<usize> {
+        let mut starts: Vec<usize> = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, message)| matches!(message, Message::User { .. }))
+            .map(|(index, _)| index)
+            .collect();
+        if !self.messages.is_empty() && starts.first() != Some(&0) {
+            starts.insert(0, 0);
+        }
+        starts
+    }
 }
 
 impl SpecialField for ChatHistory {}
 
 impl History for ChatHistory {
     fn to_messages(&self) -> Vec<Message> {
-        self.messages.clone()
+        match self.truncation {
+            TruncationMode::None => self.messages.clone(),
+            TruncationMode::LastNTurns(n) => self.truncate_to_last_n_turns(n).messages,
+            TruncationMode::TokenBudget(budget) => self.truncate_to_budget(budget, None).messages,
+        }
     }
 }
 
 /// Example implementation for a simple tools type
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct ToolSet {
     pub tools: Vec<AvailableTool>,
 }
 
+impl ToolSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builder() -> ToolSetBuilder {
+        ToolSetBuilder::default()
+    }
+
+    /// Add a tool taking arguments of type `T`, built with `AvailableTool::from_schema`
+    pub fn add<T: JsonSchema>(
+        &mut self,
+        name: impl Into<String>,
+        desc: impl Into<String>,
+    ) -> &mut Self {
+        self.tools.push(AvailableTool::from_schema::<T>(name, desc));
+        self
+    }
+
+    pub fn add_tool(&mut self, tool: AvailableTool) -> &mut Self {
+        self.tools.push(tool);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+}
+
+/// Builds a `ToolSet`, one tool at a time
+#[derive(Debug, Clone, Default)]
+pub struct ToolSetBuilder {
+    tools: ToolSet,
+}
+
+impl ToolSetBuilder {
+    /// See `ToolSet::add`
+    pub fn tool<T: JsonSchema>(mut self, name: impl Into<String>, desc: impl Into<String>) -> Self {
+        self.tools.add::<T>(name, desc);
+        self
+    }
+
+    pub fn available_tool(mut self, tool: AvailableTool) -> Self {
+        self.tools.add_tool(tool);
+        self
+    }
+
+    pub fn build(self) -> ToolSet {
+        self.tools
+    }
+}
+
 impl SpecialField for ToolSet {}
 
 impl Tools for ToolSet {
@@ -76,7 +236,117 @@ impl ToolCalls for ToolCallSet {
     }
 }
 
+impl ToolCallSet {
+    /// Execute every call concurrently, keeping results in call order
+    pub async fn execute(&self, executor: &dyn ToolExecutor) -> ToolExecutionResults<String> {
+        let outcomes = join_all(self.calls.iter().map(|call| executor.execute(call))).await;
+        let results = self
+            .calls
+            .iter()
+            .cloned()
+            .zip(outcomes)
+            .map(|(call, outcome)| ToolExecutionResult { call, outcome })
+            .collect();
+        ToolExecutionResults { results }
+    }
+
+    /// Execute every call and return the `Message::Tool` results to append to the conversation
+    pub async fn execute_and_to_messages(&self, executor: &dyn ToolExecutor) -> Vec<Message> {
+        self.execute(executor).await.to_tool_messages()
+    }
+}
+
 // Convenience type aliases
 pub type DefaultHistory = ChatHistory;
 pub type DefaultTools = ToolSet;
-pub type DefaultToolCalls = ToolCallSet;
\ No newline at end of file
+pub type DefaultToolCalls = ToolCallSet;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+
+    // Three turns: a greeting, a tool-assisted question and a follow-up
+    fn history() -> ChatHistory {
+        ChatHistory::new(vec![
+            Message::user("Hi there"),
+            Message::assistant(Some("Hello"), None),
+            Message::user("What is the weather?"),
+            Message::assistant(None::<String>, Some(Vec::new())),
+            Message::tool("Sunny and warm", "call_1"),
+            Message::assistant(Some("It is sunny"), None),
+            Message::user("Thanks"),
+        ])
+    }
+
+    #[test]
+    fn test_last_n_turns_keeps_whole_turns() {
+        let history = history();
+
+        assert_eq!(history.truncate_to_last_n_turns(1).messages.len(), 1);
+        let two = history.truncate_to_last_n_turns(2).messages;
+        assert_eq!(two.len(), 5);
+        assert_eq!(two[0], Message::user("What is the weather?"));
+        assert_eq!(history.truncate_to_last_n_turns(10).messages.len(), 7);
+        assert!(history.truncate_to_last_n_turns(0).messages.is_empty());
+    }
+
+    #[test]
+    fn test_messages_before_first_user_message_form_a_turn() {
+        let history = ChatHistory::new(vec![
+            Message::system("Be brief"),
+            Message::user("Hi"),
+            Message::assistant(Some("Hello"), None),
+        ]);
+
+        assert_eq!(history.truncate_to_last_n_turns(1).messages.len(), 2);
+        assert_eq!(history.truncate_to_last_n_turns(2).messages.len(), 3);
+    }
+
+    #[test]
+    fn test_token_budget_keeps_most_recent_turns_that_fit() {
+        let history = history();
+
+        // The last turn is one word and the one before it ten
+        assert_eq!(history.truncate_to_token_budget(1, words).messages.len(), 1);
+        assert_eq!(history.truncate_to_token_budget(10, words).messages.len(), 1);
+        assert_eq!(history.truncate_to_token_budget(11, words).messages.len(), 5);
+        assert_eq!(history.truncate_to_token_budget(100, words).messages.len(), 7);
+    }
+
+    #[test]
+    fn test_token_budget_drops_a_single_message_over_budget() {
+        let history = ChatHistory::new(vec![Message::user("one two three four")]);
+
+        assert!(history.truncate_to_token_budget(3, words).messages.is_empty());
+        assert!(history.truncate_to_token_budget(0, words).messages.is_empty());
+        assert!(
+            ChatHistory::new(Vec::new())
+                .truncate_to_token_budget(5, words)
+                .messages
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_to_messages_applies_truncation_mode() {
+        assert_eq!(history().to_messages().len(), 7);
+        assert_eq!(
+            history()
+                .with_truncation(TruncationMode::LastNTurns(2))
+                .to_messages()
+                .len(),
+            5
+        );
+        assert_eq!(
+            history()
+                .with_truncation(TruncationMode::TokenBudget(11))
+                .to_messages()
+                .len(),
+            5
+        );
+    }
+}
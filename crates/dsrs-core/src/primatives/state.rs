@@ -0,0 +1,158 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version written by `ModuleStateV1::save`
+pub const CURRENT_STATE_VERSION: u32 = 1;
+
+/// A single learned parameter of a module
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterValue {
+    Demos(JsonValue),
+    Instructions(String),
+}
+
+/// Checkpoint of a module's learned parameters, keyed by parameter name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleStateV1 {
+    // Written as `version`; earlier checkpoints named it `schema_version`
+    #[serde(rename = "version", alias = "schema_version")]
+    pub schema_version: u32,
+    /// Type of the module the parameters came from, see `Module::module_type`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module_type: Option<String>,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub parameters: HashMap<String, ParameterValue>,
+}
+
+impl ModuleStateV1 {
+    pub fn new(parameters: HashMap<String, ParameterValue>) -> Self {
+        ModuleStateV1 {
+            schema_version: CURRENT_STATE_VERSION,
+            module_type: None,
+            created_at: now(),
+            parameters,
+        }
+    }
+
+    /// Convert a raw checkpoint written by an older version into the current format.
+    ///
+    /// Version 0 checkpoints predate versioning: a flat object mapping parameter names
+    /// to instruction strings or demo arrays.
+    pub fn migrate(raw: JsonValue, from_version: u32) -> Result<ModuleStateV1> {
+        match from_version {
+            0 => {
+                let object = raw
+                    .as_object()
+                    .ok_or_else(|| anyhow!("Version 0 state must be a JSON object"))?;
+                let parameters = object
+                    .iter()
+                    .map(|(name, value)| {
+                        let value = match value {
+                            JsonValue::String(instructions) => {
+                                ParameterValue::Instructions(instructions.clone())
+                            }
+                            demos => ParameterValue::Demos(demos.clone()),
+                        };
+                        (name.clone(), value)
+                    })
+                    .collect();
+                Ok(ModuleStateV1::new(parameters))
+            }
+            1 => serde_json::from_value(raw)
+                .map_err(|e| anyhow!("Failed to parse version 1 state: {}", e)),
+            version => Err(anyhow!(
+                "Unsupported state version {} (latest is {})",
+                version,
+                CURRENT_STATE_VERSION
+            )),
+        }
+    }
+
+    /// Parse a checkpoint of any supported version
+    pub fn from_json(json: &str) -> Result<ModuleStateV1> {
+        let raw: JsonValue =
+            serde_json::from_str(json).map_err(|e| anyhow!("Failed to parse state: {}", e))?;
+        let version = match raw.get("version").or_else(|| raw.get("schema_version")) {
+            Some(version) => version
+                .as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| anyhow!("Invalid schema_version: {}", version))?,
+            None => 0,
+        };
+        Self::migrate(raw, version)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| anyhow!("Failed to serialize state: {}", e))?;
+        std::fs::write(path, json)
+            .map_err(|e| anyhow!("Failed to write state to {}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<ModuleStateV1> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read state from {}: {}", path.display(), e))?;
+        Self::from_json(&json)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_migrates_version_0_state() {
+        let old = r#"{
+            "instructions": "Answer concisely.",
+            "demos": [{"question": "2+2?", "answer": "4"}]
+        }"#;
+
+        let state = ModuleStateV1::from_json(old).unwrap();
+
+        assert_eq!(state.schema_version, CURRENT_STATE_VERSION);
+        assert!(state.created_at > 0);
+        assert_eq!(
+            state.parameters["instructions"],
+            ParameterValue::Instructions("Answer concisely.".to_string())
+        );
+        assert_eq!(
+            state.parameters["demos"],
+            ParameterValue::Demos(json!([{"question": "2+2?", "answer": "4"}]))
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let state = ModuleStateV1::new(HashMap::from([(
+            "predict.instructions".to_string(),
+            ParameterValue::Instructions("Be brief.".to_string()),
+        )]));
+        let path = std::env::temp_dir().join(format!("dsrs-state-{}.json", std::process::id()));
+
+        state.save(&path).unwrap();
+        let loaded = ModuleStateV1::load(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), state);
+    }
+
+    #[test]
+    fn test_rejects_future_versions() {
+        let future = r#"{"schema_version": 7, "created_at": 0, "parameters": {}}"#;
+        assert!(ModuleStateV1::from_json(future).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use schemars::Schema;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+/// A single failed check on a signature's inputs or outputs
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{field}: {message}")]
+pub struct ValidationError {
+    pub field: String,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        ValidationError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Every failed check on a value, such as the inputs rejected by `Adapter::validate_inputs`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Error)]
+#[error("Validation failed: {}", errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+pub struct ValidationErrors {
+    pub errors: Vec<ValidationError>,
+}
+
+impl ValidationErrors {
+    pub fn new(errors: Vec<ValidationError>) -> Self {
+        ValidationErrors { errors }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// `Ok` when there are no errors
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() { Ok(()) } else { Err(self) }
+    }
+}
+
+/// A boxed validator closure
+pub type Validator<T> = Box<dyn Fn(&T) -> Result<(), ValidationError> + Send + Sync>;
+
+/// Composes validators that are run in the order they were added
+pub struct ValidationChain<T> {
+    validators: Vec<Validator<T>>,
+}
+
+impl<T> Default for ValidationChain<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ValidationChain<T> {
+    pub fn new() -> Self {
+        ValidationChain {
+            validators: Vec::new(),
+        }
+    }
+
+    pub fn add(
+        &mut self,
+        validator: impl Fn(&T) -> Result<(), ValidationError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.validators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.validators.is_empty()
+    }
+
+    /// Run every validator and collect all failures
+    pub fn run_all(&self, value: &T) -> Vec<ValidationError> {
+        self.validators
+            .iter()
+            .filter_map(|validator| validator(value).err())
+            .collect()
+    }
+
+    /// Run validators until the first failure
+    pub fn run_short_circuit(&self, value: &T) -> Result<(), ValidationError> {
+        self.validators
+            .iter()
+            .try_for_each(|validator| validator(value))
+    }
+}
+
+// Look up a top-level field on the JSON representation of a value
+fn field_value<T: Serialize>(value: &T, field: &str) -> Result<JsonValue, ValidationError> {
+    let json = serde_json::to_value(value)
+        .map_err(|e| ValidationError::new(field, format!("failed to serialize: {}", e)))?;
+    json.get(field)
+        .cloned()
+        .ok_or_else(|| ValidationError::new(field, "field is missing"))
+}
+
+/// Validator requiring `field` to be a string that isn't empty or only whitespace
+pub fn non_empty_string<T: Serialize>(
+    field: &str,
+) -> impl Fn(&T) -> Result<(), ValidationError> + Send + Sync + 'static {
+    let field = field.to_string();
+    move |value: &T| match field_value(value, &field)? {
+        JsonValue::String(s) if !s.trim().is_empty() => Ok(()),
+        JsonValue::String(_) => Err(ValidationError::new(&field, "must not be empty")),
+        _ => Err(ValidationError::new(&field, "must be a string")),
+    }
+}
+
+/// Validator requiring `field` to be a number within `min..=max`
+pub fn numeric_range<T: Serialize>(
+    field: &str,
+    min: f64,
+    max: f64,
+) -> impl Fn(&T) -> Result<(), ValidationError> + Send + Sync + 'static {
+    let field = field.to_string();
+    move |value: &T| match field_value(value, &field)?.as_f64() {
+        Some(n) if (min..=max).contains(&n) => Ok(()),
+        Some(n) => Err(ValidationError::new(
+            &field,
+            format!("{} is outside the range {}..={}", n, min, max),
+        )),
+        None => Err(ValidationError::new(&field, "must be a number")),
+    }
+}
+
+/// Check `value` against the required fields and property types of a JSON schema, recursing
+/// into nested objects. Nested fields are reported by dotted path, e.g. `address.city`
+pub fn validate_schema(value: &JsonValue, schema: &Schema) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    check_value(value, schema.as_value(), schema.as_value(), "", &mut errors);
+    errors
+}
+
+/// Check `value` against every constraint of a JSON schema, such as the patterns and ranges
+/// from `schemars` attributes, using the `jsonschema` crate. Fields are reported by dotted
+/// path; an invalid schema is reported as a single error on the empty field
+pub fn validate_json_schema(value: &JsonValue, schema: &Schema) -> Vec<ValidationError> {
+    let validator = match jsonschema::validator_for(schema.as_value()) {
+        Ok(validator) => validator,
+        Err(e) => return vec![ValidationError::new("", format!("invalid schema: {}", e))],
+    };
+    validator
+        .iter_errors(value)
+        .map(|error| {
+            let field = error.instance_path.as_str().trim_start_matches('/').replace('/', ".");
+            ValidationError::new(field, error.to_string())
+        })
+        .collect()
+}
+
+fn check_value(
+    value: &JsonValue,
+    schema: &JsonValue,
+    root: &JsonValue,
+    path: &str,
+    errors: &mut Vec<ValidationError>,
+) {
+    let schema = resolve_ref(schema, root);
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            JsonValue::String(kind) => vec![kind.as_str()],
+            JsonValue::Array(kinds) => kinds.iter().filter_map(JsonValue::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|kind| has_type(value, kind)) {
+            errors.push(ValidationError::new(
+                path,
+                format!("expected {}, found {}", allowed.join(" or "), json_type(value)),
+            ));
+            return;
+        }
+    }
+
+    let JsonValue::Object(fields) = value else {
+        return;
+    };
+    let field_path = |name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        }
+    };
+    for required in schema
+        .get("required")
+        .and_then(JsonValue::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(JsonValue::as_str)
+    {
+        if !fields.contains_key(required) {
+            errors.push(ValidationError::new(
+                field_path(required),
+                "missing required field",
+            ));
+        }
+    }
+    if let Some(properties) = schema.get("properties").and_then(JsonValue::as_object) {
+        for (name, property) in properties {
+            if let Some(field) = fields.get(name) {
+                check_value(field, property, root, &field_path(name), errors);
+            }
+        }
+    }
+}
+
+// Follows local references such as `#/$defs/Address`
+fn resolve_ref<'a>(schema: &'a JsonValue, root: &'a JsonValue) -> &'a JsonValue {
+    schema
+        .get("$ref")
+        .and_then(JsonValue::as_str)
+        .and_then(|reference| reference.strip_prefix('#'))
+        .and_then(|pointer| root.pointer(pointer))
+        .unwrap_or(schema)
+}
+
+fn has_type(value: &JsonValue, kind: &str) -> bool {
+    match kind {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        kind => json_type(value) == kind,
+    }
+}
+
+fn json_type(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Answer {
+        answer: String,
+        confidence: f64,
+    }
+
+    fn chain() -> ValidationChain<Answer> {
+        let mut chain = ValidationChain::new();
+        chain
+            .add(non_empty_string("answer"))
+            .add(numeric_range("confidence", 0.0, 1.0));
+        chain
+    }
+
+    #[test]
+    fn test_run_all_collects_every_failure() {
+        let invalid = Answer {
+            answer: "  ".to_string(),
+            confidence: 1.5,
+        };
+
+        let errors = chain().run_all(&invalid);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0], ValidationError::new("answer", "must not be empty"));
+        assert_eq!(errors[1].field, "confidence");
+    }
+
+    #[test]
+    fn test_run_short_circuit_stops_at_first_failure() {
+        let invalid = Answer {
+            answer: "".to_string(),
+            confidence: 1.5,
+        };
+        let valid = Answer {
+            answer: "Paris".to_string(),
+            confidence: 0.9,
+        };
+
+        assert_eq!(chain().run_short_circuit(&invalid).unwrap_err().field, "answer");
+        assert!(chain().run_short_circuit(&valid).is_ok());
+        assert!(chain().run_all(&valid).is_empty());
+    }
+}
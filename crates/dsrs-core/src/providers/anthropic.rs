@@ -0,0 +1,343 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::error::rate_limit_error;
+use super::models::*;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use reqwest::{Client, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+// The messages API requires `max_tokens`; used when the config leaves it unset
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Provider for Claude models through the Anthropic messages API
+pub struct AnthropicProvider {
+    client: Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(api_key: String) -> Self {
+        AnthropicProvider {
+            client: Client::new(),
+            api_key,
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
+    }
+
+    /// Point the provider at a different host, e.g. a proxy
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+}
+
+// Also the body Bedrock takes for Claude models, which carries an `anthropic_version` in
+// place of the `model`
+#[derive(Serialize)]
+pub(super) struct MessagesRequest<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) model: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) anthropic_version: Option<&'static str>,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    messages: Vec<WireMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<WireTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct WireMessage {
+    role: &'static str,
+    content: Vec<ContentBlock>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum ContentBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: ImageSource,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: JsonValue,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+    },
+    // Block types this provider doesn't use, e.g. `thinking`
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum ImageSource {
+    Base64 { media_type: String, data: String },
+    Url { url: String },
+}
+
+#[derive(Serialize)]
+struct WireTool {
+    name: String,
+    description: String,
+    input_schema: JsonValue,
+}
+
+#[derive(Deserialize)]
+pub(super) struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    stop_reason: Option<String>,
+    usage: Option<WireUsage>,
+}
+
+#[derive(Deserialize)]
+struct WireUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+// System prompts, assistant turns and tool results are text-only here
+fn text(content: &ContentTypes) -> String {
+    content.as_text().unwrap_or_default().to_string()
+}
+
+// `data:` URLs are unpacked into a base64 source, since the API only fetches http(s) URLs
+fn user_block(content: &ContentTypes) -> ContentBlock {
+    let source = match content {
+        ContentTypes::Text(text) => return ContentBlock::Text { text: text.clone() },
+        ContentTypes::Image { url, .. } => match url
+            .strip_prefix("data:")
+            .and_then(|url| url.split_once(";base64,"))
+        {
+            Some((media_type, data)) => ImageSource::Base64 {
+                media_type: media_type.to_string(),
+                data: data.to_string(),
+            },
+            None => ImageSource::Url { url: url.clone() },
+        },
+        ContentTypes::ImageBytes {
+            data, media_type, ..
+        } => ImageSource::Base64 {
+            media_type: media_type.clone(),
+            data: BASE64_STANDARD.encode(data),
+        },
+    };
+    ContentBlock::Image { source }
+}
+
+// Anthropic takes the system prompt as a top-level field and only allows user and
+// assistant turns, so tool results are sent as `tool_result` blocks in a user turn
+fn to_wire_messages(messages: &[Message]) -> (Option<String>, Vec<WireMessage>) {
+    let mut system = Vec::new();
+    let mut wire: Vec<WireMessage> = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match message {
+            Message::System { content } => {
+                system.push(text(content));
+                continue;
+            }
+            Message::User { content } => ("user", content.iter().map(user_block).collect()),
+            Message::Assistant {
+                content,
+                tool_calls,
+            } => {
+                let mut blocks: Vec<ContentBlock> = content
+                    .iter()
+                    .map(|content| ContentBlock::Text {
+                        text: text(content),
+                    })
+                    .collect();
+                blocks.extend(tool_calls.iter().flatten().map(|call| ContentBlock::ToolUse {
+                    id: call.id.clone(),
+                    name: call.name.clone(),
+                    input: call.arguments.clone(),
+                }));
+                ("assistant", blocks)
+            }
+            Message::Tool {
+                content,
+                tool_call_id,
+            } => ("user", vec![ContentBlock::ToolResult {
+                tool_use_id: tool_call_id.clone(),
+                content: text(content),
+            }]),
+        };
+
+        // Results of parallel tool calls belong in a single user turn
+        let is_tool_result = message.is_tool_call_response();
+        match wire.last_mut() {
+            Some(last)
+                if is_tool_result
+                    && last.role == "user"
+                    && matches!(last.content.last(), Some(ContentBlock::ToolResult { .. })) =>
+            {
+                last.content.extend(blocks)
+            }
+            _ => wire.push(WireMessage {
+                role,
+                content: blocks,
+            }),
+        }
+    }
+
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    (system, wire)
+}
+
+impl From<&AvailableTool> for WireTool {
+    fn from(tool: &AvailableTool) -> Self {
+        WireTool {
+            name: tool.name.clone(),
+            description: tool.desc.clone(),
+            // `input_schema` is required, so tools without arguments get an empty object schema
+            input_schema: tool
+                .input_schema_json
+                .clone()
+                .unwrap_or_else(|| serde_json::json!({"type": "object", "properties": {}})),
+        }
+    }
+}
+
+fn finish_reason(stop_reason: Option<&str>) -> FinishReason {
+    match stop_reason {
+        Some("max_tokens") => FinishReason::Length,
+        Some("tool_use") => FinishReason::ToolCalls,
+        Some("refusal") => FinishReason::ContentFilter,
+        _ => FinishReason::Stop,
+    }
+}
+
+impl<'a> MessagesRequest<'a> {
+    pub(super) fn new(messages: &[Message], config: &'a CompletionConfig) -> Self {
+        let (system, messages) = to_wire_messages(messages);
+        MessagesRequest {
+            model: Some(&config.model),
+            anthropic_version: None,
+            max_tokens: config.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            system,
+            messages,
+            tools: config
+                .tools
+                .as_ref()
+                .map(|tools| tools.iter().map(WireTool::from).collect()),
+            temperature: config.temperature,
+            top_p: config.top_p,
+            stop_sequences: config.stop.clone(),
+        }
+    }
+}
+
+impl From<MessagesResponse> for CompletionResponse {
+    fn from(response: MessagesResponse) -> Self {
+        let mut texts = Vec::new();
+        let mut calls = Vec::new();
+        for block in response.content {
+            match block {
+                ContentBlock::Text { text } => texts.push(text),
+                ContentBlock::ToolUse { id, name, input } => calls.push(ToolCall {
+                    id,
+                    name,
+                    arguments: input,
+                }),
+                ContentBlock::Image { .. }
+                | ContentBlock::ToolResult { .. }
+                | ContentBlock::Unknown => {}
+            }
+        }
+
+        let content = (!texts.is_empty()).then(|| texts.concat());
+        let tool_calls = (!calls.is_empty()).then_some(calls);
+        CompletionResponse::new(
+            Message::assistant(content, tool_calls),
+            finish_reason(response.stop_reason.as_deref()),
+        )
+        .with_usage(
+            response
+                .usage
+                .map(|usage| UsageStats::new(usage.input_tokens, usage.output_tokens)),
+        )
+    }
+}
+
+async fn error_from_response(response: Response) -> ProviderError {
+    if let Some(error) = rate_limit_error(&response, "retry-after") {
+        return error;
+    }
+    let status = response.status();
+
+    let body = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(error) => ProviderError::AnthropicError {
+            error_type: error.error.kind,
+            message: error.error.message,
+        },
+        Err(_) => ProviderError::ApiError {
+            status: status.as_u16(),
+            message: body,
+        },
+    }
+}
+
+impl CompletionProvider for AnthropicProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let mut body = {
+            let guard = messages.read().await;
+            serde_json::to_value(MessagesRequest::new(&guard, &config))
+                .expect("request bodies always serialize")
+        };
+        config.apply_extra(&mut body);
+
+        let response = self
+            .client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let response: MessagesResponse = response.json().await?;
+        Ok(response.into())
+    }
+}
@@ -0,0 +1,81 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+use super::openai::{
+    chat_request_builder, complete_chat, open_stream, request_body, stream_chat,
+};
+use super::streaming::CompletionStream;
+
+use async_openai::Client;
+use async_openai::config::AzureConfig;
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Provider for OpenAI models deployed through Azure OpenAI.
+///
+/// The deployment determines the model, so `CompletionConfig::model` is ignored and
+/// never sent to the endpoint.
+pub struct AzureOpenAIProvider {
+    client: Client<AzureConfig>,
+    deployment_name: String,
+}
+
+impl AzureOpenAIProvider {
+    pub fn new(
+        resource_name: String,
+        deployment_name: String,
+        api_key: String,
+        api_version: String,
+    ) -> Self {
+        let config = AzureConfig::new()
+            .with_api_base(format!("https://{}.openai.azure.com", resource_name))
+            .with_deployment_id(deployment_name.clone())
+            .with_api_version(api_version)
+            .with_api_key(api_key);
+        AzureOpenAIProvider {
+            client: Client::with_config(config),
+            deployment_name,
+        }
+    }
+
+    /// Point the provider at a different host than `https://{resource}.openai.azure.com`
+    pub fn with_api_base(mut self, api_base: impl Into<String>) -> Self {
+        let config = self.client.config().clone().with_api_base(api_base);
+        self.client = Client::with_config(config);
+        self
+    }
+
+    async fn request(
+        &self,
+        messages: &RwLock<Vec<Message>>,
+        config: CompletionConfig,
+    ) -> Result<JsonValue, ProviderError> {
+        // The request type requires a model, so the deployment name stands in for it
+        let config = config.with_model(self.deployment_name.clone());
+        let request = chat_request_builder(messages, &config).await.build()?;
+        Ok(request_body(request, &config))
+    }
+}
+
+impl CompletionProvider for AzureOpenAIProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let request = self.request(&messages, config).await?;
+        complete_chat(&self.client, request).await
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        stream_chat(Box::pin(async move {
+            let request = self.request(&messages, config).await?;
+            open_stream(&self.client, request).await
+        }))
+    }
+}
@@ -0,0 +1,533 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::anthropic::{ContentBlock, MessagesRequest, MessagesResponse};
+use super::models::*;
+use super::streaming::{CompletionStream, StreamChunk, ToolCallAccumulator};
+
+use aws_config::{BehaviorVersion, Region};
+use aws_sdk_bedrockruntime::Client;
+use aws_sdk_bedrockruntime::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_bedrockruntime::operation::invoke_model_with_response_stream as invoke_stream;
+use aws_sdk_bedrockruntime::primitives::Blob;
+use aws_sdk_bedrockruntime::types::ResponseStream;
+use futures::future::BoxFuture;
+use futures::{StreamExt, stream};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::{OnceCell, RwLock};
+
+// The messages API version Bedrock serves Claude models with
+const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+/// Provider for models hosted on AWS Bedrock, through the `InvokeModel` and
+/// `InvokeModelWithResponseStream` APIs.
+///
+/// Each model family has its own request body, so only Anthropic's Claude and Meta's Llama
+/// models are supported, including through cross-region inference profiles such as
+/// `us.anthropic.claude-3-5-haiku-20241022-v1:0`. Credentials come from the standard AWS
+/// chain: environment variables, shared config files, then the instance or task role.
+/// The model given to `new` is used unless `CompletionConfig::model` is set.
+pub struct BedrockProvider {
+    client: OnceCell<Client>,
+    region: String,
+    model_id: String,
+}
+
+impl BedrockProvider {
+    /// The client is created on the first request, when the credential chain is resolved
+    pub fn new(region: String, model_id: String) -> Self {
+        BedrockProvider {
+            client: OnceCell::new(),
+            region,
+            model_id,
+        }
+    }
+
+    /// Use an already configured client, e.g. with explicit credentials or a custom endpoint
+    pub fn with_client(client: Client, model_id: String) -> Self {
+        let region = client
+            .config()
+            .region()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        BedrockProvider {
+            client: OnceCell::new_with(Some(client)),
+            region,
+            model_id,
+        }
+    }
+
+    async fn client(&self) -> &Client {
+        self.client
+            .get_or_init(|| async {
+                let config = aws_config::defaults(BehaviorVersion::latest())
+                    .region(Region::new(self.region.clone()))
+                    .load()
+                    .await;
+                Client::new(&config)
+            })
+            .await
+    }
+
+    fn model_id<'a>(&'a self, config: &'a CompletionConfig) -> &'a str {
+        if config.model.is_empty() {
+            &self.model_id
+        } else {
+            &config.model
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ModelFamily {
+    Claude,
+    Llama,
+}
+
+// Model ids may carry a region prefix or be full inference profile ARNs
+fn model_family(model_id: &str) -> Result<ModelFamily, ProviderError> {
+    if model_id.contains("anthropic.") {
+        Ok(ModelFamily::Claude)
+    } else if model_id.contains("meta.llama") {
+        Ok(ModelFamily::Llama)
+    } else {
+        Err(ProviderError::BedrockError {
+            error_type: "UnsupportedModel".to_string(),
+            message: format!("no request format is known for {}", model_id),
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct LlamaRequest {
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_gen_len: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+}
+
+// Also the shape of each streamed Llama chunk, carrying the next slice of the generation
+#[derive(Deserialize)]
+struct LlamaResponse {
+    #[serde(default)]
+    generation: String,
+    prompt_token_count: Option<u32>,
+    generation_token_count: Option<u32>,
+    stop_reason: Option<String>,
+}
+
+// Streamed Claude events that carry content; the rest, e.g. `message_delta`, are skipped
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClaudeStreamEvent {
+    ContentBlockStart {
+        index: u32,
+        content_block: ContentBlock,
+    },
+    ContentBlockDelta {
+        index: u32,
+        delta: ClaudeDelta,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClaudeDelta {
+    TextDelta {
+        text: String,
+    },
+    InputJsonDelta {
+        partial_json: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+// Text of a message; images are dropped, since they aren't sent to Llama models
+fn text(message: &Message) -> String {
+    match message {
+        Message::User { content } => ContentTypes::join_text(content),
+        Message::System { content } | Message::Tool { content, .. } => {
+            content.as_text().unwrap_or_default().to_string()
+        }
+        Message::Assistant { content, .. } => content
+            .as_ref()
+            .and_then(ContentTypes::as_text)
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+// The conversation in the Llama 3 chat template, ending with an open assistant turn
+fn llama_prompt(messages: &[Message]) -> String {
+    let mut prompt = "<|begin_of_text|>".to_string();
+    for message in messages {
+        let role = match message {
+            Message::System { .. } => "system",
+            Message::User { .. } => "user",
+            Message::Assistant { .. } => "assistant",
+            Message::Tool { .. } => "ipython",
+        };
+        prompt.push_str(&format!(
+            "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+            role,
+            text(message)
+        ));
+    }
+    prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+    prompt
+}
+
+fn request_body(
+    family: ModelFamily,
+    messages: &[Message],
+    config: &CompletionConfig,
+    model_id: &str,
+) -> Result<Vec<u8>, ProviderError> {
+    let mut body = match family {
+        ModelFamily::Claude => {
+            let mut request = MessagesRequest::new(messages, config);
+            request.model = None;
+            request.anthropic_version = Some(BEDROCK_ANTHROPIC_VERSION);
+            serde_json::to_value(&request)
+        }
+        ModelFamily::Llama => {
+            // Tool definitions can't be passed to Llama models through `InvokeModel`
+            if config.tools.as_ref().is_some_and(|tools| !tools.is_empty()) {
+                return Err(ProviderError::ToolsNotSupported {
+                    model: model_id.to_string(),
+                });
+            }
+            serde_json::to_value(&LlamaRequest {
+                prompt: llama_prompt(messages),
+                max_gen_len: config.max_tokens,
+                temperature: config.temperature,
+                top_p: config.top_p,
+            })
+        }
+    }
+    .expect("request bodies always serialize");
+    config.apply_extra(&mut body);
+    Ok(serde_json::to_vec(&body).expect("request bodies always serialize"))
+}
+
+fn invalid_body(error: serde_json::Error) -> ProviderError {
+    ProviderError::BedrockError {
+        error_type: "InvalidResponse".to_string(),
+        message: error.to_string(),
+    }
+}
+
+impl From<LlamaResponse> for CompletionResponse {
+    fn from(response: LlamaResponse) -> Self {
+        let finish_reason = match response.stop_reason.as_deref() {
+            Some("length") => FinishReason::Length,
+            _ => FinishReason::Stop,
+        };
+        let usage = response
+            .prompt_token_count
+            .zip(response.generation_token_count)
+            .map(|(prompt, generation)| UsageStats::new(prompt, generation));
+        CompletionResponse::new(
+            Message::assistant(Some(response.generation), None),
+            finish_reason,
+        )
+        .with_usage(usage)
+    }
+}
+
+fn parse_response(family: ModelFamily, body: &[u8]) -> Result<CompletionResponse, ProviderError> {
+    match family {
+        ModelFamily::Claude => serde_json::from_slice::<MessagesResponse>(body).map(Into::into),
+        ModelFamily::Llama => serde_json::from_slice::<LlamaResponse>(body).map(Into::into),
+    }
+    .map_err(invalid_body)
+}
+
+// Text and tool call fragments of a streamed chunk. Events without content, such as the
+// stop reason, are skipped
+fn parse_chunk(
+    family: ModelFamily,
+    bytes: &[u8],
+    tool_calls: &mut ToolCallAccumulator,
+) -> Result<Vec<StreamChunk>, ProviderError> {
+    let text = match family {
+        ModelFamily::Llama => Some(
+            serde_json::from_slice::<LlamaResponse>(bytes)
+                .map_err(invalid_body)?
+                .generation,
+        ),
+        ModelFamily::Claude => {
+            match serde_json::from_slice::<ClaudeStreamEvent>(bytes).map_err(invalid_body)? {
+                ClaudeStreamEvent::ContentBlockStart {
+                    content_block: ContentBlock::Text { text },
+                    ..
+                } => Some(text),
+                ClaudeStreamEvent::ContentBlockStart {
+                    index,
+                    content_block: ContentBlock::ToolUse { id, name, .. },
+                } => {
+                    tool_calls.push(index, Some(&id), Some(&name), None);
+                    None
+                }
+                ClaudeStreamEvent::ContentBlockDelta {
+                    delta: ClaudeDelta::TextDelta { text },
+                    ..
+                } => Some(text),
+                ClaudeStreamEvent::ContentBlockDelta {
+                    index,
+                    delta: ClaudeDelta::InputJsonDelta { partial_json },
+                } => {
+                    tool_calls.push(index, None, None, Some(&partial_json));
+                    None
+                }
+                _ => None,
+            }
+        }
+    };
+    Ok(text
+        .filter(|text| !text.is_empty())
+        .map(StreamChunk::Text)
+        .into_iter()
+        .collect())
+}
+
+fn error_from_sdk<E, R>(error: SdkError<E, R>) -> ProviderError
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+    R: std::fmt::Debug,
+{
+    match &error {
+        SdkError::TimeoutError(_) => return ProviderError::Timeout,
+        SdkError::DispatchFailure(_) => {
+            return ProviderError::NetworkError(DisplayErrorContext(&error).to_string());
+        }
+        _ => {}
+    }
+
+    let error_type = error.code().unwrap_or("Unknown").to_string();
+    let message = error
+        .message()
+        .map(str::to_string)
+        .unwrap_or_else(|| DisplayErrorContext(&error).to_string());
+    match error_type.as_str() {
+        "ThrottlingException" => ProviderError::RateLimit { retry_after: None },
+        "ServiceUnavailableException" | "ModelNotReadyException" => {
+            ProviderError::ServiceUnavailable
+        }
+        "ModelTimeoutException" => ProviderError::Timeout,
+        "AccessDeniedException" | "UnrecognizedClientException" | "ExpiredTokenException" => {
+            ProviderError::AuthenticationFailed
+        }
+        _ => ProviderError::BedrockError {
+            error_type,
+            message,
+        },
+    }
+}
+
+// Holds the event receiver, whose type isn't exported. Boxed, as it is large
+type ResponseReceiver = Box<invoke_stream::InvokeModelWithResponseStreamOutput>;
+
+enum StreamState<'a> {
+    Connecting(BoxFuture<'a, Result<(ModelFamily, ResponseReceiver), ProviderError>>),
+    Streaming(ModelFamily, ResponseReceiver, ToolCallAccumulator),
+    Done,
+}
+
+impl BedrockProvider {
+    async fn open_stream(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<(ModelFamily, ResponseReceiver), ProviderError> {
+        let model_id = self.model_id(&config);
+        let family = model_family(model_id)?;
+        let body = {
+            let guard = messages.read().await;
+            request_body(family, &guard, &config, model_id)?
+        };
+
+        let output = self
+            .client()
+            .await
+            .invoke_model_with_response_stream()
+            .model_id(model_id)
+            .content_type("application/json")
+            .body(Blob::new(body))
+            .send()
+            .await
+            .map_err(error_from_sdk)?;
+        Ok((family, Box::new(output)))
+    }
+}
+
+impl CompletionProvider for BedrockProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let model_id = self.model_id(&config);
+        let family = model_family(model_id)?;
+        let body = {
+            let guard = messages.read().await;
+            request_body(family, &guard, &config, model_id)?
+        };
+
+        let output = self
+            .client()
+            .await
+            .invoke_model()
+            .model_id(model_id)
+            .content_type("application/json")
+            .accept("application/json")
+            .body(Blob::new(body))
+            .send()
+            .await
+            .map_err(error_from_sdk)?;
+        parse_response(family, output.body().as_ref())
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        let open = Box::pin(self.open_stream(messages, config));
+        let chunks = stream::unfold(StreamState::Connecting(open), |state| async move {
+            match state {
+                StreamState::Connecting(open) => match open.await {
+                    Ok((family, receiver)) => Some((
+                        Vec::new(),
+                        StreamState::Streaming(family, receiver, ToolCallAccumulator::new()),
+                    )),
+                    Err(e) => Some((vec![Err(e)], StreamState::Done)),
+                },
+                StreamState::Streaming(family, mut receiver, mut tool_calls) => {
+                    match receiver.body.recv().await {
+                        Ok(Some(ResponseStream::Chunk(part))) => {
+                            let bytes = part.bytes().map(Blob::as_ref).unwrap_or_default();
+                            match parse_chunk(family, bytes, &mut tool_calls) {
+                                Ok(chunks) => Some((
+                                    chunks.into_iter().map(Ok).collect(),
+                                    StreamState::Streaming(family, receiver, tool_calls),
+                                )),
+                                Err(e) => Some((vec![Err(e)], StreamState::Done)),
+                            }
+                        }
+                        Ok(Some(_)) => Some((
+                            Vec::new(),
+                            StreamState::Streaming(family, receiver, tool_calls),
+                        )),
+                        Err(e) => Some((vec![Err(error_from_sdk(e))], StreamState::Done)),
+                        // Tool calls are only complete once the stream has ended
+                        Ok(None) => {
+                            let chunks = tool_calls
+                                .finish()
+                                .into_iter()
+                                .map(|call| Ok(StreamChunk::ToolCall(call)))
+                                .collect();
+                            Some((chunks, StreamState::Done))
+                        }
+                    }
+                }
+                StreamState::Done => None,
+            }
+        });
+
+        Box::pin(chunks.flat_map(stream::iter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value as JsonValue;
+
+    #[test]
+    fn test_model_family_from_ids_and_profiles() {
+        let family = |id| model_family(id).ok();
+        assert_eq!(
+            family("anthropic.claude-3-5-sonnet-20241022-v2:0"),
+            Some(ModelFamily::Claude)
+        );
+        assert_eq!(
+            family("us.anthropic.claude-3-5-haiku-20241022-v1:0"),
+            Some(ModelFamily::Claude)
+        );
+        assert_eq!(
+            family("meta.llama3-1-8b-instruct-v1:0"),
+            Some(ModelFamily::Llama)
+        );
+        assert_eq!(family("amazon.titan-text-express-v1"), None);
+    }
+
+    #[test]
+    fn test_claude_body_has_top_level_system_and_no_model() {
+        let messages = [Message::system("You are helpful."), Message::user("Hello")];
+        let config = CompletionConfig::default()
+            .with_model("anthropic.claude-3-5-haiku-20241022-v1:0")
+            .with_max_tokens(256);
+
+        let body = request_body(ModelFamily::Claude, &messages, &config, &config.model).unwrap();
+
+        assert_eq!(
+            serde_json::from_slice::<JsonValue>(&body).unwrap(),
+            serde_json::json!({
+                "anthropic_version": "bedrock-2023-05-31",
+                "max_tokens": 256,
+                "system": "You are helpful.",
+                "messages": [{"role": "user", "content": [{"type": "text", "text": "Hello"}]}]
+            })
+        );
+    }
+
+    #[test]
+    fn test_llama_prompt_uses_chat_template() {
+        let messages = [Message::system("Be brief."), Message::user("Hi")];
+
+        assert_eq!(
+            llama_prompt(&messages),
+            "<|begin_of_text|><|start_header_id|>system<|end_header_id|>\n\nBe brief.<|eot_id|>\
+             <|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+    }
+
+    #[test]
+    fn test_claude_stream_chunks_assemble_tool_calls() {
+        let events = [
+            r#"{"type": "message_start", "message": {}}"#,
+            r#"{"type": "content_block_start", "index": 0,
+                "content_block": {"type": "text", "text": ""}}"#,
+            r#"{"type": "content_block_delta", "index": 0,
+                "delta": {"type": "text_delta", "text": "Checking."}}"#,
+            r#"{"type": "content_block_start", "index": 1,
+                "content_block": {"type": "tool_use", "id": "toolu_1", "name": "lookup",
+                                  "input": {}}}"#,
+            r#"{"type": "content_block_delta", "index": 1,
+                "delta": {"type": "input_json_delta", "partial_json": "{\"q\": \"a\"}"}}"#,
+            r#"{"type": "message_delta", "delta": {"stop_reason": "tool_use"}}"#,
+        ];
+        let mut tool_calls = ToolCallAccumulator::new();
+
+        let chunks: Vec<StreamChunk> = events
+            .iter()
+            .flat_map(|event| {
+                parse_chunk(ModelFamily::Claude, event.as_bytes(), &mut tool_calls).unwrap()
+            })
+            .collect();
+
+        assert_eq!(chunks, vec![StreamChunk::Text("Checking.".to_string())]);
+        let calls = tool_calls.finish();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "toolu_1");
+        assert_eq!(calls[0].arguments, serde_json::json!({"q": "a"}));
+    }
+}
@@ -0,0 +1,208 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+use super::streaming::{CompletionStream, message_to_chunks};
+
+use async_trait::async_trait;
+use futures::{StreamExt, stream};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+/// Storage for `CachedProvider` responses, keyed by a hash of the request
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    async fn get(&self, key: u64) -> Option<CompletionResponse>;
+    async fn put(&self, key: u64, response: CompletionResponse);
+}
+
+/// Unbounded cache held in memory for the lifetime of the provider
+#[derive(Default)]
+pub struct InMemoryCache {
+    entries: Mutex<HashMap<u64, CompletionResponse>>,
+}
+
+impl InMemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CacheBackend for InMemoryCache {
+    async fn get(&self, key: u64) -> Option<CompletionResponse> {
+        self.entries.lock().unwrap().get(&key).cloned()
+    }
+
+    async fn put(&self, key: u64, response: CompletionResponse) {
+        self.entries.lock().unwrap().insert(key, response);
+    }
+}
+
+/// Wraps a provider and answers repeated requests from a cache instead of the network.
+///
+/// Requests are keyed by their messages and config; set `CompletionConfig::skip_cache`
+/// to force a fresh completion.
+pub struct CachedProvider<P: CompletionProvider, B: CacheBackend = InMemoryCache> {
+    inner: P,
+    backend: B,
+}
+
+impl<P: CompletionProvider> CachedProvider<P> {
+    pub fn new(inner: P) -> Self {
+        Self::with_backend(inner, InMemoryCache::new())
+    }
+}
+
+impl<P: CompletionProvider, B: CacheBackend> CachedProvider<P, B> {
+    pub fn with_backend(inner: P, backend: B) -> Self {
+        CachedProvider { inner, backend }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+}
+
+// Stable across runs, so persistent backends stay valid between processes
+fn cache_key(messages: &[Message], config: &CompletionConfig) -> Option<u64> {
+    let bytes = serde_json::to_vec(&(messages, config)).ok()?;
+    let hash = blake3::hash(&bytes);
+    Some(u64::from_le_bytes(hash.as_bytes()[..8].try_into().unwrap()))
+}
+
+impl<P: CompletionProvider, B: CacheBackend> CompletionProvider for CachedProvider<P, B> {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let key = cache_key(&messages.read().await, &config);
+        if let Some(key) = key
+            && !config.skip_cache
+            && let Some(response) = self.backend.get(key).await
+        {
+            return Ok(response);
+        }
+
+        let response = self.inner.complete(messages, config).await?;
+        // Stored even when the lookup was skipped, replacing the stale entry
+        if let Some(key) = key {
+            self.backend.put(key, response.clone()).await;
+        }
+        Ok(response)
+    }
+
+    // Hits are replayed from the cache; misses stream from the inner provider uncached
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        let chunks = stream::once(async move {
+            let key = cache_key(&messages.read().await, &config);
+            let cached = match key.filter(|_| !config.skip_cache) {
+                Some(key) => self.backend.get(key).await,
+                None => None,
+            };
+            match cached {
+                Some(response) => {
+                    let chunks = message_to_chunks(response.message).into_iter().map(Ok);
+                    stream::iter(chunks).boxed()
+                }
+                None => self.inner.stream(messages, config),
+            }
+        });
+        Box::pin(chunks.flatten())
+    }
+
+    fn max_context_tokens(&self) -> Option<u32> {
+        self.inner.max_context_tokens()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct CountingProvider {
+        calls: AtomicUsize,
+    }
+
+    impl CompletionProvider for CountingProvider {
+        async fn complete(
+            &self,
+            _messages: Arc<RwLock<Vec<Message>>>,
+            _config: CompletionConfig,
+        ) -> Result<CompletionResponse, ProviderError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(CompletionResponse::new(
+                Message::assistant(Some(format!("Answer {}", call)), None),
+                FinishReason::Stop,
+            ))
+        }
+    }
+
+    fn messages(text: &str) -> Arc<RwLock<Vec<Message>>> {
+        Arc::new(RwLock::new(vec![Message::user(text)]))
+    }
+
+    #[tokio::test]
+    async fn test_identical_requests_hit_cache() {
+        let provider = CachedProvider::new(CountingProvider::default());
+        let config = CompletionConfig::default();
+
+        let first = provider
+            .complete(messages("Hi"), config.clone())
+            .await
+            .unwrap();
+        let second = provider
+            .complete(messages("Hi"), config.clone())
+            .await
+            .unwrap();
+        let other = provider.complete(messages("Bye"), config).await.unwrap();
+
+        assert_eq!(first.message, second.message);
+        assert_ne!(first.message, other.message);
+        assert_eq!(provider.inner().calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_config_is_part_of_key() {
+        let provider = CachedProvider::new(CountingProvider::default());
+        let hot = CompletionConfig::default().with_temperature(1.0);
+
+        provider
+            .complete(messages("Hi"), CompletionConfig::default())
+            .await
+            .unwrap();
+        provider.complete(messages("Hi"), hot).await.unwrap();
+
+        assert_eq!(provider.inner().calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_skip_cache_refreshes_entry() {
+        let provider = CachedProvider::new(CountingProvider::default());
+        let skip = CompletionConfig::default().with_skip_cache(true);
+
+        provider
+            .complete(messages("Hi"), CompletionConfig::default())
+            .await
+            .unwrap();
+        let fresh = provider.complete(messages("Hi"), skip).await.unwrap();
+        let cached = provider
+            .complete(messages("Hi"), CompletionConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(fresh.message, Message::assistant(Some("Answer 1"), None));
+        assert_eq!(cached.message, fresh.message);
+    }
+}
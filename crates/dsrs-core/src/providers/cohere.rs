@@ -0,0 +1,391 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::error::rate_limit_error;
+use super::models::*;
+
+use reqwest::{Client, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const DEFAULT_BASE_URL: &str = "https://api.cohere.com";
+
+/// Provider for Cohere's Command models through the v1 chat API
+pub struct CohereProvider {
+    client: Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl CohereProvider {
+    pub fn new(api_key: String) -> Self {
+        CohereProvider {
+            client: Client::new(),
+            api_key,
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
+    }
+
+    /// Point the provider at a different host, e.g. a private deployment
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    chat_history: Vec<WireMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preamble: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<WireTool>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_results: Vec<WireToolResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "role", rename_all = "UPPERCASE")]
+enum WireMessage {
+    User {
+        message: String,
+    },
+    Chatbot {
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tool_calls: Option<Vec<WireToolCall>>,
+    },
+    Tool {
+        tool_results: Vec<WireToolResult>,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct WireToolCall {
+    name: String,
+    #[serde(default)]
+    parameters: JsonValue,
+}
+
+#[derive(Serialize)]
+struct WireToolResult {
+    call: WireToolCall,
+    outputs: Vec<JsonValue>,
+}
+
+#[derive(Serialize)]
+struct WireTool {
+    name: String,
+    description: String,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    parameter_definitions: Map<String, JsonValue>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    text: String,
+    #[serde(default)]
+    generation_id: String,
+    tool_calls: Option<Vec<WireToolCall>>,
+    finish_reason: Option<String>,
+    meta: Option<ResponseMeta>,
+}
+
+#[derive(Deserialize)]
+struct ResponseMeta {
+    billed_units: Option<BilledUnits>,
+}
+
+#[derive(Deserialize)]
+struct BilledUnits {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+// `complete` refuses conversations with images, so every part here is text
+fn text(content: &ContentTypes) -> String {
+    content.as_text().unwrap_or_default().to_string()
+}
+
+fn finish_reason(reason: Option<&str>) -> FinishReason {
+    match reason {
+        Some("MAX_TOKENS") => FinishReason::Length,
+        Some("ERROR_TOXIC") => FinishReason::ContentFilter,
+        _ => FinishReason::Stop,
+    }
+}
+
+// Cohere expects tool outputs as objects, so plain text results are wrapped
+fn tool_output(content: &str) -> JsonValue {
+    match serde_json::from_str::<JsonValue>(content) {
+        Ok(JsonValue::Object(output)) => JsonValue::Object(output),
+        _ => serde_json::json!({ "result": content }),
+    }
+}
+
+/// The converted conversation: the preamble, the history, and the final turn, which is
+/// either the latest user message or the results of the latest tool calls
+struct Conversation {
+    preamble: Option<String>,
+    chat_history: Vec<WireMessage>,
+    message: String,
+    tool_results: Vec<WireToolResult>,
+}
+
+// Cohere tool calls have no ids, so results are matched to their call through the id
+// the call was given when it was received
+fn to_conversation(messages: &[Message]) -> Conversation {
+    let calls: HashMap<&str, &ToolCall> = messages
+        .iter()
+        .filter_map(Message::as_tool_calls)
+        .flatten()
+        .map(|call| (call.id.as_str(), call))
+        .collect();
+
+    let mut preamble = Vec::new();
+    let mut chat_history = Vec::new();
+    for message in messages {
+        let wire = match message {
+            Message::System { content } => {
+                preamble.push(text(content));
+                continue;
+            }
+            Message::User { content } => WireMessage::User {
+                message: ContentTypes::join_text(content),
+            },
+            Message::Assistant {
+                content,
+                tool_calls,
+            } => WireMessage::Chatbot {
+                message: content.as_ref().map(text).unwrap_or_default(),
+                tool_calls: tool_calls
+                    .as_ref()
+                    .filter(|calls| !calls.is_empty())
+                    .map(|calls| calls.iter().map(WireToolCall::from).collect()),
+            },
+            Message::Tool {
+                content,
+                tool_call_id,
+            } => {
+                let call = calls.get(tool_call_id.as_str()).map_or_else(
+                    || WireToolCall {
+                        name: tool_call_id.clone(),
+                        parameters: JsonValue::Object(Map::new()),
+                    },
+                    |call| WireToolCall::from(*call),
+                );
+                let result = WireToolResult {
+                    call,
+                    outputs: vec![tool_output(&text(content))],
+                };
+                // Results of parallel tool calls belong in a single turn
+                if let Some(WireMessage::Tool { tool_results }) = chat_history.last_mut() {
+                    tool_results.push(result);
+                    continue;
+                }
+                WireMessage::Tool {
+                    tool_results: vec![result],
+                }
+            }
+        };
+        chat_history.push(wire);
+    }
+
+    let (message, tool_results) = match chat_history.pop() {
+        Some(WireMessage::User { message }) => (message, Vec::new()),
+        Some(WireMessage::Tool { tool_results }) => (String::new(), tool_results),
+        Some(other) => {
+            chat_history.push(other);
+            (String::new(), Vec::new())
+        }
+        None => (String::new(), Vec::new()),
+    };
+
+    Conversation {
+        preamble: (!preamble.is_empty()).then(|| preamble.join("\n\n")),
+        chat_history,
+        message,
+        tool_results,
+    }
+}
+
+impl From<&ToolCall> for WireToolCall {
+    fn from(tool_call: &ToolCall) -> Self {
+        WireToolCall {
+            name: tool_call.name.clone(),
+            parameters: tool_call.arguments.clone(),
+        }
+    }
+}
+
+// Cohere describes parameters with Python type names rather than a JSON schema
+fn parameter_type(schema: &JsonValue) -> &'static str {
+    match schema.get("type").and_then(JsonValue::as_str) {
+        Some("integer") => "int",
+        Some("number") => "float",
+        Some("boolean") => "bool",
+        Some("array") => "List",
+        Some("object") => "Dict",
+        _ => "str",
+    }
+}
+
+impl From<&AvailableTool> for WireTool {
+    fn from(tool: &AvailableTool) -> Self {
+        let schema = tool.input_schema_json.as_ref();
+        let required: Vec<&str> = schema
+            .and_then(|schema| schema.get("required"))
+            .and_then(JsonValue::as_array)
+            .map(|names| names.iter().filter_map(JsonValue::as_str).collect())
+            .unwrap_or_default();
+
+        let parameter_definitions = schema
+            .and_then(|schema| schema.get("properties"))
+            .and_then(JsonValue::as_object)
+            .map(|properties| {
+                properties
+                    .iter()
+                    .map(|(name, property)| {
+                        let mut definition = serde_json::json!({
+                            "type": parameter_type(property),
+                            "required": required.contains(&name.as_str()),
+                        });
+                        if let Some(description) = property.get("description") {
+                            definition["description"] = description.clone();
+                        }
+                        (name.clone(), definition)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        WireTool {
+            name: tool.name.clone(),
+            description: tool.desc.clone(),
+            parameter_definitions,
+        }
+    }
+}
+
+impl From<ChatResponse> for CompletionResponse {
+    fn from(response: ChatResponse) -> Self {
+        let tool_calls = response
+            .tool_calls
+            .filter(|calls| !calls.is_empty())
+            .map(|calls| {
+                calls
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, call)| ToolCall {
+                        id: format!("{}-{}", response.generation_id, index),
+                        name: call.name,
+                        arguments: call.parameters,
+                    })
+                    .collect::<Vec<_>>()
+            });
+        let finish_reason = match (&tool_calls, response.finish_reason.as_deref()) {
+            (Some(_), Some("COMPLETE")) => FinishReason::ToolCalls,
+            (_, reason) => finish_reason(reason),
+        };
+        let content = (!response.text.is_empty()).then_some(response.text);
+
+        let usage = response
+            .meta
+            .and_then(|meta| meta.billed_units)
+            .map(|units| UsageStats::new(units.input_tokens, units.output_tokens));
+
+        CompletionResponse::new(Message::assistant(content, tool_calls), finish_reason)
+            .with_usage(usage)
+    }
+}
+
+async fn error_from_response(response: Response) -> ProviderError {
+    if let Some(error) = rate_limit_error(&response, "retry-after") {
+        return error;
+    }
+    let status = response.status();
+
+    let body = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(error) => ProviderError::CohereError {
+            status: status.as_u16(),
+            message: error.message,
+        },
+        Err(_) => ProviderError::ApiError {
+            status: status.as_u16(),
+            message: body,
+        },
+    }
+}
+
+impl CompletionProvider for CohereProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let conversation = {
+            let guard = messages.read().await;
+            // The v1 chat API only takes text messages
+            if guard.iter().any(Message::has_images) {
+                return Err(ProviderError::ImagesNotSupported {
+                    model: config.model,
+                });
+            }
+            to_conversation(&guard)
+        };
+
+        let request = ChatRequest {
+            model: &config.model,
+            message: conversation.message,
+            chat_history: conversation.chat_history,
+            preamble: conversation.preamble,
+            tools: config
+                .tools
+                .as_ref()
+                .map(|tools| tools.iter().map(WireTool::from).collect()),
+            tool_results: conversation.tool_results,
+            temperature: config.temperature,
+            max_tokens: config.max_tokens,
+            p: config.top_p,
+            stop_sequences: config.stop.clone(),
+        };
+        let mut body = serde_json::to_value(request).unwrap();
+        config.apply_extra(&mut body);
+
+        let response = self
+            .client
+            .post(format!("{}/v1/chat", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let response: ChatResponse = response.json().await?;
+        Ok(response.into())
+    }
+}
@@ -0,0 +1,96 @@
+use async_openai::types::{CreateEmbeddingRequest, EmbeddingInput};
+use async_openai::{Client, config::OpenAIConfig};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use super::ProviderError;
+
+/// Provider that turns texts into embedding vectors
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Embed each text, returning one vector per input in the same order
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError>;
+
+    /// Length of the vectors `embed` returns, if known
+    fn embedding_dimension(&self) -> Option<usize> {
+        None
+    }
+}
+
+#[async_trait]
+impl<T: EmbeddingProvider + ?Sized> EmbeddingProvider for Arc<T> {
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        (**self).embed(texts).await
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        (**self).embedding_dimension()
+    }
+}
+
+/// Embeddings from OpenAI's embeddings API, or a compatible server via `with_base_url`
+pub struct OpenAIEmbeddingProvider {
+    client: Client<OpenAIConfig>,
+    model: String,
+    dimensions: Option<u32>,
+}
+
+impl OpenAIEmbeddingProvider {
+    pub fn new(api_key: String, model: impl Into<String>) -> Self {
+        OpenAIEmbeddingProvider {
+            client: Client::with_config(OpenAIConfig::new().with_api_key(api_key)),
+            model: model.into(),
+            dimensions: None,
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        let config = self.client.config().clone().with_api_base(base_url);
+        self.client = Client::with_config(config);
+        self
+    }
+
+    /// Shorten the vectors to `dimensions`, for `text-embedding-3` and later models
+    pub fn with_dimensions(mut self, dimensions: u32) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAIEmbeddingProvider {
+    async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+        // The API rejects an empty input
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let request = CreateEmbeddingRequest {
+            model: self.model.clone(),
+            input: EmbeddingInput::StringArray(texts),
+            encoding_format: None,
+            user: None,
+            dimensions: self.dimensions,
+        };
+        let mut data = self.client.embeddings().create(request).await?.data;
+        data.sort_by_key(|embedding| embedding.index);
+        Ok(data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    fn embedding_dimension(&self) -> Option<usize> {
+        let default = match self.model.as_str() {
+            "text-embedding-3-small" | "text-embedding-ada-002" => Some(1536),
+            "text-embedding-3-large" => Some(3072),
+            _ => None,
+        };
+        self.dimensions
+            .map(|dimensions| dimensions as usize)
+            .or(default)
+    }
+}
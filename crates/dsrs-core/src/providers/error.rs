@@ -1,8 +1,249 @@
-use async_openai::error::OpenAIError;
+use async_openai::error::{ApiError as OpenAIApiError, OpenAIError};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ProviderError {
     #[error("OpenAI error occurred: {0}")]
-    OpenAIError(#[from] OpenAIError),
+    OpenAIError(OpenAIError),
+    #[error("Anthropic error occurred ({error_type}): {message}")]
+    AnthropicError { error_type: String, message: String },
+    #[error("Cohere error occurred (status {status}): {message}")]
+    CohereError { status: u16, message: String },
+    #[error("Mistral error occurred ({error_type}): {message}")]
+    MistralError {
+        status: u16,
+        error_type: String,
+        message: String,
+    },
+    #[error("Gemini error occurred ({status}): {message}")]
+    GeminiError { status: String, message: String },
+    #[error("Bedrock error occurred ({error_type}): {message}")]
+    BedrockError { error_type: String, message: String },
+    #[error("HTTP request failed: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+    #[error("API returned status {status}: {message}")]
+    ApiError { status: u16, message: String },
+    #[error("Rate limit exceeded (retry after {retry_after:?})")]
+    RateLimit { retry_after: Option<Duration> },
+    #[error("Prompt exceeds the model's context length (max {max_tokens:?} tokens)")]
+    ContextLengthExceeded { max_tokens: Option<u32> },
+    #[error("Authentication failed")]
+    AuthenticationFailed,
+    #[error("Service unavailable")]
+    ServiceUnavailable,
+    #[error("Model {model} does not support tool calls")]
+    ToolsNotSupported { model: String },
+    #[error("Model {model} does not support image inputs")]
+    ImagesNotSupported { model: String },
+    #[error("Content contains banned term \"{term}\"")]
+    ContentFiltered { term: String },
+    #[error("Request timed out")]
+    Timeout,
+    #[error("Network error: {0}")]
+    NetworkError(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+// Map the failures we can recognize to the provider-independent variants
+impl From<OpenAIError> for ProviderError {
+    fn from(error: OpenAIError) -> Self {
+        match error {
+            OpenAIError::Reqwest(error) => reqwest_error(error)
+                .unwrap_or_else(|error| ProviderError::OpenAIError(OpenAIError::Reqwest(error))),
+            OpenAIError::ApiError(error) => openai_api_error(error),
+            OpenAIError::StreamError(message) => ProviderError::NetworkError(message),
+            error => ProviderError::OpenAIError(error),
+        }
+    }
+}
+
+// The variant for a timeout, connection failure or telling status, or the error back
+fn reqwest_error(error: reqwest::Error) -> Result<ProviderError, reqwest::Error> {
+    if error.is_timeout() {
+        return Ok(ProviderError::Timeout);
+    }
+    if error.is_connect() {
+        return Ok(ProviderError::NetworkError(error.to_string()));
+    }
+    match error.status().map(|status| status.as_u16()) {
+        Some(401 | 403) => Ok(ProviderError::AuthenticationFailed),
+        Some(429) => Ok(ProviderError::RateLimit { retry_after: None }),
+        Some(503) => Ok(ProviderError::ServiceUnavailable),
+        _ => Err(error),
+    }
+}
+
+fn openai_api_error(error: OpenAIApiError) -> ProviderError {
+    match (error.code.as_deref(), error.r#type.as_deref()) {
+        (Some("invalid_api_key"), _) | (_, Some("authentication_error")) => {
+            ProviderError::AuthenticationFailed
+        }
+        (Some("rate_limit_exceeded"), _) => ProviderError::RateLimit { retry_after: None },
+        (Some("context_length_exceeded"), _) => ProviderError::ContextLengthExceeded {
+            max_tokens: context_limit(&error.message),
+        },
+        (_, Some("server_error")) => ProviderError::ServiceUnavailable,
+        _ => ProviderError::OpenAIError(OpenAIError::ApiError(error)),
+    }
+}
+
+// Time until the rate limit resets, in seconds (`30`, `1.5`) or as a duration like
+// `30s`, `6m0s`, `1m30.5s` or `500ms`
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let secs = match value.parse::<f64>() {
+        Ok(secs) => secs,
+        Err(_) => parse_duration_secs(value)?,
+    };
+    Some(secs)
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+// Sum of `<number><unit>` parts with units `h`, `m`, `s` or `ms`
+fn parse_duration_secs(value: &str) -> Option<f64> {
+    if value.is_empty() {
+        return None;
+    }
+    let mut rest = value;
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .filter(|end| *end > 0)?;
+        let number = rest[..number_end].parse::<f64>().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        total += number * scale;
+        rest = &rest[unit_end..];
+    }
+    Some(total)
+}
+
+// `RateLimit` if `response` is a 429, waiting as long as its `header` says
+pub(crate) fn rate_limit_error(
+    response: &reqwest::Response,
+    header: &str,
+) -> Option<ProviderError> {
+    if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let retry_after = response
+        .headers()
+        .get(header)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    Some(ProviderError::RateLimit { retry_after })
+}
+
+// The limit from e.g. "This model's maximum context length is 8192 tokens. However, ..."
+fn context_limit(message: &str) -> Option<u32> {
+    let (_, rest) = message.split_once("maximum context length is ")?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+impl ProviderError {
+    /// Whether the request was rejected because of the credentials, which retrying
+    /// with the same credentials won't fix
+    pub fn is_auth_error(&self) -> bool {
+        match self {
+            ProviderError::AuthenticationFailed => true,
+            ProviderError::AnthropicError { error_type, .. } => {
+                error_type == "authentication_error" || error_type == "permission_error"
+            }
+            ProviderError::ApiError { status, .. }
+            | ProviderError::CohereError { status, .. }
+            | ProviderError::MistralError { status, .. } => {
+                *status == 401 || *status == 403
+            }
+            ProviderError::GeminiError { status, .. } => {
+                status == "UNAUTHENTICATED" || status == "PERMISSION_DENIED"
+            }
+            ProviderError::ReqwestError(error) => error
+                .status()
+                .is_some_and(|status| status.as_u16() == 401 || status.as_u16() == 403),
+            _ => false,
+        }
+    }
+
+    /// Whether the failure is likely transient, such as a rate limit, timeout or server
+    /// error, so the same request may succeed when retried
+    pub fn is_retryable(&self) -> bool {
+        let retryable_status = |status: u16| status == 408 || status == 429 || status >= 500;
+        let retryable_reqwest = |error: &reqwest::Error| {
+            error.is_timeout()
+                || error.is_connect()
+                || error
+                    .status()
+                    .is_some_and(|status| retryable_status(status.as_u16()))
+        };
+        match self {
+            ProviderError::RateLimit { .. }
+            | ProviderError::ServiceUnavailable
+            | ProviderError::Timeout
+            | ProviderError::NetworkError(_) => true,
+            ProviderError::ApiError { status, .. }
+            | ProviderError::CohereError { status, .. }
+            | ProviderError::MistralError { status, .. } => {
+                retryable_status(*status)
+            }
+            ProviderError::ReqwestError(error) => retryable_reqwest(error),
+            ProviderError::OpenAIError(OpenAIError::Reqwest(error)) => retryable_reqwest(error),
+            ProviderError::AnthropicError { error_type, .. } => matches!(
+                error_type.as_str(),
+                "rate_limit_error" | "overloaded_error" | "api_error"
+            ),
+            ProviderError::GeminiError { status, .. } => matches!(
+                status.as_str(),
+                "RESOURCE_EXHAUSTED" | "UNAVAILABLE" | "INTERNAL" | "DEADLINE_EXCEEDED"
+            ),
+            ProviderError::BedrockError { error_type, .. } => {
+                error_type == "InternalServerException"
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_after_seconds() {
+        assert_eq!(parse_retry_after("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("1.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_retry_after(" 30s "), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_parse_retry_after_compound_durations() {
+        assert_eq!(parse_retry_after("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(
+            parse_retry_after("1m30.5s"),
+            Some(Duration::from_millis(90_500))
+        );
+        assert_eq!(parse_retry_after("1h2m3s"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_retry_after("500ms"), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_parse_retry_after_rejects_garbage() {
+        assert_eq!(parse_retry_after(""), None);
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("5x"), None);
+        assert_eq!(parse_retry_after("m5"), None);
+        assert_eq!(parse_retry_after("-3"), None);
+    }
 }
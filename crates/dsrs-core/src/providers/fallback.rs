@@ -0,0 +1,221 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+use super::streaming::CompletionStream;
+use super::traits::ErasedCompletionProvider;
+
+use futures::{StreamExt, stream};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+#[derive(Clone, Debug, Default)]
+pub struct FallbackConfig {
+    /// Return authentication errors straight away instead of trying the next provider
+    pub stop_on_auth_error: bool,
+}
+
+/// Tries each provider in order until one succeeds, e.g. to fall back to another
+/// model when the first is rate limited
+pub struct FallbackProvider {
+    providers: Vec<Box<dyn ErasedCompletionProvider>>,
+    config: FallbackConfig,
+}
+
+impl FallbackProvider {
+    /// Panics if `providers` is empty
+    pub fn new(providers: Vec<Box<dyn ErasedCompletionProvider>>) -> Self {
+        assert!(
+            !providers.is_empty(),
+            "FallbackProvider needs at least one provider"
+        );
+        FallbackProvider {
+            providers,
+            config: FallbackConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: FallbackConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    // Whether to move on to the provider after `index` following `error`
+    fn should_fall_back(&self, index: usize, error: &ProviderError) -> bool {
+        if index + 1 == self.providers.len()
+            || (self.config.stop_on_auth_error && error.is_auth_error())
+        {
+            return false;
+        }
+        tracing::warn!(provider = index, error = %error, "Provider failed, trying the next one");
+        true
+    }
+}
+
+impl CompletionProvider for FallbackProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let mut index = 0;
+        loop {
+            match self.providers[index]
+                .complete_erased(messages.clone(), config.clone())
+                .await
+            {
+                Err(e) if self.should_fall_back(index, &e) => index += 1,
+                result => return result,
+            }
+        }
+    }
+
+    // Falls back only when a provider fails before producing anything; errors after
+    // part of the response has been streamed are passed through
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        let chunks = stream::once(async move {
+            let mut index = 0;
+            loop {
+                let mut inner =
+                    self.providers[index].stream_erased(messages.clone(), config.clone());
+                match inner.next().await {
+                    Some(Err(e)) if self.should_fall_back(index, &e) => index += 1,
+                    first => return stream::iter(first).chain(inner),
+                }
+            }
+        });
+        Box::pin(chunks.flatten())
+    }
+
+    // The smallest limit, so any provider in the chain can serve the request
+    fn max_context_tokens(&self) -> Option<u32> {
+        self.providers
+            .iter()
+            .filter_map(|provider| provider.max_context_tokens_erased())
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct TestProvider {
+        result: fn() -> Result<CompletionResponse, ProviderError>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl CompletionProvider for TestProvider {
+        async fn complete(
+            &self,
+            _messages: Arc<RwLock<Vec<Message>>>,
+            _config: CompletionConfig,
+        ) -> Result<CompletionResponse, ProviderError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            (self.result)()
+        }
+    }
+
+    fn provider(
+        result: fn() -> Result<CompletionResponse, ProviderError>,
+    ) -> (Box<dyn ErasedCompletionProvider>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider = TestProvider {
+            result,
+            calls: calls.clone(),
+        };
+        (Box::new(provider), calls)
+    }
+
+    fn rate_limited() -> Result<CompletionResponse, ProviderError> {
+        Err(ProviderError::RateLimit { retry_after: None })
+    }
+
+    fn unauthorized() -> Result<CompletionResponse, ProviderError> {
+        Err(ProviderError::ApiError {
+            status: 401,
+            message: "Invalid API key".to_string(),
+        })
+    }
+
+    fn answer() -> Result<CompletionResponse, ProviderError> {
+        Ok(CompletionResponse::new(
+            Message::assistant(Some("Hi"), None),
+            FinishReason::Stop,
+        ))
+    }
+
+    fn messages() -> Arc<RwLock<Vec<Message>>> {
+        Arc::new(RwLock::new(vec![Message::user("Hello")]))
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_next_provider() {
+        let (first, first_calls) = provider(rate_limited);
+        let (second, second_calls) = provider(answer);
+        let fallback = FallbackProvider::new(vec![first, second]);
+
+        let response = fallback
+            .complete(messages(), CompletionConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(response.message, Message::assistant(Some("Hi"), None));
+        assert_eq!(first_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(second_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_returns_last_error_when_all_fail() {
+        let (first, _) = provider(unauthorized);
+        let (second, _) = provider(rate_limited);
+        let fallback = FallbackProvider::new(vec![first, second]);
+
+        let result = fallback
+            .complete(messages(), CompletionConfig::default())
+            .await;
+
+        assert!(matches!(result, Err(ProviderError::RateLimit { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_stops_on_auth_error() {
+        let (first, _) = provider(unauthorized);
+        let (second, second_calls) = provider(answer);
+        let fallback = FallbackProvider::new(vec![first, second]).with_config(FallbackConfig {
+            stop_on_auth_error: true,
+        });
+
+        let result = fallback
+            .complete(messages(), CompletionConfig::default())
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ProviderError::ApiError { status: 401, .. })
+        ));
+        assert_eq!(second_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stream_falls_back_before_first_chunk() {
+        let (first, _) = provider(rate_limited);
+        let (second, _) = provider(answer);
+        let fallback = FallbackProvider::new(vec![first, second]);
+
+        let chunks: Vec<_> = fallback
+            .stream(messages(), CompletionConfig::default())
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(
+            chunks,
+            vec![crate::providers::StreamChunk::Text("Hi".to_string())]
+        );
+    }
+}
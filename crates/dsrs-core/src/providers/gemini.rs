@@ -0,0 +1,667 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::error::rate_limit_error;
+use super::models::*;
+use crate::adapters::schema_parser::inline_refs;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use reqwest::{Client, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+/// Provider for Google's Gemini models through the `generateContent` API.
+///
+/// The model given to `new` is used unless `CompletionConfig::model` is set. Images are sent
+/// inline, so only `ContentTypes::ImageBytes` reach the model; image URLs are dropped.
+pub struct GeminiProvider {
+    client: Client,
+    api_key: String,
+    model: String,
+    base_url: String,
+    config: GeminiConfig,
+}
+
+/// Gemini-specific generation options, sent with every request alongside the
+/// `CompletionConfig` ones
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeminiConfig {
+    /// Sample from the `top_k` most likely tokens
+    pub top_k: Option<u32>,
+    pub seed: Option<i64>,
+    /// Tokens the 2.5 models may spend thinking; 0 turns thinking off where supported
+    pub thinking_budget: Option<i32>,
+    pub safety_settings: Vec<SafetySetting>,
+}
+
+/// Blocking threshold for a harm category, e.g. `HARM_CATEGORY_HARASSMENT` with
+/// `BLOCK_ONLY_HIGH`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafetySetting {
+    pub category: String,
+    pub threshold: String,
+}
+
+impl GeminiProvider {
+    pub fn new(api_key: String, model: String) -> Self {
+        GeminiProvider {
+            client: Client::new(),
+            api_key,
+            model,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            config: GeminiConfig::default(),
+        }
+    }
+
+    /// Point the provider at a different host, e.g. a proxy
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_config(mut self, config: GeminiConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &GeminiConfig {
+        &self.config
+    }
+}
+
+#[derive(Serialize)]
+struct GenerateContentRequest {
+    contents: Vec<WireContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<WireContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<WireTools>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_config: Option<ToolConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<SafetySetting>,
+    #[serde(skip_serializing_if = "GenerationConfig::is_empty")]
+    generation_config: GenerationConfig,
+}
+
+#[derive(Serialize)]
+struct WireContent {
+    // The system instruction has no role
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<&'static str>,
+    parts: Vec<Part>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Part {
+    Text(String),
+    InlineData { mime_type: String, data: String },
+    FunctionCall { name: String, args: JsonValue },
+    FunctionResponse { name: String, response: JsonValue },
+}
+
+#[derive(Serialize)]
+struct WireTools {
+    function_declarations: Vec<FunctionDeclaration>,
+}
+
+#[derive(Serialize)]
+struct FunctionDeclaration {
+    name: String,
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parameters: Option<JsonValue>,
+}
+
+#[derive(Serialize)]
+struct ToolConfig {
+    function_calling_config: FunctionCallingConfig,
+}
+
+#[derive(Serialize)]
+struct FunctionCallingConfig {
+    mode: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowed_function_names: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Default, PartialEq)]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_json_schema: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thinking_config: Option<ThinkingConfig>,
+}
+
+impl GenerationConfig {
+    fn is_empty(&self) -> bool {
+        *self == GenerationConfig::default()
+    }
+}
+
+#[derive(Serialize, PartialEq)]
+struct ThinkingConfig {
+    thinking_budget: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    usage_metadata: Option<UsageMetadata>,
+    response_id: Option<String>,
+    prompt_feedback: Option<PromptFeedback>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    content: Option<CandidateContent>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CandidateContent {
+    #[serde(default)]
+    parts: Vec<ResponsePart>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResponsePart {
+    text: Option<String>,
+    function_call: Option<ResponseFunctionCall>,
+    // Thought summaries, which aren't part of the answer
+    #[serde(default)]
+    thought: bool,
+}
+
+#[derive(Deserialize)]
+struct ResponseFunctionCall {
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    args: JsonValue,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+    #[serde(default)]
+    thoughts_token_count: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    block_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+    #[serde(default)]
+    status: String,
+}
+
+// Text of a system, assistant or tool message, which can't carry images
+fn text(content: &ContentTypes) -> String {
+    content.as_text().unwrap_or_default().to_string()
+}
+
+fn user_part(content: &ContentTypes) -> Option<Part> {
+    match content {
+        ContentTypes::Text(text) => Some(Part::Text(text.clone())),
+        ContentTypes::ImageBytes {
+            data, media_type, ..
+        } => Some(Part::InlineData {
+            mime_type: media_type.clone(),
+            data: BASE64.encode(data),
+        }),
+        _ => None,
+    }
+}
+
+// Gemini expects function responses as objects, so plain text results are wrapped
+fn function_response(content: &str) -> JsonValue {
+    match serde_json::from_str::<JsonValue>(content) {
+        Ok(JsonValue::Object(response)) => JsonValue::Object(response),
+        _ => serde_json::json!({ "result": content }),
+    }
+}
+
+// Gemini takes the system prompt as `system_instruction` and calls the assistant `model`.
+// Function responses name their function rather than a call id, so results are matched to
+// their call through the id it was given when it was received
+fn to_wire_contents(messages: &[Message]) -> (Option<WireContent>, Vec<WireContent>) {
+    let calls: HashMap<&str, &ToolCall> = messages
+        .iter()
+        .filter_map(Message::as_tool_calls)
+        .flatten()
+        .map(|call| (call.id.as_str(), call))
+        .collect();
+
+    let mut system = Vec::new();
+    let mut contents: Vec<WireContent> = Vec::new();
+    for message in messages {
+        let (role, parts) = match message {
+            Message::System { content } => {
+                system.push(text(content));
+                continue;
+            }
+            Message::User { content } => ("user", content.iter().filter_map(user_part).collect()),
+            Message::Assistant {
+                content,
+                tool_calls,
+            } => {
+                let mut parts: Vec<Part> = content
+                    .iter()
+                    .map(|content| Part::Text(text(content)))
+                    .collect();
+                parts.extend(tool_calls.iter().flatten().map(|call| Part::FunctionCall {
+                    name: call.name.clone(),
+                    args: call.arguments.clone(),
+                }));
+                ("model", parts)
+            }
+            Message::Tool {
+                content,
+                tool_call_id,
+            } => {
+                let name = calls
+                    .get(tool_call_id.as_str())
+                    .map_or_else(|| tool_call_id.clone(), |call| call.name.clone());
+                (
+                    "user",
+                    vec![Part::FunctionResponse {
+                        name,
+                        response: function_response(&text(content)),
+                    }],
+                )
+            }
+        };
+        // Empty turns are rejected
+        if parts.is_empty() {
+            continue;
+        }
+
+        // Results of parallel function calls belong in a single user turn
+        match contents.last_mut() {
+            Some(last)
+                if message.is_tool_call_response()
+                    && matches!(last.parts.last(), Some(Part::FunctionResponse { .. })) =>
+            {
+                last.parts.extend(parts)
+            }
+            _ => contents.push(WireContent {
+                role: Some(role),
+                parts,
+            }),
+        }
+    }
+
+    let system = (!system.is_empty()).then(|| WireContent {
+        role: None,
+        parts: vec![Part::Text(system.join("\n\n"))],
+    });
+    (system, contents)
+}
+
+fn is_null_schema(schema: &JsonValue) -> bool {
+    schema.get("type").and_then(JsonValue::as_str) == Some("null")
+}
+
+/// Function parameters as the OpenAPI 3.0 subset Gemini declares them with: no `$ref`s,
+/// type arrays or `additionalProperties`, and nullability as a `nullable` flag. Keywords
+/// outside the subset are dropped
+fn openapi_schema(schema: &JsonValue) -> JsonValue {
+    let Some(map) = schema.as_object() else {
+        return schema.clone();
+    };
+
+    let mut converted = Map::new();
+    let mut nullable = false;
+    for (key, value) in map {
+        match key.as_str() {
+            "type" => {
+                let types = value
+                    .as_array()
+                    .map_or_else(|| vec![value], |t| t.iter().collect());
+                nullable |= types.iter().any(|t| t.as_str() == Some("null"));
+                if let Some(kind) = types.into_iter().find(|t| t.as_str() != Some("null")) {
+                    converted.insert(key.clone(), kind.clone());
+                }
+            }
+            "properties" => {
+                let properties = value
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, property)| (name.clone(), openapi_schema(property)))
+                    .collect();
+                converted.insert(key.clone(), JsonValue::Object(properties));
+            }
+            "items" => {
+                converted.insert(key.clone(), openapi_schema(value));
+            }
+            "anyOf" | "oneOf" => {
+                let branches: Vec<&JsonValue> = value.as_array().into_iter().flatten().collect();
+                let non_null: Vec<&JsonValue> = branches
+                    .iter()
+                    .copied()
+                    .filter(|b| !is_null_schema(b))
+                    .collect();
+                nullable |= non_null.len() < branches.len();
+                match non_null.as_slice() {
+                    // `Option<T>` of a non-primitive `T`
+                    [branch] => {
+                        if let JsonValue::Object(branch) = openapi_schema(branch) {
+                            for (keyword, schema) in branch {
+                                converted.entry(keyword).or_insert(schema);
+                            }
+                        }
+                    }
+                    // Documented enum variants
+                    _ if non_null.iter().all(|b| b.get("const").is_some()) => {
+                        converted.insert("type".to_string(), "string".into());
+                        let values = non_null.iter().map(|b| b["const"].clone()).collect();
+                        converted.insert("enum".to_string(), JsonValue::Array(values));
+                    }
+                    _ => {
+                        let branches = non_null.into_iter().map(openapi_schema).collect();
+                        converted.insert("anyOf".to_string(), JsonValue::Array(branches));
+                    }
+                }
+            }
+            "enum" => {
+                let values = value.as_array().into_iter().flatten();
+                nullable |= values.clone().any(JsonValue::is_null);
+                let values = values.filter(|v| !v.is_null()).cloned().collect();
+                converted.insert(key.clone(), JsonValue::Array(values));
+            }
+            "const" => {
+                converted.insert("enum".to_string(), JsonValue::Array(vec![value.clone()]));
+            }
+            "description" | "required" | "format" | "minimum" | "maximum" | "minItems"
+            | "maxItems" | "minLength" | "maxLength" | "pattern" => {
+                converted.insert(key.clone(), value.clone());
+            }
+            _ => {}
+        }
+    }
+    if nullable {
+        converted.insert("nullable".to_string(), JsonValue::Bool(true));
+    }
+    JsonValue::Object(converted)
+}
+
+impl From<&AvailableTool> for FunctionDeclaration {
+    fn from(tool: &AvailableTool) -> Self {
+        // Object schemas without properties are rejected, so argument-less tools omit them
+        let parameters = tool
+            .input_schema_json
+            .as_ref()
+            .map(|schema| openapi_schema(&inline_refs(schema)))
+            .filter(|schema| {
+                schema
+                    .get("properties")
+                    .and_then(JsonValue::as_object)
+                    .is_some_and(|properties| !properties.is_empty())
+            });
+        FunctionDeclaration {
+            name: tool.name.clone(),
+            description: tool.desc.clone(),
+            parameters,
+        }
+    }
+}
+
+impl From<&ToolChoice> for FunctionCallingConfig {
+    fn from(choice: &ToolChoice) -> Self {
+        let (mode, allowed_function_names) = match choice {
+            ToolChoice::Auto => ("AUTO", None),
+            ToolChoice::None => ("NONE", None),
+            ToolChoice::Required => ("ANY", None),
+            ToolChoice::Specific(name) => ("ANY", Some(vec![name.clone()])),
+        };
+        FunctionCallingConfig {
+            mode,
+            allowed_function_names,
+        }
+    }
+}
+
+fn generation_config(config: &CompletionConfig, gemini: &GeminiConfig) -> GenerationConfig {
+    let (response_mime_type, response_json_schema) = match &config.response_format {
+        Some(ResponseFormat::Text) => (Some("text/plain"), None),
+        Some(ResponseFormat::JsonObject) => (Some("application/json"), None),
+        Some(ResponseFormat::JsonSchema { schema, .. }) => {
+            (Some("application/json"), Some(schema.clone()))
+        }
+        None => (None, None),
+    };
+    GenerationConfig {
+        temperature: config.temperature,
+        top_p: config.top_p,
+        top_k: gemini.top_k,
+        max_output_tokens: config.max_tokens,
+        stop_sequences: config.stop.clone(),
+        seed: gemini.seed,
+        response_mime_type,
+        response_json_schema,
+        thinking_config: gemini
+            .thinking_budget
+            .map(|thinking_budget| ThinkingConfig { thinking_budget }),
+    }
+}
+
+fn request_body(
+    messages: &[Message],
+    config: &CompletionConfig,
+    gemini: &GeminiConfig,
+) -> JsonValue {
+    let (system_instruction, contents) = to_wire_contents(messages);
+    let request = GenerateContentRequest {
+        contents,
+        system_instruction,
+        tools: config.tools.as_ref().map(|tools| {
+            vec![WireTools {
+                function_declarations: tools.iter().map(FunctionDeclaration::from).collect(),
+            }]
+        }),
+        tool_config: config.tool_choice.as_ref().map(|choice| ToolConfig {
+            function_calling_config: choice.into(),
+        }),
+        safety_settings: gemini.safety_settings.clone(),
+        generation_config: generation_config(config, gemini),
+    };
+    let mut body = serde_json::to_value(request).unwrap();
+    config.apply_extra(&mut body);
+    body
+}
+
+fn finish_reason(reason: Option<&str>) -> FinishReason {
+    match reason {
+        Some("MAX_TOKENS") => FinishReason::Length,
+        Some("SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII") => {
+            FinishReason::ContentFilter
+        }
+        _ => FinishReason::Stop,
+    }
+}
+
+impl From<GenerateContentResponse> for CompletionResponse {
+    fn from(response: GenerateContentResponse) -> Self {
+        let usage = response.usage_metadata.map(|usage| {
+            UsageStats::new(
+                usage.prompt_token_count,
+                usage.candidates_token_count + usage.thoughts_token_count,
+            )
+        });
+
+        // A blocked prompt gets no candidates at all
+        let Some(candidate) = response.candidates.into_iter().next() else {
+            let blocked = response
+                .prompt_feedback
+                .is_some_and(|feedback| feedback.block_reason.is_some());
+            let reason = if blocked {
+                FinishReason::ContentFilter
+            } else {
+                FinishReason::Stop
+            };
+            return CompletionResponse::new(Message::assistant(None::<String>, None), reason)
+                .with_usage(usage);
+        };
+
+        let response_id = response.response_id.unwrap_or_default();
+        let mut texts = Vec::new();
+        let mut calls = Vec::new();
+        let parts = candidate
+            .content
+            .map(|content| content.parts)
+            .unwrap_or_default();
+        for part in parts.into_iter().filter(|part| !part.thought) {
+            if let Some(text) = part.text {
+                texts.push(text);
+            }
+            // Calls only carry an id on some models
+            if let Some(call) = part.function_call {
+                calls.push(ToolCall {
+                    id: call
+                        .id
+                        .unwrap_or_else(|| format!("{}-{}", response_id, calls.len())),
+                    name: call.name,
+                    arguments: call.args,
+                });
+            }
+        }
+
+        let finish_reason = match (calls.is_empty(), candidate.finish_reason.as_deref()) {
+            (false, Some("STOP") | None) => FinishReason::ToolCalls,
+            (_, reason) => finish_reason(reason),
+        };
+        let content = (!texts.is_empty()).then(|| texts.concat());
+        let tool_calls = (!calls.is_empty()).then_some(calls);
+        CompletionResponse::new(Message::assistant(content, tool_calls), finish_reason)
+            .with_usage(usage)
+    }
+}
+
+async fn error_from_response(response: Response) -> ProviderError {
+    if let Some(error) = rate_limit_error(&response, "retry-after") {
+        return error;
+    }
+    let status = response.status();
+
+    let body = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        Ok(error) => ProviderError::GeminiError {
+            status: error.error.status,
+            message: error.error.message,
+        },
+        Err(_) => ProviderError::ApiError {
+            status: status.as_u16(),
+            message: body,
+        },
+    }
+}
+
+impl CompletionProvider for GeminiProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let body = {
+            let guard = messages.read().await;
+            request_body(&guard, &config, &self.config)
+        };
+
+        let model = if config.model.is_empty() {
+            &self.model
+        } else {
+            &config.model
+        };
+        let response = self
+            .client
+            .post(format!(
+                "{}/v1beta/models/{}:generateContent",
+                self.base_url, model
+            ))
+            .header("x-goog-api-key", &self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let response: GenerateContentResponse = response.json().await?;
+        Ok(response.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openapi_schema_flattens_nullable_and_drops_unsupported_keywords() {
+        let schema = serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "SearchArgs",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {
+                "query": {"type": "string", "description": "What to search for"},
+                "limit": {"type": ["integer", "null"], "format": "uint32", "minimum": 0},
+                "sort": {"anyOf": [{"const": "date"}, {"const": "relevance"}, {"type": "null"}]}
+            },
+            "required": ["query"]
+        });
+
+        assert_eq!(
+            openapi_schema(&schema),
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "What to search for"},
+                    "limit": {"type": "integer", "format": "uint32", "minimum": 0, "nullable": true},
+                    "sort": {"type": "string", "enum": ["date", "relevance"], "nullable": true}
+                },
+                "required": ["query"]
+            })
+        );
+    }
+}
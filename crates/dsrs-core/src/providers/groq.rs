@@ -0,0 +1,161 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+use super::openai::{chat_request_builder, completion_response, request_body, stream_chat};
+use super::streaming::CompletionStream;
+use crate::adapters::schema_parser::forbid_additional_properties;
+
+use async_openai::Client;
+use async_openai::config::OpenAIConfig;
+use async_openai::error::OpenAIError;
+use async_openai::types::{
+    ChatCompletionResponseStream, ChatCompletionTool,
+    CreateChatCompletionResponse, CreateChatCompletionStreamResponse,
+};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const DEFAULT_BASE_URL: &str = "https://api.groq.com/openai/v1";
+
+/// Provider for models hosted on Groq's OpenAI-compatible API
+pub struct GroqProvider {
+    client: Client<OpenAIConfig>,
+}
+
+impl GroqProvider {
+    pub fn new(api_key: String) -> Self {
+        Self::with_base_url(api_key, DEFAULT_BASE_URL.to_string())
+    }
+
+    pub fn with_base_url(api_key: String, base_url: String) -> Self {
+        let config = OpenAIConfig::new()
+            .with_api_key(api_key)
+            .with_api_base(base_url);
+        GroqProvider {
+            client: Client::with_config(config),
+        }
+    }
+
+    async fn request(
+        &self,
+        messages: &RwLock<Vec<Message>>,
+        mut config: CompletionConfig,
+    ) -> Result<JsonValue, ProviderError> {
+        let tools = config.tools.take();
+        let mut builder = chat_request_builder(messages, &config).await;
+        if let Some(tools) = tools {
+            builder.tools(tools.iter().map(groq_tool).collect::<Vec<_>>());
+        }
+        let mut request = builder.build()?;
+        // Groq rejects requests that set a service tier
+        request.service_tier = None;
+        Ok(request_body(request, &config))
+    }
+}
+
+// Groq requires every object in a tool's input schema to forbid additional properties
+fn groq_tool(tool: &AvailableTool) -> ChatCompletionTool {
+    let mut tool = ChatCompletionTool::from(tool);
+    let parameters = tool
+        .function
+        .parameters
+        .get_or_insert_with(|| serde_json::json!({"type": "object", "properties": {}}));
+    forbid_additional_properties(parameters);
+    tool
+}
+
+// Groq reports tiers such as `on_demand` that `async-openai` can't deserialize, so
+// responses are parsed without the field
+fn without_service_tier<T: DeserializeOwned>(mut response: JsonValue) -> Result<T, OpenAIError> {
+    if let JsonValue::Object(map) = &mut response {
+        map.remove("service_tier");
+    }
+    serde_json::from_value(response).map_err(OpenAIError::JSONDeserialize)
+}
+
+impl CompletionProvider for GroqProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let request = self.request(&messages, config).await?;
+        let response: JsonValue = self.client.chat().create_byot(request).await?;
+        let response: CreateChatCompletionResponse = without_service_tier(response)?;
+        Ok(completion_response(response))
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        stream_chat(Box::pin(async move {
+            let mut request = self.request(&messages, config).await?;
+            request["stream"] = JsonValue::Bool(true);
+            let chunks = self
+                .client
+                .chat()
+                .create_stream_byot::<_, JsonValue>(request)
+                .await?;
+            let chunks: ChatCompletionResponseStream = Box::pin(chunks.map(|chunk| {
+                chunk.and_then(without_service_tier::<CreateChatCompletionStreamResponse>)
+            }));
+            Ok(chunks)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_schemas_forbid_additional_properties() {
+        let tool = AvailableTool {
+            name: "search".to_string(),
+            desc: "Search the web".to_string(),
+            input_schema_json: Some(serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "q": {"type": "string"},
+                    "filters": {"type": "object", "properties": {"site": {"type": "string"}}}
+                }
+            })),
+        };
+
+        let parameters = groq_tool(&tool).function.parameters.unwrap();
+
+        assert_eq!(parameters["additionalProperties"], false);
+        assert_eq!(
+            parameters["properties"]["filters"]["additionalProperties"],
+            false
+        );
+        assert!(
+            parameters["properties"]["q"]
+                .get("additionalProperties")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_tool_without_schema_gets_empty_object() {
+        let tool = AvailableTool {
+            name: "now".to_string(),
+            desc: "Current time".to_string(),
+            input_schema_json: None,
+        };
+
+        assert_eq!(
+            groq_tool(&tool).function.parameters,
+            Some(serde_json::json!({
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            }))
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::error::rate_limit_error;
+use super::models::*;
+
+use reqwest::{Client, Response, StatusCode};
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+const DEFAULT_BASE_URL: &str = "https://api-inference.huggingface.co";
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Provider for the OpenAI-compatible chat completions endpoint of the Hugging Face Inference API.
+///
+/// The model is fixed at construction time (it is part of the endpoint URL), so
+/// `CompletionConfig::model` is not used by this provider.
+pub struct HuggingFaceProvider {
+    client: Client,
+    api_key: String,
+    model: String,
+    base_url: String,
+    poll_interval: Duration,
+}
+
+impl HuggingFaceProvider {
+    pub fn new(api_key: String, model: String) -> Self {
+        HuggingFaceProvider {
+            client: Client::new(),
+            api_key,
+            model,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Point the provider at a different host, e.g. a dedicated inference endpoint
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// How often `wait_for_model` checks the model status
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    fn model_url(&self) -> String {
+        format!("{}/models/{}", self.base_url, self.model)
+    }
+
+    /// Poll the model status until it is loaded, failing with `ProviderError::Timeout` after `max_wait`
+    pub async fn wait_for_model(&self, max_wait: Duration) -> Result<(), ProviderError> {
+        let started = Instant::now();
+
+        loop {
+            let response = self
+                .client
+                .get(self.model_url())
+                .bearer_auth(&self.api_key)
+                .send()
+                .await?;
+
+            if response.status().is_success() {
+                let status: JsonValue = response.json().await?;
+                let loaded = status.get("loaded").and_then(|l| l.as_bool()) == Some(true);
+                if loaded || status.get("pipeline_tag").is_some_and(|tag| !tag.is_null()) {
+                    return Ok(());
+                }
+            } else if response.status() != StatusCode::SERVICE_UNAVAILABLE {
+                return Err(error_from_response(response).await);
+            }
+
+            let elapsed = started.elapsed();
+            if elapsed >= max_wait {
+                return Err(ProviderError::Timeout);
+            }
+            tokio::time::sleep(self.poll_interval.min(max_wait - elapsed)).await;
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<WireMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<WireTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+enum WireMessage {
+    System {
+        content: String,
+    },
+    User {
+        content: String,
+    },
+    Assistant {
+        content: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tool_calls: Option<Vec<WireToolCall>>,
+    },
+    Tool {
+        content: String,
+        tool_call_id: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireToolCall {
+    id: String,
+    #[serde(rename = "type", default = "function_type")]
+    kind: String,
+    function: WireFunctionCall,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireFunctionCall {
+    name: String,
+    arguments: String,
+}
+
+#[derive(Serialize)]
+struct WireTool {
+    #[serde(rename = "type")]
+    kind: String,
+    function: WireFunction,
+}
+
+#[derive(Serialize)]
+struct WireFunction {
+    name: String,
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parameters: Option<JsonValue>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+    usage: Option<UsageStats>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponseMessage {
+    content: Option<String>,
+    tool_calls: Option<Vec<WireToolCall>>,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+// Callers reject messages with images up front, so only text is left to convert
+fn text(content: &ContentTypes) -> String {
+    content.as_text().unwrap_or_default().to_string()
+}
+
+// Missing or unrecognised reasons are treated as a normal stop
+fn finish_reason(reason: Option<&str>) -> FinishReason {
+    match reason {
+        Some("length") => FinishReason::Length,
+        Some("tool_calls") => FinishReason::ToolCalls,
+        Some("content_filter") => FinishReason::ContentFilter,
+        _ => FinishReason::Stop,
+    }
+}
+
+impl From<&ToolCall> for WireToolCall {
+    fn from(tool_call: &ToolCall) -> Self {
+        WireToolCall {
+            id: tool_call.id.clone(),
+            kind: function_type(),
+            function: WireFunctionCall {
+                name: tool_call.name.clone(),
+                arguments: match &tool_call.arguments {
+                    JsonValue::String(raw) => raw.clone(),
+                    arguments => arguments.to_string(),
+                },
+            },
+        }
+    }
+}
+
+impl From<WireToolCall> for ToolCall {
+    fn from(tool_call: WireToolCall) -> Self {
+        // Arguments arrive as a JSON-encoded string; keep the raw string if it isn't valid JSON
+        let arguments = serde_json::from_str(&tool_call.function.arguments)
+            .unwrap_or(JsonValue::String(tool_call.function.arguments));
+        ToolCall {
+            id: tool_call.id,
+            name: tool_call.function.name,
+            arguments,
+        }
+    }
+}
+
+impl From<&Message> for WireMessage {
+    fn from(message: &Message) -> Self {
+        match message {
+            Message::System { content } => WireMessage::System {
+                content: text(content),
+            },
+            Message::User { content } => WireMessage::User {
+                content: ContentTypes::join_text(content),
+            },
+            Message::Assistant {
+                content,
+                tool_calls,
+            } => WireMessage::Assistant {
+                content: content.as_ref().map(text),
+                tool_calls: tool_calls
+                    .as_ref()
+                    .filter(|calls| !calls.is_empty())
+                    .map(|calls| calls.iter().map(WireToolCall::from).collect()),
+            },
+            Message::Tool {
+                content,
+                tool_call_id,
+            } => WireMessage::Tool {
+                content: text(content),
+                tool_call_id: tool_call_id.clone(),
+            },
+        }
+    }
+}
+
+impl From<&AvailableTool> for WireTool {
+    fn from(tool: &AvailableTool) -> Self {
+        WireTool {
+            kind: function_type(),
+            function: WireFunction {
+                name: tool.name.clone(),
+                description: tool.desc.clone(),
+                parameters: tool.input_schema_json.clone(),
+            },
+        }
+    }
+}
+
+async fn error_from_response(response: Response) -> ProviderError {
+    if let Some(error) = rate_limit_error(&response, "x-ratelimit-reset-requests") {
+        return error;
+    }
+    let status = response.status();
+
+    let body = response.text().await.unwrap_or_default();
+    let error_json = serde_json::from_str::<JsonValue>(&body).ok();
+
+    // A model that is still loading answers 503 with an estimated load time
+    if status == StatusCode::SERVICE_UNAVAILABLE
+        && error_json
+            .as_ref()
+            .is_some_and(|json| json.get("estimated_time").is_some())
+    {
+        return ProviderError::Timeout;
+    }
+
+    let message = error_json
+        .as_ref()
+        .and_then(|json| json.get("error"))
+        .map(|error| match error {
+            JsonValue::String(message) => message.clone(),
+            other => other.to_string(),
+        })
+        .unwrap_or(body);
+
+    ProviderError::ApiError {
+        status: status.as_u16(),
+        message,
+    }
+}
+
+impl CompletionProvider for HuggingFaceProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let request_messages = {
+            let guard = messages.read().await;
+            // The text-generation chat route has no image parts
+            if guard.iter().any(Message::has_images) {
+                return Err(ProviderError::ImagesNotSupported {
+                    model: self.model.clone(),
+                });
+            }
+            guard.iter().map(WireMessage::from).collect::<Vec<_>>()
+        };
+
+        let request = ChatRequest {
+            model: &self.model,
+            messages: request_messages,
+            tools: config
+                .tools
+                .as_ref()
+                .map(|tools| tools.iter().map(WireTool::from).collect()),
+            temperature: config.temperature,
+            max_tokens: config.max_tokens,
+            top_p: config.top_p,
+            stop: config.stop.clone(),
+        };
+        let mut body = serde_json::to_value(request).unwrap();
+        config.apply_extra(&mut body);
+
+        let response = self
+            .client
+            .post(format!("{}/v1/chat/completions", self.model_url()))
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let response: ChatResponse = response.json().await?;
+        let usage = response.usage;
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::ApiError {
+                status: StatusCode::OK.as_u16(),
+                message: "Response contained no choices".to_string(),
+            })?;
+
+        let finish_reason = finish_reason(choice.finish_reason.as_deref());
+        let tool_calls = choice
+            .message
+            .tool_calls
+            .map(|calls| calls.into_iter().map(ToolCall::from).collect());
+
+        Ok(CompletionResponse::new(
+            Message::assistant(choice.message.content, tool_calls),
+            finish_reason,
+        )
+        .with_usage(usage))
+    }
+}
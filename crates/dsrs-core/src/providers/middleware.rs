@@ -0,0 +1,386 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::RwLock;
+
+/// Intercepts the requests and responses of a `MiddlewareStack`. Every hook defaults to
+/// passing things through unchanged, so a middleware only implements the ones it needs
+#[async_trait]
+pub trait ProviderMiddleware: Send + Sync {
+    /// The messages and config to send instead, or an error to fail the request with
+    async fn before_request(
+        &self,
+        messages: &[Message],
+        config: &CompletionConfig,
+    ) -> Result<(Vec<Message>, CompletionConfig), ProviderError> {
+        Ok((messages.to_vec(), config.clone()))
+    }
+
+    /// The message to return instead, or an error to fail the request with
+    async fn after_response(&self, response: &Message) -> Result<Message, ProviderError> {
+        Ok(response.clone())
+    }
+
+    /// Called with the token usage of each response that reports it
+    fn on_usage(&self, _config: &CompletionConfig, _usage: &UsageStats) {}
+}
+
+/// Wraps a provider and passes every request through its middleware, in the order they
+/// were added, before the call and every response through them in the same order after.
+/// Streaming waits for the whole response so the middleware see it, like the default
+/// `stream`
+pub struct MiddlewareStack<P: CompletionProvider> {
+    inner: P,
+    middleware: Vec<Box<dyn ProviderMiddleware>>,
+}
+
+impl<P: CompletionProvider> MiddlewareStack<P> {
+    pub fn new(inner: P) -> Self {
+        MiddlewareStack {
+            inner,
+            middleware: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, middleware: impl ProviderMiddleware + 'static) -> Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+}
+
+impl<P: CompletionProvider> CompletionProvider for MiddlewareStack<P> {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        mut config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let mut messages = messages.read().await.clone();
+        for middleware in &self.middleware {
+            (messages, config) = middleware.before_request(&messages, &config).await?;
+        }
+
+        // A copy, so rewritten messages don't end up in the caller's conversation
+        let mut response = self
+            .inner
+            .complete(Arc::new(RwLock::new(messages)), config.clone())
+            .await?;
+
+        for middleware in &self.middleware {
+            response.message = middleware.after_response(&response.message).await?;
+            if let Some(usage) = &response.usage {
+                middleware.on_usage(&config, usage);
+            }
+        }
+        Ok(response)
+    }
+
+    fn max_context_tokens(&self) -> Option<u32> {
+        self.inner.max_context_tokens()
+    }
+}
+
+/// Logs each request's model and message count and each response's token usage as
+/// `tracing` debug events
+pub struct LoggingMiddleware;
+
+#[async_trait]
+impl ProviderMiddleware for LoggingMiddleware {
+    async fn before_request(
+        &self,
+        messages: &[Message],
+        config: &CompletionConfig,
+    ) -> Result<(Vec<Message>, CompletionConfig), ProviderError> {
+        tracing::debug!(
+            model = %config.model,
+            messages = messages.len(),
+            "Sending completion request"
+        );
+        Ok((messages.to_vec(), config.clone()))
+    }
+
+    async fn after_response(&self, response: &Message) -> Result<Message, ProviderError> {
+        tracing::debug!(response = ?response, "Received completion response");
+        Ok(response.clone())
+    }
+
+    fn on_usage(&self, config: &CompletionConfig, usage: &UsageStats) {
+        tracing::debug!(
+            model = %config.model,
+            prompt_tokens = usage.prompt_tokens,
+            completion_tokens = usage.completion_tokens,
+            "Completion token usage"
+        );
+    }
+}
+
+/// Fails requests and responses whose text contains any of the banned strings, ignoring
+/// case, with `ProviderError::ContentFiltered`
+pub struct ContentFilterMiddleware {
+    banned: Vec<String>,
+}
+
+impl ContentFilterMiddleware {
+    pub fn new(banned: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        ContentFilterMiddleware {
+            banned: banned
+                .into_iter()
+                .map(|term| term.into().to_lowercase())
+                .collect(),
+        }
+    }
+
+    fn check(&self, message: &Message) -> Result<(), ProviderError> {
+        let text = message_text(message).to_lowercase();
+        match self.banned.iter().find(|term| text.contains(term.as_str())) {
+            Some(term) => Err(ProviderError::ContentFiltered { term: term.clone() }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl ProviderMiddleware for ContentFilterMiddleware {
+    async fn before_request(
+        &self,
+        messages: &[Message],
+        config: &CompletionConfig,
+    ) -> Result<(Vec<Message>, CompletionConfig), ProviderError> {
+        messages
+            .iter()
+            .try_for_each(|message| self.check(message))?;
+        Ok((messages.to_vec(), config.clone()))
+    }
+
+    async fn after_response(&self, response: &Message) -> Result<Message, ProviderError> {
+        self.check(response)?;
+        Ok(response.clone())
+    }
+}
+
+/// Adds up the tokens of every response that reports its usage, and their cost at a flat
+/// price per 1000 tokens, e.g. `ModelInfo::cost_per_1k_tokens`. Share it between the stack
+/// and its reader with an `Arc`
+pub struct CostTrackingMiddleware {
+    cost_per_1k_tokens: f64,
+    prompt_tokens: AtomicU64,
+    completion_tokens: AtomicU64,
+}
+
+impl CostTrackingMiddleware {
+    pub fn new(cost_per_1k_tokens: f64) -> Self {
+        CostTrackingMiddleware {
+            cost_per_1k_tokens,
+            prompt_tokens: AtomicU64::new(0),
+            completion_tokens: AtomicU64::new(0),
+        }
+    }
+
+    pub fn prompt_tokens(&self) -> u64 {
+        self.prompt_tokens.load(Ordering::Relaxed)
+    }
+
+    pub fn completion_tokens(&self) -> u64 {
+        self.completion_tokens.load(Ordering::Relaxed)
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens() + self.completion_tokens()
+    }
+
+    pub fn total_cost(&self) -> f64 {
+        self.total_tokens() as f64 / 1000.0 * self.cost_per_1k_tokens
+    }
+}
+
+#[async_trait]
+impl ProviderMiddleware for CostTrackingMiddleware {
+    fn on_usage(&self, _config: &CompletionConfig, usage: &UsageStats) {
+        self.prompt_tokens
+            .fetch_add(usage.prompt_tokens as u64, Ordering::Relaxed);
+        self.completion_tokens
+            .fetch_add(usage.completion_tokens as u64, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl<M: ProviderMiddleware + ?Sized> ProviderMiddleware for Arc<M> {
+    async fn before_request(
+        &self,
+        messages: &[Message],
+        config: &CompletionConfig,
+    ) -> Result<(Vec<Message>, CompletionConfig), ProviderError> {
+        self.as_ref().before_request(messages, config).await
+    }
+
+    async fn after_response(&self, response: &Message) -> Result<Message, ProviderError> {
+        self.as_ref().after_response(response).await
+    }
+
+    fn on_usage(&self, config: &CompletionConfig, usage: &UsageStats) {
+        self.as_ref().on_usage(config, usage)
+    }
+}
+
+// All the text of a message, one part per line
+fn message_text(message: &Message) -> String {
+    match message {
+        Message::System { content } | Message::Tool { content, .. } => {
+            content.as_text().unwrap_or_default().to_string()
+        }
+        Message::User { content } => ContentTypes::join_text(content),
+        Message::Assistant {
+            content,
+            tool_calls,
+        } => {
+            let mut parts: Vec<String> = content
+                .iter()
+                .filter_map(|content| content.as_text().map(str::to_string))
+                .collect();
+            parts.extend(
+                tool_calls
+                    .iter()
+                    .flatten()
+                    .map(|call| call.arguments.to_string()),
+            );
+            parts.join("\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Replies with the text of the last message it was sent, and records the requests
+    #[derive(Default)]
+    struct EchoProvider {
+        requests: Mutex<Vec<(Vec<Message>, CompletionConfig)>>,
+    }
+
+    impl CompletionProvider for EchoProvider {
+        async fn complete(
+            &self,
+            messages: Arc<RwLock<Vec<Message>>>,
+            config: CompletionConfig,
+        ) -> Result<CompletionResponse, ProviderError> {
+            let messages = messages.read().await.clone();
+            let reply = messages.last().map(message_text).unwrap_or_default();
+            self.requests.lock().unwrap().push((messages, config));
+            Ok(
+                CompletionResponse::new(Message::assistant(Some(reply), None), FinishReason::Stop)
+                    .with_usage(Some(UsageStats::new(1500, 500))),
+            )
+        }
+    }
+
+    // Appends its tag to the last message on the way in and to the reply on the way out
+    struct TagMiddleware(&'static str);
+
+    #[async_trait]
+    impl ProviderMiddleware for TagMiddleware {
+        async fn before_request(
+            &self,
+            messages: &[Message],
+            config: &CompletionConfig,
+        ) -> Result<(Vec<Message>, CompletionConfig), ProviderError> {
+            let mut messages = messages.to_vec();
+            let last = messages.pop().map(|message| message_text(&message));
+            messages.push(Message::user(format!(
+                "{}{}",
+                last.unwrap_or_default(),
+                self.0
+            )));
+            Ok((messages, config.clone()))
+        }
+
+        async fn after_response(&self, response: &Message) -> Result<Message, ProviderError> {
+            Ok(Message::assistant(
+                Some(format!("{}{}", message_text(response), self.0)),
+                None,
+            ))
+        }
+    }
+
+    fn conversation(text: &str) -> Arc<RwLock<Vec<Message>>> {
+        Arc::new(RwLock::new(vec![Message::user(text)]))
+    }
+
+    #[tokio::test]
+    async fn test_middleware_runs_in_order_before_and_after() {
+        let stack = MiddlewareStack::new(EchoProvider::default())
+            .with(TagMiddleware(" a"))
+            .with(TagMiddleware(" b"));
+        let messages = conversation("hi");
+
+        let response = stack
+            .complete(messages.clone(), CompletionConfig::default())
+            .await
+            .unwrap();
+
+        let sent = stack.inner().requests.lock().unwrap()[0].0.clone();
+        assert_eq!(message_text(&sent[0]), "hi a b");
+        assert_eq!(message_text(&response.message), "hi a b a b");
+        // The caller's conversation is left as it was
+        assert_eq!(*messages.read().await, vec![Message::user("hi")]);
+    }
+
+    #[tokio::test]
+    async fn test_content_filter_blocks_banned_strings() {
+        let stack = MiddlewareStack::new(EchoProvider::default())
+            .with(ContentFilterMiddleware::new(["secret"]));
+
+        let result = stack
+            .complete(conversation("The SECRET code"), CompletionConfig::default())
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(ProviderError::ContentFiltered { term }) if term == "secret"
+        ));
+        assert!(stack.inner().requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_content_filter_checks_responses() {
+        let stack = MiddlewareStack::new(EchoProvider::default())
+            .with(ContentFilterMiddleware::new(["secret"]))
+            .with(TagMiddleware(" secret"));
+
+        let result = stack
+            .complete(conversation("hi"), CompletionConfig::default())
+            .await;
+
+        // The tag is added after the filter passed the request, and echoed back
+        assert!(matches!(result, Err(ProviderError::ContentFiltered { .. })));
+        assert_eq!(stack.inner().requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cost_tracking_accumulates_usage() {
+        let costs = Arc::new(CostTrackingMiddleware::new(0.01));
+        let stack = MiddlewareStack::new(EchoProvider::default())
+            .with(LoggingMiddleware)
+            .with(costs.clone());
+
+        for _ in 0..2 {
+            stack
+                .complete(conversation("hi"), CompletionConfig::default())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(costs.prompt_tokens(), 3000);
+        assert_eq!(costs.completion_tokens(), 1000);
+        assert_eq!(costs.total_tokens(), 4000);
+        assert!((costs.total_cost() - 0.04).abs() < 1e-9);
+    }
+}
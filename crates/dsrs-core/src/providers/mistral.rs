@@ -0,0 +1,421 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::error::rate_limit_error;
+use super::models::*;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+const DEFAULT_BASE_URL: &str = "https://api.mistral.ai/v1";
+// Mistral only accepts tool call ids of exactly this many alphanumeric characters
+const TOOL_CALL_ID_LEN: usize = 9;
+
+/// Provider for the Mistral AI chat completions API.
+///
+/// Mistral-specific parameters such as `random_seed` or `safe_prompt` can be passed
+/// through `CompletionConfig::extra`.
+pub struct MistralProvider {
+    client: Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl MistralProvider {
+    pub fn new(api_key: String) -> Self {
+        MistralProvider {
+            client: Client::new(),
+            api_key,
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
+    }
+
+    /// Point the provider at a different host, e.g. a self-deployed model
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<WireMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<WireTool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+enum WireMessage {
+    System {
+        content: String,
+    },
+    User {
+        content: WireUserContent,
+    },
+    Assistant {
+        // Mistral rejects a null content, even alongside tool calls
+        content: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tool_calls: Option<Vec<WireToolCall>>,
+    },
+    Tool {
+        content: String,
+        tool_call_id: String,
+        // The name of the called function, looked up from the matching tool call
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum WireUserContent {
+    Text(String),
+    Parts(Vec<WireContentPart>),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WireContentPart {
+    Text { text: String },
+    // An http(s) or base64 `data:` URL
+    ImageUrl { image_url: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireToolCall {
+    id: String,
+    #[serde(rename = "type", default = "function_type")]
+    kind: String,
+    function: WireFunctionCall,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WireFunctionCall {
+    name: String,
+    // Sent as a JSON-encoded string; responses from some models contain an object instead
+    arguments: JsonValue,
+}
+
+#[derive(Serialize)]
+struct WireTool {
+    #[serde(rename = "type")]
+    kind: String,
+    function: WireFunction,
+}
+
+#[derive(Serialize)]
+struct WireFunction {
+    name: String,
+    description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parameters: Option<JsonValue>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+    usage: Option<UsageStats>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponseMessage {
+    content: Option<String>,
+    tool_calls: Option<Vec<WireToolCall>>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(rename = "type")]
+    kind: String,
+    message: JsonValue,
+}
+
+fn function_type() -> String {
+    "function".to_string()
+}
+
+// Only user messages can carry images, the other roles take plain text
+fn text(content: &ContentTypes) -> String {
+    content.as_text().unwrap_or_default().to_string()
+}
+
+// Text-only messages keep the plain string form; images need the list of parts
+fn user_content(parts: &[ContentTypes]) -> WireUserContent {
+    if parts.iter().all(|part| part.as_text().is_some()) {
+        return WireUserContent::Text(ContentTypes::join_text(parts));
+    }
+    WireUserContent::Parts(
+        parts
+            .iter()
+            .map(|part| match part {
+                ContentTypes::Text(text) => WireContentPart::Text { text: text.clone() },
+                ContentTypes::Image { url, .. } => WireContentPart::ImageUrl {
+                    image_url: url.clone(),
+                },
+                ContentTypes::ImageBytes {
+                    data, media_type, ..
+                } => WireContentPart::ImageUrl {
+                    image_url: format!(
+                        "data:{};base64,{}",
+                        media_type,
+                        BASE64_STANDARD.encode(data)
+                    ),
+                },
+            })
+            .collect(),
+    )
+}
+
+fn finish_reason(reason: Option<&str>) -> FinishReason {
+    match reason {
+        Some("length") | Some("model_length") => FinishReason::Length,
+        Some("tool_calls") => FinishReason::ToolCalls,
+        _ => FinishReason::Stop,
+    }
+}
+
+// Ids created by other providers don't fit Mistral's format, so they are replaced by
+// one derived from their hash, keeping tool calls and their results paired up
+fn tool_call_id(id: &str) -> String {
+    if id.len() == TOOL_CALL_ID_LEN && id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return id.to_string();
+    }
+    blake3::hash(id.as_bytes()).to_hex()[..TOOL_CALL_ID_LEN].to_string()
+}
+
+impl From<&ToolCall> for WireToolCall {
+    fn from(tool_call: &ToolCall) -> Self {
+        WireToolCall {
+            id: tool_call_id(&tool_call.id),
+            kind: function_type(),
+            function: WireFunctionCall {
+                name: tool_call.name.clone(),
+                // Arguments that never parsed as JSON are sent back as they arrived
+                arguments: JsonValue::String(match &tool_call.arguments {
+                    JsonValue::String(raw) => raw.clone(),
+                    arguments => arguments.to_string(),
+                }),
+            },
+        }
+    }
+}
+
+impl From<WireToolCall> for ToolCall {
+    fn from(tool_call: WireToolCall) -> Self {
+        // Keep the raw string if the arguments aren't valid JSON
+        let arguments = match tool_call.function.arguments {
+            JsonValue::String(raw) => serde_json::from_str(&raw).unwrap_or(JsonValue::String(raw)),
+            other => other,
+        };
+        ToolCall {
+            id: tool_call.id,
+            name: tool_call.function.name,
+            arguments,
+        }
+    }
+}
+
+fn to_wire_messages(messages: &[Message]) -> Vec<WireMessage> {
+    let tool_names: HashMap<&str, &str> = messages
+        .iter()
+        .filter_map(Message::as_tool_calls)
+        .flatten()
+        .map(|call| (call.id.as_str(), call.name.as_str()))
+        .collect();
+
+    messages
+        .iter()
+        .map(|message| match message {
+            Message::System { content } => WireMessage::System {
+                content: text(content),
+            },
+            Message::User { content } => WireMessage::User {
+                content: user_content(content),
+            },
+            Message::Assistant {
+                content,
+                tool_calls,
+            } => WireMessage::Assistant {
+                content: content.as_ref().map(text).unwrap_or_default(),
+                tool_calls: tool_calls
+                    .as_ref()
+                    .filter(|calls| !calls.is_empty())
+                    .map(|calls| calls.iter().map(WireToolCall::from).collect()),
+            },
+            Message::Tool {
+                content,
+                tool_call_id: id,
+            } => WireMessage::Tool {
+                content: text(content),
+                tool_call_id: tool_call_id(id),
+                name: tool_names.get(id.as_str()).map(|name| name.to_string()),
+            },
+        })
+        .collect()
+}
+
+impl From<&AvailableTool> for WireTool {
+    fn from(tool: &AvailableTool) -> Self {
+        WireTool {
+            kind: function_type(),
+            function: WireFunction {
+                name: tool.name.clone(),
+                description: tool.desc.clone(),
+                parameters: tool.input_schema_json.clone(),
+            },
+        }
+    }
+}
+
+fn request_body(messages: &[Message], config: &CompletionConfig) -> JsonValue {
+    let request = ChatRequest {
+        model: &config.model,
+        messages: to_wire_messages(messages),
+        tools: config
+            .tools
+            .as_ref()
+            .map(|tools| tools.iter().map(WireTool::from).collect()),
+        temperature: config.temperature,
+        max_tokens: config.max_tokens,
+        top_p: config.top_p,
+        stop: config.stop.clone(),
+    };
+    let mut body = serde_json::to_value(request).unwrap();
+    config.apply_extra(&mut body);
+    body
+}
+
+async fn error_from_response(response: Response) -> ProviderError {
+    if let Some(error) = rate_limit_error(&response, "retry-after") {
+        return error;
+    }
+    let status = response.status();
+
+    let body = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<ErrorResponse>(&body) {
+        // Validation errors carry the details as an object rather than a string
+        Ok(error) => ProviderError::MistralError {
+            status: status.as_u16(),
+            error_type: error.kind,
+            message: match error.message {
+                JsonValue::String(message) => message,
+                other => other.to_string(),
+            },
+        },
+        Err(_) => ProviderError::ApiError {
+            status: status.as_u16(),
+            message: body,
+        },
+    }
+}
+
+impl CompletionProvider for MistralProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let body = {
+            let guard = messages.read().await;
+            request_body(&guard, &config)
+        };
+
+        let response = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let response: ChatResponse = response.json().await?;
+        let usage = response.usage;
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::ApiError {
+                status: StatusCode::OK.as_u16(),
+                message: "Response contained no choices".to_string(),
+            })?;
+
+        let finish_reason = finish_reason(choice.finish_reason.as_deref());
+        let tool_calls = choice
+            .message
+            .tool_calls
+            .map(|calls| calls.into_iter().map(ToolCall::from).collect());
+        // Mistral returns an empty string rather than null alongside tool calls
+        let content = choice.message.content.filter(|content| !content.is_empty());
+
+        Ok(CompletionResponse::new(
+            Message::assistant(content, tool_calls),
+            finish_reason,
+        )
+        .with_usage(usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tool_conversation() -> Vec<Message> {
+        let call = ToolCall {
+            id: "call_0f3a9c2b7d".to_string(),
+            name: "search".to_string(),
+            arguments: serde_json::json!({"q": "rust"}),
+        };
+        vec![
+            Message::system("You are helpful."),
+            Message::user("Search for rust"),
+            Message::assistant(None::<String>, Some(vec![call])),
+            Message::tool("Rust is a language", "call_0f3a9c2b7d"),
+        ]
+    }
+
+    #[test]
+    fn test_tool_call_serialization() {
+        let config = CompletionConfig::default().with_model("mistral-large-latest");
+        insta::assert_json_snapshot!(request_body(&tool_conversation(), &config));
+    }
+
+    #[test]
+    fn test_extra_fields_are_merged() {
+        let config = CompletionConfig::default()
+            .with_model("mistral-small-latest")
+            .with_temperature(0.5)
+            .with_extra(serde_json::json!({"random_seed": 42, "safe_prompt": true}));
+        insta::assert_json_snapshot!(request_body(&[Message::user("Hi")], &config));
+    }
+
+    #[test]
+    fn test_valid_tool_call_ids_are_kept() {
+        assert_eq!(tool_call_id("D681PevKs"), "D681PevKs");
+        assert_eq!(tool_call_id("call_0f3a9c2b7d").len(), TOOL_CALL_ID_LEN);
+    }
+}
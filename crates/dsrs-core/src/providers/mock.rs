@@ -0,0 +1,203 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+/// What a `MockProvider` answers once its scripted responses have run out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExhaustedBehavior {
+    /// Fail with a 400 `ApiError`, which is never retried
+    #[default]
+    Error,
+    /// Keep answering with the last scripted response
+    RepeatLast,
+}
+
+/// Provider for tests: answers with scripted messages in order and records every
+/// conversation it receives, so nothing touches the network
+pub struct MockProvider {
+    responses: Mutex<VecDeque<Message>>,
+    last: Mutex<Option<Message>>,
+    when_exhausted: ExhaustedBehavior,
+    received: Mutex<Vec<Vec<Message>>>,
+}
+
+impl MockProvider {
+    pub fn new(responses: Vec<Message>) -> Self {
+        MockProvider {
+            responses: Mutex::new(responses.into()),
+            last: Mutex::new(None),
+            when_exhausted: ExhaustedBehavior::default(),
+            received: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A provider answering with each text as a plain assistant message
+    pub fn with_texts<T: Into<String>>(texts: impl IntoIterator<Item = T>) -> Self {
+        Self::new(
+            texts
+                .into_iter()
+                .map(|text| Message::assistant(Some(text), None))
+                .collect(),
+        )
+    }
+
+    pub fn with_exhausted_behavior(mut self, behavior: ExhaustedBehavior) -> Self {
+        self.when_exhausted = behavior;
+        self
+    }
+
+    /// Every conversation received so far, oldest first
+    pub fn received(&self) -> Vec<Vec<Message>> {
+        self.received.lock().unwrap().clone()
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.received.lock().unwrap().len()
+    }
+
+    /// The conversation of call `index` as pretty JSON, for `insta::assert_snapshot!`
+    pub fn received_snapshot(&self, index: usize) -> String {
+        let received = self.received.lock().unwrap();
+        let messages = received.get(index).unwrap_or_else(|| {
+            panic!(
+                "MockProvider received {} calls, not {}",
+                received.len(),
+                index + 1
+            )
+        });
+        snapshot(messages)
+    }
+
+    /// Panic, showing both snapshots, unless call `index` received exactly `expected`
+    #[track_caller]
+    pub fn assert_called_with(&self, index: usize, expected: &[Message]) {
+        let actual = self.received_snapshot(index);
+        let expected = snapshot(expected);
+        assert!(
+            actual == expected,
+            "MockProvider call {} did not match\n--- expected ---\n{}\n--- received ---\n{}",
+            index,
+            expected,
+            actual
+        );
+    }
+
+    fn next_response(&self) -> Option<Message> {
+        let mut last = self.last.lock().unwrap();
+        match self.responses.lock().unwrap().pop_front() {
+            Some(response) => {
+                *last = Some(response.clone());
+                Some(response)
+            }
+            None if self.when_exhausted == ExhaustedBehavior::RepeatLast => last.clone(),
+            None => None,
+        }
+    }
+}
+
+fn snapshot(messages: &[Message]) -> String {
+    serde_json::to_string_pretty(messages).unwrap_or_else(|e| format!("<unserializable: {}>", e))
+}
+
+impl CompletionProvider for MockProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        _config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let conversation = messages.read().await.clone();
+        self.received.lock().unwrap().push(conversation);
+
+        // A client error, so `retry_with_backoff` doesn't wait out its backoff on it
+        let message = self
+            .next_response()
+            .ok_or_else(|| ProviderError::ApiError {
+                status: 400,
+                message: "MockProvider has no responses left".to_string(),
+            })?;
+        let finish_reason = match &message {
+            Message::Assistant {
+                tool_calls: Some(calls),
+                ..
+            } if !calls.is_empty() => FinishReason::ToolCalls,
+            _ => FinishReason::Stop,
+        };
+        Ok(CompletionResponse::new(message, finish_reason))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conversation(text: &str) -> Arc<RwLock<Vec<Message>>> {
+        Arc::new(RwLock::new(vec![Message::user(text)]))
+    }
+
+    #[tokio::test]
+    async fn test_responses_in_order_then_error() {
+        let provider = MockProvider::with_texts(["first", "second"]);
+
+        for expected in ["first", "second"] {
+            let response = provider
+                .complete(conversation("hi"), CompletionConfig::default())
+                .await
+                .unwrap();
+            assert_eq!(response.message, Message::assistant(Some(expected), None));
+        }
+        let exhausted = provider
+            .complete(conversation("hi"), CompletionConfig::default())
+            .await;
+
+        let error = exhausted.unwrap_err();
+        assert!(matches!(error, ProviderError::ApiError { status: 400, .. }));
+        assert!(!error.is_retryable());
+        assert_eq!(provider.call_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_repeat_last_when_exhausted() {
+        let provider = MockProvider::with_texts(["only"])
+            .with_exhausted_behavior(ExhaustedBehavior::RepeatLast);
+
+        for _ in 0..3 {
+            let response = provider
+                .complete(conversation("hi"), CompletionConfig::default())
+                .await
+                .unwrap();
+            assert_eq!(response.message, Message::assistant(Some("only"), None));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_records_received_messages() {
+        let provider = MockProvider::with_texts(["a", "b"]);
+        provider
+            .complete(conversation("first"), CompletionConfig::default())
+            .await
+            .unwrap();
+        provider
+            .complete(conversation("second"), CompletionConfig::default())
+            .await
+            .unwrap();
+
+        provider.assert_called_with(1, &[Message::user("second")]);
+        insta::assert_snapshot!(provider.received_snapshot(0));
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "did not match")]
+    async fn test_assert_called_with_mismatch_panics() {
+        let provider = MockProvider::with_texts(["a"]);
+        provider
+            .complete(conversation("first"), CompletionConfig::default())
+            .await
+            .unwrap();
+
+        provider.assert_called_with(0, &[Message::user("other")]);
+    }
+}
@@ -1,9 +1,60 @@
+pub mod anthropic;
+pub mod azure;
+#[cfg(feature = "bedrock")]
+pub mod bedrock;
+pub mod cached;
+pub mod cohere;
+pub mod embedding;
 pub mod error;
+pub mod fallback;
+pub mod gemini;
+pub mod groq;
+pub mod huggingface;
+pub mod middleware;
+pub mod mistral;
+pub mod mock;
 pub mod models;
+pub mod ollama;
 pub mod openai;
+#[cfg(feature = "assistants")]
+pub mod openai_assistant;
+pub mod rate_limited;
+pub mod registry;
+pub mod replay;
+pub mod retry;
+pub mod streaming;
+#[cfg(feature = "tracing")]
+pub mod traced;
 pub mod traits;
 
+pub use anthropic::AnthropicProvider;
+pub use azure::AzureOpenAIProvider;
+#[cfg(feature = "bedrock")]
+pub use bedrock::BedrockProvider;
+pub use cached::{CacheBackend, CachedProvider, InMemoryCache};
+pub use cohere::CohereProvider;
+pub use embedding::{EmbeddingProvider, OpenAIEmbeddingProvider};
 pub use error::ProviderError;
+pub use fallback::{FallbackConfig, FallbackProvider};
+pub use gemini::{GeminiConfig, GeminiProvider, SafetySetting};
+pub use groq::GroqProvider;
+pub use huggingface::HuggingFaceProvider;
+pub use middleware::{
+    ContentFilterMiddleware, CostTrackingMiddleware, LoggingMiddleware, MiddlewareStack,
+    ProviderMiddleware,
+};
+pub use mistral::MistralProvider;
+pub use mock::{ExhaustedBehavior, MockProvider};
 pub use models::*;
+pub use ollama::OllamaProvider;
 pub use openai::OpenAIProvider;
-pub use traits::CompletionProvider;
+#[cfg(feature = "assistants")]
+pub use openai_assistant::{AssistantId, MessageId, OpenAIAssistantProvider, ThreadId};
+pub use rate_limited::RateLimitedProvider;
+pub use registry::{ModelInfo, ModelRegistry, ProviderType};
+pub use replay::ReplayProvider;
+pub use retry::{RetryConfig, retry_with_backoff};
+pub use streaming::{CompletionStream, StreamChunk};
+#[cfg(feature = "tracing")]
+pub use traced::TracingProvider;
+pub use traits::{CompletionProvider, ErasedCompletionProvider};
@@ -3,25 +3,94 @@ pub use serde::{Deserialize, Serialize};
 
 // MARK: Base
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ContentTypes {
     Text(String),
+    /// Image fetched by the provider from `url`, which may also be a `data:` URL
+    Image {
+        url: String,
+        detail: Option<ImageDetail>,
+    },
+    /// Inline image, e.g. `media_type: "image/png"`
+    ImageBytes {
+        data: Vec<u8>,
+        media_type: String,
+        detail: Option<ImageDetail>,
+    },
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Resolution at which a vision model looks at an image
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageDetail {
+    Low,
+    High,
+    Auto,
+}
+
+impl ContentTypes {
+    pub fn image_url(url: impl Into<String>) -> Self {
+        ContentTypes::Image {
+            url: url.into(),
+            detail: None,
+        }
+    }
+
+    pub fn image_bytes(data: Vec<u8>, media_type: impl Into<String>) -> Self {
+        ContentTypes::ImageBytes {
+            data,
+            media_type: media_type.into(),
+            detail: None,
+        }
+    }
+
+    /// The text, or `None` for images
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            ContentTypes::Text(text) => Some(text),
+            ContentTypes::Image { .. } | ContentTypes::ImageBytes { .. } => None,
+        }
+    }
+
+    /// Text parts joined by newlines, for providers and formats without image support
+    pub fn join_text(parts: &[ContentTypes]) -> String {
+        parts
+            .iter()
+            .filter_map(ContentTypes::as_text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
     pub name: String,
     pub arguments: serde_json::Value,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl ToolCall {
+    /// The arguments as `T`, deserialized without cloning them
+    pub fn deserialize_arguments<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T> {
+        T::deserialize(&self.arguments)
+    }
+
+    /// The argument named `key` as `T`; fails if there is no such argument
+    pub fn argument<T: serde::de::DeserializeOwned>(&self, key: &str) -> serde_json::Result<T> {
+        let value = self.arguments.get(key).ok_or_else(|| {
+            serde::de::Error::custom(format!("missing argument `{}` in call to {}", key, self.name))
+        })?;
+        T::deserialize(value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Message {
     System {
         content: ContentTypes,
     },
+    /// Multi-part, so text can be mixed with images
     User {
-        content: ContentTypes,
+        content: Vec<ContentTypes>,
     },
     Assistant {
         content: Option<ContentTypes>,
@@ -36,10 +105,15 @@ pub enum Message {
 impl Message {
     pub fn user(content: impl Into<String>) -> Self {
         Message::User {
-            content: ContentTypes::Text(content.into()),
+            content: vec![ContentTypes::Text(content.into())],
         }
     }
 
+    /// User message with several parts, such as a question and the images it is about
+    pub fn user_parts(content: Vec<ContentTypes>) -> Self {
+        Message::User { content }
+    }
+
     pub fn assistant(
         content: Option<impl Into<String>>,
         tool_calls: Option<Vec<ToolCall>>,
@@ -62,6 +136,67 @@ impl Message {
             tool_call_id: tool_call_id.into(),
         }
     }
+
+    /// Tool message carrying `result` serialized as JSON. Strings are sent as-is rather
+    /// than quoted
+    pub fn tool_result(
+        tool_call_id: impl Into<String>,
+        result: impl Serialize,
+    ) -> serde_json::Result<Self> {
+        let content = match serde_json::to_value(result)? {
+            serde_json::Value::String(text) => text,
+            value => value.to_string(),
+        };
+        Ok(Message::tool(content, tool_call_id))
+    }
+
+    /// Tool message reporting that the call failed, so the model can recover
+    pub fn tool_error(tool_call_id: impl Into<String>, error: impl std::fmt::Display) -> Self {
+        Message::tool(format!("Error: {}", error), tool_call_id)
+    }
+
+    /// The text content, or the first text part of a user message; `None` for images and
+    /// tool-only assistant messages
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Message::System { content } | Message::Tool { content, .. } => content.as_text(),
+            Message::User { content } => content.iter().find_map(ContentTypes::as_text),
+            Message::Assistant { content, .. } => content.as_ref()?.as_text(),
+        }
+    }
+
+    /// The tool calls of an assistant message that made any
+    pub fn as_tool_calls(&self) -> Option<&[ToolCall]> {
+        match self {
+            Message::Assistant {
+                tool_calls: Some(calls),
+                ..
+            } => Some(calls),
+            _ => None,
+        }
+    }
+
+    pub fn is_assistant(&self) -> bool {
+        matches!(self, Message::Assistant { .. })
+    }
+
+    /// Whether this is a tool message answering one of the assistant's tool calls
+    pub fn is_tool_call_response(&self) -> bool {
+        matches!(self, Message::Tool { .. })
+    }
+
+    /// Whether any of the content is an image
+    pub fn has_images(&self) -> bool {
+        match self {
+            Message::System { content } | Message::Tool { content, .. } => {
+                content.as_text().is_none()
+            }
+            Message::User { content } => content.iter().any(|part| part.as_text().is_none()),
+            Message::Assistant { content, .. } => {
+                content.as_ref().is_some_and(|content| content.as_text().is_none())
+            }
+        }
+    }
 }
 
 // MARK: Completions
@@ -73,8 +208,388 @@ pub struct AvailableTool {
     pub input_schema_json: Option<serde_json::Value>,
 }
 
+impl AvailableTool {
+    /// Tool taking arguments of type `A`, described by `A`'s doc comment
+    pub fn for_args<A: JsonSchema>(name: impl Into<String>) -> Self {
+        let schema = crate::adapters::schema_parser::tool_input_schema::<A>();
+        let desc = schema
+            .get("description")
+            .and_then(|desc| desc.as_str())
+            .unwrap_or_default()
+            .to_string();
+        AvailableTool {
+            name: name.into(),
+            desc,
+            input_schema_json: Some(schema),
+        }
+    }
+
+    /// Tool for an async function taking a single argument, whose type gives the schema
+    pub fn from_fn<A, F, Fut>(name: impl Into<String>, _function: &F) -> Self
+    where
+        A: JsonSchema,
+        F: Fn(A) -> Fut,
+        Fut: std::future::Future,
+    {
+        Self::for_args::<A>(name)
+    }
+
+    /// Tool taking arguments of type `T`, like `for_args` with an explicit description
+    pub fn from_schema<T: JsonSchema>(name: impl Into<String>, desc: impl Into<String>) -> Self {
+        Self::for_args::<T>(name).with_desc(desc)
+    }
+
+    pub fn builder() -> AvailableToolBuilder {
+        AvailableToolBuilder::default()
+    }
+
+    pub fn with_desc(mut self, desc: impl Into<String>) -> Self {
+        self.desc = desc.into();
+        self
+    }
+
+    /// Forbid additional properties on every object of the input schema, for providers
+    /// that require it (Groq, OpenAI's strict mode)
+    pub fn with_strict_schema(mut self) -> Self {
+        if let Some(schema) = &mut self.input_schema_json {
+            crate::adapters::schema_parser::forbid_additional_properties(schema);
+        }
+        self
+    }
+}
+
+/// Builds an `AvailableTool`; unset fields are empty and there is no input schema
+#[derive(Clone, Debug, Default)]
+pub struct AvailableToolBuilder {
+    name: String,
+    desc: String,
+    input_schema_json: Option<serde_json::Value>,
+    strict: bool,
+}
+
+impl AvailableToolBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn desc(mut self, desc: impl Into<String>) -> Self {
+        self.desc = desc.into();
+        self
+    }
+
+    /// Input schema generated from `T`, as with `AvailableTool::for_args`
+    pub fn input_schema<T: JsonSchema>(mut self) -> Self {
+        self.input_schema_json = Some(crate::adapters::schema_parser::tool_input_schema::<T>());
+        self
+    }
+
+    pub fn input_schema_json(mut self, schema: serde_json::Value) -> Self {
+        self.input_schema_json = Some(schema);
+        self
+    }
+
+    /// See `AvailableTool::with_strict_schema`; applied on `build`, whenever the schema is set
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn build(self) -> AvailableTool {
+        let tool = AvailableTool {
+            name: self.name,
+            desc: self.desc,
+            input_schema_json: self.input_schema_json,
+        };
+        if self.strict {
+            tool.with_strict_schema()
+        } else {
+            tool
+        }
+    }
+}
+
+/// Where a system injection is placed relative to the adapter-generated system prompt
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InjectionPosition {
+    Prepend,
+    Append,
+}
+
+/// Text applied to the system message of every request made with a config, regardless of adapter
 #[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SystemInjection {
+    pub text: String,
+    pub position: InjectionPosition,
+}
+
+/// Model, tools and sampling parameters for a request. Prefer `CompletionConfig::builder()`;
+/// every field besides `model` defaults to leaving the choice to the provider
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct CompletionConfig {
     pub model: String,
     pub tools: Option<Vec<AvailableTool>>,
+    #[serde(default)]
+    pub system_injections: Vec<SystemInjection>,
+    // Sampling parameters; unset fields are left to the provider's defaults
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Upper bound on generated tokens
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+    /// Provider-specific fields merged into the request body, e.g. Mistral's `random_seed`
+    #[serde(default)]
+    pub extra: Option<serde_json::Value>,
+    /// Fetch a fresh completion even when the provider is wrapped in a `CachedProvider`
+    #[serde(skip)]
+    pub skip_cache: bool,
+    /// Constrain the completion text, for providers with a native response format
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+    /// Whether and which of `tools` the model must call
+    #[serde(default)]
+    pub tool_choice: Option<ToolChoice>,
+}
+
+/// How the model may use the available tools
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ToolChoice {
+    /// Answer directly or call tools, as the model sees fit
+    Auto,
+    /// Never call a tool
+    None,
+    /// Call at least one tool
+    Required,
+    /// Call the named tool
+    Specific(String),
+}
+
+/// Shape the completion text must take
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ResponseFormat {
+    Text,
+    /// Any valid JSON object
+    JsonObject,
+    /// JSON matching `schema`; with `strict` the provider guarantees it
+    JsonSchema {
+        name: String,
+        description: Option<String>,
+        schema: serde_json::Value,
+        strict: bool,
+    },
+}
+
+impl CompletionConfig {
+    pub fn builder() -> CompletionConfigBuilder {
+        CompletionConfigBuilder::default()
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    pub fn with_tools(mut self, tools: Vec<AvailableTool>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    pub fn with_tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    pub fn with_stop(mut self, stop: Vec<String>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    pub fn with_response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = Some(response_format);
+        self
+    }
+
+    pub fn with_extra(mut self, extra: serde_json::Value) -> Self {
+        self.extra = Some(extra);
+        self
+    }
+
+    pub fn with_skip_cache(mut self, skip_cache: bool) -> Self {
+        self.skip_cache = skip_cache;
+        self
+    }
+
+    /// Add a global system prompt injection (e.g. a content policy) applied after any earlier ones
+    pub fn with_system_injection(mut self, text: String, position: InjectionPosition) -> Self {
+        self.system_injections.push(SystemInjection { text, position });
+        self
+    }
+
+    /// Merge the `extra` fields into a serialized request body, overriding existing keys
+    pub fn apply_extra(&self, body: &mut serde_json::Value) {
+        if let (Some(serde_json::Value::Object(extra)), serde_json::Value::Object(body)) =
+            (&self.extra, body)
+        {
+            body.extend(extra.clone());
+        }
+    }
+
+    /// Apply all system injections, in order, to the given system message content
+    pub fn apply_system_injections(&self, content: String) -> String {
+        self.system_injections
+            .iter()
+            .fold(content, |content, injection| match injection.position {
+                InjectionPosition::Prepend => format!("{}\n{}", injection.text, content),
+                InjectionPosition::Append => format!("{}\n{}", content, injection.text),
+            })
+    }
+}
+
+/// Builds a `CompletionConfig`; unset fields keep their `Default` values
+#[derive(Clone, Debug, Default)]
+pub struct CompletionConfigBuilder {
+    config: CompletionConfig,
+}
+
+impl CompletionConfigBuilder {
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.config = self.config.with_model(model);
+        self
+    }
+
+    pub fn tools(mut self, tools: Vec<AvailableTool>) -> Self {
+        self.config = self.config.with_tools(tools);
+        self
+    }
+
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.config = self.config.with_tool_choice(tool_choice);
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.config = self.config.with_temperature(temperature);
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.config = self.config.with_max_tokens(max_tokens);
+        self
+    }
+
+    pub fn top_p(mut self, top_p: f32) -> Self {
+        self.config = self.config.with_top_p(top_p);
+        self
+    }
+
+    pub fn stop(mut self, stop: Vec<String>) -> Self {
+        self.config = self.config.with_stop(stop);
+        self
+    }
+
+    pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.config = self.config.with_response_format(response_format);
+        self
+    }
+
+    pub fn extra(mut self, extra: serde_json::Value) -> Self {
+        self.config = self.config.with_extra(extra);
+        self
+    }
+
+    /// See `CompletionConfig::with_system_injection`
+    pub fn system_injection(
+        mut self,
+        text: impl Into<String>,
+        position: InjectionPosition,
+    ) -> Self {
+        self.config = self.config.with_system_injection(text.into(), position);
+        self
+    }
+
+    pub fn build(self) -> CompletionConfig {
+        self.config
+    }
+}
+
+/// Why the provider stopped generating
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinishReason {
+    Stop,
+    Length,
+    ToolCalls,
+    ContentFilter,
+}
+
+/// Token counts reported by the provider for a completion
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageStats {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl UsageStats {
+    pub fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        UsageStats {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+}
+
+impl std::ops::Add for UsageStats {
+    type Output = UsageStats;
+
+    fn add(self, other: UsageStats) -> UsageStats {
+        UsageStats {
+            prompt_tokens: self.prompt_tokens + other.prompt_tokens,
+            completion_tokens: self.completion_tokens + other.completion_tokens,
+            total_tokens: self.total_tokens + other.total_tokens,
+        }
+    }
+}
+
+/// A completion together with the metadata reported by the provider
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompletionResponse {
+    pub message: Message,
+    /// `None` when the provider doesn't report token counts
+    pub usage: Option<UsageStats>,
+    pub finish_reason: FinishReason,
+}
+
+impl CompletionResponse {
+    pub fn new(message: Message, finish_reason: FinishReason) -> Self {
+        CompletionResponse {
+            message,
+            usage: None,
+            finish_reason,
+        }
+    }
+
+    pub fn with_usage(mut self, usage: Option<UsageStats>) -> Self {
+        self.usage = usage;
+        self
+    }
 }
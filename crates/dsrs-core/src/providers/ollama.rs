@@ -0,0 +1,132 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+use super::openai::{
+    chat_request_builder, complete_chat, open_stream, request_body, stream_chat,
+};
+use super::streaming::CompletionStream;
+
+use async_openai::Client;
+use async_openai::config::OpenAIConfig;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
+
+const DEFAULT_BASE_URL: &str = "http://localhost:11434";
+
+/// Provider for models served by a local Ollama instance through its
+/// OpenAI-compatible endpoint.
+///
+/// Not every Ollama model supports tool calls, so by default the provider asks the
+/// server for the model's capabilities before sending a request with tools.
+pub struct OllamaProvider {
+    client: Client<OpenAIConfig>,
+    http: reqwest::Client,
+    base_url: String,
+    model_check: bool,
+    // Whether each model supports tools, as reported by `/api/show`
+    tool_support: Mutex<HashMap<String, bool>>,
+}
+
+#[derive(Deserialize)]
+struct ShowResponse {
+    // Missing on older Ollama versions
+    capabilities: Option<Vec<String>>,
+}
+
+impl OllamaProvider {
+    pub fn new(base_url: String) -> Self {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        // Ollama doesn't check the API key, so the client is built without one
+        let config = OpenAIConfig::new().with_api_base(format!("{}/v1", base_url));
+        OllamaProvider {
+            client: Client::with_config(config),
+            http: reqwest::Client::new(),
+            base_url,
+            model_check: true,
+            tool_support: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether to check that the model supports tools before sending them; disable
+    /// when the model is known to support them to skip the extra request
+    pub fn with_model_check(mut self, model_check: bool) -> Self {
+        self.model_check = model_check;
+        self
+    }
+
+    async fn supports_tools(&self, model: &str) -> Result<bool, ProviderError> {
+        if let Some(supported) = self.tool_support.lock().await.get(model) {
+            return Ok(*supported);
+        }
+
+        let response = self
+            .http
+            .post(format!("{}/api/show", self.base_url))
+            .json(&serde_json::json!({ "model": model }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(ProviderError::ApiError {
+                status: response.status().as_u16(),
+                message: response.text().await.unwrap_or_default(),
+            });
+        }
+
+        // Servers that don't report capabilities get the benefit of the doubt
+        let show: ShowResponse = response.json().await?;
+        let supported = show
+            .capabilities
+            .is_none_or(|capabilities| capabilities.iter().any(|c| c == "tools"));
+        self.tool_support
+            .lock()
+            .await
+            .insert(model.to_string(), supported);
+        Ok(supported)
+    }
+
+    async fn request(
+        &self,
+        messages: &RwLock<Vec<Message>>,
+        config: CompletionConfig,
+    ) -> Result<JsonValue, ProviderError> {
+        let has_tools = config.tools.as_ref().is_some_and(|tools| !tools.is_empty());
+        if self.model_check && has_tools && !self.supports_tools(&config.model).await? {
+            return Err(ProviderError::ToolsNotSupported {
+                model: config.model,
+            });
+        }
+        let request = chat_request_builder(messages, &config).await.build()?;
+        Ok(request_body(request, &config))
+    }
+}
+
+impl Default for OllamaProvider {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_URL.to_string())
+    }
+}
+
+impl CompletionProvider for OllamaProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let request = self.request(&messages, config).await?;
+        complete_chat(&self.client, request).await
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        stream_chat(Box::pin(async move {
+            let request = self.request(&messages, config).await?;
+            open_stream(&self.client, request).await
+        }))
+    }
+}
@@ -1,21 +1,35 @@
 use super::CompletionProvider;
 use super::ProviderError;
 use super::models::*;
+use super::streaming::{CompletionStream, StreamChunk, ToolCallAccumulator};
 
 use async_openai::types::{
     ChatCompletionRequestSystemMessageContent, ChatCompletionRequestToolMessageContent,
 };
-use async_openai::{Client, config::OpenAIConfig};
+use async_openai::{
+    Client,
+    config::{Config, OpenAIConfig},
+};
 
 use async_openai::types::{
     ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessageArgs,
     ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestMessage,
     ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestToolMessageArgs,
     ChatCompletionRequestUserMessageArgs, ChatCompletionRequestUserMessageContent,
-    ChatCompletionTool, ChatCompletionToolArgs, ChatCompletionToolType,
-    CreateChatCompletionRequestArgs, FunctionCall, FunctionObjectArgs, ServiceTier,
+    ChatCompletionNamedToolChoice, ChatCompletionResponseStream, ChatCompletionTool,
+    ChatCompletionToolArgs, ChatCompletionToolChoiceOption, ChatCompletionToolType, CreateChatCompletionRequest, CreateChatCompletionRequestArgs,
+    CreateChatCompletionResponse, FinishReason as OpenAIFinishReason, FunctionCall, FunctionName,
+    FunctionObjectArgs, ResponseFormat as OpenAIResponseFormat, ResponseFormatJsonSchema, Stop,
 };
 
+use async_openai::types::{
+    ChatCompletionRequestMessageContentPartImage, ChatCompletionRequestMessageContentPartText,
+    ChatCompletionRequestUserMessageContentPart, ImageDetail as OpenAIImageDetail, ImageUrl,
+};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use futures::future::BoxFuture;
+use futures::{StreamExt, stream};
+use serde_json::Value as JsonValue;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -36,39 +50,81 @@ impl OpenAIProvider {
     }
 }
 
-impl From<&ContentTypes> for ChatCompletionRequestUserMessageContent {
+impl From<ImageDetail> for OpenAIImageDetail {
+    fn from(detail: ImageDetail) -> Self {
+        match detail {
+            ImageDetail::Low => OpenAIImageDetail::Low,
+            ImageDetail::High => OpenAIImageDetail::High,
+            ImageDetail::Auto => OpenAIImageDetail::Auto,
+        }
+    }
+}
+
+impl From<&ContentTypes> for ChatCompletionRequestUserMessageContentPart {
     fn from(content: &ContentTypes) -> Self {
+        let image = |url: String, detail: &Option<ImageDetail>| {
+            ChatCompletionRequestUserMessageContentPart::ImageUrl(
+                ChatCompletionRequestMessageContentPartImage {
+                    image_url: ImageUrl {
+                        url,
+                        detail: detail.map(OpenAIImageDetail::from),
+                    },
+                },
+            )
+        };
         match content {
-            ContentTypes::Text(text) => ChatCompletionRequestUserMessageContent::Text(text.clone()),
+            ContentTypes::Text(text) => ChatCompletionRequestUserMessageContentPart::Text(
+                ChatCompletionRequestMessageContentPartText { text: text.clone() },
+            ),
+            ContentTypes::Image { url, detail } => image(url.clone(), detail),
+            // Inline images are sent as base64 data URLs
+            ContentTypes::ImageBytes {
+                data,
+                media_type,
+                detail,
+            } => image(
+                format!("data:{};base64,{}", media_type, BASE64_STANDARD.encode(data)),
+                detail,
+            ),
         }
     }
 }
 
+// A lone text part is sent as plain text, anything else as an array of parts
+fn user_content(parts: &[ContentTypes]) -> ChatCompletionRequestUserMessageContent {
+    match parts {
+        [ContentTypes::Text(text)] => ChatCompletionRequestUserMessageContent::Text(text.clone()),
+        parts => ChatCompletionRequestUserMessageContent::Array(
+            parts
+                .iter()
+                .map(ChatCompletionRequestUserMessageContentPart::from)
+                .collect(),
+        ),
+    }
+}
+
+// Only user messages take images; elsewhere just the text is sent
 impl From<&ContentTypes> for ChatCompletionRequestAssistantMessageContent {
     fn from(content: &ContentTypes) -> Self {
-        match content {
-            ContentTypes::Text(text) => {
-                ChatCompletionRequestAssistantMessageContent::Text(text.clone())
-            }
-        }
+        ChatCompletionRequestAssistantMessageContent::Text(
+            content.as_text().unwrap_or_default().to_string(),
+        )
     }
 }
 
 impl From<&ContentTypes> for ChatCompletionRequestToolMessageContent {
     fn from(content: &ContentTypes) -> Self {
-        match content {
-            ContentTypes::Text(text) => ChatCompletionRequestToolMessageContent::Text(text.clone()),
-        }
+        ChatCompletionRequestToolMessageContent::Text(
+            content.as_text().unwrap_or_default().to_string(),
+        )
     }
 }
 
 impl From<&ContentTypes> for ChatCompletionRequestSystemMessageContent {
     fn from(content: &ContentTypes) -> Self {
-        match content {
-            ContentTypes::Text(text) => {
-                ChatCompletionRequestSystemMessageContent::Text(text.clone())
-            }
-        }
+        ChatCompletionRequestSystemMessageContent::Text(
+            content.as_text().unwrap_or_default().to_string(),
+        )
     }
 }
 
@@ -79,7 +135,11 @@ impl From<&ToolCall> for ChatCompletionMessageToolCall {
             r#type: async_openai::types::ChatCompletionToolType::Function,
             function: FunctionCall {
                 name: tool_call.name.clone(),
-                arguments: serde_json::to_string(&tool_call.arguments).unwrap(),
+                // Arguments that never parsed as JSON are sent back as they arrived
+                arguments: match &tool_call.arguments {
+                    serde_json::Value::String(raw) => raw.clone(),
+                    arguments => arguments.to_string(),
+                },
             },
         }
     }
@@ -90,7 +150,7 @@ impl From<&Message> for ChatCompletionRequestMessage {
         match message {
             Message::User { content } => ChatCompletionRequestMessage::User(
                 ChatCompletionRequestUserMessageArgs::default()
-                    .content(content)
+                    .content(user_content(content))
                     .build()
                     .unwrap(),
             ),
@@ -99,14 +159,14 @@ impl From<&Message> for ChatCompletionRequestMessage {
                 tool_calls,
             } => {
                 let mut builder = ChatCompletionRequestAssistantMessageArgs::default();
-                if let Some(calls) = tool_calls {
-                    if !calls.is_empty() {
-                        let openai_tool_calls: Vec<ChatCompletionMessageToolCall> = calls
-                            .iter()
-                            .map(ChatCompletionMessageToolCall::from)
-                            .collect();
-                        builder.tool_calls(openai_tool_calls);
-                    }
+                if let Some(calls) = tool_calls
+                    && !calls.is_empty()
+                {
+                    let openai_tool_calls: Vec<ChatCompletionMessageToolCall> = calls
+                        .iter()
+                        .map(ChatCompletionMessageToolCall::from)
+                        .collect();
+                    builder.tool_calls(openai_tool_calls);
                 }
                 if let Some(content) = content {
                     builder.content(content);
@@ -159,11 +219,241 @@ impl From<&AvailableTool> for ChatCompletionTool {
 
 impl From<ChatCompletionMessageToolCall> for ToolCall {
     fn from(tool_call: ChatCompletionMessageToolCall) -> Self {
+        // Arguments arrive as a JSON-encoded string; keep the raw string if it isn't valid JSON
+        let arguments = serde_json::from_str(&tool_call.function.arguments)
+            .unwrap_or(serde_json::Value::String(tool_call.function.arguments));
         ToolCall {
             id: tool_call.id,
             name: tool_call.function.name,
-            arguments: serde_json::to_value(tool_call.function.arguments).unwrap(),
+            arguments,
+        }
+    }
+}
+
+impl From<OpenAIFinishReason> for FinishReason {
+    fn from(reason: OpenAIFinishReason) -> Self {
+        match reason {
+            OpenAIFinishReason::Stop => FinishReason::Stop,
+            OpenAIFinishReason::Length => FinishReason::Length,
+            OpenAIFinishReason::ToolCalls | OpenAIFinishReason::FunctionCall => {
+                FinishReason::ToolCalls
+            }
+            OpenAIFinishReason::ContentFilter => FinishReason::ContentFilter,
+        }
+    }
+}
+
+impl From<ResponseFormat> for OpenAIResponseFormat {
+    fn from(format: ResponseFormat) -> Self {
+        match format {
+            ResponseFormat::Text => OpenAIResponseFormat::Text,
+            ResponseFormat::JsonObject => OpenAIResponseFormat::JsonObject,
+            ResponseFormat::JsonSchema {
+                name,
+                description,
+                schema,
+                strict,
+            } => OpenAIResponseFormat::JsonSchema {
+                json_schema: ResponseFormatJsonSchema {
+                    description,
+                    name,
+                    schema: Some(schema),
+                    strict: Some(strict),
+                },
+            },
+        }
+    }
+}
+
+impl From<ToolChoice> for ChatCompletionToolChoiceOption {
+    fn from(choice: ToolChoice) -> Self {
+        match choice {
+            ToolChoice::Auto => ChatCompletionToolChoiceOption::Auto,
+            ToolChoice::None => ChatCompletionToolChoiceOption::None,
+            ToolChoice::Required => ChatCompletionToolChoiceOption::Required,
+            ToolChoice::Specific(name) => {
+                ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+                    r#type: ChatCompletionToolType::Function,
+                    function: FunctionName { name },
+                })
+            }
+        }
+    }
+}
+
+// Request builder shared by the providers that go through `async-openai`; callers add
+// their provider-specific options before building
+pub(crate) async fn chat_request_builder(
+    messages: &RwLock<Vec<Message>>,
+    config: &CompletionConfig,
+) -> CreateChatCompletionRequestArgs {
+    // Clone the messages and immediately release the lock
+    let request_messages = {
+        let guard = messages.read().await;
+        guard
+            .iter()
+            .map(ChatCompletionRequestMessage::from)
+            .collect::<Vec<ChatCompletionRequestMessage>>()
+    };
+
+    let available_tools = config.tools.as_ref().map(|tools| {
+        tools
+            .iter()
+            .map(ChatCompletionTool::from)
+            .collect::<Vec<ChatCompletionTool>>()
+    });
+
+    let mut builder = CreateChatCompletionRequestArgs::default();
+    builder.messages(request_messages).model(&config.model);
+    if let Some(tools) = available_tools {
+        builder.tools(tools);
+    }
+    if let Some(temperature) = config.temperature {
+        builder.temperature(temperature);
+    }
+    if let Some(max_tokens) = config.max_tokens {
+        builder.max_completion_tokens(max_tokens);
+    }
+    if let Some(top_p) = config.top_p {
+        builder.top_p(top_p);
+    }
+    if let Some(stop) = config.stop.clone() {
+        builder.stop(Stop::StringArray(stop));
+    }
+    if let Some(format) = config.response_format.clone() {
+        builder.response_format(OpenAIResponseFormat::from(format));
+    }
+    if let Some(choice) = config.tool_choice.clone() {
+        builder.tool_choice(ChatCompletionToolChoiceOption::from(choice));
+    }
+    builder
+}
+
+// The typed request has no room for `CompletionConfig::extra`, so requests are sent as
+// JSON through the client's `byot` methods with the extra fields merged in
+pub(crate) fn request_body(
+    request: CreateChatCompletionRequest,
+    config: &CompletionConfig,
+) -> JsonValue {
+    let mut body = serde_json::to_value(request).expect("request bodies always serialize");
+    config.apply_extra(&mut body);
+    body
+}
+
+pub(crate) fn completion_response(response: CreateChatCompletionResponse) -> CompletionResponse {
+    let usage = response.usage.map(|usage| UsageStats {
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        total_tokens: usage.total_tokens,
+    });
+    let first_choice = response
+        .choices
+        .into_iter()
+        .next()
+        .map(|choice| {
+            let content = choice.message.content;
+            let calls = choice
+                .message
+                .tool_calls
+                .map(|calls| calls.into_iter().map(ToolCall::from).collect());
+            let finish_reason = choice
+                .finish_reason
+                .map(FinishReason::from)
+                .unwrap_or(FinishReason::Stop);
+            (content, calls, finish_reason)
+        })
+        .unwrap();
+
+    CompletionResponse::new(
+        Message::assistant(first_choice.0, first_choice.1),
+        first_choice.2,
+    )
+    .with_usage(usage)
+}
+
+pub(crate) async fn complete_chat<C: Config>(
+    client: &Client<C>,
+    body: JsonValue,
+) -> Result<CompletionResponse, ProviderError> {
+    let response: CreateChatCompletionResponse = client.chat().create_byot(body).await?;
+    Ok(completion_response(response))
+}
+
+pub(crate) async fn open_stream<C: Config>(
+    client: &Client<C>,
+    mut body: JsonValue,
+) -> Result<ChatCompletionResponseStream, ProviderError> {
+    // With `byot` enabled the client no longer sets this itself
+    body["stream"] = JsonValue::Bool(true);
+    Ok(client.chat().create_stream_byot(body).await?)
+}
+
+enum StreamState<'a> {
+    Connecting(BoxFuture<'a, Result<ChatCompletionResponseStream, ProviderError>>),
+    Streaming(ChatCompletionResponseStream, ToolCallAccumulator),
+    Done,
+}
+
+// Turns the chunk stream opened by `open` into a `CompletionStream`
+pub(crate) fn stream_chat<'a>(
+    open: BoxFuture<'a, Result<ChatCompletionResponseStream, ProviderError>>,
+) -> CompletionStream<'a> {
+    let chunks = stream::unfold(StreamState::Connecting(open), |state| async move {
+        match state {
+            StreamState::Connecting(open) => match open.await {
+                Ok(inner) => Some((
+                    Vec::new(),
+                    StreamState::Streaming(inner, ToolCallAccumulator::new()),
+                )),
+                Err(e) => Some((vec![Err(e)], StreamState::Done)),
+            },
+            StreamState::Streaming(mut inner, mut tool_calls) => match inner.next().await {
+                Some(Ok(response)) => {
+                    let mut chunks = Vec::new();
+                    for choice in response.choices {
+                        if let Some(text) = choice.delta.content
+                            && !text.is_empty()
+                        {
+                            chunks.push(Ok(StreamChunk::Text(text)));
+                        }
+                        for call in choice.delta.tool_calls.into_iter().flatten() {
+                            let function = call.function.as_ref();
+                            tool_calls.push(
+                                call.index,
+                                call.id.as_deref(),
+                                function.and_then(|f| f.name.as_deref()),
+                                function.and_then(|f| f.arguments.as_deref()),
+                            );
+                        }
+                    }
+                    Some((chunks, StreamState::Streaming(inner, tool_calls)))
+                }
+                Some(Err(e)) => Some((vec![Err(e.into())], StreamState::Done)),
+                // Tool calls are only complete once the stream has ended
+                None => {
+                    let chunks = tool_calls
+                        .finish()
+                        .into_iter()
+                        .map(|call| Ok(StreamChunk::ToolCall(call)))
+                        .collect();
+                    Some((chunks, StreamState::Done))
+                }
+            },
+            StreamState::Done => None,
         }
+    });
+
+    Box::pin(chunks.flat_map(stream::iter))
+}
+
+impl OpenAIProvider {
+    async fn request(
+        &self,
+        messages: &RwLock<Vec<Message>>,
+        config: CompletionConfig,
+    ) -> Result<JsonValue, ProviderError> {
+        let request = chat_request_builder(messages, &config).await.build()?;
+        Ok(request_body(request, &config))
     }
 }
 
@@ -172,59 +462,19 @@ impl CompletionProvider for OpenAIProvider {
         &self,
         messages: Arc<RwLock<Vec<Message>>>,
         config: CompletionConfig,
-    ) -> Result<Message, ProviderError> {
-        // Clone the messages and immediately release the lock
-        let request_messages = {
-            let guard = messages.read().await;
-            guard
-                .iter()
-                .map(ChatCompletionRequestMessage::from)
-                .collect::<Vec<ChatCompletionRequestMessage>>()
-        };
-
-        let available_tools = match config.tools {
-            Some(tools) => {
-                let tool_vec = tools
-                    .iter()
-                    .map(ChatCompletionTool::from)
-                    .collect::<Vec<ChatCompletionTool>>();
-                Some(tool_vec)
-            }
-            None => None,
-        };
-
-        let mut builder = CreateChatCompletionRequestArgs::default();
-
-        let request = if let Some(tools) = available_tools {
-            builder
-                .messages(request_messages)
-                .model(config.model)
-                .tools(tools)
-                .service_tier(ServiceTier::Flex) // Groq sending unsupported service tier back, need to specify
-                .build()?
-        } else {
-            builder
-                .messages(request_messages)
-                .model(config.model)
-                .service_tier(ServiceTier::Flex) // Groq sending unsupported service tier back, need to specify
-                .build()?
-        };
+    ) -> Result<CompletionResponse, ProviderError> {
+        let request = self.request(&messages, config).await?;
+        complete_chat(&self.client, request).await
+    }
 
-        let response = self.client.chat().create(request).await?;
-        let first_choice = response
-            .choices
-            .into_iter()
-            .next()
-            .and_then(|choice| {
-                let content = choice.message.content;
-                let calls = choice
-                    .message
-                    .tool_calls
-                    .map(|calls| calls.into_iter().map(ToolCall::from).collect());
-                Some((content, calls))
-            })
-            .unwrap();
-
-        Ok(Message::assistant(first_choice.0, first_choice.1))
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        stream_chat(Box::pin(async move {
+            let request = self.request(&messages, config).await?;
+            open_stream(&self.client, request).await
+        }))
     }
 }
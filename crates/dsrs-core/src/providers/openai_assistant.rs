@@ -0,0 +1,278 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+
+use async_openai::error::{ApiError, OpenAIError};
+use async_openai::types::{
+    CreateAssistantRequestArgs, CreateMessageRequestArgs, CreateRunRequestArgs,
+    CreateThreadRequest, MessageContent, MessageRole, RunStatus,
+};
+use async_openai::{Client, config::OpenAIConfig};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Identifier of an assistant created through the Assistants API
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssistantId(pub String);
+
+/// Identifier of a server-side conversation thread
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ThreadId(pub String);
+
+/// Identifier of a message within a thread
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageId(pub String);
+
+/// Provider backed by the OpenAI Assistants API, which keeps conversation state on the server.
+///
+/// The thread methods can be used directly for multi-turn conversations. As a
+/// `CompletionProvider`, every `complete` call is a single-turn interaction on a new thread.
+pub struct OpenAIAssistantProvider {
+    client: Client<OpenAIConfig>,
+    poll_interval: Duration,
+    // Assistants created by `complete`, keyed by (instructions, model)
+    assistants: Mutex<HashMap<(String, String), AssistantId>>,
+}
+
+impl OpenAIAssistantProvider {
+    pub fn new(api_key: String, base_url: Option<String>) -> Self {
+        let config = OpenAIConfig::new().with_api_key(api_key);
+        let config = if let Some(url) = base_url {
+            config.with_api_base(url)
+        } else {
+            config
+        };
+        OpenAIAssistantProvider {
+            client: Client::with_config(config),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            assistants: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// How often `run` checks the run status
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub async fn create_assistant(
+        &self,
+        instructions: &str,
+        model: &str,
+    ) -> Result<AssistantId, ProviderError> {
+        let request = CreateAssistantRequestArgs::default()
+            .instructions(instructions)
+            .model(model)
+            .build()?;
+        let assistant = self.client.assistants().create(request).await?;
+        Ok(AssistantId(assistant.id))
+    }
+
+    pub async fn create_thread(&self) -> Result<ThreadId, ProviderError> {
+        let thread = self
+            .client
+            .threads()
+            .create(CreateThreadRequest::default())
+            .await?;
+        Ok(ThreadId(thread.id))
+    }
+
+    /// Add a user message to a thread
+    pub async fn add_message(
+        &self,
+        thread_id: &ThreadId,
+        content: &str,
+    ) -> Result<MessageId, ProviderError> {
+        self.add_message_with_role(thread_id, MessageRole::User, content)
+            .await
+    }
+
+    async fn add_message_with_role(
+        &self,
+        thread_id: &ThreadId,
+        role: MessageRole,
+        content: &str,
+    ) -> Result<MessageId, ProviderError> {
+        let request = CreateMessageRequestArgs::default()
+            .role(role)
+            .content(content)
+            .build()?;
+        let message = self
+            .client
+            .threads()
+            .messages(&thread_id.0)
+            .create(request)
+            .await?;
+        Ok(MessageId(message.id))
+    }
+
+    /// Run the assistant on a thread, poll until the run finishes and return the
+    /// text of the assistant's last message
+    pub async fn run(
+        &self,
+        assistant_id: &AssistantId,
+        thread_id: &ThreadId,
+    ) -> Result<String, ProviderError> {
+        let (text, _) = self
+            .run_with_config(assistant_id, thread_id, &CompletionConfig::default())
+            .await?;
+        Ok(text)
+    }
+
+    // Runs accept sampling overrides but not stop sequences
+    async fn run_with_config(
+        &self,
+        assistant_id: &AssistantId,
+        thread_id: &ThreadId,
+        config: &CompletionConfig,
+    ) -> Result<(String, Option<UsageStats>), ProviderError> {
+        let threads = self.client.threads();
+        let runs = threads.runs(&thread_id.0);
+        let mut request = CreateRunRequestArgs::default();
+        request.assistant_id(assistant_id.0.clone());
+        if let Some(temperature) = config.temperature {
+            request.temperature(temperature);
+        }
+        if let Some(max_tokens) = config.max_tokens {
+            request.max_completion_tokens(max_tokens);
+        }
+        if let Some(top_p) = config.top_p {
+            request.top_p(top_p);
+        }
+        let mut run = runs.create(request.build()?).await?;
+
+        loop {
+            match run.status {
+                RunStatus::Queued | RunStatus::InProgress | RunStatus::Cancelling => {
+                    tokio::time::sleep(self.poll_interval).await;
+                    run = runs.retrieve(&run.id).await?;
+                }
+                RunStatus::Completed => break,
+                RunStatus::RequiresAction => {
+                    return Err(run_error("run requires tool outputs, which are not supported"));
+                }
+                RunStatus::Expired => return Err(ProviderError::Timeout),
+                status => {
+                    let message = match run.last_error {
+                        Some(error) => error.message,
+                        None => format!("run ended with status {:?}", status),
+                    };
+                    return Err(run_error(message));
+                }
+            }
+        }
+
+        let messages = self
+            .client
+            .threads()
+            .messages(&thread_id.0)
+            .list(&[("order", "desc"), ("limit", "1")])
+            .await?;
+
+        let usage = run
+            .usage
+            .map(|usage| UsageStats::new(usage.prompt_tokens, usage.completion_tokens));
+        let text = messages
+            .data
+            .into_iter()
+            .find(|message| message.role == MessageRole::Assistant)
+            .map(|message| {
+                message
+                    .content
+                    .into_iter()
+                    .filter_map(|content| match content {
+                        MessageContent::Text(text) => Some(text.text.value),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .ok_or_else(|| run_error("run completed without an assistant message"))?;
+        Ok((text, usage))
+    }
+
+    // Reuse one assistant per distinct system prompt and model
+    async fn assistant_for(
+        &self,
+        instructions: String,
+        model: String,
+    ) -> Result<AssistantId, ProviderError> {
+        let mut assistants = self.assistants.lock().await;
+        let key = (instructions, model);
+        if let Some(id) = assistants.get(&key) {
+            return Ok(id.clone());
+        }
+        let id = self.create_assistant(&key.0, &key.1).await?;
+        assistants.insert(key, id.clone());
+        Ok(id)
+    }
+}
+
+fn run_error(message: impl Into<String>) -> ProviderError {
+    ProviderError::OpenAIError(OpenAIError::ApiError(ApiError {
+        message: message.into(),
+        r#type: None,
+        param: None,
+        code: None,
+    }))
+}
+
+// Images are dropped; threads are replayed as text
+fn text_of(content: &ContentTypes) -> &str {
+    content.as_text().unwrap_or_default()
+}
+
+impl CompletionProvider for OpenAIAssistantProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        // System messages become the assistant instructions, the rest is replayed onto the thread
+        let (instructions, turns) = {
+            let guard = messages.read().await;
+            let mut instructions = Vec::new();
+            let mut turns = Vec::new();
+            for message in guard.iter() {
+                match message {
+                    Message::System { content } => instructions.push(text_of(content).to_string()),
+                    Message::User { content } => {
+                        turns.push((MessageRole::User, ContentTypes::join_text(content)))
+                    }
+                    Message::Assistant {
+                        content: Some(content),
+                        ..
+                    } => turns.push((MessageRole::Assistant, text_of(content).to_string())),
+                    Message::Assistant { content: None, .. } => {}
+                    // Threads only hold user and assistant messages
+                    Message::Tool { content, .. } => {
+                        turns.push((MessageRole::User, text_of(content).to_string()))
+                    }
+                }
+            }
+            (instructions.join("\n\n"), turns)
+        };
+
+        let assistant_id = self
+            .assistant_for(instructions, config.model.clone())
+            .await?;
+        let thread_id = self.create_thread().await?;
+        for (role, content) in turns {
+            self.add_message_with_role(&thread_id, role, &content)
+                .await?;
+        }
+
+        let (text, usage) = self
+            .run_with_config(&assistant_id, &thread_id, &config)
+            .await?;
+        Ok(
+            CompletionResponse::new(Message::assistant(Some(text), None), FinishReason::Stop)
+                .with_usage(usage),
+        )
+    }
+}
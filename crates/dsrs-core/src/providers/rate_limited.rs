@@ -0,0 +1,226 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+use super::streaming::CompletionStream;
+use crate::adapters::traits::count_message_tokens;
+
+use futures::{StreamExt, stream};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::Instant;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+// Starts full and refills continuously, so the limit allows short bursts
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_minute: u32) -> Self {
+        TokenBucket {
+            capacity: per_minute as f64,
+            available: per_minute as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.available =
+            (self.available + elapsed * self.capacity / WINDOW.as_secs_f64()).min(self.capacity);
+        self.refilled_at = now;
+    }
+
+    // Settle the difference between an estimate and the actual amount; the balance may go
+    // negative, which holds back the next requests until it is repaid
+    fn correct(&mut self, estimated: f64, actual: f64) {
+        self.refill();
+        self.available = (self.available + estimated - actual).min(self.capacity);
+    }
+
+    // How long until `amount` is available, after taking it if it already is
+    fn try_take(&mut self, amount: f64) -> Option<Duration> {
+        self.refill();
+        // Requests larger than the whole bucket only wait for it to fill up
+        let amount = amount.min(self.capacity);
+        if self.available >= amount {
+            self.available -= amount;
+            return None;
+        }
+        let missing = amount - self.available;
+        Some(WINDOW.mul_f64(missing / self.capacity))
+    }
+}
+
+// Sleeps until the bucket can cover `amount`; waiters are served in order since the
+// lock is held while sleeping
+async fn take(bucket: &Mutex<TokenBucket>, amount: f64) {
+    let mut bucket = bucket.lock().await;
+    while let Some(wait) = bucket.try_take(amount) {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Wraps a provider and holds requests back to stay under a requests-per-minute and,
+/// optionally, a tokens-per-minute limit
+pub struct RateLimitedProvider<P: CompletionProvider> {
+    inner: P,
+    requests: Mutex<TokenBucket>,
+    tokens: Option<Mutex<TokenBucket>>,
+}
+
+impl<P: CompletionProvider> RateLimitedProvider<P> {
+    pub fn new(inner: P, requests_per_minute: u32, tokens_per_minute: Option<u32>) -> Self {
+        RateLimitedProvider {
+            inner,
+            requests: Mutex::new(TokenBucket::new(requests_per_minute)),
+            tokens: tokens_per_minute.map(|limit| Mutex::new(TokenBucket::new(limit))),
+        }
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    // Returns the tokens taken for the request, to be corrected from its usage
+    async fn acquire(&self, messages: &RwLock<Vec<Message>>) -> f64 {
+        take(&self.requests, 1.0).await;
+        match &self.tokens {
+            Some(tokens) => {
+                let estimate = (count_message_tokens(&messages.read().await, None) as f64)
+                    .min(tokens.lock().await.capacity);
+                take(tokens, estimate).await;
+                estimate
+            }
+            None => 0.0,
+        }
+    }
+}
+
+impl<P: CompletionProvider> CompletionProvider for RateLimitedProvider<P> {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let estimate = self.acquire(&messages).await;
+        let response = self.inner.complete(messages, config).await?;
+        if let (Some(tokens), Some(usage)) = (&self.tokens, response.usage) {
+            tokens
+                .lock()
+                .await
+                .correct(estimate, usage.total_tokens as f64);
+        }
+        Ok(response)
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        let chunks = stream::once(async move {
+            self.acquire(&messages).await;
+            self.inner.stream(messages, config)
+        });
+        Box::pin(chunks.flatten())
+    }
+
+    fn max_context_tokens(&self) -> Option<u32> {
+        self.inner.max_context_tokens()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EchoProvider {
+        usage: Option<UsageStats>,
+    }
+
+    const NO_USAGE: EchoProvider = EchoProvider { usage: None };
+
+    impl CompletionProvider for EchoProvider {
+        async fn complete(
+            &self,
+            _messages: Arc<RwLock<Vec<Message>>>,
+            _config: CompletionConfig,
+        ) -> Result<CompletionResponse, ProviderError> {
+            Ok(
+                CompletionResponse::new(Message::assistant(Some("ok"), None), FinishReason::Stop)
+                    .with_usage(self.usage),
+            )
+        }
+    }
+
+    fn messages(text: &str) -> Arc<RwLock<Vec<Message>>> {
+        Arc::new(RwLock::new(vec![Message::user(text)]))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_wait_for_bucket_to_refill() {
+        let provider = RateLimitedProvider::new(NO_USAGE, 2, None);
+        let started = Instant::now();
+
+        for _ in 0..3 {
+            provider
+                .complete(messages("Hi"), CompletionConfig::default())
+                .await
+                .unwrap();
+        }
+
+        // The first two fit in the bucket, the third waits for one request to refill
+        assert_eq!(started.elapsed(), Duration::from_secs(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_limit_uses_word_estimate() {
+        let provider = RateLimitedProvider::new(NO_USAGE, 100, Some(10));
+        let started = Instant::now();
+
+        let ten_words = "one two three four five six seven eight nine ten";
+        provider
+            .complete(messages(ten_words), CompletionConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        provider
+            .complete(
+                messages("five words in this one"),
+                CompletionConfig::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_estimate_corrected_from_usage() {
+        // Each request is estimated at one token but reports using ten
+        let provider = RateLimitedProvider::new(
+            EchoProvider {
+                usage: Some(UsageStats::new(5, 5)),
+            },
+            100,
+            Some(10),
+        );
+        let started = Instant::now();
+
+        for _ in 0..2 {
+            provider
+                .complete(messages("Hi"), CompletionConfig::default())
+                .await
+                .unwrap();
+        }
+
+        // The first request used the whole minute's budget, so the second waits for one token
+        assert_eq!(started.elapsed(), Duration::from_secs(6));
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use super::models::CompletionConfig;
+
+/// Which provider serves a model
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProviderType {
+    OpenAI,
+    AzureOpenAI,
+    Anthropic,
+    Cohere,
+    Mistral,
+    Gemini,
+    Groq,
+    Ollama,
+    HuggingFace,
+}
+
+/// What a model is called by its provider and what it can do
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelInfo {
+    /// The model string sent to the provider
+    pub id: String,
+    pub provider_type: ProviderType,
+    /// Prompt plus completion tokens the model accepts
+    pub context_length: u32,
+    pub supports_tools: bool,
+    pub supports_vision: bool,
+    /// USD per 1,000 prompt tokens; `None` for local models
+    pub cost_per_1k_tokens: Option<f64>,
+}
+
+impl ModelInfo {
+    pub fn new(id: impl Into<String>, provider_type: ProviderType, context_length: u32) -> Self {
+        ModelInfo {
+            id: id.into(),
+            provider_type,
+            context_length,
+            supports_tools: false,
+            supports_vision: false,
+            cost_per_1k_tokens: None,
+        }
+    }
+
+    pub fn with_tools(mut self) -> Self {
+        self.supports_tools = true;
+        self
+    }
+
+    pub fn with_vision(mut self) -> Self {
+        self.supports_vision = true;
+        self
+    }
+
+    pub fn with_cost_per_1k_tokens(mut self, cost: f64) -> Self {
+        self.cost_per_1k_tokens = Some(cost);
+        self
+    }
+}
+
+/// Friendly model names mapped to their `ModelInfo`. `ModelRegistry::default()` knows a
+/// set of well-known models; `ModelRegistry::new()` starts empty
+#[derive(Clone, Debug)]
+pub struct ModelRegistry {
+    models: HashMap<String, ModelInfo>,
+}
+
+impl ModelRegistry {
+    pub fn new() -> Self {
+        ModelRegistry {
+            models: HashMap::new(),
+        }
+    }
+
+    /// Add a model, replacing any registered under the same name
+    pub fn register(&mut self, name: impl Into<String>, model_info: ModelInfo) -> &mut Self {
+        self.models.insert(name.into(), model_info);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ModelInfo> {
+        self.models.get(name)
+    }
+
+    /// A config with the model's provider id filled in
+    pub fn config_for(&self, name: &str) -> Option<CompletionConfig> {
+        self.get(name)
+            .map(|info| CompletionConfig::builder().model(&info.id).build())
+    }
+
+    /// Registered names, in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.models.keys().map(String::as_str)
+    }
+}
+
+impl Default for ModelRegistry {
+    fn default() -> Self {
+        use ProviderType::*;
+
+        let mut registry = ModelRegistry::new();
+        registry
+            .register(
+                "gpt-4o",
+                ModelInfo::new("gpt-4o", OpenAI, 128_000)
+                    .with_tools()
+                    .with_vision()
+                    .with_cost_per_1k_tokens(0.0025),
+            )
+            .register(
+                "gpt-4o-mini",
+                ModelInfo::new("gpt-4o-mini", OpenAI, 128_000)
+                    .with_tools()
+                    .with_vision()
+                    .with_cost_per_1k_tokens(0.00015),
+            )
+            .register(
+                "claude-3-5-sonnet-20241022",
+                ModelInfo::new("claude-3-5-sonnet-20241022", Anthropic, 200_000)
+                    .with_tools()
+                    .with_vision()
+                    .with_cost_per_1k_tokens(0.003),
+            )
+            .register(
+                "claude-3-5-haiku-20241022",
+                ModelInfo::new("claude-3-5-haiku-20241022", Anthropic, 200_000)
+                    .with_tools()
+                    .with_cost_per_1k_tokens(0.0008),
+            )
+            .register(
+                "command-r-plus",
+                ModelInfo::new("command-r-plus", Cohere, 128_000)
+                    .with_tools()
+                    .with_cost_per_1k_tokens(0.0025),
+            )
+            .register(
+                "mistral-large-latest",
+                ModelInfo::new("mistral-large-latest", Mistral, 128_000)
+                    .with_tools()
+                    .with_cost_per_1k_tokens(0.002),
+            )
+            .register(
+                "gemini-2.5-pro",
+                ModelInfo::new("gemini-2.5-pro", Gemini, 1_048_576)
+                    .with_tools()
+                    .with_vision()
+                    .with_cost_per_1k_tokens(0.00125),
+            )
+            .register(
+                "gemini-2.5-flash",
+                ModelInfo::new("gemini-2.5-flash", Gemini, 1_048_576)
+                    .with_tools()
+                    .with_vision()
+                    .with_cost_per_1k_tokens(0.0003),
+            )
+            // Groq's name for the model
+            .register(
+                "llama-3.1-8b-instruct",
+                ModelInfo::new("llama-3.1-8b-instant", Groq, 131_072)
+                    .with_tools()
+                    .with_cost_per_1k_tokens(0.00005),
+            )
+            .register(
+                "llama3.1",
+                ModelInfo::new("llama3.1", Ollama, 131_072).with_tools(),
+            );
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_for_uses_provider_model_id() {
+        let registry = ModelRegistry::default();
+
+        let config = registry.config_for("llama-3.1-8b-instruct").unwrap();
+
+        assert_eq!(config.model, "llama-3.1-8b-instant");
+        assert_eq!(
+            registry.get("llama-3.1-8b-instruct").unwrap().provider_type,
+            ProviderType::Groq
+        );
+        assert!(registry.config_for("gpt-2").is_none());
+    }
+
+    #[test]
+    fn test_register_custom_model() {
+        let mut registry = ModelRegistry::new();
+        registry.register(
+            "house-model",
+            ModelInfo::new("org/house-model-v2", ProviderType::HuggingFace, 8_192),
+        );
+
+        let info = registry.get("house-model").unwrap();
+
+        assert_eq!(info.context_length, 8_192);
+        assert!(!info.supports_tools && info.cost_per_1k_tokens.is_none());
+        assert_eq!(
+            registry.config_for("house-model").unwrap().model,
+            "org/house-model-v2"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use super::CompletionProvider;
+use super::ErasedCompletionProvider;
+use super::ProviderError;
+use super::models::*;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+/// A recorded request/response pair, one per line of a cassette file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CassetteEntry {
+    /// Hex blake3 hash of `messages` and `config`
+    pub hash: String,
+    pub messages: Vec<Message>,
+    pub config: CompletionConfig,
+    pub response: CompletionResponse,
+}
+
+enum Mode {
+    Record {
+        inner: Box<dyn ErasedCompletionProvider>,
+        file: Mutex<File>,
+    },
+    // Responses recorded for the same request are returned in order, the last one repeating
+    Replay {
+        responses: Mutex<HashMap<String, VecDeque<CompletionResponse>>>,
+    },
+}
+
+/// Records real responses to a JSONL cassette once, then replays them without network
+/// calls, like VCR cassettes
+pub struct ReplayProvider {
+    mode: Mode,
+}
+
+impl ReplayProvider {
+    /// Forward requests to `inner` and write each exchange to `path`, replacing its contents
+    pub fn record(
+        inner: impl CompletionProvider + 'static,
+        path: impl AsRef<Path>,
+    ) -> std::io::Result<Self> {
+        Ok(ReplayProvider {
+            mode: Mode::Record {
+                inner: Box::new(inner),
+                file: Mutex::new(File::create(path)?),
+            },
+        })
+    }
+
+    /// Answer from the cassette at `path`; requests it has no recording for fail
+    pub fn replay(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut responses: HashMap<String, VecDeque<CompletionResponse>> = HashMap::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: CassetteEntry = serde_json::from_str(&line)?;
+            responses
+                .entry(entry.hash)
+                .or_default()
+                .push_back(entry.response);
+        }
+
+        Ok(ReplayProvider {
+            mode: Mode::Replay {
+                responses: Mutex::new(responses),
+            },
+        })
+    }
+}
+
+/// Hash identifying a request in a cassette
+pub fn request_hash(messages: &[Message], config: &CompletionConfig) -> String {
+    let bytes = serde_json::to_vec(&(messages, config)).unwrap_or_default();
+    blake3::hash(&bytes).to_hex().to_string()
+}
+
+impl CompletionProvider for ReplayProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let conversation = messages.read().await.clone();
+        let hash = request_hash(&conversation, &config);
+
+        match &self.mode {
+            Mode::Record { inner, file } => {
+                let response = inner.complete_erased(messages, config.clone()).await?;
+                let entry = CassetteEntry {
+                    hash,
+                    messages: conversation,
+                    config,
+                    response: response.clone(),
+                };
+                let line = serde_json::to_string(&entry).map_err(std::io::Error::from)?;
+                writeln!(file.lock().unwrap(), "{}", line)?;
+                Ok(response)
+            }
+            Mode::Replay { responses } => {
+                let mut responses = responses.lock().unwrap();
+                let recorded = responses
+                    .get_mut(&hash)
+                    .ok_or_else(|| ProviderError::ApiError {
+                        status: 404,
+                        message: format!("No recorded response for request {}", hash),
+                    })?;
+                let response = if recorded.len() > 1 {
+                    recorded.pop_front()
+                } else {
+                    recorded.front().cloned()
+                };
+                Ok(response.expect("cassette entries always hold a response"))
+            }
+        }
+    }
+
+    fn max_context_tokens(&self) -> Option<u32> {
+        match &self.mode {
+            Mode::Record { inner, .. } => inner.max_context_tokens_erased(),
+            Mode::Replay { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::MockProvider;
+
+    fn conversation(text: &str) -> Arc<RwLock<Vec<Message>>> {
+        Arc::new(RwLock::new(vec![Message::user(text)]))
+    }
+
+    fn cassette(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("dsrs-{}-{}.jsonl", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn test_replays_recorded_responses() {
+        let path = cassette("replay");
+        let recorder =
+            ReplayProvider::record(MockProvider::with_texts(["Paris", "Rome", "Lisbon"]), &path)
+                .unwrap();
+        for question in ["France?", "Italy?", "Italy?"] {
+            recorder
+                .complete(conversation(question), CompletionConfig::default())
+                .await
+                .unwrap();
+        }
+        drop(recorder);
+
+        let replay = ReplayProvider::replay(&path).unwrap();
+        let answer = |response: CompletionResponse| response.message;
+        let config = CompletionConfig::default;
+
+        let france = replay
+            .complete(conversation("France?"), config())
+            .await
+            .unwrap();
+        assert_eq!(answer(france), Message::assistant(Some("Paris"), None));
+        // Repeated requests replay in recorded order, then the last answer repeats
+        for expected in ["Rome", "Lisbon", "Lisbon"] {
+            let italy = replay
+                .complete(conversation("Italy?"), config())
+                .await
+                .unwrap();
+            assert_eq!(answer(italy), Message::assistant(Some(expected), None));
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unrecorded_request_fails() {
+        let path = cassette("unrecorded");
+        let recorder = ReplayProvider::record(MockProvider::with_texts(["Paris"]), &path).unwrap();
+        recorder
+            .complete(conversation("France?"), CompletionConfig::default())
+            .await
+            .unwrap();
+
+        let replay = ReplayProvider::replay(&path).unwrap();
+        let other_model = CompletionConfig::default().with_model("other-model");
+        let result = replay.complete(conversation("France?"), other_model).await;
+
+        assert!(matches!(
+            result,
+            Err(ProviderError::ApiError { status: 404, .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
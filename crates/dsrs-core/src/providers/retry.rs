@@ -0,0 +1,185 @@
+use std::future::Future;
+use std::time::Duration;
+
+use super::ProviderError;
+
+/// Exponential backoff for retrying transient provider errors
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryConfig {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: usize,
+    pub initial_delay: Duration,
+    /// Each delay is the previous one times this factor
+    pub backoff_factor: f64,
+    pub max_delay: Duration,
+    /// Pick each delay uniformly between zero and the backoff delay, so clients that
+    /// failed together don't retry together
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(500),
+            backoff_factor: 2.0,
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// No retries at all
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before retry number `retry` (starting at 0), without jitter
+    pub fn delay_for(&self, retry: usize) -> Duration {
+        let factor = self
+            .backoff_factor
+            .max(1.0)
+            .powi(retry.min(i32::MAX as usize) as i32);
+        let delay = self.initial_delay.as_secs_f64() * factor;
+        Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()))
+    }
+
+    // The delay to actually wait, honouring a rate limit's `retry_after` over the backoff
+    fn wait_for(&self, retry: usize, error: &ProviderError) -> Duration {
+        if let ProviderError::RateLimit {
+            retry_after: Some(retry_after),
+        } = error
+        {
+            return (*retry_after).min(self.max_delay);
+        }
+        let delay = self.delay_for(retry);
+        if self.jitter {
+            delay.mul_f64(rand::random::<f64>())
+        } else {
+            delay
+        }
+    }
+}
+
+/// Run `operation`, retrying retryable errors (see `ProviderError::is_retryable`) with
+/// exponential backoff. Returns the last error once the retries run out, or the first
+/// non-retryable one
+pub async fn retry_with_backoff<T, F, Fut>(
+    config: &RetryConfig,
+    mut operation: F,
+) -> Result<T, ProviderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ProviderError>>,
+{
+    let mut retry = 0;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) if retry < config.max_retries && error.is_retryable() => {
+                let delay = config.wait_for(retry, &error);
+                tracing::warn!(
+                    retry = retry + 1,
+                    delay_ms = delay.as_millis() as u64,
+                    "Retrying after provider error: {}",
+                    error
+                );
+                tokio::time::sleep(delay).await;
+                retry += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            max_retries: 2,
+            initial_delay: Duration::from_millis(100),
+            backoff_factor: 2.0,
+            max_delay: Duration::from_millis(300),
+            jitter: false,
+        }
+    }
+
+    #[test]
+    fn test_delays_grow_up_to_max_delay() {
+        let config = config();
+
+        assert_eq!(config.delay_for(0), Duration::from_millis(100));
+        assert_eq!(config.delay_for(1), Duration::from_millis(200));
+        assert_eq!(config.delay_for(2), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn test_jitter_stays_below_backoff_delay() {
+        let config = RetryConfig {
+            jitter: true,
+            ..config()
+        };
+
+        for _ in 0..20 {
+            assert!(config.wait_for(1, &ProviderError::Timeout) <= Duration::from_millis(200));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_transient_errors_until_success() {
+        let attempts = AtomicUsize::new(0);
+        let started = tokio::time::Instant::now();
+
+        let result = retry_with_backoff(&config(), || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(ProviderError::Timeout),
+                _ => Ok("done"),
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gives_up_after_max_retries() {
+        let attempts = AtomicUsize::new(0);
+
+        let result: Result<(), _> = retry_with_backoff(&config(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(ProviderError::RateLimit {
+                retry_after: Some(Duration::from_secs(1)),
+            })
+        })
+        .await;
+
+        assert!(matches!(result, Err(ProviderError::RateLimit { .. })));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_permanent_errors() {
+        let attempts = AtomicUsize::new(0);
+
+        let result: Result<(), _> = retry_with_backoff(&config(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(ProviderError::ApiError {
+                status: 401,
+                message: "invalid key".to_string(),
+            })
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,137 @@
+use futures::Stream;
+use std::collections::BTreeMap;
+use std::pin::Pin;
+
+use super::ProviderError;
+use super::models::{ContentTypes, Message, ToolCall};
+
+/// A piece of a streamed completion
+#[derive(Clone, Debug, PartialEq)]
+pub enum StreamChunk {
+    /// The next slice of assistant text
+    Text(String),
+    /// A tool call, emitted only once its arguments have been fully received
+    ToolCall(ToolCall),
+}
+
+pub type CompletionStream<'a> =
+    Pin<Box<dyn Stream<Item = Result<StreamChunk, ProviderError>> + Send + 'a>>;
+
+/// Split a complete assistant message into chunks, for providers without native streaming
+pub fn message_to_chunks(message: Message) -> Vec<StreamChunk> {
+    match message {
+        Message::Assistant {
+            content,
+            tool_calls,
+        } => {
+            let text = content
+                .as_ref()
+                .and_then(ContentTypes::as_text)
+                .map(|text| StreamChunk::Text(text.to_string()));
+            text.into_iter()
+                .chain(tool_calls.into_iter().flatten().map(StreamChunk::ToolCall))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[derive(Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Buffers streamed tool call fragments, which arrive keyed by index with the
+/// arguments split across chunks as partial JSON
+#[derive(Default)]
+pub struct ToolCallAccumulator {
+    calls: BTreeMap<u32, PartialToolCall>,
+}
+
+impl ToolCallAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(
+        &mut self,
+        index: u32,
+        id: Option<&str>,
+        name: Option<&str>,
+        arguments: Option<&str>,
+    ) {
+        let call = self.calls.entry(index).or_default();
+        if let Some(id) = id {
+            call.id.push_str(id);
+        }
+        if let Some(name) = name {
+            call.name.push_str(name);
+        }
+        if let Some(arguments) = arguments {
+            call.arguments.push_str(arguments);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// The assembled tool calls in index order
+    pub fn finish(self) -> Vec<ToolCall> {
+        self.calls
+            .into_values()
+            .map(|call| {
+                // Keep the raw string if the arguments never became valid JSON
+                let arguments = match call.arguments.trim() {
+                    "" => serde_json::json!({}),
+                    raw => serde_json::from_str(raw)
+                        .unwrap_or(serde_json::Value::String(call.arguments.clone())),
+                };
+                ToolCall {
+                    id: call.id,
+                    name: call.name,
+                    arguments,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulates_interleaved_tool_call_fragments() {
+        let mut accumulator = ToolCallAccumulator::new();
+        accumulator.push(0, Some("call_1"), Some("search"), Some("{\"q\": "));
+        accumulator.push(1, Some("call_2"), Some("lookup"), None);
+        accumulator.push(0, None, None, Some("\"rust\"}"));
+        accumulator.push(1, None, None, Some("{\"id\": 7}"));
+
+        let calls = accumulator.finish();
+
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].name, "search");
+        assert_eq!(calls[0].arguments, serde_json::json!({"q": "rust"}));
+        assert_eq!(calls[1].arguments, serde_json::json!({"id": 7}));
+    }
+
+    #[test]
+    fn test_message_to_chunks() {
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            name: "search".to_string(),
+            arguments: serde_json::json!({}),
+        };
+        let chunks = message_to_chunks(Message::assistant(Some("Hello"), Some(vec![call.clone()])));
+
+        assert_eq!(
+            chunks,
+            vec![StreamChunk::Text("Hello".to_string()), StreamChunk::ToolCall(call)]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use super::CompletionProvider;
+use super::ProviderError;
+use super::models::*;
+use super::streaming::CompletionStream;
+
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::Instrument;
+use tracing::field::Empty;
+
+/// Wraps a provider and runs each `complete` call in an `llm.complete` span, with the
+/// model, provider, token usage and finish reason as `llm.*` attributes. Export the spans
+/// with any `tracing` subscriber, such as `tracing-opentelemetry` for Jaeger
+pub struct TracingProvider<P: CompletionProvider> {
+    inner: P,
+    provider_name: String,
+}
+
+impl<P: CompletionProvider> TracingProvider<P> {
+    /// `llm.provider` defaults to the wrapped type's name, e.g. `OpenAIProvider`
+    pub fn new(inner: P) -> Self {
+        TracingProvider {
+            inner,
+            provider_name: short_type_name::<P>().to_string(),
+        }
+    }
+
+    pub fn with_provider_name(mut self, provider_name: impl Into<String>) -> Self {
+        self.provider_name = provider_name.into();
+        self
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+}
+
+impl<P: CompletionProvider> CompletionProvider for TracingProvider<P> {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let span = tracing::info_span!(
+            "llm.complete",
+            llm.model = %config.model,
+            llm.provider = %self.provider_name,
+            llm.prompt_tokens = Empty,
+            llm.completion_tokens = Empty,
+            llm.finish_reason = Empty,
+            otel.status_code = Empty,
+            error = Empty,
+        );
+        let result = self
+            .inner
+            .complete(messages, config)
+            .instrument(span.clone())
+            .await;
+
+        match &result {
+            Ok(response) => {
+                if let Some(usage) = response.usage {
+                    span.record("llm.prompt_tokens", usage.prompt_tokens);
+                    span.record("llm.completion_tokens", usage.completion_tokens);
+                }
+                span.record(
+                    "llm.finish_reason",
+                    tracing::field::debug(response.finish_reason),
+                );
+            }
+            Err(e) => {
+                span.record("otel.status_code", "ERROR");
+                span.record("error", tracing::field::display(e));
+            }
+        }
+        result
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        self.inner.stream(messages, config)
+    }
+
+    fn max_context_tokens(&self) -> Option<u32> {
+        self.inner.max_context_tokens()
+    }
+}
+
+// `a::b::Provider<c::D>` -> `Provider`
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    struct UsageProvider;
+
+    impl CompletionProvider for UsageProvider {
+        async fn complete(
+            &self,
+            _messages: Arc<RwLock<Vec<Message>>>,
+            _config: CompletionConfig,
+        ) -> Result<CompletionResponse, ProviderError> {
+            Ok(
+                CompletionResponse::new(Message::assistant(Some("Hi"), None), FinishReason::Stop)
+                    .with_usage(Some(UsageStats::new(12, 3))),
+            )
+        }
+    }
+
+    // Collects the fields of every span, as `name=value` strings
+    #[derive(Clone, Default)]
+    struct FieldRecorder {
+        fields: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Visit for FieldRecorder {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.fields
+                .lock()
+                .unwrap()
+                .push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for FieldRecorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            span.record(&mut self.clone());
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, values: &Record<'_>) {
+            values.record(&mut self.clone());
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+        fn event(&self, _event: &Event<'_>) {}
+        fn enter(&self, _span: &Id) {}
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_span_records_model_usage_and_finish_reason() {
+        let recorder = FieldRecorder::default();
+        let _guard = tracing::subscriber::set_default(recorder.clone());
+        let provider = TracingProvider::new(UsageProvider);
+        let config = CompletionConfig::default().with_model("gpt-test");
+
+        provider
+            .complete(Arc::new(RwLock::new(vec![Message::user("Hello")])), config)
+            .await
+            .unwrap();
+
+        let fields = recorder.fields.lock().unwrap().clone();
+        for expected in [
+            "llm.model=gpt-test",
+            "llm.provider=UsageProvider",
+            "llm.prompt_tokens=12",
+            "llm.completion_tokens=3",
+            "llm.finish_reason=Stop",
+        ] {
+            assert!(fields.iter().any(|field| field == expected), "{:?}", fields);
+        }
+    }
+}
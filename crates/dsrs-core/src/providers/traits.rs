@@ -1,6 +1,9 @@
+use futures::future::{BoxFuture, join_all};
+use futures::{StreamExt, stream};
 use std::future::Future;
 
-use super::{CompletionConfig, Message, ProviderError};
+use super::streaming::{CompletionStream, message_to_chunks};
+use super::{CompletionConfig, CompletionResponse, Message, ProviderError};
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -10,5 +13,127 @@ pub trait CompletionProvider: Send + Sync {
         &self,
         messages: Arc<RwLock<Vec<Message>>>,
         config: CompletionConfig,
-    ) -> impl Future<Output = Result<Message, ProviderError>> + Send;
+    ) -> impl Future<Output = Result<CompletionResponse, ProviderError>> + Send;
+
+    /// Stream the completion as it is generated. The default waits for `complete` and
+    /// yields the whole response at once
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        Box::pin(
+            stream::once(self.complete(messages, config))
+                .flat_map(|result| {
+                    let chunks = match result {
+                        Ok(response) => message_to_chunks(response.message)
+                            .into_iter()
+                            .map(Ok)
+                            .collect(),
+                        Err(e) => vec![Err(e)],
+                    };
+                    stream::iter(chunks)
+                }),
+        )
+    }
+
+    /// Complete several independent requests, returning the results in request order. The
+    /// default runs `complete` for all of them concurrently; providers with a batch
+    /// endpoint can send them together instead
+    fn complete_many(
+        &self,
+        requests: Vec<(Arc<RwLock<Vec<Message>>>, CompletionConfig)>,
+    ) -> impl Future<Output = Vec<Result<CompletionResponse, ProviderError>>> + Send {
+        join_all(
+            requests
+                .into_iter()
+                .map(|(messages, config)| self.complete(messages, config)),
+        )
+    }
+
+    /// Largest `max_tokens` the model accepts, if known
+    fn max_context_tokens(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Object-safe counterpart of `CompletionProvider`, for holding different providers
+/// behind `Box<dyn ErasedCompletionProvider>`
+pub trait ErasedCompletionProvider: Send + Sync {
+    fn complete_erased<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> BoxFuture<'a, Result<CompletionResponse, ProviderError>>;
+
+    fn stream_erased<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a>;
+
+    fn complete_many_erased<'a>(
+        &'a self,
+        requests: Vec<(Arc<RwLock<Vec<Message>>>, CompletionConfig)>,
+    ) -> BoxFuture<'a, Vec<Result<CompletionResponse, ProviderError>>>;
+
+    fn max_context_tokens_erased(&self) -> Option<u32>;
+}
+
+impl<P: CompletionProvider> ErasedCompletionProvider for P {
+    fn complete_erased<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> BoxFuture<'a, Result<CompletionResponse, ProviderError>> {
+        Box::pin(self.complete(messages, config))
+    }
+
+    fn stream_erased<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        self.stream(messages, config)
+    }
+
+    fn complete_many_erased<'a>(
+        &'a self,
+        requests: Vec<(Arc<RwLock<Vec<Message>>>, CompletionConfig)>,
+    ) -> BoxFuture<'a, Vec<Result<CompletionResponse, ProviderError>>> {
+        Box::pin(self.complete_many(requests))
+    }
+
+    fn max_context_tokens_erased(&self) -> Option<u32> {
+        self.max_context_tokens()
+    }
+}
+
+impl CompletionProvider for Box<dyn ErasedCompletionProvider> {
+    fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> impl Future<Output = Result<CompletionResponse, ProviderError>> + Send {
+        self.as_ref().complete_erased(messages, config)
+    }
+
+    fn stream<'a>(
+        &'a self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        self.as_ref().stream_erased(messages, config)
+    }
+
+    fn complete_many(
+        &self,
+        requests: Vec<(Arc<RwLock<Vec<Message>>>, CompletionConfig)>,
+    ) -> impl Future<Output = Vec<Result<CompletionResponse, ProviderError>>> + Send {
+        self.as_ref().complete_many_erased(requests)
+    }
+
+    fn max_context_tokens(&self) -> Option<u32> {
+        self.as_ref().max_context_tokens_erased()
+    }
 }
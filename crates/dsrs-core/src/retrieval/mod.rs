@@ -0,0 +1,191 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+
+use crate::modules::rag::{Document, Retriever};
+use crate::predict::demo_selector::cosine_similarity;
+use crate::providers::embedding::EmbeddingProvider;
+
+/// Documents indexed by their embeddings, searched by similarity to a query embedding
+#[async_trait]
+pub trait VectorStore: Send + Sync {
+    async fn add(&mut self, doc: Document, embedding: Vec<f32>);
+
+    /// At most `top_k` documents with their similarity to the query, most similar first
+    async fn search(&self, query_embedding: &[f32], top_k: usize) -> Vec<(Document, f32)>;
+
+    /// Number of documents in the store
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Embed `texts` with `provider` and add them as documents, with their position in the
+    /// store as their ids
+    async fn add_texts(
+        &mut self,
+        texts: Vec<String>,
+        provider: &dyn EmbeddingProvider,
+    ) -> Result<()> {
+        let embeddings = provider.embed(texts.clone()).await?;
+        if embeddings.len() != texts.len() {
+            return Err(anyhow!(
+                "Expected {} embeddings, got {}",
+                texts.len(),
+                embeddings.len()
+            ));
+        }
+        for (text, embedding) in texts.into_iter().zip(embeddings) {
+            let id = self.len().to_string();
+            self.add(Document::new(id, text), embedding).await;
+        }
+        Ok(())
+    }
+}
+
+/// Brute-force cosine similarity search over documents held in memory
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryVectorStore {
+    entries: Vec<(Document, Vec<f32>)>,
+}
+
+impl InMemoryVectorStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn add(&mut self, doc: Document, embedding: Vec<f32>) {
+        self.entries.push((doc, embedding));
+    }
+
+    async fn search(&self, query_embedding: &[f32], top_k: usize) -> Vec<(Document, f32)> {
+        let mut scored: Vec<(&Document, f32)> = self
+            .entries
+            .iter()
+            .map(|(doc, embedding)| (doc, cosine_similarity(query_embedding, embedding)))
+            .collect();
+        // Stable sort, so ties keep insertion order
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored
+            .into_iter()
+            .take(top_k)
+            .map(|(doc, score)| (doc.clone(), score))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// A `Retriever` embedding queries with `E` and looking them up in `V`. Documents come back
+/// with their similarity as their score
+pub struct EmbeddingRetriever<E: EmbeddingProvider, V: VectorStore> {
+    embedder: E,
+    store: V,
+}
+
+impl<E: EmbeddingProvider, V: VectorStore> EmbeddingRetriever<E, V> {
+    pub fn new(embedder: E, store: V) -> Self {
+        EmbeddingRetriever { embedder, store }
+    }
+
+    pub fn embedder(&self) -> &E {
+        &self.embedder
+    }
+
+    pub fn store(&self) -> &V {
+        &self.store
+    }
+
+    pub fn store_mut(&mut self) -> &mut V {
+        &mut self.store
+    }
+
+    /// Embed `texts` with the retriever's embedder and add them to its store
+    pub async fn add_texts(&mut self, texts: Vec<String>) -> Result<()> {
+        self.store.add_texts(texts, &self.embedder).await
+    }
+}
+
+#[async_trait]
+impl<E: EmbeddingProvider, V: VectorStore> Retriever for EmbeddingRetriever<E, V> {
+    async fn retrieve(&self, query: &str, top_k: usize) -> Result<Vec<Document>> {
+        let query = self
+            .embedder
+            .embed(vec![query.to_string()])
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Embedding provider returned no embedding for the query"))?;
+        Ok(self
+            .store
+            .search(&query, top_k)
+            .await
+            .into_iter()
+            .map(|(doc, score)| doc.with_score(score))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ProviderError;
+
+    // Embeds texts by whether they mention cats, dogs or fish
+    struct TopicEmbedder;
+
+    #[async_trait]
+    impl EmbeddingProvider for TopicEmbedder {
+        async fn embed(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, ProviderError> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    ["cat", "dog", "fish"]
+                        .iter()
+                        .map(|topic| if text.contains(topic) { 1.0 } else { 0.0 })
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store_ranks_by_cosine_similarity() {
+        let mut store = InMemoryVectorStore::new();
+        store.add(Document::new("a", "cats"), vec![1.0, 0.0]).await;
+        store.add(Document::new("b", "both"), vec![1.0, 1.0]).await;
+        store.add(Document::new("c", "dogs"), vec![0.0, 1.0]).await;
+
+        let results = store.search(&[1.0, 0.1], 2).await;
+
+        let ids: Vec<&str> = results.iter().map(|(doc, _)| doc.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert!(results[0].1 > results[1].1);
+        assert_eq!(store.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_embedding_retriever_finds_added_texts() {
+        let mut retriever = EmbeddingRetriever::new(TopicEmbedder, InMemoryVectorStore::new());
+        retriever
+            .add_texts(vec![
+                "a dog park".to_string(),
+                "a cat cafe".to_string(),
+                "a fish market".to_string(),
+            ])
+            .await
+            .unwrap();
+
+        let documents = retriever.retrieve("where can my cat go", 1).await.unwrap();
+
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].id, "1");
+        assert_eq!(documents[0].text, "a cat cafe");
+        assert_eq!(documents[0].score, Some(1.0));
+    }
+}
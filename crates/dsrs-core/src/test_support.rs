@@ -0,0 +1,195 @@
+//! Signature and module fixtures shared by the unit tests
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::primatives::{Module, Signature, SignatureSchema};
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct TextInputs {
+    pub text: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct TextOutputs {
+    pub text: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize)]
+pub(crate) struct SummaryOutputs {
+    pub summary: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize)]
+pub(crate) struct LengthOutputs {
+    pub length: usize,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+pub(crate) struct Number {
+    pub value: i64,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct CapitalInputs {
+    pub country: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct CapitalOutputs {
+    pub capital: String,
+    pub continent: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+pub(crate) struct QaInputs {
+    pub question: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize)]
+pub(crate) struct QaOutputs {
+    pub answer: String,
+    pub year: u32,
+}
+
+#[derive(Signature)]
+#[signature(name = "Echo", desc = "", inputs = TextInputs, outputs = TextOutputs)]
+pub(crate) struct EchoSig {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+#[derive(Signature)]
+#[signature(name = "Summary", desc = "", inputs = TextInputs, outputs = SummaryOutputs)]
+pub(crate) struct SummarySig {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+#[derive(Signature)]
+#[signature(name = "Length", desc = "", inputs = TextInputs, outputs = LengthOutputs)]
+pub(crate) struct LengthSig {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+#[derive(Signature)]
+#[signature(name = "Number", desc = "", inputs = Number, outputs = Number)]
+pub(crate) struct NumberSig {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+#[derive(Signature)]
+#[signature(name = "Capital", desc = "", inputs = CapitalInputs, outputs = CapitalOutputs)]
+pub(crate) struct CapitalSignature {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+#[derive(Signature)]
+#[signature(name = "QA", desc = "", inputs = QaInputs, outputs = QaOutputs)]
+pub(crate) struct QaSignature {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+// Compiles only if `value` can cross threads, e.g. into `tokio::spawn`
+pub(crate) fn assert_send<T: Send>(_value: T) {}
+
+pub(crate) fn text(text: &str) -> TextInputs {
+    TextInputs {
+        text: text.to_string(),
+    }
+}
+
+/// Echoes its input and counts the calls
+#[derive(Default)]
+pub(crate) struct CountingEcho {
+    pub calls: AtomicUsize,
+}
+
+#[async_trait]
+impl Module for CountingEcho {
+    type Sig = EchoSig;
+
+    async fn aforward(&self, inputs: TextInputs) -> Result<TextOutputs> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(TextOutputs { text: inputs.text })
+    }
+}
+
+/// Keeps the first two words
+pub(crate) struct Summarizer;
+
+#[async_trait]
+impl Module for Summarizer {
+    type Sig = SummarySig;
+
+    async fn aforward(&self, inputs: TextInputs) -> Result<SummaryOutputs> {
+        Ok(SummaryOutputs {
+            summary: inputs
+                .text
+                .split_whitespace()
+                .take(2)
+                .collect::<Vec<_>>()
+                .join(" "),
+        })
+    }
+}
+
+/// Counts the bytes
+pub(crate) struct Measurer;
+
+#[async_trait]
+impl Module for Measurer {
+    type Sig = LengthSig;
+
+    async fn aforward(&self, inputs: TextInputs) -> Result<LengthOutputs> {
+        Ok(LengthOutputs {
+            length: inputs.text.len(),
+        })
+    }
+}
+
+/// Applies `op` and records its name when run
+pub(crate) struct Step {
+    pub name: &'static str,
+    pub op: fn(i64) -> i64,
+    pub log: Arc<Mutex<Vec<&'static str>>>,
+}
+
+#[async_trait]
+impl Module for Step {
+    type Sig = NumberSig;
+
+    async fn aforward(&self, inputs: Number) -> Result<Number> {
+        self.log.lock().unwrap().push(self.name);
+        Ok(Number {
+            value: (self.op)(inputs.value),
+        })
+    }
+}
+
+/// Knows two capitals, gets one continent wrong and fails on anything else
+pub(crate) struct LookupModule;
+
+#[async_trait]
+impl Module for LookupModule {
+    type Sig = CapitalSignature;
+
+    async fn aforward(&self, inputs: CapitalInputs) -> Result<CapitalOutputs> {
+        let (capital, continent) = match inputs.country.as_str() {
+            "France" => ("Paris", "Europe"),
+            "Japan" => ("Tokyo", "Europe"),
+            country => return Err(anyhow!("Unknown country {}", country)),
+        };
+        Ok(CapitalOutputs {
+            capital: capital.to_string(),
+            continent: continent.to_string(),
+        })
+    }
+}
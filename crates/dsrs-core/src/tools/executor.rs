@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use std::fmt;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::providers::models::ToolCall;
+
+/// Broad category of a tool failure, shown to the model alongside the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolErrorKind {
+    NotFound,
+    InvalidArguments,
+    ExecutionFailed,
+}
+
+impl fmt::Display for ToolErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            ToolErrorKind::NotFound => "not_found",
+            ToolErrorKind::InvalidArguments => "invalid_arguments",
+            ToolErrorKind::ExecutionFailed => "execution_failed",
+        };
+        f.write_str(kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{kind}: {message}")]
+pub struct ToolError {
+    pub kind: ToolErrorKind,
+    pub message: String,
+}
+
+impl ToolError {
+    pub fn new(kind: ToolErrorKind, message: impl Into<String>) -> Self {
+        ToolError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// Runs tool calls requested by the model
+#[async_trait]
+pub trait ToolExecutor: Send + Sync {
+    async fn execute(&self, call: &ToolCall) -> Result<String, ToolError>;
+}
+
+#[async_trait]
+impl<T: ToolExecutor + ?Sized> ToolExecutor for Arc<T> {
+    async fn execute(&self, call: &ToolCall) -> Result<String, ToolError> {
+        (**self).execute(call).await
+    }
+}
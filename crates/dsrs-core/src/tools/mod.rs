@@ -0,0 +1,17 @@
+pub mod executor;
+pub mod registry;
+pub mod results;
+
+pub use dsrs_macros::tool;
+pub use executor::{ToolError, ToolErrorKind, ToolExecutor};
+pub use registry::FunctionRegistry;
+pub use results::{ToolExecutionResult, ToolExecutionResults};
+
+/// `AvailableTool` for an async function taking a single argument, e.g.
+/// `tool_from_fn!(search, my_search_fn)`. The fallback for functions without `#[tool]`
+#[macro_export]
+macro_rules! tool_from_fn {
+    ($name:ident, $function:path) => {
+        $crate::providers::models::AvailableTool::from_fn(stringify!($name), &$function)
+    };
+}
@@ -0,0 +1,196 @@
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use indexmap::IndexMap;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use std::future::Future;
+
+use super::{ToolError, ToolErrorKind, ToolExecutor};
+use crate::providers::models::{AvailableTool, ToolCall};
+
+type Handler =
+    Box<dyn Fn(JsonValue) -> BoxFuture<'static, Result<String, ToolError>> + Send + Sync>;
+
+struct RegisteredFunction {
+    tool: AvailableTool,
+    handler: Handler,
+}
+
+/// Local async functions exposed as tools, executing the calls that name them
+#[derive(Default)]
+pub struct FunctionRegistry {
+    functions: IndexMap<String, RegisteredFunction>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `function` as the tool `name`. Its argument schema comes from `A`, and the
+    /// tool description from `A`'s doc comment. Registering a name again replaces the function
+    pub fn register<A, F, Fut>(&mut self, name: &str, function: F) -> &mut Self
+    where
+        A: DeserializeOwned + JsonSchema,
+        F: Fn(A) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, ToolError>> + Send + 'static,
+    {
+        self.register_tool(AvailableTool::from_fn(name, &function), function)
+    }
+
+    /// Register `function` under a prepared definition, such as one generated by `#[tool]`
+    pub fn register_tool<A, F, Fut>(&mut self, tool: AvailableTool, function: F) -> &mut Self
+    where
+        A: DeserializeOwned,
+        F: Fn(A) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String, ToolError>> + Send + 'static,
+    {
+        let handler = move |arguments| -> BoxFuture<'static, Result<String, ToolError>> {
+            match serde_json::from_value::<A>(arguments) {
+                Ok(args) => Box::pin(function(args)),
+                Err(e) => Box::pin(std::future::ready(Err(ToolError::new(
+                    ToolErrorKind::InvalidArguments,
+                    e.to_string(),
+                )))),
+            }
+        };
+
+        let handler: Handler = Box::new(handler);
+        self.functions
+            .insert(tool.name.clone(), RegisteredFunction { tool, handler });
+        self
+    }
+
+    /// The registered tools, in registration order, to offer the model
+    pub fn tools(&self) -> Vec<AvailableTool> {
+        self.functions
+            .values()
+            .map(|function| function.tool.clone())
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+}
+
+#[async_trait]
+impl ToolExecutor for FunctionRegistry {
+    async fn execute(&self, call: &ToolCall) -> Result<String, ToolError> {
+        let function = self.functions.get(&call.name).ok_or_else(|| {
+            ToolError::new(
+                ToolErrorKind::NotFound,
+                format!("unknown tool {}", call.name),
+            )
+        })?;
+        (function.handler)(call.arguments.clone()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    /// Search the web
+    #[derive(Deserialize, JsonSchema)]
+    struct SearchArgs {
+        /// What to search for
+        query: String,
+    }
+
+    /// Look up a word's definition
+    #[derive(Deserialize, JsonSchema)]
+    struct DefineArgs {
+        word: String,
+    }
+
+    #[crate::tools::tool(description = "Define a word in one sentence")]
+    async fn define(args: DefineArgs) -> Result<String, ToolError> {
+        Ok(format!("{} means something", args.word))
+    }
+
+    async fn lookup_definition(args: DefineArgs) -> Result<String, ToolError> {
+        Ok(args.word)
+    }
+
+    fn registry() -> FunctionRegistry {
+        let mut registry = FunctionRegistry::new();
+        registry.register("search", |args: SearchArgs| async move {
+            Ok(format!("results for {}", args.query))
+        });
+        registry
+    }
+
+    fn call(name: &str, arguments: JsonValue) -> ToolCall {
+        ToolCall {
+            id: "call_1".to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    #[test]
+    fn test_register_generates_available_tool() {
+        let tools = registry().tools();
+
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "search");
+        assert_eq!(tools[0].desc, "Search the web");
+        let schema = tools[0].input_schema_json.as_ref().unwrap();
+        assert_eq!(schema["properties"]["query"]["type"], "string");
+    }
+
+    #[tokio::test]
+    async fn test_execute_calls_registered_function() {
+        let output = registry()
+            .execute(&call("search", serde_json::json!({"query": "rust"})))
+            .await
+            .unwrap();
+
+        assert_eq!(output, "results for rust");
+    }
+
+    #[tokio::test]
+    async fn test_execute_reports_unknown_tools_and_bad_arguments() {
+        let registry = registry();
+
+        let unknown = registry
+            .execute(&call("weather", serde_json::json!({})))
+            .await
+            .unwrap_err();
+        assert_eq!(unknown.kind, ToolErrorKind::NotFound);
+
+        let invalid = registry
+            .execute(&call("search", serde_json::json!({"q": "rust"})))
+            .await
+            .unwrap_err();
+        assert_eq!(invalid.kind, ToolErrorKind::InvalidArguments);
+    }
+
+    #[tokio::test]
+    async fn test_tool_attribute_generates_definition() {
+        let tool = define_tool();
+        assert_eq!(tool.name, "define");
+        assert_eq!(tool.desc, "Define a word in one sentence");
+        let schema = tool.input_schema_json.as_ref().unwrap();
+        assert_eq!(schema["properties"]["word"]["type"], "string");
+
+        let mut registry = FunctionRegistry::new();
+        registry.register_tool(define_tool(), define);
+        let output = registry
+            .execute(&call("define", serde_json::json!({"word": "tool"})))
+            .await
+            .unwrap();
+        assert_eq!(output, "tool means something");
+    }
+
+    #[test]
+    fn test_tool_from_fn_uses_argument_docs() {
+        let tool = crate::tool_from_fn!(define_word, lookup_definition);
+
+        assert_eq!(tool.name, "define_word");
+        assert_eq!(tool.desc, "Look up a word's definition");
+    }
+}
@@ -0,0 +1,167 @@
+use std::fmt::Display;
+
+use super::ToolError;
+use crate::providers::models::{Message, ToolCall};
+
+/// The outcome of executing a single tool call
+#[derive(Debug, Clone)]
+pub struct ToolExecutionResult<T> {
+    pub call: ToolCall,
+    pub outcome: Result<T, ToolError>,
+}
+
+/// Outcomes of a batch of tool calls, in the order the calls were made
+#[derive(Debug, Clone)]
+pub struct ToolExecutionResults<T> {
+    pub results: Vec<ToolExecutionResult<T>>,
+}
+
+impl<T> ToolExecutionResults<T> {
+    pub fn all_succeeded(&self) -> bool {
+        self.results.iter().all(|result| result.outcome.is_ok())
+    }
+
+    pub fn failed_calls(&self) -> Vec<(&ToolCall, &ToolError)> {
+        self.results
+            .iter()
+            .filter_map(|result| match &result.outcome {
+                Ok(_) => None,
+                Err(error) => Some((&result.call, error)),
+            })
+            .collect()
+    }
+}
+
+impl<T: Display> ToolExecutionResults<T> {
+    /// One `Message::Tool` per call, ready to append to the conversation.
+    /// Failures are reported to the model rather than dropped so it can recover
+    pub fn to_tool_messages(&self) -> Vec<Message> {
+        self.results
+            .iter()
+            .map(|result| match &result.outcome {
+                Ok(value) => Message::tool(value.to_string(), result.call.id.clone()),
+                Err(error) => Message::tool_error(
+                    result.call.id.clone(),
+                    format!("{} ({})", error.message, error.kind),
+                ),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primatives::ToolCallSet;
+    use crate::providers::models::ContentTypes;
+    use crate::tools::{ToolErrorKind, ToolExecutor};
+    use async_trait::async_trait;
+
+    // Adds the `a` and `b` arguments; every other tool is unknown
+    struct Calculator;
+
+    #[async_trait]
+    impl ToolExecutor for Calculator {
+        async fn execute(&self, call: &ToolCall) -> Result<String, ToolError> {
+            if call.name != "add" {
+                return Err(ToolError::new(
+                    ToolErrorKind::NotFound,
+                    format!("unknown tool {}", call.name),
+                ));
+            }
+            let arg = |name: &str| {
+                call.arguments[name].as_i64().ok_or_else(|| {
+                    ToolError::new(
+                        ToolErrorKind::InvalidArguments,
+                        format!("missing integer argument {}", name),
+                    )
+                })
+            };
+            Ok((arg("a")? + arg("b")?).to_string())
+        }
+    }
+
+    fn call(id: &str, name: &str, arguments: serde_json::Value) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            name: name.to_string(),
+            arguments,
+        }
+    }
+
+    fn tool_text(message: &Message) -> (&str, &str) {
+        match message {
+            Message::Tool {
+                content: ContentTypes::Text(text),
+                tool_call_id,
+            } => (text, tool_call_id),
+            other => panic!("Expected a tool message, got {:?}", other),
+        }
+    }
+
+    fn calls() -> ToolCallSet {
+        ToolCallSet {
+            calls: vec![
+                call("1", "add", serde_json::json!({"a": 2, "b": 3})),
+                call("2", "search", serde_json::json!({})),
+                call("3", "add", serde_json::json!({"a": 1})),
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_failure_results() {
+        let results = calls().execute(&Calculator).await;
+
+        assert!(!results.all_succeeded());
+        let failed = results.failed_calls();
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed[0].0.id, "2");
+        assert_eq!(failed[0].1.kind, ToolErrorKind::NotFound);
+        assert_eq!(failed[1].1.kind, ToolErrorKind::InvalidArguments);
+    }
+
+    #[tokio::test]
+    async fn test_execute_and_to_messages() {
+        let messages = calls().execute_and_to_messages(&Calculator).await;
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(tool_text(&messages[0]), ("5", "1"));
+        assert_eq!(
+            tool_text(&messages[1]),
+            ("Error: unknown tool search (not_found)", "2")
+        );
+        assert_eq!(
+            tool_text(&messages[2]),
+            ("Error: missing integer argument b (invalid_arguments)", "3")
+        );
+    }
+
+    #[test]
+    fn test_all_succeeded_when_empty_or_ok() {
+        let empty: ToolExecutionResults<String> = ToolExecutionResults { results: vec![] };
+        assert!(empty.all_succeeded());
+
+        let ok = ToolExecutionResults {
+            results: vec![ToolExecutionResult {
+                call: call("1", "add", serde_json::json!({})),
+                outcome: Ok(4),
+            }],
+        };
+        assert!(ok.all_succeeded());
+        assert!(ok.failed_calls().is_empty());
+        assert_eq!(tool_text(&ok.to_tool_messages()[0]), ("4", "1"));
+    }
+
+    #[test]
+    fn test_tool_result_serializes_and_tool_error_prefixes() {
+        let result = Message::tool_result("1", serde_json::json!({"sum": 4})).unwrap();
+        assert_eq!(tool_text(&result), (r#"{"sum":4}"#, "1"));
+
+        let text = Message::tool_result("2", "plain text").unwrap();
+        assert_eq!(tool_text(&text), ("plain text", "2"));
+
+        let error = Message::tool_error("3", "timed out");
+        assert_eq!(tool_text(&error), ("Error: timed out", "3"));
+    }
+}
@@ -1,13 +1,2030 @@
-use insta::assert_snapshot;
-use schemars::{JsonSchema, Schema};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::RwLock;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
 
 use dsrs_core::{
     adapters::{
         chat_adapter::ChatAdapter,
+        context_window::{ContextWindowManager, TrimStrategy},
+        cot_adapter::ChainOfThoughtAdapter,
         json_adapter::JsonAdapter,
-        traits::{Adapter, AdapterConfig, Demo},
+        markdown_adapter::MarkdownAdapter,
+        pot_adapter::ProgramOfThoughtAdapter,
+        structured_output_adapter::StructuredOutputAdapter,
+        traits::{Adapter, AdapterConfig, ContextTruncationStrategy, Demo, FieldUpdate},
+        xml_adapter::XmlAdapter,
+        yaml_adapter::YamlAdapter,
+    },
+    evaluation::EvaluationMetric,
+    predict::Predict,
+    primatives::{
+        ChatHistory, DynamicSignature, Module, Signature, SignatureSchema, ToolCallSet,
+        ValidationError, ValidationErrors,
+    },
+    providers::models::{
+        AvailableTool, CompletionConfig, CompletionResponse, ContentTypes, FinishReason,
+        InjectionPosition, Message, ResponseFormat, ToolCall, UsageStats,
+    },
+    providers::{
+        CachedProvider, CompletionProvider, CompletionStream, MockProvider, ProviderError,
+        StreamChunk,
     },
-    primatives::{ChatHistory, History, Signature, ToolCallSet, ToolCalls, ToolSet, Tools},
-    providers::models::{AvailableTool, ContentTypes, Message, ToolCall},
 };
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+struct QaInputs {
+    /// The question to answer
+    question: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq)]
+struct QaOutputs {
+    /// The answer to the question
+    answer: String,
+    /// How confident the model is in its answer
+    confidence: f64,
+}
+
+struct QaSignature;
+
+impl Signature for QaSignature {
+    type Inputs = QaInputs;
+    type Outputs = QaOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Answer the question."
+    }
+
+    fn name(&self) -> &str {
+        "QA"
+    }
+
+    fn desc(&self) -> &str {
+        "Question answering"
+    }
+}
+
+#[test]
+fn test_chat_adapter_parse() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let completion = "[[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.9\n\n[[ ## completed ## ]]";
+
+    let outputs = <ChatAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        outputs,
+        QaOutputs {
+            answer: "Paris".to_string(),
+            confidence: 0.9,
+        }
+    );
+}
+
+#[test]
+fn test_json_adapter_parse() {
+    let adapter = JsonAdapter::new(AdapterConfig::default());
+    let completion = "Here you go: {\"answer\": \"Paris\", \"confidence\": 0.9}";
+
+    let outputs = <JsonAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+}
+
+#[test]
+fn test_json_adapter_parse_repairs_almost_valid_json() {
+    let adapter = JsonAdapter::new(AdapterConfig::default());
+    let completion = "```json\n{answer: 'Paris', \"confidence\": 0.9,}\n```";
+
+    let outputs = <JsonAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    assert_eq!(outputs.confidence, 0.9);
+}
+
+#[test]
+fn test_xml_adapter_parse_entities_and_cdata() {
+    let adapter = XmlAdapter::new(AdapterConfig::default());
+    let completion = "<response>\n<answer>Tom &amp; Jerry &lt;3 &#x263A;</answer>\n\
+        <confidence><![CDATA[0.9]]></confidence>\n</response>";
+
+    let outputs = <XmlAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        outputs,
+        QaOutputs {
+            answer: "Tom & Jerry <3 \u{263A}".to_string(),
+            confidence: 0.9,
+        }
+    );
+}
+
+#[test]
+fn test_xml_adapter_round_trips_markup_in_values() {
+    let adapter = XmlAdapter::new(AdapterConfig::default());
+    let outputs = QaOutputs {
+        answer: "Use <b>bold</b> & <![CDATA[raw]]> text".to_string(),
+        confidence: 0.5,
+    };
+    let schema = QaSignature::prompt_output_schema();
+
+    let formatted =
+        <XmlAdapter as Adapter<QaSignature>>::format_assistant_message_content(&adapter, &outputs, &schema);
+    let parsed = <XmlAdapter as Adapter<QaSignature>>::parse(&adapter, &formatted, &schema).unwrap();
+
+    assert!(formatted.starts_with("<answer>\n"));
+    assert_eq!(parsed, outputs);
+}
+
+#[test]
+fn test_xml_adapter_keeps_cdata_verbatim() {
+    let adapter = XmlAdapter::new(AdapterConfig::default());
+    let completion = "<answer><![CDATA[if a < b && c > d { </answer> }]]></answer><confidence>1</confidence>";
+
+    let outputs = <XmlAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(outputs.answer, "if a < b && c > d { </answer> }");
+}
+
+#[test]
+fn test_markdown_adapter_parse_keeps_markdown_in_values() {
+    let adapter = MarkdownAdapter::new(AdapterConfig::default());
+    let completion = "Here is my response.\n\n## answer\nParis.\n\n## Details\n### History\n\
+        ```markdown\n## confidence\nnot a field\n```\n\n## confidence\n```json\n0.75\n```";
+
+    let outputs = <MarkdownAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        outputs.answer,
+        "Paris.\n\n## Details\n### History\n```markdown\n## confidence\nnot a field\n```"
+    );
+    assert_eq!(outputs.confidence, 0.75);
+}
+
+#[test]
+fn test_markdown_adapter_round_trip() {
+    let adapter = MarkdownAdapter::new(AdapterConfig::default());
+    let outputs = QaOutputs {
+        answer: "- first\n- second\n\n### Notes\nDone.".to_string(),
+        confidence: 0.5,
+    };
+    let schema = QaSignature::prompt_output_schema();
+
+    let formatted = <MarkdownAdapter as Adapter<QaSignature>>::format_assistant_message_content(
+        &adapter, &outputs, &schema,
+    );
+    let parsed =
+        <MarkdownAdapter as Adapter<QaSignature>>::parse(&adapter, &formatted, &schema).unwrap();
+
+    assert!(formatted.starts_with("## answer\n- first"));
+    assert_eq!(parsed, outputs);
+}
+
+#[test]
+fn test_yaml_adapter_parse_block_scalar_from_fence() {
+    let adapter = YamlAdapter::new(AdapterConfig::default());
+    let completion = "Sure, here you go:\n\n```yaml\nanswer: |\n  Paris.\n  Population: about 2 million.\nconfidence: 0.9\n```";
+
+    let outputs = <YamlAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        outputs,
+        QaOutputs {
+            answer: "Paris.\nPopulation: about 2 million.\n".to_string(),
+            confidence: 0.9,
+        }
+    );
+}
+
+#[test]
+fn test_yaml_adapter_coerces_implicit_types_for_string_fields() {
+    let adapter = YamlAdapter::new(AdapterConfig::default());
+    let schema = QaSignature::prompt_output_schema();
+
+    let outputs =
+        <YamlAdapter as Adapter<QaSignature>>::parse(&adapter, "answer: 1889\nconfidence: 1", &schema)
+            .unwrap();
+    assert_eq!(outputs.answer, "1889");
+
+    let outputs =
+        <YamlAdapter as Adapter<QaSignature>>::parse(&adapter, "answer: true\nconfidence: 1", &schema)
+            .unwrap();
+    assert_eq!(outputs.answer, "true");
+}
+
+#[test]
+fn test_yaml_adapter_round_trip() {
+    let adapter = YamlAdapter::new(AdapterConfig::default());
+    let outputs = QaOutputs {
+        answer: "yes\nacross: several lines".to_string(),
+        confidence: 0.5,
+    };
+    let schema = QaSignature::prompt_output_schema();
+
+    let formatted = <YamlAdapter as Adapter<QaSignature>>::format_assistant_message_content(
+        &adapter, &outputs, &schema,
+    );
+    let parsed =
+        <YamlAdapter as Adapter<QaSignature>>::parse(&adapter, &formatted, &schema).unwrap();
+
+    assert_eq!(parsed, outputs);
+}
+
+fn qa_inputs() -> QaInputs {
+    QaInputs {
+        question: "What is the capital of France?".to_string(),
+    }
+}
+
+fn system_text(messages: &[Message]) -> &str {
+    match &messages[0] {
+        Message::System {
+            content: ContentTypes::Text(text),
+        } => text,
+        other => panic!("Expected a system message first, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_system_injections_in_system_message() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let config = CompletionConfig {
+        model: "test-model".to_string(),
+        ..Default::default()
+    }
+    .with_system_injection("Always respond in English.".to_string(), InjectionPosition::Prepend)
+    .with_system_injection("Never mention competitors.".to_string(), InjectionPosition::Append);
+
+    let messages = <ChatAdapter as Adapter<QaSignature>>::format_messages(
+        &adapter,
+        &config,
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+    )
+    .unwrap();
+
+    let system = system_text(&messages);
+    assert!(system.starts_with("Always respond in English.\n"));
+    assert!(system.ends_with("\nNever mention competitors.\n"));
+}
+
+#[test]
+fn test_chat_adapter_numbered_field_structure() {
+    let adapter = ChatAdapter::new(AdapterConfig::default()).with_numbered_fields(true);
+
+    let structure = <ChatAdapter as Adapter<QaSignature>>::format_field_structure(
+        &adapter,
+        &QaSignature::prompt_input_schema(),
+        &QaSignature::prompt_output_schema(),
+    );
+    assert!(structure.contains("[[ ## question ## ]]\nString"));
+    assert!(structure.contains("[[ ## 1. answer ## ]]\nString"));
+    assert!(structure.contains("[[ ## 2. confidence ## ]]\nNumber"));
+
+    let user = <ChatAdapter as Adapter<QaSignature>>::format_user_message_content(
+        &adapter,
+        &qa_inputs(),
+        &QaSignature::prompt_input_schema(),
+        &QaSignature::prompt_output_schema(),
+    );
+    assert!(user.contains("`[[ ## 1. answer ## ]]`, then `[[ ## 2. confidence ## ]]`"));
+
+    let assistant = <ChatAdapter as Adapter<QaSignature>>::format_assistant_message_content(
+        &adapter,
+        &QaOutputs {
+            answer: "Paris".to_string(),
+            confidence: 0.9,
+        },
+        &QaSignature::prompt_output_schema(),
+    );
+    assert_eq!(
+        assistant,
+        "[[ ## 1. answer ## ]]\nParis\n\n[[ ## 2. confidence ## ]]\n0.9\n\n[[ ## completed ## ]]"
+    );
+}
+
+#[test]
+fn test_chat_adapter_parse_numbered_and_unnumbered_headers() {
+    let adapter = ChatAdapter::new(AdapterConfig::default()).with_numbered_fields(true);
+    let completion = "[[ ## 1. answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.9\n\n[[ ## completed ## ]]";
+
+    let outputs = <ChatAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    assert_eq!(outputs.confidence, 0.9);
+}
+
+// Fields declared out of alphabetical order, to check prompts follow the schema
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+struct ReviewInputs {
+    title: String,
+    body: String,
+    author: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct ReviewOutputs {
+    verdict: String,
+    summary: String,
+}
+
+struct ReviewSignature;
+
+impl Signature for ReviewSignature {
+    type Inputs = ReviewInputs;
+    type Outputs = ReviewOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Review the post."
+    }
+
+    fn name(&self) -> &str {
+        "Review"
+    }
+
+    fn desc(&self) -> &str {
+        "Post review"
+    }
+}
+
+fn assert_in_order(text: &str, names: &[&str]) {
+    let positions: Vec<usize> = names
+        .iter()
+        .map(|name| {
+            text.find(&format!("[[ ## {} ## ]]", name))
+                .unwrap_or_else(|| panic!("{} missing from {}", name, text))
+        })
+        .collect();
+    assert!(positions.is_sorted(), "{:?} out of order in {}", names, text);
+}
+
+#[test]
+fn test_chat_adapter_fields_follow_schema_order() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let input_schema = ReviewSignature::prompt_input_schema();
+    let output_schema = ReviewSignature::prompt_output_schema();
+
+    let structure = <ChatAdapter as Adapter<ReviewSignature>>::format_field_structure(
+        &adapter,
+        &input_schema,
+        &output_schema,
+    );
+    assert_in_order(&structure, &["title", "body", "author", "verdict", "summary"]);
+
+    let user = <ChatAdapter as Adapter<ReviewSignature>>::format_user_message_content(
+        &adapter,
+        &ReviewInputs {
+            title: "Hello".to_string(),
+            body: "First post".to_string(),
+            author: "Sam".to_string(),
+        },
+        &input_schema,
+        &output_schema,
+    );
+    assert_in_order(&user, &["title", "body", "author"]);
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+struct TaggingInputs {
+    topics: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, PartialEq)]
+struct TaggingOutputs {
+    keywords: Vec<String>,
+    counts: Vec<u32>,
+}
+
+struct TaggingSignature;
+
+impl Signature for TaggingSignature {
+    type Inputs = TaggingInputs;
+    type Outputs = TaggingOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Tag the topics."
+    }
+
+    fn name(&self) -> &str {
+        "Tagging"
+    }
+
+    fn desc(&self) -> &str {
+        "Keyword tagging"
+    }
+}
+
+#[test]
+fn test_chat_adapter_parses_line_separated_lists() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let completion = "[[ ## keywords ## ]]\n- rust\n- 2024\n\n[[ ## counts ## ]]\n1. 3\n2. 7\n\n[[ ## completed ## ]]";
+
+    let outputs = <ChatAdapter as Adapter<TaggingSignature>>::parse(
+        &adapter,
+        completion,
+        &TaggingSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        outputs,
+        TaggingOutputs {
+            keywords: vec!["rust".to_string(), "2024".to_string()],
+            counts: vec![3, 7],
+        }
+    );
+
+    let json = "[[ ## keywords ## ]]\n[\"rust\"]\n\n[[ ## counts ## ]]\n[3]\n\n[[ ## completed ## ]]";
+    let outputs = <ChatAdapter as Adapter<TaggingSignature>>::parse(
+        &adapter,
+        json,
+        &TaggingSignature::prompt_output_schema(),
+    )
+    .unwrap();
+    assert_eq!(outputs.counts, vec![3]);
+
+    let strict = ChatAdapter::new(AdapterConfig::default()).with_list_splitting(false);
+    assert!(
+        <ChatAdapter as Adapter<TaggingSignature>>::parse(
+            &strict,
+            completion,
+            &TaggingSignature::prompt_output_schema(),
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_chat_adapter_bulleted_lists_round_trip() {
+    let adapter = ChatAdapter::new(AdapterConfig::default()).with_bulleted_lists(true);
+    let outputs = TaggingOutputs {
+        keywords: vec!["rust".to_string(), "async".to_string()],
+        counts: vec![3, 7],
+    };
+
+    let user = <ChatAdapter as Adapter<TaggingSignature>>::format_user_message_content(
+        &adapter,
+        &TaggingInputs {
+            topics: vec!["Rust".to_string(), "Tokio".to_string()],
+        },
+        &TaggingSignature::prompt_input_schema(),
+        &TaggingSignature::prompt_output_schema(),
+    );
+    assert!(user.starts_with("[[ ## topics ## ]]\n- Rust\n- Tokio\n\n"));
+
+    let assistant = <ChatAdapter as Adapter<TaggingSignature>>::format_assistant_message_content(
+        &adapter,
+        &outputs,
+        &TaggingSignature::prompt_output_schema(),
+    );
+    assert_eq!(
+        assistant,
+        "[[ ## keywords ## ]]\n- rust\n- async\n\n[[ ## counts ## ]]\n- 3\n- 7\n\n[[ ## completed ## ]]"
+    );
+    let parsed = <ChatAdapter as Adapter<TaggingSignature>>::parse(
+        &adapter,
+        &assistant,
+        &TaggingSignature::prompt_output_schema(),
+    )
+    .unwrap();
+    assert_eq!(parsed, outputs);
+}
+
+#[test]
+fn test_chat_adapter_plain_lists_round_trip() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let outputs = TaggingOutputs {
+        keywords: vec!["rust".to_string(), "async".to_string()],
+        counts: vec![3, 7],
+    };
+
+    let assistant = <ChatAdapter as Adapter<TaggingSignature>>::format_assistant_message_content(
+        &adapter,
+        &outputs,
+        &TaggingSignature::prompt_output_schema(),
+    );
+    assert_eq!(
+        assistant,
+        "[[ ## keywords ## ]]\nrust\nasync\n\n[[ ## counts ## ]]\n3\n7\n\n[[ ## completed ## ]]"
+    );
+    let parsed = <ChatAdapter as Adapter<TaggingSignature>>::parse(
+        &adapter,
+        &assistant,
+        &TaggingSignature::prompt_output_schema(),
+    )
+    .unwrap();
+    assert_eq!(parsed, outputs);
+}
+
+#[test]
+fn test_system_prompt_prefix_and_suffix_wrap_system_message() {
+    let adapter = ChatAdapter::new(
+        AdapterConfig::default()
+            .with_system_prompt_prefix("Always respond in English.")
+            .with_system_prompt_suffix("Never skip the completed marker."),
+    );
+
+    let messages = <ChatAdapter as Adapter<QaSignature>>::format_messages(
+        &adapter,
+        &CompletionConfig::default(),
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+    )
+    .unwrap();
+
+    let system = system_text(&messages);
+    assert!(system.starts_with("Always respond in English.\n"), "{}", system);
+    assert!(system.ends_with("\nNever skip the completed marker.\n"), "{}", system);
+    assert!(system.contains("Answer the question."), "{}", system);
+}
+
+// Tailors the question field's description per tenant
+struct TenantQaSignature {
+    tenant: String,
+}
+
+impl Signature for TenantQaSignature {
+    type Inputs = QaInputs;
+    type Outputs = QaOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Answer the question."
+    }
+
+    fn name(&self) -> &str {
+        "TenantQA"
+    }
+
+    fn desc(&self) -> &str {
+        "Question answering"
+    }
+
+    fn description_for_field(&self, field_name: &str, is_input: bool) -> Option<String> {
+        (field_name == "question" && is_input)
+            .then(|| format!("A question from an {} customer", self.tenant))
+    }
+}
+
+#[test]
+fn test_description_for_field_overrides_schema_description() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let signature = TenantQaSignature {
+        tenant: "Acme".to_string(),
+    };
+
+    let messages = <ChatAdapter as Adapter<TenantQaSignature>>::format_messages_filtered(
+        &adapter,
+        &signature,
+        &CompletionConfig::default(),
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+        &TenantQaSignature::prompt_input_schema(),
+        &TenantQaSignature::prompt_output_schema(),
+        None,
+    )
+    .unwrap();
+
+    let system = system_text(&messages);
+    assert!(system.contains("- question: A question from an Acme customer"), "{}", system);
+    assert!(!system.contains("The question to answer"), "{}", system);
+}
+
+#[tokio::test]
+async fn test_system_message_stable_across_inputs() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let config = CompletionConfig {
+        model: "test-model".to_string(),
+        ..Default::default()
+    };
+    let other_inputs = QaInputs {
+        question: "How tall is the Eiffel Tower?".to_string(),
+    };
+
+    let first = <ChatAdapter as Adapter<QaSignature>>::format_messages(
+        &adapter,
+        &config,
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+    )
+    .unwrap();
+    let second = <ChatAdapter as Adapter<QaSignature>>::format_messages(
+        &adapter,
+        &config,
+        "Answer the question.",
+        &[],
+        &other_inputs,
+    )
+    .unwrap();
+
+    assert_eq!(system_text(&first).as_bytes(), system_text(&second).as_bytes());
+    assert_eq!(
+        system_text(&first),
+        <ChatAdapter as Adapter<QaSignature>>::format_system_message(
+            &adapter,
+            &config,
+            "Answer the question.",
+            &QaSignature::prompt_input_schema(),
+            &QaSignature::prompt_output_schema(),
+        )
+    );
+
+    let answer =
+        "[[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.9\n\n[[ ## completed ## ]]";
+    let mut predict = Predict::new(QaSignature, MockProvider::with_texts([answer, answer]));
+    predict.set_config(config);
+    predict.aforward(qa_inputs()).await.unwrap();
+    predict.aforward(other_inputs).await.unwrap();
+
+    let received = predict.lm().received();
+    let precomputed = predict.precompute_system_message();
+    assert_eq!(system_text(&received[0]).as_bytes(), precomputed.as_bytes());
+    assert_eq!(system_text(&received[1]).as_bytes(), precomputed.as_bytes());
+}
+
+#[test]
+fn test_cot_adapter_puts_rationale_first_in_structure() {
+    let adapter = ChainOfThoughtAdapter::new(ChatAdapter::new(AdapterConfig::default()));
+
+    let structure = <ChainOfThoughtAdapter<ChatAdapter> as Adapter<QaSignature>>::format_field_structure(
+        &adapter,
+        &QaSignature::prompt_input_schema(),
+        &QaSignature::prompt_output_schema(),
+    );
+
+    let rationale = structure.find("[[ ## rationale ## ]]\nString").unwrap();
+    let answer = structure.find("[[ ## answer ## ]]").unwrap();
+    assert!(structure.find("[[ ## question ## ]]").unwrap() < rationale);
+    assert!(rationale < answer);
+}
+
+#[test]
+fn test_cot_adapter_parse_records_rationale() {
+    let adapter = ChainOfThoughtAdapter::new(ChatAdapter::new(AdapterConfig::default()));
+    let completion = "[[ ## rationale ## ]]\nFrance's capital has been Paris since 987.\n\n\
+        [[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.9\n\n[[ ## completed ## ]]";
+    assert_eq!(adapter.last_rationale(), None);
+
+    let outputs = <ChainOfThoughtAdapter<ChatAdapter> as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        outputs,
+        QaOutputs {
+            answer: "Paris".to_string(),
+            confidence: 0.9,
+        }
+    );
+    assert_eq!(
+        adapter.last_rationale().as_deref(),
+        Some("France's capital has been Paris since 987.")
+    );
+}
+
+#[test]
+fn test_cot_adapter_wraps_xml_adapter() {
+    let adapter = ChainOfThoughtAdapter::new(XmlAdapter::new(AdapterConfig::default()));
+    let completion = "<rationale>It is &lt;definitely&gt; Paris.</rationale>\n\
+        <answer>Paris</answer>\n<confidence>0.8</confidence>";
+
+    let outputs = <ChainOfThoughtAdapter<XmlAdapter> as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    assert_eq!(adapter.last_rationale().as_deref(), Some("It is <definitely> Paris."));
+}
+
+const PROGRAM_COMPLETION: &str = "Counting the letters:\n```python\nword = 'Paris'\n\
+    print(len(word))\n```";
+
+#[test]
+fn test_pot_adapter_runs_code_and_parses_printed_outputs() {
+    let ran = Arc::new(Mutex::new(Vec::new()));
+    let log = ran.clone();
+    let interpreter = move |code: &str| -> anyhow::Result<String> {
+        log.lock().unwrap().push(code.to_string());
+        Ok("[[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.5\n".to_string())
+    };
+    let adapter = ProgramOfThoughtAdapter::with_interpreter(
+        ChatAdapter::new(AdapterConfig::default()),
+        interpreter,
+    );
+
+    let user = <ProgramOfThoughtAdapter<_> as Adapter<QaSignature>>::format_user_message_content(
+        &adapter,
+        &qa_inputs(),
+        &QaSignature::prompt_input_schema(),
+        &QaSignature::prompt_output_schema(),
+    );
+    assert!(user.contains("```python fenced block"), "{}", user);
+
+    let outputs = <ProgramOfThoughtAdapter<_> as Adapter<QaSignature>>::parse(
+        &adapter,
+        PROGRAM_COMPLETION,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    assert_eq!(outputs.confidence, 0.5);
+    assert_eq!(*ran.lock().unwrap(), vec!["word = 'Paris'\nprint(len(word))"]);
+    assert_eq!(adapter.last_code().as_deref(), Some("word = 'Paris'\nprint(len(word))"));
+
+    let missing = <ProgramOfThoughtAdapter<_> as Adapter<QaSignature>>::parse(
+        &adapter,
+        "[[ ## answer ## ]]\nParis",
+        &QaSignature::prompt_output_schema(),
+    );
+    assert!(missing.is_err());
+    assert_eq!(adapter.last_code(), None);
+}
+
+#[test]
+fn test_pot_adapter_noop_interpreter_returns_code_for_single_field() {
+    let adapter = ProgramOfThoughtAdapter::new(AdapterConfig::default());
+    let schema = schemars::Schema::try_from(serde_json::json!({
+        "type": "object",
+        "properties": {"program": {"type": "string"}},
+        "required": ["program"]
+    }))
+    .unwrap();
+
+    let outputs = <ProgramOfThoughtAdapter as Adapter<DynamicSignature>>::parse(
+        &adapter,
+        PROGRAM_COMPLETION,
+        &schema,
+    )
+    .unwrap();
+
+    assert_eq!(outputs["program"], "word = 'Paris'\nprint(len(word))");
+}
+
+#[test]
+fn test_structured_output_adapter_requests_strict_json_schema() {
+    let config = AdapterConfig::default().with_native_function_calling(true);
+    let adapter = StructuredOutputAdapter::new(config);
+
+    let (_, config) = <StructuredOutputAdapter as Adapter<QaSignature>>::prepare_request(
+        &adapter,
+        CompletionConfig::default(),
+        &QaSignature,
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+        None,
+    )
+    .unwrap();
+
+    let Some(ResponseFormat::JsonSchema {
+        name,
+        schema,
+        strict,
+        ..
+    }) = config.response_format
+    else {
+        panic!("Expected a JSON schema response format");
+    };
+    assert_eq!(name, "QaOutputs");
+    assert!(strict);
+    assert_eq!(schema["required"], serde_json::json!(["answer", "confidence"]));
+    assert_eq!(schema["additionalProperties"], false);
+    assert!(schema.get("$schema").is_none());
+    assert!(schema["properties"]["confidence"].get("format").is_none());
+
+    let outputs = <StructuredOutputAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        r#"{"answer": "Paris", "confidence": 0.9}"#,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+    assert_eq!(outputs.answer, "Paris");
+}
+
+#[test]
+fn test_structured_output_adapter_without_native_mode_extracts_json() {
+    let adapter = StructuredOutputAdapter::new(AdapterConfig::default());
+
+    let (_, config) = <StructuredOutputAdapter as Adapter<QaSignature>>::prepare_request(
+        &adapter,
+        CompletionConfig::default(),
+        &QaSignature,
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(config.response_format, None);
+
+    let outputs = <StructuredOutputAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        r#"Here it is: {"answer": "Paris", "confidence": 0.9}"#,
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+    assert_eq!(outputs.confidence, 0.9);
+}
+
+// Provider that always answers with the same assistant text
+struct StaticProvider {
+    text: String,
+}
+
+impl CompletionProvider for StaticProvider {
+    async fn complete(
+        &self,
+        _messages: Arc<RwLock<Vec<Message>>>,
+        _config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        Ok(CompletionResponse::new(
+            Message::assistant(Some(self.text.clone()), None),
+            FinishReason::Stop,
+        ))
+    }
+}
+
+// Provider that plays back queued responses and records each request
+struct ScriptedProvider {
+    responses: Mutex<Vec<CompletionResponse>>,
+    requests: Mutex<Vec<(Vec<Message>, CompletionConfig)>>,
+    max_context_tokens: Option<u32>,
+}
+
+impl ScriptedProvider {
+    fn new(responses: Vec<(&str, FinishReason)>, max_context_tokens: Option<u32>) -> Self {
+        let mut responses: Vec<CompletionResponse> = responses
+            .into_iter()
+            .map(|(text, reason)| CompletionResponse::new(Message::assistant(Some(text), None), reason))
+            .collect();
+        responses.reverse();
+        ScriptedProvider {
+            responses: Mutex::new(responses),
+            requests: Mutex::new(Vec::new()),
+            max_context_tokens,
+        }
+    }
+
+    fn sent_max_tokens(&self) -> Vec<Option<u32>> {
+        self.requests.lock().unwrap().iter().map(|(_, config)| config.max_tokens).collect()
+    }
+}
+
+impl CompletionProvider for ScriptedProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let sent = messages.read().await.clone();
+        self.requests.lock().unwrap().push((sent, config));
+        Ok(self.responses.lock().unwrap().pop().expect("no scripted response left"))
+    }
+
+    fn max_context_tokens(&self) -> Option<u32> {
+        self.max_context_tokens
+    }
+}
+
+// Provider that streams a fixed completion in small pieces
+struct ChunkedProvider {
+    chunks: Vec<&'static str>,
+}
+
+impl CompletionProvider for ChunkedProvider {
+    async fn complete(
+        &self,
+        _messages: Arc<RwLock<Vec<Message>>>,
+        _config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        unreachable!("generate_streaming should only call stream")
+    }
+
+    fn stream<'a>(
+        &'a self,
+        _messages: Arc<RwLock<Vec<Message>>>,
+        _config: CompletionConfig,
+    ) -> CompletionStream<'a> {
+        let chunks = self.chunks.iter().map(|chunk| Ok(StreamChunk::Text(chunk.to_string())));
+        Box::pin(futures::stream::iter(chunks))
+    }
+}
+
+#[tokio::test]
+async fn test_generate_streaming_reports_tokens() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let provider = ChunkedProvider {
+        chunks: vec![
+            "[[ ## answer ## ]]\nPa",
+            "ris\n\n[[ ## confidence ## ]]\n0.",
+            "9\n\n[[ ## completed ## ]]",
+        ],
+    };
+    let config = CompletionConfig {
+        model: "test-model".to_string(),
+        ..Default::default()
+    };
+    let tokens = Mutex::new(Vec::new());
+
+    let outputs = adapter
+        .generate_streaming(
+            &provider,
+            config,
+            &QaSignature,
+            "Answer the question.",
+            &[],
+            &qa_inputs(),
+            &|token| tokens.lock().unwrap().push(token.to_string()),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    assert_eq!(outputs.confidence, 0.9);
+    assert_eq!(*tokens.lock().unwrap(), provider.chunks);
+}
+
+const TRUNCATED_ANSWER: &str = "[[ ## answer ## ]]\nThe capital of France is";
+const FULL_ANSWER: &str =
+    "[[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.9\n\n[[ ## completed ## ]]";
+
+async fn generate_after_truncation(
+    auto_expand_max_tokens: bool,
+    max_context_tokens: Option<u32>,
+) -> ScriptedProvider {
+    let config = AdapterConfig::default().with_auto_expand_max_tokens(auto_expand_max_tokens);
+    let adapter = ChatAdapter::new(config);
+    let provider = ScriptedProvider::new(
+        vec![(TRUNCATED_ANSWER, FinishReason::Length), (FULL_ANSWER, FinishReason::Stop)],
+        max_context_tokens,
+    );
+    let config = CompletionConfig {
+        model: "test-model".to_string(),
+        max_tokens: Some(100),
+        ..Default::default()
+    };
+
+    let outputs = adapter
+        .generate(&provider, config, &QaSignature, "Answer the question.", &[], &qa_inputs())
+        .await
+        .unwrap();
+    assert_eq!(outputs.answer, "Paris");
+
+    provider
+}
+
+#[tokio::test]
+async fn test_truncated_response_retries_with_expanded_max_tokens() {
+    let provider = generate_after_truncation(true, None).await;
+
+    assert_eq!(provider.sent_max_tokens(), vec![Some(100), Some(150)]);
+
+    // The retry carries the truncated answer followed by the nudge to be concise
+    let requests = provider.requests.lock().unwrap();
+    let retry = &requests[1].0;
+    match &retry[retry.len() - 2..] {
+        [
+            Message::Assistant {
+                content: Some(ContentTypes::Text(truncated)),
+                ..
+            },
+            Message::User { content: nudge },
+        ] => {
+            assert_eq!(truncated, TRUNCATED_ANSWER);
+            assert!(ContentTypes::join_text(nudge).contains("cut off due to length"));
+        }
+        other => panic!("Unexpected retry messages: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_expanded_max_tokens_capped_by_provider_limit() {
+    let provider = generate_after_truncation(true, Some(120)).await;
+    assert_eq!(provider.sent_max_tokens(), vec![Some(100), Some(120)]);
+}
+
+#[tokio::test]
+async fn test_max_tokens_unchanged_without_auto_expand() {
+    let provider = generate_after_truncation(false, None).await;
+    assert_eq!(provider.sent_max_tokens(), vec![Some(100), Some(100)]);
+}
+
+#[tokio::test]
+async fn test_generate_with_stats_sums_usage_across_attempts() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let provider = ScriptedProvider::new(
+        vec![(TRUNCATED_ANSWER, FinishReason::Length), (FULL_ANSWER, FinishReason::Stop)],
+        None,
+    );
+    for response in provider.responses.lock().unwrap().iter_mut() {
+        response.usage = Some(UsageStats::new(20, 10));
+    }
+    let config = CompletionConfig {
+        model: "test-model".to_string(),
+        ..Default::default()
+    };
+
+    let (outputs, response) = adapter
+        .generate_with_stats(
+            &provider,
+            config,
+            &QaSignature,
+            "Answer the question.",
+            &[],
+            &qa_inputs(),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    assert_eq!(response.finish_reason, FinishReason::Stop);
+    assert_eq!(response.usage, Some(UsageStats::new(40, 20)));
+}
+
+#[tokio::test]
+async fn test_parse_retry_bypasses_cache() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let provider = CachedProvider::new(ScriptedProvider::new(
+        vec![("not a valid answer", FinishReason::Stop), (FULL_ANSWER, FinishReason::Stop)],
+        None,
+    ));
+    let config = CompletionConfig {
+        model: "test-model".to_string(),
+        ..Default::default()
+    };
+
+    let outputs = adapter
+        .generate(&provider, config, &QaSignature, "Answer the question.", &[], &qa_inputs())
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    assert_eq!(provider.inner().requests.lock().unwrap().len(), 2);
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+struct ToolUseInputs {
+    question: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize)]
+struct ToolUseOutputs {
+    answer: Option<String>,
+    #[signature(tool_calls)]
+    tool_calls: Option<ToolCallSet>,
+}
+
+/// Answer the question, looking it up when needed
+#[derive(dsrs_core::primatives::Signature)]
+#[signature(name = "ToolUse", inputs = ToolUseInputs, outputs = ToolUseOutputs)]
+struct ToolUseSignature {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+#[tokio::test]
+async fn test_empty_text_with_tool_calls_is_tool_only() {
+    // An empty string isn't valid JSON, so parsing it would fail
+    let adapter = JsonAdapter::new(AdapterConfig::default());
+    let call = ToolCall {
+        id: "call_1".to_string(),
+        name: "lookup".to_string(),
+        arguments: serde_json::json!({"q": "capital of France"}),
+    };
+    // Some providers send an empty string rather than no content alongside tool calls
+    let provider = ScriptedProvider::new(vec![("", FinishReason::ToolCalls)], None);
+    provider.responses.lock().unwrap()[0].message =
+        Message::assistant(Some(""), Some(vec![call.clone()]));
+    let config = CompletionConfig::default()
+        .with_tools(vec![AvailableTool::builder().name("lookup").build()]);
+    let signature = ToolUseSignature {
+        instructions: String::new(),
+    };
+    let inputs = ToolUseInputs {
+        question: "What is the capital of France?".to_string(),
+    };
+
+    let outputs = adapter
+        .generate(&provider, config, &signature, "", &[], &inputs)
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, None);
+    assert_eq!(outputs.tool_calls.unwrap().calls, vec![call]);
+    assert_eq!(provider.requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_pre_and_post_generate_hooks_edit_messages_and_outputs() {
+    let adapter = ChatAdapter::new(AdapterConfig {
+        pre_generate: Some(Arc::new(|messages: &mut Vec<Message>| {
+            messages.push(Message::user("Answer in one word."))
+        })),
+        post_generate: Some(Arc::new(|outputs: &mut serde_json::Value| {
+            let answer = outputs["answer"].as_str().unwrap().to_uppercase();
+            outputs["answer"] = serde_json::json!(answer);
+        })),
+        ..Default::default()
+    });
+    let provider = ScriptedProvider::new(vec![(FULL_ANSWER, FinishReason::Stop)], None);
+
+    let outputs: QaOutputs = adapter
+        .generate(&provider, CompletionConfig::default(), &QaSignature, "", &[], &qa_inputs())
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, "PARIS");
+    let requests = provider.requests.lock().unwrap();
+    assert_eq!(requests[0].0.last(), Some(&Message::user("Answer in one word.")));
+}
+
+async fn requests_after_parse_failure(use_correction_prompt: bool) -> Vec<Vec<Message>> {
+    let adapter =
+        ChatAdapter::new(AdapterConfig::default().with_correction_prompt(use_correction_prompt));
+    let provider = ScriptedProvider::new(
+        vec![("not a valid answer", FinishReason::Stop), (FULL_ANSWER, FinishReason::Stop)],
+        None,
+    );
+
+    adapter
+        .generate(&provider, CompletionConfig::default(), &QaSignature, "", &[], &qa_inputs())
+        .await
+        .unwrap();
+
+    let requests = provider.requests.lock().unwrap();
+    requests.iter().map(|(messages, _)| messages.clone()).collect()
+}
+
+#[tokio::test]
+async fn test_parse_failure_retries_with_correction_prompt() {
+    let requests = requests_after_parse_failure(true).await;
+
+    let first = requests[0].len();
+    assert_eq!(requests[1].len(), first + 2);
+    assert!(matches!(
+        &requests[1][first],
+        Message::Assistant { content: Some(ContentTypes::Text(text)), .. } if text == "not a valid answer"
+    ));
+    let Message::User { content } = &requests[1][first + 1] else {
+        panic!("Expected a correction message");
+    };
+    let correction = ContentTypes::join_text(content);
+    assert!(correction.starts_with("Your previous response was invalid: "));
+    assert!(correction.ends_with("Please try again following the format."));
+}
+
+#[tokio::test]
+async fn test_parse_failure_resends_same_messages_without_correction_prompt() {
+    let requests = requests_after_parse_failure(false).await;
+
+    assert_eq!(requests[0].len(), requests[1].len());
+}
+
+#[tokio::test]
+async fn test_exhausted_mock_is_not_retried() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let provider = MockProvider::new(Vec::new());
+
+    let error = adapter
+        .generate(&provider, CompletionConfig::default(), &QaSignature, "", &[], &qa_inputs())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(
+        error.downcast_ref::<ProviderError>(),
+        Some(ProviderError::ApiError { status: 400, .. })
+    ));
+    assert_eq!(provider.call_count(), 1);
+}
+
+struct EventCounter(Arc<AtomicUsize>);
+
+impl<S: tracing::Subscriber> Layer<S> for EventCounter {
+    fn on_event(&self, _event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+async fn count_debug_events(debug_mode: bool) -> usize {
+    let count = Arc::new(AtomicUsize::new(0));
+    let subscriber = tracing_subscriber::registry().with(EventCounter(count.clone()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let adapter = ChatAdapter::new(AdapterConfig::default().with_debug_mode(debug_mode));
+    let provider = StaticProvider {
+        text: "[[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.9\n\n[[ ## completed ## ]]"
+            .to_string(),
+    };
+    let config = CompletionConfig {
+        model: "test-model".to_string(),
+        ..Default::default()
+    };
+
+    adapter
+        .generate(&provider, config, &QaSignature, "Answer the question.", &[], &qa_inputs())
+        .await
+        .unwrap();
+
+    count.load(Ordering::SeqCst)
+}
+
+#[tokio::test]
+async fn test_debug_events_only_when_enabled() {
+    assert_eq!(count_debug_events(false).await, 0);
+    // One event for the outgoing messages and one for the raw completion
+    assert_eq!(count_debug_events(true).await, 2);
+}
+
+#[test]
+fn test_debug_format_messages_truncates_content() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let messages = vec![
+        Message::system("x".repeat(300)),
+        Message::user("What is the capital of France?"),
+    ];
+
+    let formatted =
+        <ChatAdapter as Adapter<QaSignature>>::debug_format_messages(&adapter, &messages);
+
+    assert_eq!(
+        formatted,
+        format!("[0] system: {}...\n[1] user: What is the capital of France?", "x".repeat(200))
+    );
+}
+
+fn recording_config() -> (AdapterConfig, Arc<Mutex<Vec<FieldUpdate>>>) {
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let sink = updates.clone();
+    let config = AdapterConfig {
+        on_partial_output: Some(Arc::new(move |update| sink.lock().unwrap().push(update))),
+        ..AdapterConfig::default()
+    };
+    (config, updates)
+}
+
+fn collect_updates(completion: &str) -> Vec<FieldUpdate> {
+    let (config, updates) = recording_config();
+    let adapter = ChatAdapter::new(config);
+
+    let _ = <ChatAdapter as Adapter<QaSignature>>::parse(
+        &adapter,
+        completion,
+        &QaSignature::prompt_output_schema(),
+    );
+
+    updates.lock().unwrap().clone()
+}
+
+fn summarize(updates: &[FieldUpdate]) -> Vec<(&str, bool)> {
+    updates
+        .iter()
+        .map(|u| (u.field_name.as_str(), u.is_final))
+        .collect()
+}
+
+#[test]
+fn test_partial_output_callback_parse_only_emits_partial_updates() {
+    let updates = collect_updates(
+        "[[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.9\n\n[[ ## completed ## ]]",
+    );
+
+    assert_eq!(summarize(&updates), vec![("answer", false), ("confidence", false)]);
+    assert_eq!(updates[1].value, serde_json::json!(0.9));
+}
+
+#[test]
+fn test_partial_output_callback_without_completed_marker() {
+    let updates = collect_updates("[[ ## answer ## ]]\nParis");
+
+    assert_eq!(updates.len(), 1);
+    assert!(!updates[0].is_final);
+}
+
+#[tokio::test]
+async fn test_partial_output_callback_final_only_after_validation() {
+    let (config, updates) = recording_config();
+    let adapter = ChatAdapter::new(config);
+    let provider = ScriptedProvider::new(
+        vec![(OUT_OF_RANGE_ANSWER, FinishReason::Stop), (FULL_ANSWER, FinishReason::Stop)],
+        None,
+    );
+
+    adapter
+        .generate(&provider, CompletionConfig::default(), &RatedSignature, "", &[], &qa_inputs())
+        .await
+        .unwrap();
+
+    let updates = updates.lock().unwrap();
+    // The rejected first answer is never reported as final
+    assert_eq!(
+        summarize(&updates),
+        vec![
+            ("answer", false),
+            ("confidence", false),
+            ("answer", false),
+            ("confidence", false),
+            ("answer", true),
+            ("confidence", true),
+        ]
+    );
+    assert_eq!(updates[5].value, serde_json::json!(0.9));
+}
+
+#[test]
+fn test_partial_output_callback_skips_chain_of_thought_rationale_parse() {
+    let (config, updates) = recording_config();
+    let adapter = ChainOfThoughtAdapter::new(ChatAdapter::new(config));
+
+    <ChainOfThoughtAdapter<ChatAdapter> as Adapter<QaSignature>>::parse(
+        &adapter,
+        "[[ ## rationale ## ]]\nIt is the capital.\n\n[[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n0.9\n\n[[ ## completed ## ]]",
+        &QaSignature::prompt_output_schema(),
+    )
+    .unwrap();
+
+    // Each field once, from the parse of the outputs
+    let updates = updates.lock().unwrap();
+    for field in ["answer", "confidence"] {
+        assert_eq!(updates.iter().filter(|u| u.field_name == field).count(), 1);
+    }
+}
+
+fn demos_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("dsrs-demos-{}-{}.jsonl", name, std::process::id()))
+}
+
+#[test]
+fn test_demo_jsonl_round_trip() {
+    let path = demos_path("round-trip");
+    let demos = vec![
+        Demo {
+            inputs: qa_inputs(),
+            outputs: QaOutputs {
+                answer: "Paris".to_string(),
+                confidence: 0.9,
+            },
+        },
+        Demo {
+            inputs: QaInputs {
+                question: "Line one\nline two".to_string(),
+            },
+            outputs: QaOutputs {
+                answer: "Two lines".to_string(),
+                confidence: 0.5,
+            },
+        },
+    ];
+
+    Demo::save_jsonl(&demos, &path).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+    let loaded: Vec<Demo<QaInputs, QaOutputs>> = Demo::load_jsonl(&path).unwrap();
+
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded[1].inputs.question, "Line one\nline two");
+    assert_eq!(loaded[0].outputs, demos[0].outputs);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_demo_load_jsonl_reports_line_number() {
+    let path = demos_path("bad-line");
+    std::fs::write(
+        &path,
+        "{\"inputs\": {\"question\": \"Q\"}, \"outputs\": {\"answer\": \"A\", \"confidence\": 1.0}}\n\
+         \n\
+         {\"inputs\": {\"question\": \"Q\"}}\n",
+    )
+    .unwrap();
+
+    let Err(error) = Demo::<QaInputs, QaOutputs>::load_jsonl(&path) else {
+        panic!("Expected the missing outputs to fail");
+    };
+
+    assert!(error.to_string().contains("line 3"), "{}", error);
+    std::fs::remove_file(&path).unwrap();
+}
+
+// Reference-free: trusts the model's own confidence
+struct ConfidenceMetric;
+
+impl EvaluationMetric<QaSignature> for ConfidenceMetric {
+    fn score(&self, _inputs: &QaInputs, _expected: &QaOutputs, actual: &QaOutputs) -> f64 {
+        actual.confidence
+    }
+}
+
+fn numbered_demos(count: usize) -> Vec<Demo<QaInputs, QaOutputs>> {
+    (0..count)
+        .map(|index| Demo {
+            inputs: QaInputs {
+                question: format!("Question {}", index),
+            },
+            outputs: QaOutputs {
+                answer: format!("Answer {}", index),
+                confidence: index as f64 / count as f64,
+            },
+        })
+        .collect()
+}
+
+#[test]
+fn test_demo_filter_by_score_keeps_demos_at_threshold() {
+    let demos = numbered_demos(4);
+    assert_eq!(demos[2].score(&ConfidenceMetric), 0.5);
+
+    let kept = Demo::filter_by_score(demos, 0.5, &ConfidenceMetric);
+
+    let answers: Vec<_> = kept.iter().map(|demo| demo.outputs.answer.as_str()).collect();
+    assert_eq!(answers, ["Answer 2", "Answer 3"]);
+}
+
+#[test]
+fn test_demo_sample_and_shuffle_are_reproducible_with_seed() {
+    let mut demos = numbered_demos(10);
+
+    let questions = |demos: &[&Demo<QaInputs, QaOutputs>]| -> Vec<String> {
+        demos.iter().map(|demo| demo.inputs.question.clone()).collect()
+    };
+
+    let sample = questions(&Demo::sample(&demos, 3, &mut StdRng::seed_from_u64(7)));
+    let again = questions(&Demo::sample(&demos, 3, &mut StdRng::seed_from_u64(7)));
+    assert_eq!(sample, again);
+    assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 3);
+    assert_eq!(Demo::sample(&demos, 20, &mut StdRng::seed_from_u64(7)).len(), 10);
+
+    let original = questions(&demos.iter().collect::<Vec<_>>());
+    Demo::shuffle(&mut demos, &mut StdRng::seed_from_u64(7));
+    let mut shuffled = questions(&demos.iter().collect::<Vec<_>>());
+    assert_ne!(shuffled, original);
+    shuffled.sort();
+    assert_eq!(shuffled, original);
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+struct ChatTurnInputs {
+    /// The user's message
+    message: String,
+    #[signature(history)]
+    history: Option<ChatHistory>,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+struct ChatTurnOutputs {
+    /// The reply
+    reply: String,
+}
+
+#[derive(dsrs_core::primatives::Signature)]
+#[signature(
+    name = "ChatTurn",
+    desc = "Reply to the user",
+    inputs = ChatTurnInputs,
+    outputs = ChatTurnOutputs
+)]
+struct ChatTurnSignature {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+fn chat_turn_demo(message: &str) -> Demo<ChatTurnInputs, ChatTurnOutputs> {
+    Demo {
+        inputs: ChatTurnInputs {
+            message: message.to_string(),
+            history: None,
+        },
+        outputs: ChatTurnOutputs {
+            reply: format!("Reply to {}", message),
+        },
+    }
+}
+
+// System message, four history messages, two demos and the current message; every
+// message counts as one token, so the budget is a message count
+fn truncated_request(budget: usize, strategy: ContextTruncationStrategy) -> Vec<Message> {
+    chat_turn_request(AdapterConfig {
+        max_context_tokens: Some(budget),
+        token_count_fn: Some(Arc::new(|_| 1)),
+        context_truncation: strategy,
+        ..Default::default()
+    })
+}
+
+// The request `truncated_request` describes, truncated as `config` says
+fn chat_turn_request(config: AdapterConfig) -> Vec<Message> {
+    let adapter = ChatAdapter::new(config);
+    let inputs = ChatTurnInputs {
+        message: "Current".to_string(),
+        history: Some(ChatHistory::new(vec![
+            Message::user("History 1"),
+            Message::assistant(Some("History 2"), None),
+            Message::user("History 3"),
+            Message::assistant(Some("History 4"), None),
+        ])),
+    };
+    let signature = ChatTurnSignature {
+        instructions: String::new(),
+    };
+    let demos = vec![chat_turn_demo("Demo 1"), chat_turn_demo("Demo 2")];
+
+    let (messages, _) = <ChatAdapter as Adapter<ChatTurnSignature>>::prepare_request(
+        &adapter,
+        CompletionConfig::default(),
+        &signature,
+        "",
+        &demos,
+        &inputs,
+        None,
+    )
+    .unwrap();
+    messages
+}
+
+fn contents(messages: &[Message]) -> Vec<String> {
+    messages
+        .iter()
+        .skip(1)
+        .map(|message| match message {
+            Message::User { content } => ContentTypes::join_text(content),
+            Message::Assistant {
+                content: Some(ContentTypes::Text(text)),
+                ..
+            } => text.clone(),
+            other => panic!("Unexpected message {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_context_within_budget_is_untouched() {
+    assert_eq!(
+        truncated_request(10, ContextTruncationStrategy::TruncateOldestFirst).len(),
+        10
+    );
+}
+
+#[test]
+fn test_truncate_oldest_first_drops_history_then_demos() {
+    let messages = truncated_request(5, ContextTruncationStrategy::TruncateOldestFirst);
+
+    let kept = contents(&messages);
+    assert_eq!(kept.len(), 3);
+    assert!(kept[0].contains("Demo 2"));
+    assert!(kept[1].contains("Reply to Demo 2"));
+    assert!(kept[2].contains("Current"));
+}
+
+#[test]
+fn test_truncate_demos_keeps_history() {
+    let messages = truncated_request(7, ContextTruncationStrategy::TruncateDemos);
+
+    let kept = contents(&messages);
+    assert_eq!(&kept[..4], ["History 1", "History 2", "History 3", "History 4"]);
+    assert_eq!(kept.len(), 5);
+}
+
+#[test]
+fn test_truncate_history_keeps_recent_turns_and_demos() {
+    let messages = truncated_request(8, ContextTruncationStrategy::TruncateHistory);
+    let kept = contents(&messages);
+    assert_eq!(&kept[..2], ["History 3", "History 4"]);
+    assert_eq!(kept.len(), 7);
+
+    // Over budget with no history left, so the demos stay
+    assert_eq!(
+        truncated_request(1, ContextTruncationStrategy::TruncateHistory).len(),
+        6
+    );
+}
+
+#[test]
+fn test_truncate_history_drops_whole_turns() {
+    // One message over budget still drops the whole first turn, reply included
+    let messages = truncated_request(9, ContextTruncationStrategy::TruncateHistory);
+
+    let kept = contents(&messages);
+    assert_eq!(&kept[..2], ["History 3", "History 4"]);
+    assert_eq!(kept.len(), 7);
+}
+
+#[test]
+fn test_context_window_manager_on_adapter_config_truncates_demos() {
+    let adapter = ChatAdapter::new(AdapterConfig {
+        context_window: Some(
+            ContextWindowManager::new(7, |_| 1).with_strategy(TrimStrategy::TrimDemoMessages),
+        ),
+        ..Default::default()
+    });
+    let inputs = ChatTurnInputs {
+        message: "Current".to_string(),
+        history: Some(ChatHistory::new(vec![
+            Message::user("History 1"),
+            Message::assistant(Some("History 2"), None),
+        ])),
+    };
+    let signature = ChatTurnSignature {
+        instructions: String::new(),
+    };
+    let demos = vec![chat_turn_demo("Demo 1"), chat_turn_demo("Demo 2")];
+
+    let (messages, _) = <ChatAdapter as Adapter<ChatTurnSignature>>::prepare_request(
+        &adapter,
+        CompletionConfig::default(),
+        &signature,
+        "",
+        &demos,
+        &inputs,
+        None,
+    )
+    .unwrap();
+
+    let kept = contents(&messages);
+    assert_eq!(kept.len(), 5);
+    assert_eq!(&kept[..2], ["History 1", "History 2"]);
+    assert!(kept[2].contains("Demo 2"));
+}
+
+fn windowed_request(budget: usize, strategy: TrimStrategy) -> Vec<Message> {
+    chat_turn_request(AdapterConfig::default().with_context_window(
+        ContextWindowManager::new(budget, |_| 1).with_strategy(strategy),
+    ))
+}
+
+#[test]
+fn test_context_window_manager_trim_history_first_drops_history_before_demos() {
+    let kept = contents(&windowed_request(6, TrimStrategy::TrimHistoryFirst));
+    assert_eq!(kept.len(), 5);
+    assert!(kept.iter().all(|content| !content.contains("History")));
+    assert!(kept[0].contains("Demo 1"));
+
+    // Once the history is gone, the demos go too
+    let kept = contents(&windowed_request(4, TrimStrategy::TrimHistoryFirst));
+    assert_eq!(kept.len(), 3);
+    assert!(kept[0].contains("Demo 2"));
+}
+
+#[test]
+fn test_trim_history_first_agrees_with_adapter_truncation() {
+    let untrimmed = windowed_request(100, TrimStrategy::TrimHistoryFirst);
+    for budget in [9, 6, 4, 2] {
+        let mut trimmed = untrimmed.clone();
+        ContextWindowManager::new(budget, |_| 1)
+            .trim_to_fit(&mut trimmed, TrimStrategy::TrimHistoryFirst);
+        assert_eq!(
+            trimmed,
+            windowed_request(budget, TrimStrategy::TrimHistoryFirst),
+            "budget {}",
+            budget
+        );
+    }
+}
+
+fn window_messages() -> Vec<Message> {
+    vec![
+        Message::system("System"),
+        Message::user("Old question"),
+        Message::assistant(None::<String>, Some(vec![])),
+        Message::tool("Tool result", "call_1"),
+        Message::assistant(Some("Old answer"), None),
+        Message::user("Demo question"),
+        Message::assistant(Some("Demo answer"), None),
+        Message::user("Current"),
+    ]
+}
+
+#[test]
+fn test_context_window_manager_estimates_and_checks_fit() {
+    let manager = ContextWindowManager::new(10, |text| text.split_whitespace().count());
+    let messages = window_messages();
+
+    // The empty assistant message counts nothing
+    assert_eq!(manager.estimate_tokens(&messages), 12);
+    assert!(!manager.fits_in_window(&messages));
+    assert!(manager.fits_in_window(&messages[..5]));
+}
+
+#[test]
+fn test_context_window_manager_trim_strategies() {
+    let manager = ContextWindowManager::new(5, |_| 1);
+    let trimmed = |strategy| {
+        let mut messages = window_messages();
+        let dropped = manager.trim_to_fit(&mut messages, strategy);
+        (dropped, messages)
+    };
+
+    // The tool call goes with its result
+    let (dropped, messages) = trimmed(TrimStrategy::TrimOldestMessages);
+    assert_eq!(dropped, 3);
+    assert_eq!(messages[1], Message::assistant(Some("Old answer"), None));
+
+    // The whole first turn goes at once
+    let (dropped, messages) = trimmed(TrimStrategy::TrimHistoryFirst);
+    assert_eq!(dropped, 4);
+    assert_eq!(messages[1], Message::user("Demo question"));
+
+    // The tool call has no text, so only the demo pair goes
+    let (dropped, messages) = trimmed(TrimStrategy::TrimDemoMessages);
+    assert_eq!(dropped, 2);
+    assert_eq!(messages[4], Message::assistant(Some("Old answer"), None));
+    assert_eq!(messages[5], Message::user("Current"));
+}
+
+// Inputs whose serialized form breaks their own schema
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+struct LookupInputs {
+    // An empty query is left out, so the required field goes missing
+    #[serde(skip_serializing_if = "String::is_empty")]
+    query: String,
+    // Sent as text though the schema promises a number
+    #[schemars(with = "u32")]
+    limit: String,
+}
+
+struct LookupSignature;
+
+impl Signature for LookupSignature {
+    type Inputs = LookupInputs;
+    type Outputs = QaOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Look it up."
+    }
+
+    fn name(&self) -> &str {
+        "Lookup"
+    }
+
+    fn desc(&self) -> &str {
+        "Lookup"
+    }
+}
+
+#[tokio::test]
+async fn test_generate_rejects_invalid_inputs_before_calling_provider() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let provider = ScriptedProvider::new(vec![(FULL_ANSWER, FinishReason::Stop)], None);
+    let inputs = LookupInputs {
+        query: String::new(),
+        limit: "ten".to_string(),
+    };
+
+    let error = adapter
+        .generate(&provider, CompletionConfig::default(), &LookupSignature, "", &[], &inputs)
+        .await
+        .unwrap_err();
+
+    let errors = error.downcast::<ValidationErrors>().unwrap().errors;
+    assert_eq!(
+        errors,
+        vec![
+            ValidationError::new("query", "missing required field"),
+            ValidationError::new("limit", "expected integer, found string"),
+        ]
+    );
+    assert!(provider.requests.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_validate_inputs_accepts_well_formed_inputs() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+
+    <ChatAdapter as Adapter<QaSignature>>::validate_inputs(&adapter, &QaSignature, &qa_inputs())
+        .unwrap();
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+struct RatedOutputs {
+    answer: String,
+    #[schemars(range(min = 0.0, max = 1.0))]
+    confidence: f64,
+}
+
+struct RatedSignature;
+
+impl Signature for RatedSignature {
+    type Inputs = QaInputs;
+    type Outputs = RatedOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Answer the question."
+    }
+
+    fn name(&self) -> &str {
+        "Rated"
+    }
+
+    fn desc(&self) -> &str {
+        "Rated answers"
+    }
+}
+
+const OUT_OF_RANGE_ANSWER: &str =
+    "[[ ## answer ## ]]\nParis\n\n[[ ## confidence ## ]]\n1.5\n\n[[ ## completed ## ]]";
+
+#[tokio::test]
+async fn test_invalid_outputs_are_retried_with_the_errors() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let provider = ScriptedProvider::new(
+        vec![(OUT_OF_RANGE_ANSWER, FinishReason::Stop), (FULL_ANSWER, FinishReason::Stop)],
+        None,
+    );
+
+    let outputs = adapter
+        .generate(&provider, CompletionConfig::default(), &RatedSignature, "", &[], &qa_inputs())
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.confidence, 0.9);
+    let requests = provider.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    let Some(Message::User { content }) = requests[1].0.last() else {
+        panic!("Expected the retry to end with a user message");
+    };
+    let retry = ContentTypes::join_text(content);
+    assert!(retry.contains("confidence"), "{}", retry);
+    assert!(retry.contains("1.5"), "{}", retry);
+}
+
+#[tokio::test]
+async fn test_invalid_outputs_fail_once_attempts_run_out() {
+    let adapter = ChatAdapter::new(AdapterConfig::default().with_max_retries(1));
+    let provider = ScriptedProvider::new(vec![(OUT_OF_RANGE_ANSWER, FinishReason::Stop)], None);
+
+    let error = adapter
+        .generate(&provider, CompletionConfig::default(), &RatedSignature, "", &[], &qa_inputs())
+        .await
+        .unwrap_err();
+
+    let errors = error.downcast::<ValidationErrors>().unwrap().errors;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].field, "confidence");
+}
+
+fn last_user_text(messages: &[Message]) -> String {
+    let Some(Message::User { content }) = messages.last() else {
+        panic!("Expected the messages to end with a user message");
+    };
+    ContentTypes::join_text(content)
+}
+
+#[test]
+fn test_format_messages_with_context_prepends_context_to_user_message() {
+    let config = CompletionConfig::default();
+    let context = Some("Paris has been France's capital since 987.");
+
+    let chat = ChatAdapter::new(AdapterConfig::default());
+    let messages = <ChatAdapter as Adapter<QaSignature>>::format_messages_with_context(
+        &chat,
+        &config,
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+        context,
+    )
+    .unwrap();
+    assert!(last_user_text(&messages).starts_with(
+        "[[ ## context ## ]]\nParis has been France's capital since 987.\n\n[[ ## question ## ]]"
+    ));
+
+    let json = JsonAdapter::new(AdapterConfig::default());
+    let messages = <JsonAdapter as Adapter<QaSignature>>::format_messages_with_context(
+        &json,
+        &config,
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+        context,
+    )
+    .unwrap();
+    assert!(last_user_text(&messages).starts_with(
+        "context: Paris has been France's capital since 987.\nquestion: What is the capital"
+    ));
+
+    let without = <ChatAdapter as Adapter<QaSignature>>::format_messages_with_context(
+        &chat,
+        &config,
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+        None,
+    )
+    .unwrap();
+    let plain = <ChatAdapter as Adapter<QaSignature>>::format_messages(
+        &chat,
+        &config,
+        "Answer the question.",
+        &[],
+        &qa_inputs(),
+    )
+    .unwrap();
+    assert_eq!(without, plain);
+}
+
+#[tokio::test]
+async fn test_generate_with_context_sends_context_with_inputs() {
+    let adapter = ChatAdapter::new(AdapterConfig::default());
+    let provider = ScriptedProvider::new(vec![(FULL_ANSWER, FinishReason::Stop)], None);
+
+    let outputs = adapter
+        .generate_with_context(
+            &provider,
+            CompletionConfig::default(),
+            &QaSignature,
+            "Answer the question.",
+            &[],
+            &qa_inputs(),
+            Some("Paris is the capital and largest city of France."),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    let requests = provider.requests.lock().unwrap();
+    let user = last_user_text(&requests[0].0);
+    assert!(user.starts_with("[[ ## context ## ]]\nParis is the capital"), "{}", user);
+    assert!(user.contains("[[ ## question ## ]]\nWhat is the capital of France?"), "{}", user);
+}
+
+#[test]
+fn test_adapter_config_with_methods() {
+    let config = AdapterConfig::default()
+        .with_native_function_calling(true)
+        .with_max_retries(5)
+        .with_cache(false)
+        .with_max_context_tokens(1024)
+        .with_token_count_fn(|text| text.len());
+
+    assert!(config.use_native_function_calling);
+    assert_eq!(config.max_retries, 5);
+    assert!(!config.enable_cache);
+    assert_eq!(config.max_context_tokens, Some(1024));
+    assert_eq!((config.token_count_fn.unwrap())("four"), 4);
+    assert!(config.use_correction_prompt && !config.debug_mode);
+}
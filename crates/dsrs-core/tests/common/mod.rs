@@ -0,0 +1,76 @@
+// Signature fixtures shared by the integration tests; each test crate uses only some of them
+#![allow(dead_code)]
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use dsrs_core::primatives::Signature;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct QaInputs {
+    /// The question to answer
+    pub question: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct QaOutputs {
+    /// The answer to the question
+    pub answer: String,
+}
+
+pub struct QaSignature;
+
+impl Signature for QaSignature {
+    type Inputs = QaInputs;
+    type Outputs = QaOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Answer the question."
+    }
+
+    fn name(&self) -> &str {
+        "QA"
+    }
+
+    fn desc(&self) -> &str {
+        "Question answering"
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct ContextQaInputs {
+    /// Background for the question
+    pub context: String,
+    /// The question to answer
+    pub question: String,
+}
+
+/// Like `QaSignature`, answering from the given context
+pub struct ContextQaSignature;
+
+impl Signature for ContextQaSignature {
+    type Inputs = ContextQaInputs;
+    type Outputs = QaOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Answer the question."
+    }
+
+    fn name(&self) -> &str {
+        "ContextQA"
+    }
+
+    fn desc(&self) -> &str {
+        "Question answering from context"
+    }
+}
+
+pub fn question(text: &str) -> QaInputs {
+    QaInputs {
+        question: text.to_string(),
+    }
+}
@@ -0,0 +1,803 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use async_trait::async_trait;
+
+mod common;
+
+use common::{ContextQaInputs, ContextQaSignature, QaOutputs};
+use dsrs_core::{
+    adapters::{chat_adapter::ChatAdapter, traits::AdapterConfig},
+    evaluation::{EvaluationMetric, LLMJudgeMetric},
+    modules::{
+        ChainModule, CritiqueAndRevise, Document, JudgeModule, ParallelModule, RAGModule, ReActModule, Retriever, SelfConsistency,
+        format_documents, majority_vote_strings,
+    },
+    predict::Predict,
+    primatives::{
+        Module, Signature, SignatureValidationError, ValidationChain, ValidationError,
+        ValidationErrors,
+    },
+    providers::{MockProvider, OpenAIProvider},
+    providers::models::{ContentTypes, Message, ToolCall},
+    tools::{FunctionRegistry, ToolError, ToolErrorKind, ToolExecutor},
+};
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+struct DocumentInputs {
+    /// The document to summarize
+    document: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+struct SummaryOutputs {
+    /// A one-sentence summary
+    summary: String,
+}
+
+struct SummarizeSignature;
+
+impl Signature for SummarizeSignature {
+    type Inputs = DocumentInputs;
+    type Outputs = SummaryOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Summarize the document."
+    }
+
+    fn name(&self) -> &str {
+        "Summarize"
+    }
+
+    fn desc(&self) -> &str {
+        "Document summarization"
+    }
+}
+
+fn document(text: &str) -> DocumentInputs {
+    DocumentInputs {
+        document: text.to_string(),
+    }
+}
+
+fn answer_completion(answer: &str) -> String {
+    format!("[[ ## answer ## ]]\n{}\n\n[[ ## completed ## ]]", answer)
+}
+
+fn qa_predict(answer: &str) -> Predict<ContextQaSignature, MockProvider> {
+    Predict::new(
+        ContextQaSignature,
+        MockProvider::with_texts([answer_completion(answer)]),
+    )
+}
+
+fn tower_question() -> ContextQaInputs {
+    ContextQaInputs {
+        context: "The Eiffel Tower is in Paris.".to_string(),
+        question: "Where is the tower?".to_string(),
+    }
+}
+
+fn summarize_then_answer(
+    summary: &str,
+    answer: &str,
+) -> ChainModule<
+    Predict<SummarizeSignature, MockProvider>,
+    Predict<ContextQaSignature, MockProvider>,
+> {
+    let summarize = Predict::new(
+        SummarizeSignature,
+        MockProvider::with_texts([format!(
+            "[[ ## summary ## ]]\n{}\n\n[[ ## completed ## ]]",
+            summary
+        )]),
+    );
+    let answer = qa_predict(answer);
+    ChainModule::new(summarize, answer, |outputs: SummaryOutputs| ContextQaInputs {
+        context: outputs.summary,
+        question: "Where is the tower?".to_string(),
+    })
+}
+
+#[tokio::test]
+async fn test_chain_pipes_outputs_into_next_module() {
+    let chain = summarize_then_answer("The Eiffel Tower is in Paris.", "Paris");
+
+    let outputs = chain
+        .aforward(document("A long article about the Eiffel Tower."))
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    let received = chain.second().lm().received();
+    assert!(format!("{:?}", received[0]).contains("The Eiffel Tower is in Paris."));
+}
+
+#[tokio::test]
+async fn test_chain_stops_when_first_module_fails() {
+    let summarize = Predict::new(SummarizeSignature, MockProvider::new(Vec::new()));
+    let answer = Predict::new(ContextQaSignature, MockProvider::with_texts(["unused"]));
+    let chain = ChainModule::new(summarize, answer, |outputs: SummaryOutputs| ContextQaInputs {
+        context: outputs.summary,
+        question: String::new(),
+    });
+
+    assert!(chain.aforward(document("Anything")).await.is_err());
+    assert_eq!(chain.second().lm().call_count(), 0);
+}
+
+#[test]
+fn test_chain_parameters_cover_both_modules() {
+    let chain = summarize_then_answer("", "");
+
+    assert_eq!(chain.parameters().len(), 4);
+    let names: Vec<String> = chain
+        .named_parameters()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "first.demos",
+            "first.instructions",
+            "second.demos",
+            "second.instructions"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_parallel_runs_every_module() {
+    let parallel = ParallelModule::new(vec![qa_predict("Paris"), qa_predict("Lyon")]);
+
+    let outputs = parallel.aforward(tower_question()).await.unwrap();
+
+    let answers: Vec<&str> = outputs.iter().map(|o| o.answer.as_str()).collect();
+    assert_eq!(answers, vec!["Paris", "Lyon"]);
+    assert!(parallel.modules().iter().all(|m| m.lm().call_count() == 1));
+}
+
+#[tokio::test]
+async fn test_parallel_fails_if_any_module_fails() {
+    let failing = Predict::new(ContextQaSignature, MockProvider::new(Vec::new()));
+    let parallel = ParallelModule::new(vec![qa_predict("Paris"), failing]);
+
+    assert!(parallel.aforward(tower_question()).await.is_err());
+}
+
+// Answers without calling a model
+struct FixedAnswer(&'static str);
+
+#[async_trait]
+impl Module for FixedAnswer {
+    type Sig = ContextQaSignature;
+
+    async fn aforward(&self, _inputs: ContextQaInputs) -> anyhow::Result<QaOutputs> {
+        Ok(QaOutputs {
+            answer: self.0.to_string(),
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_parallel_runs_boxed_modules_of_different_types() {
+    let modules: Vec<Box<dyn Module<Sig = ContextQaSignature>>> =
+        vec![Box::new(qa_predict("Paris")), Box::new(FixedAnswer("Lyon"))];
+    let parallel = ParallelModule::new(modules);
+
+    let outputs = parallel.aforward(tower_question()).await.unwrap();
+
+    let answers: Vec<&str> = outputs.iter().map(|o| o.answer.as_str()).collect();
+    assert_eq!(answers, vec!["Paris", "Lyon"]);
+    assert_eq!(parallel.modules()[0].parameters().len(), 2);
+    assert!(parallel.modules()[1].parameters().is_empty());
+}
+
+#[tokio::test]
+async fn test_parallel_with_merge_majority_votes() {
+    let voting = ParallelModule::new(vec![
+        qa_predict("Paris"),
+        qa_predict("Lyon"),
+        qa_predict("Paris"),
+    ])
+    .with_merge(|outputs: Vec<QaOutputs>| {
+        let count = |answer: &str| outputs.iter().filter(|o| o.answer == answer).count();
+        outputs
+            .iter()
+            .max_by_key(|o| count(&o.answer))
+            .cloned()
+            .unwrap()
+    });
+
+    let outputs = voting.aforward(tower_question()).await.unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    let names: Vec<String> = voting
+        .named_parameters()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(names[0], "0.demos");
+    assert_eq!(names.len(), 6);
+}
+
+// Knows where one landmark is
+struct LandmarkLookup;
+
+#[async_trait]
+impl ToolExecutor for LandmarkLookup {
+    async fn execute(&self, call: &ToolCall) -> Result<String, ToolError> {
+        match call.arguments["landmark"].as_str() {
+            Some("Eiffel Tower") => Ok("Paris".to_string()),
+            _ => Err(ToolError::new(
+                ToolErrorKind::InvalidArguments,
+                "unknown landmark",
+            )),
+        }
+    }
+}
+
+fn lookup(id: &str) -> ToolCall {
+    ToolCall {
+        id: id.to_string(),
+        name: "lookup".to_string(),
+        arguments: serde_json::json!({"landmark": "Eiffel Tower"}),
+    }
+}
+
+fn lookup_call(id: &str) -> Message {
+    Message::assistant(None::<String>, Some(vec![lookup(id)]))
+}
+
+#[tokio::test]
+async fn test_react_runs_tools_until_final_answer() {
+    let lm = MockProvider::new(vec![
+        lookup_call("call_1"),
+        Message::assistant(Some(answer_completion("Paris")), None),
+    ]);
+    let react = ReActModule::new(ContextQaSignature, lm).with_executor(LandmarkLookup);
+
+    let outputs = react.aforward(tower_question()).await.unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    let received = react.predict().lm().received();
+    assert_eq!(received.len(), 2);
+    let second = &received[1];
+    assert_eq!(second[second.len() - 2], lookup_call("call_1"));
+    assert_eq!(second[second.len() - 1], Message::tool("Paris", "call_1"));
+}
+
+#[tokio::test]
+async fn test_react_stops_after_max_steps() {
+    let lm = MockProvider::new(vec![
+        lookup_call("call_1"),
+        lookup_call("call_2"),
+        Message::assistant(Some(answer_completion("Paris")), None),
+    ]);
+    let react = ReActModule::new(ContextQaSignature, lm)
+        .with_executor(LandmarkLookup)
+        .with_max_steps(2);
+
+    let error = react.aforward(tower_question()).await.unwrap_err();
+
+    assert!(error.to_string().contains("2 steps"));
+    assert_eq!(react.predict().lm().call_count(), 2);
+}
+
+// Like `ContextQaSignature`, accepting only one-word answers
+struct OneWordAnswerSignature;
+
+impl Signature for OneWordAnswerSignature {
+    type Inputs = ContextQaInputs;
+    type Outputs = QaOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Answer the question in one word."
+    }
+
+    fn name(&self) -> &str {
+        "OneWordAnswer"
+    }
+
+    fn desc(&self) -> &str {
+        "One-word question answering"
+    }
+
+    fn output_validators(&self) -> ValidationChain<QaOutputs> {
+        let mut validators = ValidationChain::new();
+        validators.add(|outputs: &QaOutputs| {
+            if outputs.answer.split_whitespace().count() == 1 {
+                Ok(())
+            } else {
+                Err(ValidationError::new("answer", "must be one word"))
+            }
+        });
+        validators
+    }
+}
+
+fn lookup_then_answers(answers: &[&str]) -> MockProvider {
+    let mut responses = vec![lookup_call("call_1")];
+    responses.extend(
+        answers
+            .iter()
+            .map(|answer| Message::assistant(Some(answer_completion(answer)), None)),
+    );
+    MockProvider::new(responses)
+}
+
+#[tokio::test]
+async fn test_react_retries_invalid_final_answer() {
+    let lm = lookup_then_answers(&["The city of Paris", "Paris"]);
+    let react = ReActModule::new(OneWordAnswerSignature, lm).with_executor(LandmarkLookup);
+
+    let outputs = react.aforward(tower_question()).await.unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    let received = react.predict().lm().received();
+    assert_eq!(received.len(), 3);
+    let Some(Message::User { content }) = received[2].last() else {
+        panic!("Expected the retry to end with a correction");
+    };
+    let correction = ContentTypes::join_text(content);
+    assert!(correction.contains("must be one word"), "{}", correction);
+}
+
+#[tokio::test]
+async fn test_react_rejects_invalid_final_answer_once_attempts_run_out() {
+    let lm = lookup_then_answers(&["The city of Paris"]);
+    let predict = Predict::with_adapter(
+        OneWordAnswerSignature,
+        lm,
+        ChatAdapter::new(AdapterConfig::default().with_max_retries(1)),
+    );
+    let react = ReActModule::from_predict(predict).with_executor(LandmarkLookup);
+
+    let error = react.aforward(tower_question()).await.unwrap_err();
+
+    let errors = error.downcast::<ValidationErrors>().unwrap().errors;
+    assert_eq!(errors, vec![ValidationError::new("answer", "must be one word")]);
+    assert_eq!(react.predict().lm().call_count(), 2);
+}
+
+// Like `ContextQaSignature`, but claiming tool inputs without a field for the calls
+struct MisconfiguredSignature;
+
+impl Signature for MisconfiguredSignature {
+    type Inputs = ContextQaInputs;
+    type Outputs = QaOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Answer the question."
+    }
+
+    fn name(&self) -> &str {
+        "Misconfigured"
+    }
+
+    fn desc(&self) -> &str {
+        "Misconfigured question answering"
+    }
+
+    fn validate(&self) -> Result<(), Vec<SignatureValidationError>> {
+        Err(vec![SignatureValidationError::ToolsWithoutToolCalls])
+    }
+}
+
+#[tokio::test]
+async fn test_react_checks_signature_before_calling_provider() {
+    let lm = lookup_then_answers(&["Paris"]);
+    let react = ReActModule::new(MisconfiguredSignature, lm).with_executor(LandmarkLookup);
+
+    let error = react.aforward(tower_question()).await.unwrap_err();
+
+    assert!(error.to_string().starts_with("Invalid signature Misconfigured"), "{}", error);
+    assert_eq!(react.predict().lm().call_count(), 0);
+}
+
+/// Find the city a landmark is in
+#[derive(Deserialize, JsonSchema)]
+struct LookupArgs {
+    landmark: String,
+}
+
+#[tokio::test]
+async fn test_react_with_function_registry() {
+    let mut registry = FunctionRegistry::new();
+    registry.register("lookup", |args: LookupArgs| async move {
+        Ok(format!("The {} is in Paris", args.landmark))
+    });
+    let lm = MockProvider::new(vec![
+        lookup_call("call_1"),
+        Message::assistant(Some(answer_completion("Paris")), None),
+    ]);
+    let react = ReActModule::new(ContextQaSignature, lm).with_registry(registry);
+
+    let outputs = react.aforward(tower_question()).await.unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    let tools = react.predict().config().tools.clone().unwrap();
+    assert_eq!(tools[0].name, "lookup");
+    assert_eq!(tools[0].desc, "Find the city a landmark is in");
+    let received = react.predict().lm().received();
+    assert_eq!(
+        received[1].last(),
+        Some(&Message::tool("The Eiffel Tower is in Paris", "call_1"))
+    );
+}
+
+#[tokio::test]
+async fn test_react_with_function_registry_over_openai() {
+    let mut server = mockito::Server::new_async().await;
+    let tool_call = server
+        .mock("POST", "/chat/completions")
+        .with_body(
+            serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "test-model",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": null, "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {
+                            "name": "lookup",
+                            "arguments": "{\"landmark\": \"Eiffel Tower\"}"
+                        }
+                    }]},
+                    "finish_reason": "tool_calls"
+                }]
+            })
+            .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+    // The call goes back as a single JSON encoding of the arguments, followed by the result
+    let answer = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::Regex(
+                r#""arguments":"\{\\"landmark\\":\\"Eiffel Tower\\"\}""#.to_string(),
+            ),
+            mockito::Matcher::Regex("The Eiffel Tower is in Paris".to_string()),
+        ]))
+        .with_body(
+            serde_json::json!({
+                "id": "chatcmpl-2",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "test-model",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": answer_completion("Paris")},
+                    "finish_reason": "stop"
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let mut registry = FunctionRegistry::new();
+    registry.register("lookup", |args: LookupArgs| async move {
+        Ok(format!("The {} is in Paris", args.landmark))
+    });
+    let lm = OpenAIProvider::new("openai-key".to_string(), Some(server.url()));
+    let react = ReActModule::new(ContextQaSignature, lm).with_registry(registry);
+
+    let outputs = react.aforward(tower_question()).await.unwrap();
+
+    tool_call.assert_async().await;
+    answer.assert_async().await;
+    assert_eq!(outputs.answer, "Paris");
+}
+
+#[tokio::test]
+async fn test_react_without_executor_returns_after_tool_call() {
+    let lm = MockProvider::new(vec![Message::assistant(
+        Some(answer_completion("Let me look that up")),
+        Some(vec![lookup("call_1")]),
+    )]);
+    let react = ReActModule::new(ContextQaSignature, lm);
+
+    let outputs = react.aforward(tower_question()).await.unwrap();
+
+    assert_eq!(outputs.answer, "Let me look that up");
+    assert_eq!(react.predict().lm().call_count(), 1);
+}
+
+// Returns the documents mentioning any word of the query, in order
+struct KeywordRetriever {
+    documents: Vec<Document>,
+}
+
+#[async_trait]
+impl Retriever for KeywordRetriever {
+    async fn retrieve(&self, query: &str, top_k: usize) -> anyhow::Result<Vec<Document>> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        Ok(self
+            .documents
+            .iter()
+            .filter(|document| {
+                let text = document.text.to_lowercase();
+                words.iter().any(|word| text.contains(word.as_str()))
+            })
+            .take(top_k)
+            .cloned()
+            .collect())
+    }
+}
+
+fn landmark_rag(answer: &str) -> RAGModule<ContextQaSignature, MockProvider, KeywordRetriever> {
+    let retriever = KeywordRetriever {
+        documents: vec![
+            Document::new("eiffel", "The Eiffel Tower is in Paris."),
+            Document::new("colosseum", "The Colosseum is in Rome."),
+            Document::new("tower-of-pisa", "The Leaning Tower of Pisa is in Pisa."),
+        ],
+    };
+    RAGModule::new(
+        qa_predict(answer),
+        retriever,
+        |inputs: &ContextQaInputs| inputs.question.clone(),
+        |inputs: &mut ContextQaInputs, documents| inputs.context = format_documents(&documents),
+    )
+}
+
+#[tokio::test]
+async fn test_rag_injects_retrieved_documents_into_inputs() {
+    let rag = landmark_rag("Paris").with_top_k(1);
+    let inputs = ContextQaInputs {
+        context: String::new(),
+        question: "Where is the Eiffel Tower?".to_string(),
+    };
+
+    let outputs = rag.aforward(inputs).await.unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    let prompt = format!("{:?}", rag.predict().lm().received()[0]);
+    assert!(prompt.contains("[1] The Eiffel Tower is in Paris."), "{}", prompt);
+    assert!(!prompt.contains("Pisa"), "{}", prompt);
+    assert_eq!(rag.named_parameters()[0].0, "predict.demos");
+}
+
+#[test]
+fn test_format_documents_numbers_passages() {
+    let documents = [
+        Document::new("a", "First.").with_score(0.9),
+        Document::new("b", "Second."),
+    ];
+
+    assert_eq!(format_documents(&documents), "[1] First.\n\n[2] Second.");
+}
+
+fn sampled_answers(answers: &[&str]) -> Predict<ContextQaSignature, MockProvider> {
+    Predict::new(
+        ContextQaSignature,
+        MockProvider::with_texts(answers.iter().map(|answer| answer_completion(answer))),
+    )
+}
+
+#[tokio::test]
+async fn test_self_consistency_votes_over_samples() {
+    let voting = SelfConsistency::new(
+        sampled_answers(&["Paris.", "Lyon", "paris"]),
+        3,
+        |outputs: Vec<QaOutputs>| majority_vote_strings(outputs, |output| &output.answer),
+    );
+
+    let outputs = voting.aforward(tower_question()).await.unwrap();
+
+    assert_eq!(outputs.answer, "Paris.");
+    assert_eq!(voting.predict().lm().call_count(), 3);
+    assert_eq!(voting.predict().config().temperature, Some(0.7));
+}
+
+#[tokio::test]
+async fn test_self_consistency_votes_among_successful_samples() {
+    let voting = SelfConsistency::new(sampled_answers(&["Lyon"]), 2, |mut outputs| {
+        assert_eq!(outputs.len(), 1);
+        outputs.remove(0)
+    });
+
+    let outputs = voting.aforward(tower_question()).await.unwrap();
+
+    assert_eq!(outputs.answer, "Lyon");
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+struct CritiqueInputs {
+    /// The question that was answered
+    question: String,
+    /// The answer to review
+    answer: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+struct CritiqueOutputs {
+    /// What is wrong with the answer
+    critique: String,
+}
+
+struct CritiqueSignature;
+
+impl Signature for CritiqueSignature {
+    type Inputs = CritiqueInputs;
+    type Outputs = CritiqueOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Critique the answer."
+    }
+
+    fn name(&self) -> &str {
+        "Critique"
+    }
+
+    fn desc(&self) -> &str {
+        "Answer review"
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+struct ReviseInputs {
+    /// The question that was answered
+    question: String,
+    /// The answer to improve
+    answer: String,
+    /// What is wrong with the answer
+    critique: String,
+}
+
+struct ReviseSignature;
+
+impl Signature for ReviseSignature {
+    type Inputs = ReviseInputs;
+    type Outputs = QaOutputs;
+
+    fn set_instructions(&mut self, _instructions: String) {}
+
+    fn get_instructions(&self) -> &str {
+        "Revise the answer."
+    }
+
+    fn name(&self) -> &str {
+        "Revise"
+    }
+
+    fn desc(&self) -> &str {
+        "Answer revision"
+    }
+}
+
+fn critique_and_revise(
+    answers: &[&str],
+    critiques: &[&str],
+    revisions: &[&str],
+) -> CritiqueAndRevise<ContextQaSignature, CritiqueSignature, ReviseSignature, MockProvider> {
+    let critique = Predict::new(
+        CritiqueSignature,
+        MockProvider::with_texts(critiques.iter().map(|critique| {
+            format!("[[ ## critique ## ]]\n{}\n\n[[ ## completed ## ]]", critique)
+        })),
+    );
+    let revise = Predict::new(
+        ReviseSignature,
+        MockProvider::with_texts(revisions.iter().map(|answer| answer_completion(answer))),
+    );
+    CritiqueAndRevise::new(
+        sampled_answers(answers),
+        critique,
+        revise,
+        |inputs: &ContextQaInputs, outputs: &QaOutputs| CritiqueInputs {
+            question: inputs.question.clone(),
+            answer: outputs.answer.clone(),
+        },
+        |inputs: &ContextQaInputs, outputs: &QaOutputs, critique: &CritiqueOutputs| ReviseInputs {
+            question: inputs.question.clone(),
+            answer: outputs.answer.clone(),
+            critique: critique.critique.clone(),
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_critique_and_revise_feeds_each_revision_back() {
+    let module = critique_and_revise(
+        &["Lyon"],
+        &["Lyon is wrong.", "Add the country."],
+        &["Paris", "Paris, France"],
+    )
+    .with_max_revisions(2);
+
+    let outputs = module.aforward(tower_question()).await.unwrap();
+
+    assert_eq!(outputs.answer, "Paris, France");
+    let critiques = module.critique().lm().received();
+    assert!(format!("{:?}", critiques[1]).contains("Paris"));
+    let revisions = module.revise().lm().received();
+    assert!(format!("{:?}", revisions[1]).contains("Add the country."));
+}
+
+#[test]
+fn test_critique_and_revise_parameters_cover_all_modules() {
+    let module = critique_and_revise(&[], &[], &[]);
+
+    let names: Vec<String> = module
+        .named_parameters()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "draft.demos",
+            "draft.instructions",
+            "critique.demos",
+            "critique.instructions",
+            "revise.demos",
+            "revise.instructions"
+        ]
+    );
+    assert_eq!(module.parameters().len(), 6);
+}
+
+fn verdict(score: &str, explanation: &str) -> String {
+    format!(
+        "[[ ## score ## ]]\n{}\n\n[[ ## explanation ## ]]\n{}\n\n[[ ## completed ## ]]",
+        score, explanation
+    )
+}
+
+fn paris_answer() -> QaOutputs {
+    QaOutputs {
+        answer: "Paris".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_judge_scores_actual_against_expected() {
+    let judge: JudgeModule<ContextQaSignature, MockProvider> = JudgeModule::new(
+        MockProvider::with_texts([verdict("1.5", "Correct and concise.")]),
+        "The answer names the right city",
+    );
+    let actual = QaOutputs {
+        answer: "It is in Paris.".to_string(),
+    };
+
+    let score = judge
+        .evaluate(&tower_question(), &paris_answer(), &actual)
+        .await
+        .unwrap();
+
+    assert_eq!(score, 1.0);
+    let prompt = format!("{:?}", judge.predict().lm().received()[0]);
+    assert!(prompt.contains("It is in Paris."), "{}", prompt);
+    assert!(prompt.contains("The answer names the right city"), "{}", prompt);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_llm_judge_metric_uses_judge_score() {
+    let metric = LLMJudgeMetric::new(JudgeModule::<ContextQaSignature, _>::new(
+        MockProvider::with_texts([verdict("0.25", "Wrong city.")]),
+        "The answer names the right city",
+    ));
+    let actual = QaOutputs {
+        answer: "Lyon".to_string(),
+    };
+
+    let score = metric.score(&tower_question(), &paris_answer(), &actual);
+
+    assert_eq!(score, 0.25);
+}
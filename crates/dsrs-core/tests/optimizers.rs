@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+mod common;
+
+use common::{QaInputs, QaOutputs, QaSignature};
+use dsrs_core::{
+    evaluation::{ExactMatchMetric, LabeledDataset, LabeledExample},
+    modules::ChainOfThought,
+    optimizers::{BootstrapFewShot, InstructionOptimizer, PromptOptimizer},
+    predict::Predict,
+    providers::{
+        CompletionConfig, CompletionProvider, CompletionResponse, ContentTypes, FinishReason,
+        Message, MockProvider, ProviderError,
+    },
+};
+
+fn example(question: &str, answer: &str) -> (QaInputs, QaOutputs) {
+    (
+        QaInputs {
+            question: question.to_string(),
+        },
+        QaOutputs {
+            answer: answer.to_string(),
+        },
+    )
+}
+
+fn completion(answer: &str) -> String {
+    format!("[[ ## answer ## ]]\n{}\n\n[[ ## completed ## ]]", answer)
+}
+
+fn trainset() -> Vec<(QaInputs, QaOutputs)> {
+    vec![
+        example("Capital of France?", "Paris"),
+        example("Capital of Spain?", "Madrid"),
+        example("Capital of Italy?", "Rome"),
+    ]
+}
+
+// Full marks for answers matching the training set
+fn exact_match() -> impl Fn(&QaInputs, &QaOutputs) -> f64 + Send + Sync + 'static {
+    let expected: HashMap<String, String> = trainset()
+        .into_iter()
+        .map(|(inputs, outputs)| (inputs.question, outputs.answer))
+        .collect();
+    move |inputs, outputs| {
+        if expected.get(&inputs.question) == Some(&outputs.answer) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_bootstrap_keeps_passing_predictions() {
+    let lm = MockProvider::with_texts([
+        completion("Paris"),
+        completion("Lisbon"),
+        completion("Rome"),
+    ]);
+    let mut predict = Predict::new(QaSignature, lm);
+
+    BootstrapFewShot::new(exact_match(), trainset(), 2)
+        .compile(&mut predict)
+        .await
+        .unwrap();
+
+    let demos: Vec<&str> = predict
+        .demos()
+        .iter()
+        .map(|demo| demo.outputs.answer.as_str())
+        .collect();
+    assert_eq!(demos, vec!["Paris", "Rome"]);
+    assert_eq!(predict.lm().call_count(), 3);
+}
+
+#[tokio::test]
+async fn test_bootstrap_fills_remaining_slots_with_labeled_examples() {
+    let lm = MockProvider::with_texts([
+        completion("Paris"),
+        completion("Lisbon"),
+        completion("Milan"),
+    ]);
+    let mut predict = Predict::new(QaSignature, lm);
+
+    BootstrapFewShot::new(exact_match(), trainset(), 2)
+        .compile(&mut predict)
+        .await
+        .unwrap();
+
+    let demos: Vec<(&str, &str)> = predict
+        .demos()
+        .iter()
+        .map(|demo| (demo.inputs.question.as_str(), demo.outputs.answer.as_str()))
+        .collect();
+    assert_eq!(
+        demos,
+        vec![
+            ("Capital of France?", "Paris"),
+            ("Capital of Spain?", "Madrid")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_bootstrap_fails_when_every_prediction_fails() {
+    let mut predict = Predict::new(QaSignature, MockProvider::new(Vec::new()));
+
+    let result = BootstrapFewShot::new(exact_match(), trainset(), 2)
+        .compile(&mut predict)
+        .await;
+
+    assert!(result.is_err());
+    assert!(predict.demos().is_empty());
+}
+
+#[tokio::test]
+async fn test_bootstrap_with_evaluation_metric() {
+    let lm = MockProvider::with_texts([
+        completion("Paris"),
+        completion("Madrid"),
+        completion("Milan"),
+    ]);
+    let mut predict = Predict::new(QaSignature, lm);
+
+    BootstrapFewShot::with_metric(ExactMatchMetric, trainset(), 2)
+        .compile(&mut predict)
+        .await
+        .unwrap();
+
+    let demos: Vec<&str> = predict
+        .demos()
+        .iter()
+        .map(|demo| demo.outputs.answer.as_str())
+        .collect();
+    assert_eq!(demos, vec!["Paris", "Madrid"]);
+}
+
+#[tokio::test]
+async fn test_bootstrap_sets_demos_through_module_parameters() {
+    let cot_completion = |answer: &str| {
+        format!(
+            "[[ ## rationale ## ]]\nIt is the capital.\n\n[[ ## answer ## ]]\n{}\n\n[[ ## completed ## ]]",
+            answer
+        )
+    };
+    let lm = MockProvider::with_texts([
+        cot_completion("Paris"),
+        cot_completion("Madrid"),
+        cot_completion("Rome"),
+    ]);
+    let mut cot = ChainOfThought::new(QaSignature, lm);
+
+    BootstrapFewShot::with_metric(ExactMatchMetric, trainset(), 1)
+        .compile(&mut cot)
+        .await
+        .unwrap();
+
+    assert_eq!(cot.predict().demos().len(), 1);
+    assert_eq!(cot.predict().demos()[0].outputs.answer, "Paris");
+}
+
+// Answers the capital questions right only when told to name the capital city
+struct InstructionSensitiveProvider;
+
+impl CompletionProvider for InstructionSensitiveProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        _config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let messages = messages.read().await;
+        let text = |message: &Message| match message {
+            Message::System { content } => content.as_text().unwrap_or_default().to_string(),
+            Message::User { content } => ContentTypes::join_text(content),
+            _ => String::new(),
+        };
+        let system = text(&messages[0]);
+        let question = text(messages.last().unwrap());
+        let answer = match trainset()
+            .into_iter()
+            .find(|(inputs, _)| question.contains(&inputs.question))
+        {
+            Some((_, outputs)) if system.contains("capital city") => outputs.answer,
+            _ => "I don't know".to_string(),
+        };
+        Ok(CompletionResponse::new(
+            Message::assistant(Some(completion(&answer)), None),
+            FinishReason::Stop,
+        ))
+    }
+}
+
+fn train_set() -> LabeledDataset<QaInputs, QaOutputs> {
+    LabeledDataset::new(
+        trainset()
+            .into_iter()
+            .map(|(inputs, outputs)| LabeledExample::new(inputs, outputs))
+            .collect(),
+    )
+}
+
+fn proposal(instruction: &str) -> String {
+    format!(
+        "[[ ## instruction ## ]]\n{}\n\n[[ ## completed ## ]]",
+        instruction
+    )
+}
+
+#[tokio::test]
+async fn test_instruction_optimizer_keeps_best_candidate() {
+    let proposer = MockProvider::with_texts([
+        proposal("Think it through."),
+        proposal("Name the capital city only."),
+    ]);
+    let mut optimizer =
+        InstructionOptimizer::new(proposer, ExactMatchMetric).with_num_candidates(2);
+    let mut predict = Predict::new(QaSignature, InstructionSensitiveProvider);
+
+    let result = optimizer
+        .optimize(&mut predict, &train_set())
+        .await
+        .unwrap();
+
+    assert_eq!(result.instruction, "Name the capital city only.");
+    assert_eq!(result.score, 1.0);
+    assert_eq!(result.iterations, 1);
+    assert_eq!(predict.instructions(), "Name the capital city only.");
+    // The proposer is shown the instruction it is improving on
+    let request = &optimizer.proposer().lm().received()[0];
+    assert!(
+        ContentTypes::join_text(match request.last().unwrap() {
+            Message::User { content } => content,
+            _ => panic!("expected a user message"),
+        })
+        .contains("Answer the question.")
+    );
+}
+
+#[tokio::test]
+async fn test_instruction_optimizer_keeps_starting_instruction_when_no_candidate_beats_it() {
+    let proposer = MockProvider::with_texts([proposal("Think it through.")]);
+    let mut optimizer =
+        InstructionOptimizer::new(proposer, ExactMatchMetric).with_num_candidates(1);
+    let mut predict = Predict::new(QaSignature, InstructionSensitiveProvider);
+    predict.set_instructions("Give the capital city.");
+
+    let result = optimizer
+        .optimize(&mut predict, &train_set())
+        .await
+        .unwrap();
+
+    assert_eq!(result.instruction, "Give the capital city.");
+    assert_eq!(result.score, 1.0);
+    assert_eq!(predict.instructions(), "Give the capital city.");
+}
@@ -0,0 +1,398 @@
+use std::sync::{Arc, Mutex};
+use tokio::sync::RwLock;
+
+mod common;
+
+use common::{QaInputs, QaOutputs, QaSignature, question};
+use dsrs_core::{
+    adapters::{
+        json_adapter::JsonAdapter,
+        traits::{AdapterConfig, Demo},
+    },
+    modules::ChainOfThought,
+    predict::Predict,
+    primatives::{DynamicSignature, Module, ModuleParameter, Signature},
+    providers::models::{
+        CompletionConfig, CompletionResponse, ContentTypes, FinishReason, Message,
+    },
+    providers::{CompletionProvider, ExhaustedBehavior, MockProvider, ProviderError},
+};
+
+// Answers with fixed text and records every request, including its config
+struct RecordingProvider {
+    text: String,
+    requests: Mutex<Vec<(Vec<Message>, CompletionConfig)>>,
+}
+
+impl RecordingProvider {
+    fn new(text: &str) -> Self {
+        RecordingProvider {
+            text: text.to_string(),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl CompletionProvider for RecordingProvider {
+    async fn complete(
+        &self,
+        messages: Arc<RwLock<Vec<Message>>>,
+        config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        let messages = messages.read().await.clone();
+        self.requests.lock().unwrap().push((messages, config));
+        Ok(CompletionResponse::new(
+            Message::assistant(Some(self.text.as_str()), None),
+            FinishReason::Stop,
+        ))
+    }
+}
+
+fn text(message: &Message) -> &str {
+    let text = match message {
+        Message::System { content } => content.as_text(),
+        Message::User { content } => content.first().and_then(ContentTypes::as_text),
+        _ => None,
+    };
+    text.unwrap_or_else(|| panic!("Expected a system or user message, got {:?}", message))
+}
+
+#[tokio::test]
+async fn test_predict_runs_adapter_and_provider() {
+    let lm = MockProvider::with_texts(["[[ ## answer ## ]]\nParis\n\n[[ ## completed ## ]]"]);
+    let predict = Predict::new(QaSignature, lm);
+
+    let outputs = predict
+        .aforward(question("What is the capital of France?"))
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, "Paris");
+    let received = predict.lm().received();
+    assert_eq!(received.len(), 1);
+    let messages = &received[0];
+    assert!(text(&messages[0]).contains("Answer the question."));
+    assert!(text(&messages[1]).contains("What is the capital of France?"));
+}
+
+// Answers batches with a native endpoint, which garbles questions about Spain, and
+// single requests with "Madrid"
+#[derive(Default)]
+struct BatchingProvider {
+    batch_sizes: Mutex<Vec<usize>>,
+    single_calls: Mutex<usize>,
+}
+
+impl CompletionProvider for BatchingProvider {
+    async fn complete(
+        &self,
+        _messages: Arc<RwLock<Vec<Message>>>,
+        _config: CompletionConfig,
+    ) -> Result<CompletionResponse, ProviderError> {
+        *self.single_calls.lock().unwrap() += 1;
+        Ok(CompletionResponse::new(
+            Message::assistant(Some("[[ ## answer ## ]]\nMadrid\n\n[[ ## completed ## ]]"), None),
+            FinishReason::Stop,
+        ))
+    }
+
+    async fn complete_many(
+        &self,
+        requests: Vec<(Arc<RwLock<Vec<Message>>>, CompletionConfig)>,
+    ) -> Vec<Result<CompletionResponse, ProviderError>> {
+        self.batch_sizes.lock().unwrap().push(requests.len());
+        let mut responses = Vec::new();
+        for (messages, _) in requests {
+            let messages = messages.read().await;
+            let reply = if text(&messages[1]).contains("Spain") {
+                "garbled"
+            } else {
+                "[[ ## answer ## ]]\nParis\n\n[[ ## completed ## ]]"
+            };
+            responses.push(Ok(CompletionResponse::new(
+                Message::assistant(Some(reply), None),
+                FinishReason::Stop,
+            )));
+        }
+        responses
+    }
+}
+
+#[tokio::test]
+async fn test_predict_batch_uses_complete_many_and_retries_failures_alone() {
+    let predict = Predict::new(QaSignature, BatchingProvider::default());
+
+    let results = predict
+        .aforward_batch(vec![question("Capital of France?"), question("Capital of Spain?")])
+        .await;
+
+    let answers: Vec<String> = results.into_iter().map(|result| result.unwrap().answer).collect();
+    assert_eq!(answers, vec!["Paris", "Madrid"]);
+    assert_eq!(*predict.lm().batch_sizes.lock().unwrap(), vec![2]);
+    assert_eq!(*predict.lm().single_calls.lock().unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_complete_many_defaults_to_individual_completions() {
+    let lm = RecordingProvider::new("Hi");
+    let requests = ["One", "Two"]
+        .into_iter()
+        .map(|text| {
+            let messages = Arc::new(RwLock::new(vec![Message::user(text)]));
+            (messages, CompletionConfig::default())
+        })
+        .collect();
+
+    let responses = lm.complete_many(requests).await;
+
+    assert_eq!(responses.len(), 2);
+    assert!(responses.iter().all(Result::is_ok));
+    let requests = lm.requests.lock().unwrap();
+    let sent: Vec<&str> = requests.iter().map(|(messages, _)| text(&messages[0])).collect();
+    assert_eq!(sent, vec!["One", "Two"]);
+}
+
+#[tokio::test]
+async fn test_predict_builder_passes_config_demos_and_instructions() {
+    let predict = Predict::builder()
+        .signature(QaSignature)
+        .lm(RecordingProvider::new(r#"{"answer": "Berlin"}"#))
+        .adapter(JsonAdapter::new(AdapterConfig::default()))
+        .config(CompletionConfig {
+            model: "test-model".to_string(),
+            temperature: Some(0.5),
+            ..Default::default()
+        })
+        .demos(vec![Demo {
+            inputs: question("What is the capital of Italy?"),
+            outputs: QaOutputs {
+                answer: "Rome".to_string(),
+            },
+        }])
+        .instructions("Answer with a city name.")
+        .build()
+        .unwrap();
+
+    let outputs = predict
+        .aforward(question("What is the capital of Germany?"))
+        .await
+        .unwrap();
+
+    assert_eq!(outputs.answer, "Berlin");
+    let requests = predict.lm().requests.lock().unwrap();
+    let (messages, config) = &requests[0];
+    assert_eq!(config.model, "test-model");
+    assert_eq!(config.temperature, Some(0.5));
+    assert!(text(&messages[0]).contains("Answer with a city name."));
+    // System message, one demo exchange, then the question
+    assert_eq!(messages.len(), 4);
+    assert!(text(&messages[1]).contains("Italy"));
+}
+
+#[test]
+fn test_predict_builder_requires_lm() {
+    let result = Predict::<QaSignature, RecordingProvider>::builder()
+        .signature(QaSignature)
+        .build();
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_predict_surfaces_parse_failures() {
+    let lm = MockProvider::with_texts(["no fields here"])
+        .with_exhausted_behavior(ExhaustedBehavior::RepeatLast);
+    let adapter = JsonAdapter::new(AdapterConfig::default().with_max_retries(2));
+    let predict = Predict::builder()
+        .signature(QaSignature)
+        .lm(lm)
+        .adapter(adapter)
+        .build()
+        .unwrap();
+
+    let result = predict.aforward(question("Anything?")).await;
+
+    assert!(result.is_err());
+    assert_eq!(predict.lm().call_count(), 2);
+}
+
+#[tokio::test]
+async fn test_chain_of_thought_keeps_rationale_out_of_outputs() {
+    let lm = MockProvider::with_texts([
+        "[[ ## rationale ## ]]\nGermany's capital moved back to Berlin in 1990.\n\n\
+         [[ ## answer ## ]]\nBerlin\n\n[[ ## completed ## ]]",
+    ]);
+    let cot = ChainOfThought::new(QaSignature, lm);
+    assert_eq!(cot.last_rationale(), None);
+
+    let outputs = cot
+        .aforward(question("What is the capital of Germany?"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        outputs,
+        QaOutputs {
+            answer: "Berlin".to_string()
+        }
+    );
+    assert_eq!(
+        cot.last_rationale().as_deref(),
+        Some("Germany's capital moved back to Berlin in 1990.")
+    );
+    let received = cot.predict().lm().received();
+    assert!(text(&received[0][0]).contains("[[ ## rationale ## ]]"));
+}
+
+type QaDemos = Vec<Demo<QaInputs, QaOutputs>>;
+
+#[test]
+fn test_predict_exposes_demos_and_instructions_parameters() {
+    let mut predict = Predict::new(QaSignature, MockProvider::new(Vec::new()));
+
+    for parameter in predict.parameters_mut() {
+        if let Some(demos) = parameter.as_any_mut().downcast_mut::<QaDemos>() {
+            demos.push(Demo {
+                inputs: question("What is the capital of Italy?"),
+                outputs: QaOutputs {
+                    answer: "Rome".to_string(),
+                },
+            });
+        } else if let Some(instructions) = parameter.as_any_mut().downcast_mut::<String>() {
+            *instructions = "Answer tersely.".to_string();
+        }
+    }
+
+    assert_eq!(predict.demos().len(), 1);
+    assert_eq!(predict.instructions(), "Answer tersely.");
+    let names: Vec<&str> = predict.parameters().iter().map(|p| p.name()).collect();
+    assert_eq!(names, vec!["demos", "instructions"]);
+}
+
+#[test]
+fn test_chain_of_thought_prefixes_named_parameters() {
+    let cot = ChainOfThought::new(QaSignature, MockProvider::new(Vec::new()));
+
+    let named: Vec<(String, &dyn ModuleParameter)> = cot.named_parameters();
+    let names: Vec<&str> = named.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, vec!["predict.demos", "predict.instructions"]);
+    let instructions = named[1].1.as_any().downcast_ref::<String>().unwrap();
+    assert_eq!(instructions, "Answer the question.");
+}
+
+fn state_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("dsrs-{}-{}.json", name, std::process::id()))
+}
+
+#[test]
+fn test_predict_save_and_load_restores_parameters() {
+    let path = state_path("predict-state");
+    let trained = Predict::builder()
+        .signature(QaSignature)
+        .lm(MockProvider::new(Vec::new()))
+        .demos(vec![Demo {
+            inputs: question("What is the capital of Italy?"),
+            outputs: QaOutputs {
+                answer: "Rome".to_string(),
+            },
+        }])
+        .instructions("Answer with a city name.")
+        .build()
+        .unwrap();
+
+    trained.save(&path).unwrap();
+    let saved: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let mut fresh = Predict::new(QaSignature, MockProvider::new(Vec::new()));
+    fresh.load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(saved["version"], 1);
+    assert_eq!(saved["module_type"], "Predict");
+    assert_eq!(
+        saved["parameters"]["instructions"],
+        serde_json::json!({"instructions": "Answer with a city name."})
+    );
+    assert_eq!(fresh.instructions(), "Answer with a city name.");
+    assert_eq!(fresh.demos().len(), 1);
+    assert_eq!(fresh.demos()[0].outputs.answer, "Rome");
+}
+
+#[test]
+fn test_load_rejects_state_of_another_module_type() {
+    let path = state_path("cot-state");
+    Predict::new(QaSignature, MockProvider::new(Vec::new()))
+        .save(&path)
+        .unwrap();
+
+    let mut cot = ChainOfThought::new(QaSignature, MockProvider::new(Vec::new()));
+    let error = cot.load(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(
+        error.to_string().contains("Predict module, not ChainOfThought"),
+        "{}",
+        error
+    );
+}
+
+fn dynamic_signature() -> DynamicSignature {
+    DynamicSignature::new(
+        "Capital",
+        "Name the capital of the country.",
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "country": {"type": "string", "description": "The country to look up"}
+            },
+            "required": ["country"]
+        }),
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "capital": {"type": "string"},
+                "population": {"type": "integer", "minimum": 0}
+            },
+            "required": ["capital", "population"]
+        }),
+    )
+}
+
+#[tokio::test]
+async fn test_dynamic_signature_formats_and_parses_with_runtime_schemas() {
+    let lm = MockProvider::with_texts([
+        "[[ ## capital ## ]]\nParis\n\n[[ ## population ## ]]\n2100000\n\n[[ ## completed ## ]]",
+    ]);
+    let predict = Predict::new(dynamic_signature(), lm);
+
+    let outputs = predict
+        .aforward(serde_json::json!({"country": "France"}))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        outputs,
+        serde_json::json!({"capital": "Paris", "population": 2100000})
+    );
+    let messages = &predict.lm().received()[0];
+    assert!(text(&messages[0]).contains("The country to look up"));
+    assert!(text(&messages[0]).contains("Name the capital of the country."));
+    let user = text(&messages[1]);
+    assert!(user.contains("[[ ## country ## ]]\nFrance"));
+    assert!(user.contains("`[[ ## capital ## ]]`, then `[[ ## population ## ]]`"));
+}
+
+#[tokio::test]
+async fn test_dynamic_signature_validates_against_runtime_schemas() {
+    let predict = Predict::new(dynamic_signature(), MockProvider::new(Vec::new()));
+    let error = predict
+        .aforward(serde_json::json!({"region": "Europe"}))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("country"), "{}", error);
+    assert_eq!(predict.lm().call_count(), 0);
+
+    let untyped =
+        DynamicSignature::new("Untyped", "", serde_json::json!({}), serde_json::json!(true));
+    assert_eq!(untyped.validate().unwrap_err().len(), 2);
+}
@@ -0,0 +1,1612 @@
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use dsrs_core::providers::{
+    AnthropicProvider, AzureOpenAIProvider, CohereProvider, CompletionProvider,
+    EmbeddingProvider, GeminiConfig, GeminiProvider, GroqProvider, HuggingFaceProvider,
+    MistralProvider, OllamaProvider, OpenAIEmbeddingProvider, OpenAIProvider, ProviderError,
+    RetryConfig, StreamChunk,
+    models::{
+        AvailableTool, CompletionConfig, ContentTypes, FinishReason, ImageDetail,
+        InjectionPosition, Message, ResponseFormat, ToolCall, ToolChoice, UsageStats,
+    },
+    retry_with_backoff,
+};
+
+fn config() -> CompletionConfig {
+    CompletionConfig {
+        model: "test-model".to_string(),
+        ..Default::default()
+    }
+}
+
+fn conversation() -> Arc<RwLock<Vec<Message>>> {
+    Arc::new(RwLock::new(vec![
+        Message::system("You are helpful."),
+        Message::user("Hello"),
+    ]))
+}
+
+// MARK: CompletionConfig
+
+#[test]
+fn test_completion_config_builder_sets_fields() {
+    let config = CompletionConfig::builder()
+        .model("gpt-4o")
+        .temperature(0.2)
+        .max_tokens(256)
+        .stop(vec!["END".to_string()])
+        .tool_choice(ToolChoice::None)
+        .system_injection("Always respond in English.", InjectionPosition::Prepend)
+        .build();
+
+    assert_eq!(config.model, "gpt-4o");
+    assert_eq!(config.temperature, Some(0.2));
+    assert_eq!(config.max_tokens, Some(256));
+    assert_eq!(config.stop, Some(vec!["END".to_string()]));
+    assert_eq!(config.tool_choice, Some(ToolChoice::None));
+    assert_eq!(config.system_injections.len(), 1);
+    assert!(config.tools.is_none() && config.top_p.is_none() && config.extra.is_none());
+    assert!(!config.skip_cache);
+}
+
+#[test]
+fn test_completion_config_with_methods_override_a_base() {
+    let base = CompletionConfig::builder().model("gpt-4o-mini").top_p(0.9).build();
+    let config = base
+        .clone()
+        .with_model("gpt-4o")
+        .with_temperature(0.0)
+        .with_skip_cache(true);
+
+    assert_eq!(config.model, "gpt-4o");
+    assert_eq!(config.temperature, Some(0.0));
+    assert_eq!(config.top_p, Some(0.9));
+    assert!(config.skip_cache);
+    assert_eq!(base.model, "gpt-4o-mini");
+}
+
+// MARK: Message
+
+#[test]
+fn test_message_accessors() {
+    let call = ToolCall {
+        id: "call_1".to_string(),
+        name: "weather".to_string(),
+        arguments: serde_json::json!({"city": "Paris"}),
+    };
+    let calling = Message::assistant(None::<String>, Some(vec![call.clone()]));
+    let image_first = Message::user_parts(vec![
+        ContentTypes::image_url("https://example.com/cat.png"),
+        ContentTypes::Text("What is this?".to_string()),
+    ]);
+
+    assert_eq!(Message::assistant(Some("Sunny"), None).as_text(), Some("Sunny"));
+    assert_eq!(image_first.as_text(), Some("What is this?"));
+    assert_eq!(Message::tool("18C", "call_1").as_text(), Some("18C"));
+    assert_eq!(calling.as_text(), None);
+    assert_eq!(calling.as_tool_calls(), Some(&[call][..]));
+    assert_eq!(Message::user("Hi").as_tool_calls(), None);
+    assert!(calling.is_assistant() && !image_first.is_assistant());
+    assert!(Message::tool("18C", "call_1").is_tool_call_response());
+    assert!(!calling.is_tool_call_response());
+    assert!(image_first.has_images() && !Message::user("Hi").has_images());
+}
+
+#[test]
+fn test_tool_call_deserializes_arguments() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct WeatherArgs {
+        city: String,
+        days: u32,
+    }
+    let call = ToolCall {
+        id: "call_1".to_string(),
+        name: "weather".to_string(),
+        arguments: serde_json::json!({"city": "Paris", "days": 3}),
+    };
+
+    assert_eq!(
+        call.deserialize_arguments::<WeatherArgs>().unwrap(),
+        WeatherArgs {
+            city: "Paris".to_string(),
+            days: 3
+        }
+    );
+    assert_eq!(call.argument::<u32>("days").unwrap(), 3);
+    assert!(call.argument::<u32>("city").is_err());
+    let missing = call.argument::<String>("units").unwrap_err();
+    assert!(missing.to_string().contains("missing argument `units`"), "{}", missing);
+}
+
+#[test]
+fn test_available_tool_from_schema_and_builder() {
+    /// Where to search
+    #[derive(schemars::JsonSchema)]
+    #[allow(dead_code)]
+    struct SearchArgs {
+        query: String,
+        filters: Option<SearchFilters>,
+    }
+    #[derive(schemars::JsonSchema)]
+    #[allow(dead_code)]
+    struct SearchFilters {
+        site: String,
+    }
+
+    let tool = AvailableTool::from_schema::<SearchArgs>("search", "Search the web");
+    let schema = tool.input_schema_json.as_ref().unwrap();
+    assert_eq!((tool.name.as_str(), tool.desc.as_str()), ("search", "Search the web"));
+    assert_eq!(schema["properties"]["query"]["type"], "string");
+    assert!(schema.get("additionalProperties").is_none());
+
+    let strict = AvailableTool::builder()
+        .strict(true)
+        .name("search")
+        .input_schema::<SearchArgs>()
+        .build();
+    let schema = strict.input_schema_json.unwrap();
+    assert_eq!(strict.desc, "");
+    assert_eq!(schema["additionalProperties"], false);
+    assert_eq!(schema["properties"]["filters"]["anyOf"][0]["additionalProperties"], false);
+}
+
+// MARK: Anthropic
+
+fn anthropic(server: &mockito::Server) -> AnthropicProvider {
+    AnthropicProvider::new("anthropic-key".to_string()).with_base_url(server.url())
+}
+
+#[tokio::test]
+async fn test_anthropic_system_and_tool_use() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_header("x-api-key", "anthropic-key")
+        .match_header("anthropic-version", "2023-06-01")
+        .match_body(mockito::Matcher::Json(serde_json::json!({
+            "model": "test-model",
+            "max_tokens": 4096,
+            "system": "You are helpful.",
+            "messages": [
+                {"role": "user", "content": [{"type": "text", "text": "Hello"}]},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "call_1", "name": "lookup", "input": {"q": "a"}},
+                    {"type": "tool_use", "id": "call_2", "name": "lookup", "input": {"q": "b"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "call_1", "content": "A"},
+                    {"type": "tool_result", "tool_use_id": "call_2", "content": "B"}
+                ]}
+            ]
+        })))
+        .with_body(
+            r#"{"content": [
+                {"type": "text", "text": "Let me check."},
+                {"type": "tool_use", "id": "call_3", "name": "lookup", "input": {"q": "c"}}
+            ], "stop_reason": "tool_use", "usage": {"input_tokens": 40, "output_tokens": 12}}"#,
+        )
+        .create_async()
+        .await;
+
+    let call = |id: &str, q: &str| ToolCall {
+        id: id.to_string(),
+        name: "lookup".to_string(),
+        arguments: serde_json::json!({ "q": q }),
+    };
+    let messages = Arc::new(RwLock::new(vec![
+        Message::system("You are helpful."),
+        Message::user("Hello"),
+        Message::assistant(None::<String>, Some(vec![call("call_1", "a"), call("call_2", "b")])),
+        Message::tool("A", "call_1"),
+        Message::tool("B", "call_2"),
+    ]));
+
+    let response = anthropic(&server).complete(messages, config()).await.unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(response.finish_reason, FinishReason::ToolCalls);
+    assert_eq!(response.usage, Some(UsageStats::new(40, 12)));
+    match response.message {
+        Message::Assistant {
+            content: Some(ContentTypes::Text(text)),
+            tool_calls: Some(calls),
+        } => {
+            assert_eq!(text, "Let me check.");
+            assert_eq!(calls.len(), 1);
+            assert_eq!(calls[0].id, "call_3");
+            assert_eq!(calls[0].arguments, serde_json::json!({"q": "c"}));
+        }
+        other => panic!("Unexpected response: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_anthropic_sends_image_blocks() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "What is in these images?"},
+                    {"type": "image", "source": {
+                        "type": "url",
+                        "url": "https://example.com/cat.png"
+                    }},
+                    {"type": "image", "source": {
+                        "type": "base64",
+                        "media_type": "image/png",
+                        "data": "AQID"
+                    }},
+                    {"type": "image", "source": {
+                        "type": "base64",
+                        "media_type": "image/gif",
+                        "data": "R0lG"
+                    }}
+                ]
+            }]
+        })))
+        .with_body(r#"{"content": [{"type": "text", "text": "Cats"}], "stop_reason": "end_turn"}"#)
+        .create_async()
+        .await;
+
+    let message = Message::user_parts(vec![
+        ContentTypes::Text("What is in these images?".to_string()),
+        ContentTypes::image_url("https://example.com/cat.png"),
+        ContentTypes::image_bytes(vec![1, 2, 3], "image/png"),
+        ContentTypes::image_url("data:image/gif;base64,R0lG"),
+    ]);
+    anthropic(&server)
+        .complete(Arc::new(RwLock::new(vec![message])), config())
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_anthropic_sends_extra_fields() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "test-model",
+            "top_k": 5,
+            "metadata": {"user_id": "u-1"}
+        })))
+        .with_body(r#"{"content": [{"type": "text", "text": "Hi"}], "stop_reason": "end_turn"}"#)
+        .create_async()
+        .await;
+
+    let config = config().with_extra(serde_json::json!({"top_k": 5, "metadata": {"user_id": "u-1"}}));
+    anthropic(&server).complete(conversation(), config).await.unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_anthropic_error_response() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1/messages")
+        .with_status(529)
+        .with_body(r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#)
+        .create_async()
+        .await;
+
+    let error = anthropic(&server)
+        .complete(conversation(), config())
+        .await
+        .unwrap_err();
+
+    match error {
+        ProviderError::AnthropicError { error_type, message } => {
+            assert_eq!(error_type, "overloaded_error");
+            assert_eq!(message, "Overloaded");
+        }
+        other => panic!("Unexpected error: {:?}", other),
+    }
+}
+
+// MARK: OpenAI
+
+#[tokio::test]
+async fn test_openai_stream_buffers_tool_calls() {
+    let chunk = |delta: serde_json::Value| {
+        let event = serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "created": 0,
+            "model": "test-model",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": null}]
+        });
+        format!("data: {}\n\n", event)
+    };
+    let body = [
+        chunk(serde_json::json!({"role": "assistant", "content": "Let me "})),
+        chunk(serde_json::json!({"content": "search."})),
+        chunk(serde_json::json!({"tool_calls": [{
+            "index": 0, "id": "call_1", "type": "function",
+            "function": {"name": "search", "arguments": "{\"q\": "}
+        }]})),
+        chunk(serde_json::json!({"tool_calls": [{"index": 0, "function": {"arguments": "\"rust\"}"}}]})),
+        "data: [DONE]\n\n".to_string(),
+    ]
+    .concat();
+
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({"stream": true})))
+        .with_header("content-type", "text/event-stream")
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let provider = OpenAIProvider::new("sk-test".to_string(), Some(server.url()));
+    let chunks: Vec<StreamChunk> = provider
+        .stream(conversation(), config())
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(
+        chunks,
+        vec![
+            StreamChunk::Text("Let me ".to_string()),
+            StreamChunk::Text("search.".to_string()),
+            StreamChunk::ToolCall(ToolCall {
+                id: "call_1".to_string(),
+                name: "search".to_string(),
+                arguments: serde_json::json!({"q": "rust"}),
+            }),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_openai_sends_json_schema_response_format() {
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {"answer": {"type": "string"}},
+        "required": ["answer"],
+        "additionalProperties": false
+    });
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {"name": "QaOutputs", "schema": schema, "strict": true}
+            }
+        })))
+        .with_body(chat_completion_body(r#"{"answer": "Hi"}"#))
+        .create_async()
+        .await;
+
+    let provider = OpenAIProvider::new("openai-key".to_string(), Some(server.url()));
+    let config = CompletionConfig {
+        response_format: Some(ResponseFormat::JsonSchema {
+            name: "QaOutputs".to_string(),
+            description: None,
+            schema: schema.clone(),
+            strict: true,
+        }),
+        ..config()
+    };
+    let response = provider.complete(conversation(), config).await.unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(
+        response.message,
+        Message::assistant(Some(r#"{"answer": "Hi"}"#), None)
+    );
+}
+
+#[tokio::test]
+async fn test_openai_sends_tool_choice() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "tool_choice": {"type": "function", "function": {"name": "search"}}
+        })))
+        .with_body(chat_completion_body("Searching"))
+        .create_async()
+        .await;
+
+    let provider = OpenAIProvider::new("openai-key".to_string(), Some(server.url()));
+    let config = CompletionConfig {
+        tools: Some(vec![AvailableTool {
+            name: "search".to_string(),
+            desc: "Search the web".to_string(),
+            input_schema_json: None,
+        }]),
+        tool_choice: Some(ToolChoice::Specific("search".to_string())),
+        ..config()
+    };
+    provider.complete(conversation(), config).await.unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_openai_sends_image_parts() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "messages": [{
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "What is in these images?"},
+                    {"type": "image_url", "image_url": {
+                        "url": "https://example.com/cat.png",
+                        "detail": "low"
+                    }},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,AQID"}}
+                ]
+            }]
+        })))
+        .with_body(chat_completion_body("A cat"))
+        .create_async()
+        .await;
+
+    let provider = OpenAIProvider::new("openai-key".to_string(), Some(server.url()));
+    let message = Message::user_parts(vec![
+        ContentTypes::Text("What is in these images?".to_string()),
+        ContentTypes::Image {
+            url: "https://example.com/cat.png".to_string(),
+            detail: Some(ImageDetail::Low),
+        },
+        ContentTypes::image_bytes(vec![1, 2, 3], "image/png"),
+    ]);
+    provider
+        .complete(Arc::new(RwLock::new(vec![message])), config())
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_openai_sends_extra_fields() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "test-model",
+            "seed": 7,
+            "logit_bias": {"50256": -100}
+        })))
+        .with_body(chat_completion_body("Hi"))
+        .create_async()
+        .await;
+
+    let provider = OpenAIProvider::new("openai-key".to_string(), Some(server.url()));
+    let config = config().with_extra(serde_json::json!({"seed": 7, "logit_bias": {"50256": -100}}));
+    provider.complete(conversation(), config).await.unwrap();
+
+    mock.assert_async().await;
+}
+
+async fn openai_error(status: usize, error: serde_json::Value) -> ProviderError {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(status)
+        .with_body(serde_json::json!({ "error": error }).to_string())
+        .create_async()
+        .await;
+
+    OpenAIProvider::new("openai-key".to_string(), Some(server.url()))
+        .complete(conversation(), config())
+        .await
+        .unwrap_err()
+}
+
+#[tokio::test]
+async fn test_openai_maps_errors_to_provider_variants() {
+    let auth = openai_error(
+        401,
+        serde_json::json!({
+            "message": "Incorrect API key provided",
+            "type": "invalid_request_error",
+            "code": "invalid_api_key"
+        }),
+    )
+    .await;
+    assert!(matches!(auth, ProviderError::AuthenticationFailed));
+    assert!(auth.is_auth_error());
+
+    let context = openai_error(
+        400,
+        serde_json::json!({
+            "message": "This model's maximum context length is 8192 tokens. However, your messages resulted in 9000 tokens.",
+            "type": "invalid_request_error",
+            "code": "context_length_exceeded"
+        }),
+    )
+    .await;
+    assert!(matches!(
+        context,
+        ProviderError::ContextLengthExceeded {
+            max_tokens: Some(8192)
+        }
+    ));
+    assert!(!context.is_retryable());
+}
+
+#[tokio::test]
+async fn test_openai_embeddings_in_input_order() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/embeddings")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "text-embedding-3-small",
+            "input": ["first", "second"],
+            "dimensions": 2
+        })))
+        .with_header("content-type", "application/json")
+        .with_body(
+            serde_json::json!({
+                "object": "list",
+                "model": "text-embedding-3-small",
+                "data": [
+                    {"object": "embedding", "index": 1, "embedding": [0.0, 1.0]},
+                    {"object": "embedding", "index": 0, "embedding": [1.0, 0.0]}
+                ],
+                "usage": {"prompt_tokens": 2, "total_tokens": 2}
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let provider = OpenAIEmbeddingProvider::new("sk-test".to_string(), "text-embedding-3-small")
+        .with_base_url(server.url())
+        .with_dimensions(2);
+    let vectors = provider
+        .embed(vec!["first".to_string(), "second".to_string()])
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    assert_eq!(provider.embedding_dimension(), Some(2));
+    assert_eq!(
+        OpenAIEmbeddingProvider::new("sk-test".to_string(), "text-embedding-3-large")
+            .embedding_dimension(),
+        Some(3072)
+    );
+}
+
+#[tokio::test]
+async fn test_openai_tool_call_arguments_deserialize() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct WeatherArgs {
+        city: String,
+        days: u32,
+    }
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_body(
+            serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "test-model",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": null, "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {
+                            "name": "weather",
+                            "arguments": "{\"city\": \"Paris\", \"days\": 3}"
+                        }
+                    }]},
+                    "finish_reason": "tool_calls"
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let provider = OpenAIProvider::new("openai-key".to_string(), Some(server.url()));
+    let response = provider.complete(conversation(), config()).await.unwrap();
+
+    let call = &response.message.as_tool_calls().unwrap()[0];
+    assert_eq!(
+        call.deserialize_arguments::<WeatherArgs>().unwrap(),
+        WeatherArgs {
+            city: "Paris".to_string(),
+            days: 3
+        }
+    );
+    assert_eq!(call.argument::<String>("city").unwrap(), "Paris");
+}
+
+// MARK: Cohere
+
+#[tokio::test]
+async fn test_cohere_preamble_and_tool_results() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/chat")
+        .match_header("authorization", "Bearer cohere-key")
+        .match_body(mockito::Matcher::Json(serde_json::json!({
+            "model": "test-model",
+            "message": "",
+            "preamble": "You are helpful.",
+            "chat_history": [
+                {"role": "USER", "message": "Look up a"},
+                {"role": "CHATBOT", "message": "", "tool_calls": [
+                    {"name": "lookup", "parameters": {"q": "a"}}
+                ]}
+            ],
+            "tool_results": [
+                {"call": {"name": "lookup", "parameters": {"q": "a"}}, "outputs": [{"result": "A"}]}
+            ]
+        })))
+        .with_body(
+            serde_json::json!({
+                "text": "",
+                "generation_id": "gen",
+                "tool_calls": [{"name": "lookup", "parameters": {"q": "b"}}],
+                "finish_reason": "COMPLETE"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let call = ToolCall {
+        id: "call_1".to_string(),
+        name: "lookup".to_string(),
+        arguments: serde_json::json!({"q": "a"}),
+    };
+    let messages = Arc::new(RwLock::new(vec![
+        Message::system("You are helpful."),
+        Message::user("Look up a"),
+        Message::assistant(None::<String>, Some(vec![call])),
+        Message::tool("A", "call_1"),
+    ]));
+    let provider = CohereProvider::new("cohere-key".to_string()).with_base_url(server.url());
+    let response = provider.complete(messages, config()).await.unwrap();
+
+    mock.assert_async().await;
+    let expected = ToolCall {
+        id: "gen-0".to_string(),
+        name: "lookup".to_string(),
+        arguments: serde_json::json!({"q": "b"}),
+    };
+    assert_eq!(response.message, Message::assistant(None::<String>, Some(vec![expected])));
+    assert_eq!(response.finish_reason, FinishReason::ToolCalls);
+}
+
+#[tokio::test]
+async fn test_cohere_sends_extra_fields() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1/chat")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "model": "test-model",
+            "k": 10,
+            "seed": 7
+        })))
+        .with_body(r#"{"text": "Hi", "generation_id": "gen", "finish_reason": "COMPLETE"}"#)
+        .create_async()
+        .await;
+
+    let provider = CohereProvider::new("cohere-key".to_string()).with_base_url(server.url());
+    let config = config().with_extra(serde_json::json!({"k": 10, "seed": 7}));
+    provider.complete(conversation(), config).await.unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_cohere_error_response() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1/chat")
+        .with_status(401)
+        .with_body(r#"{"message": "invalid api token"}"#)
+        .create_async()
+        .await;
+
+    let provider = CohereProvider::new("cohere-key".to_string()).with_base_url(server.url());
+    match provider.complete(conversation(), config()).await {
+        Err(ProviderError::CohereError { status, message }) => {
+            assert_eq!(status, 401);
+            assert_eq!(message, "invalid api token");
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|r| r.message)),
+    }
+}
+
+#[tokio::test]
+async fn test_cohere_rejects_images() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server.mock("POST", "/v1/chat").expect(0).create_async().await;
+
+    let provider = CohereProvider::new("cohere-key".to_string()).with_base_url(server.url());
+    let message = Message::user_parts(vec![
+        ContentTypes::Text("What is this?".to_string()),
+        ContentTypes::image_url("https://example.com/cat.png"),
+    ]);
+    match provider
+        .complete(Arc::new(RwLock::new(vec![message])), config())
+        .await
+    {
+        Err(ProviderError::ImagesNotSupported { model }) => assert_eq!(model, "test-model"),
+        other => panic!("Unexpected result: {:?}", other.map(|r| r.message)),
+    }
+    mock.assert_async().await;
+}
+
+// MARK: Azure OpenAI
+
+#[tokio::test]
+async fn test_azure_routes_to_deployment() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/openai/deployments/gpt-4o-prod/chat/completions")
+        .match_query(mockito::Matcher::UrlEncoded(
+            "api-version".into(),
+            "2024-10-21".into(),
+        ))
+        .match_header("api-key", "azure-key")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"model": "gpt-4o-prod"}),
+        ))
+        .with_header("content-type", "application/json")
+        .with_body(
+            serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "gpt-4o",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hi"},
+                    "finish_reason": "stop"
+                }],
+                "usage": {"prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10}
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let provider = AzureOpenAIProvider::new(
+        "my-resource".to_string(),
+        "gpt-4o-prod".to_string(),
+        "azure-key".to_string(),
+        "2024-10-21".to_string(),
+    )
+    .with_api_base(server.url());
+    let response = provider.complete(conversation(), config()).await.unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(response.message, Message::assistant(Some("Hi"), None));
+    assert_eq!(response.finish_reason, FinishReason::Stop);
+    assert_eq!(response.usage, Some(UsageStats::new(9, 1)));
+}
+
+// MARK: Mistral
+
+#[tokio::test]
+async fn test_mistral_complete_with_tool_calls() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .match_header("authorization", "Bearer mistral-key")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"model": "test-model", "random_seed": 7}),
+        ))
+        .with_body(
+            serde_json::json!({
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": "",
+                        "tool_calls": [{
+                            "id": "D681PevKs",
+                            "function": {"name": "search", "arguments": "{\"q\": \"rust\"}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let provider = MistralProvider::new("mistral-key".to_string()).with_base_url(server.url());
+    let config = CompletionConfig {
+        extra: Some(serde_json::json!({"random_seed": 7})),
+        ..config()
+    };
+    let response = provider.complete(conversation(), config).await.unwrap();
+
+    let call = ToolCall {
+        id: "D681PevKs".to_string(),
+        name: "search".to_string(),
+        arguments: serde_json::json!({"q": "rust"}),
+    };
+    assert_eq!(response.message, Message::assistant(None::<String>, Some(vec![call])));
+    assert_eq!(response.finish_reason, FinishReason::ToolCalls);
+}
+
+#[tokio::test]
+async fn test_mistral_sends_image_parts() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "messages": [
+                {"role": "system", "content": "You are helpful."},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "What is in these images?"},
+                    {"type": "image_url", "image_url": "https://example.com/cat.png"},
+                    {"type": "image_url", "image_url": "data:image/png;base64,AQID"}
+                ]}
+            ]
+        })))
+        .with_body(
+            r#"{"choices": [{"message": {"role": "assistant", "content": "Cats"}, "finish_reason": "stop"}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let provider = MistralProvider::new("mistral-key".to_string()).with_base_url(server.url());
+    let messages = vec![
+        Message::system("You are helpful."),
+        Message::user_parts(vec![
+            ContentTypes::Text("What is in these images?".to_string()),
+            ContentTypes::image_url("https://example.com/cat.png"),
+            ContentTypes::image_bytes(vec![1, 2, 3], "image/png"),
+        ]),
+    ];
+    provider
+        .complete(Arc::new(RwLock::new(messages)), config())
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_mistral_sends_tool_call_arguments_encoded_once() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "messages": [
+                {"role": "user", "content": "Search"},
+                {"role": "assistant", "content": "", "tool_calls": [
+                    {"id": "D681PevKs", "type": "function", "function": {
+                        "name": "search", "arguments": "{\"q\":\"rust\"}"
+                    }},
+                    {"id": "E792QfwLt", "type": "function", "function": {
+                        "name": "search", "arguments": "{q: rust"
+                    }}
+                ]}
+            ]
+        })))
+        .with_body(
+            r#"{"choices": [{"message": {"role": "assistant", "content": "Done"}, "finish_reason": "stop"}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let call = |id: &str, arguments: serde_json::Value| ToolCall {
+        id: id.to_string(),
+        name: "search".to_string(),
+        arguments,
+    };
+    // The second call's arguments never parsed, so they are kept as the raw string
+    let messages = vec![
+        Message::user("Search"),
+        Message::assistant(
+            None::<String>,
+            Some(vec![
+                call("D681PevKs", serde_json::json!({"q": "rust"})),
+                call("E792QfwLt", serde_json::json!("{q: rust")),
+            ]),
+        ),
+    ];
+    let provider = MistralProvider::new("mistral-key".to_string()).with_base_url(server.url());
+    provider
+        .complete(Arc::new(RwLock::new(messages)), config())
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_mistral_error_response() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(400)
+        .with_body(
+            serde_json::json!({
+                "object": "error",
+                "message": "Invalid model: test-model",
+                "type": "invalid_model",
+                "param": null,
+                "code": "1500"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let provider = MistralProvider::new("mistral-key".to_string()).with_base_url(server.url());
+    match provider.complete(conversation(), config()).await {
+        Err(ProviderError::MistralError {
+            status,
+            error_type,
+            message,
+        }) => {
+            assert_eq!(status, 400);
+            assert_eq!(error_type, "invalid_model");
+            assert_eq!(message, "Invalid model: test-model");
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|r| r.message)),
+    }
+}
+
+#[tokio::test]
+async fn test_mistral_rate_limit() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(429)
+        .with_header("retry-after", "2")
+        .create_async()
+        .await;
+
+    let provider = MistralProvider::new("mistral-key".to_string()).with_base_url(server.url());
+    match provider.complete(conversation(), config()).await.unwrap_err() {
+        ProviderError::RateLimit { retry_after } => {
+            assert_eq!(retry_after, Some(Duration::from_secs(2)))
+        }
+        other => panic!("Unexpected error: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_mistral_service_unavailable_is_retried() {
+    let mut server = mockito::Server::new_async().await;
+    let unavailable = server
+        .mock("POST", "/chat/completions")
+        .with_status(503)
+        .with_body(
+            serde_json::json!({
+                "object": "error",
+                "message": "Service unavailable",
+                "type": "service_unavailable",
+                "param": null,
+                "code": "3505"
+            })
+            .to_string(),
+        )
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_body(
+            serde_json::json!({
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hi there"},
+                    "finish_reason": "stop"
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let provider = MistralProvider::new("mistral-key".to_string()).with_base_url(server.url());
+    let retry = RetryConfig {
+        initial_delay: Duration::ZERO,
+        ..Default::default()
+    };
+    let response = retry_with_backoff(&retry, || provider.complete(conversation(), config()))
+        .await
+        .unwrap();
+
+    unavailable.assert_async().await;
+    assert_eq!(response.message, Message::assistant(Some("Hi there"), None));
+}
+
+// MARK: Gemini
+
+fn gemini(server: &mockito::Server) -> GeminiProvider {
+    GeminiProvider::new("gemini-key".to_string(), "gemini-2.5-flash".to_string())
+        .with_base_url(server.url())
+}
+
+#[tokio::test]
+async fn test_gemini_system_instruction_and_function_calls() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/v1beta/models/gemini-2.5-flash:generateContent")
+        .match_header("x-goog-api-key", "gemini-key")
+        .match_body(mockito::Matcher::Json(serde_json::json!({
+            "system_instruction": {"parts": [{"text": "You are helpful."}]},
+            "contents": [
+                {"role": "user", "parts": [{"text": "Weather in Paris and Rome?"}]},
+                {"role": "model", "parts": [
+                    {"function_call": {"name": "weather", "args": {"city": "Paris"}}},
+                    {"function_call": {"name": "weather", "args": {"city": "Rome"}}}
+                ]},
+                {"role": "user", "parts": [
+                    {"function_response": {"name": "weather", "response": {"result": "Sunny"}}},
+                    {"function_response": {"name": "weather", "response": {"temp": 21}}}
+                ]}
+            ],
+            "tools": [{"function_declarations": [{
+                "name": "weather",
+                "description": "Look up the weather",
+                "parameters": {
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"]
+                }
+            }]}],
+            "tool_config": {"function_calling_config": {"mode": "ANY"}},
+            "generation_config": {"temperature": 0.0, "top_k": 40}
+        })))
+        .with_body(
+            serde_json::json!({
+                "candidates": [{
+                    "content": {"role": "model", "parts": [
+                        {"text": "Comparing the two.", "thought": true},
+                        {"functionCall": {"name": "weather", "args": {"city": "Oslo"}}}
+                    ]},
+                    "finishReason": "STOP"
+                }],
+                "usageMetadata": {"promptTokenCount": 30, "candidatesTokenCount": 8},
+                "responseId": "resp"
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let call = |id: &str, city: &str| ToolCall {
+        id: id.to_string(),
+        name: "weather".to_string(),
+        arguments: serde_json::json!({ "city": city }),
+    };
+    let messages = Arc::new(RwLock::new(vec![
+        Message::system("You are helpful."),
+        Message::user("Weather in Paris and Rome?"),
+        Message::assistant(None::<String>, Some(vec![call("c1", "Paris"), call("c2", "Rome")])),
+        Message::tool("Sunny", "c1"),
+        Message::tool(r#"{"temp": 21}"#, "c2"),
+    ]));
+    let tool = AvailableTool {
+        name: "weather".to_string(),
+        desc: "Look up the weather".to_string(),
+        input_schema_json: Some(serde_json::json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "WeatherArgs",
+            "type": "object",
+            "properties": {"city": {"type": "string"}},
+            "required": ["city"]
+        })),
+    };
+    let config = CompletionConfig::default()
+        .with_tools(vec![tool])
+        .with_tool_choice(ToolChoice::Required)
+        .with_temperature(0.0);
+    let provider = gemini(&server).with_config(GeminiConfig {
+        top_k: Some(40),
+        ..Default::default()
+    });
+
+    let response = provider.complete(messages, config).await.unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(response.finish_reason, FinishReason::ToolCalls);
+    assert_eq!(response.usage, Some(UsageStats::new(30, 8)));
+    assert_eq!(
+        response.message,
+        Message::assistant(None::<String>, Some(vec![call("resp-0", "Oslo")]))
+    );
+}
+
+#[tokio::test]
+async fn test_gemini_error_response() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/v1beta/models/gemini-2.5-pro:generateContent")
+        .with_status(503)
+        .with_body(
+            serde_json::json!({
+                "error": {
+                    "code": 503,
+                    "message": "The model is overloaded.",
+                    "status": "UNAVAILABLE"
+                }
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let config = CompletionConfig::default().with_model("gemini-2.5-pro");
+    let error = gemini(&server).complete(conversation(), config).await.unwrap_err();
+
+    assert!(error.is_retryable());
+    match error {
+        ProviderError::GeminiError { status, message } => {
+            assert_eq!(status, "UNAVAILABLE");
+            assert_eq!(message, "The model is overloaded.");
+        }
+        other => panic!("Unexpected error: {:?}", other),
+    }
+}
+
+// MARK: Ollama
+
+fn config_with_tools() -> CompletionConfig {
+    CompletionConfig {
+        tools: Some(vec![AvailableTool {
+            name: "search".to_string(),
+            desc: "Search the web".to_string(),
+            input_schema_json: None,
+        }]),
+        ..config()
+    }
+}
+
+fn chat_completion_body(content: &str) -> String {
+    serde_json::json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 0,
+        "model": "test-model",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }]
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_ollama_rejects_tools_for_unsupported_model() {
+    let mut server = mockito::Server::new_async().await;
+    let show = server
+        .mock("POST", "/api/show")
+        .match_body(mockito::Matcher::Json(serde_json::json!({"model": "test-model"})))
+        .with_body(serde_json::json!({"capabilities": ["completion"]}).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+    let chat = server
+        .mock("POST", "/v1/chat/completions")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let provider = OllamaProvider::new(server.url());
+    for _ in 0..2 {
+        match provider.complete(conversation(), config_with_tools()).await {
+            Err(ProviderError::ToolsNotSupported { model }) => assert_eq!(model, "test-model"),
+            other => panic!("Unexpected result: {:?}", other.map(|r| r.message)),
+        }
+    }
+
+    // The capabilities are cached after the first check
+    show.assert_async().await;
+    chat.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_ollama_skips_model_check() {
+    let mut server = mockito::Server::new_async().await;
+    let show = server
+        .mock("POST", "/api/show")
+        .expect(0)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"tools": [{"function": {"name": "search"}}]}),
+        ))
+        .with_header("content-type", "application/json")
+        .with_body(chat_completion_body("Hi"))
+        .create_async()
+        .await;
+
+    let provider = OllamaProvider::new(server.url()).with_model_check(false);
+    let response = provider
+        .complete(conversation(), config_with_tools())
+        .await
+        .unwrap();
+
+    show.assert_async().await;
+    assert_eq!(response.message, Message::assistant(Some("Hi"), None));
+}
+
+// MARK: Groq
+
+#[tokio::test]
+async fn test_groq_accepts_unknown_service_tier() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "tools": [{"function": {"parameters": {"additionalProperties": false}}}]
+        })))
+        .with_header("content-type", "application/json")
+        .with_body(
+            serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "test-model",
+                "service_tier": "on_demand",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hi"},
+                    "finish_reason": "stop"
+                }]
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+
+    let provider = GroqProvider::with_base_url("gsk-test".to_string(), server.url());
+    let response = provider
+        .complete(conversation(), config_with_tools())
+        .await
+        .unwrap();
+
+    assert_eq!(response.message, Message::assistant(Some("Hi"), None));
+}
+
+// MARK: Hugging Face
+
+fn huggingface(server: &mockito::Server) -> HuggingFaceProvider {
+    HuggingFaceProvider::new("hf-key".to_string(), "meta-llama/Llama-3.1-8B".to_string())
+        .with_base_url(server.url())
+        .with_poll_interval(Duration::from_millis(10))
+}
+
+#[tokio::test]
+async fn test_huggingface_complete() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/models/meta-llama/Llama-3.1-8B/v1/chat/completions")
+        .match_header("authorization", "Bearer hf-key")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "messages": [
+                {"role": "system", "content": "You are helpful."},
+                {"role": "user", "content": "Hello"}
+            ]
+        })))
+        .with_body(
+            r#"{"choices": [{"message": {"role": "assistant", "content": "Hi there"}, "finish_reason": "length"}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let response = huggingface(&server)
+        .complete(conversation(), config())
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+    assert_eq!(response.finish_reason, FinishReason::Length);
+    assert_eq!(response.usage, None);
+    match response.message {
+        Message::Assistant {
+            content: Some(ContentTypes::Text(text)),
+            tool_calls: None,
+        } => assert_eq!(text, "Hi there"),
+        other => panic!("Unexpected response: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_huggingface_rejects_images() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/models/meta-llama/Llama-3.1-8B/v1/chat/completions")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let message = Message::user_parts(vec![ContentTypes::image_bytes(vec![1, 2, 3], "image/png")]);
+    match huggingface(&server)
+        .complete(Arc::new(RwLock::new(vec![message])), config())
+        .await
+    {
+        Err(ProviderError::ImagesNotSupported { model }) => {
+            assert_eq!(model, "meta-llama/Llama-3.1-8B")
+        }
+        other => panic!("Unexpected result: {:?}", other.map(|r| r.message)),
+    }
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_huggingface_sends_sampling_parameters() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/models/meta-llama/Llama-3.1-8B/v1/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "temperature": 0.0,
+            "max_tokens": 256,
+            "top_p": 0.5,
+            "stop": ["\n\n"],
+            "seed": 7
+        })))
+        .with_body(r#"{"choices": [{"message": {"role": "assistant", "content": "Hi"}}]}"#)
+        .create_async()
+        .await;
+    let config = CompletionConfig {
+        temperature: Some(0.0),
+        max_tokens: Some(256),
+        top_p: Some(0.5),
+        stop: Some(vec!["\n\n".to_string()]),
+        extra: Some(serde_json::json!({"seed": 7})),
+        ..config()
+    };
+
+    huggingface(&server)
+        .complete(conversation(), config)
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_huggingface_model_loading_is_timeout() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/models/meta-llama/Llama-3.1-8B/v1/chat/completions")
+        .with_status(503)
+        .with_body(r#"{"error": "Model is loading", "estimated_time": 45.0}"#)
+        .create_async()
+        .await;
+
+    let error = huggingface(&server)
+        .complete(conversation(), config())
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, ProviderError::Timeout));
+}
+
+#[tokio::test]
+async fn test_huggingface_rate_limit() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/models/meta-llama/Llama-3.1-8B/v1/chat/completions")
+        .with_status(429)
+        .with_header("x-ratelimit-reset-requests", "30")
+        .create_async()
+        .await;
+
+    let error = huggingface(&server)
+        .complete(conversation(), config())
+        .await
+        .unwrap_err();
+
+    match error {
+        ProviderError::RateLimit { retry_after } => {
+            assert_eq!(retry_after, Some(Duration::from_secs(30)))
+        }
+        other => panic!("Unexpected error: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_huggingface_wait_for_model() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("GET", "/models/meta-llama/Llama-3.1-8B")
+        .with_body(r#"{"loaded": true}"#)
+        .create_async()
+        .await;
+
+    huggingface(&server)
+        .wait_for_model(Duration::from_secs(1))
+        .await
+        .unwrap();
+
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_huggingface_wait_for_model_times_out() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/models/meta-llama/Llama-3.1-8B")
+        .with_body(r#"{"loaded": false}"#)
+        .expect_at_least(2)
+        .create_async()
+        .await;
+
+    let error = huggingface(&server)
+        .wait_for_model(Duration::from_millis(50))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, ProviderError::Timeout));
+}
+
+// MARK: OpenAI Assistants
+
+#[cfg(feature = "assistants")]
+mod assistants {
+    use super::*;
+    use dsrs_core::providers::{AssistantId, OpenAIAssistantProvider};
+    use mockito::Matcher;
+    use serde_json::json;
+
+    fn assistant_provider(server: &mockito::Server) -> OpenAIAssistantProvider {
+        OpenAIAssistantProvider::new("sk-test".to_string(), Some(server.url()))
+            .with_poll_interval(Duration::from_millis(10))
+    }
+
+    fn run_body(status: &str) -> String {
+        json!({
+            "id": "run_1",
+            "object": "thread.run",
+            "created_at": 0,
+            "thread_id": "thread_1",
+            "assistant_id": "asst_1",
+            "status": status,
+            "model": "test-model",
+            "instructions": "You are helpful.",
+            "tools": [],
+            "parallel_tool_calls": false
+        })
+        .to_string()
+    }
+
+    async fn mock_thread(server: &mut mockito::Server) -> Vec<mockito::Mock> {
+        let thread = server
+            .mock("POST", "/threads")
+            .with_body(r#"{"id": "thread_1", "object": "thread", "created_at": 0}"#)
+            .create_async()
+            .await;
+        let message = server
+            .mock("POST", "/threads/thread_1/messages")
+            .match_body(Matcher::PartialJson(json!({"role": "user", "content": "Hello"})))
+            .with_body(
+                json!({
+                    "id": "msg_1",
+                    "object": "thread.message",
+                    "created_at": 0,
+                    "thread_id": "thread_1",
+                    "role": "user",
+                    "content": [{"type": "text", "text": {"value": "Hello", "annotations": []}}]
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        vec![thread, message]
+    }
+
+    #[tokio::test]
+    async fn test_assistant_complete_polls_run() {
+        let mut server = mockito::Server::new_async().await;
+        let assistant = server
+            .mock("POST", "/assistants")
+            .match_body(Matcher::PartialJson(json!({
+                "model": "test-model",
+                "instructions": "You are helpful."
+            })))
+            .with_body(
+                json!({
+                    "id": "asst_1",
+                    "object": "assistant",
+                    "created_at": 0,
+                    "model": "test-model",
+                    "instructions": "You are helpful.",
+                    "tools": []
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let thread = mock_thread(&mut server).await;
+        let create_run = server
+            .mock("POST", "/threads/thread_1/runs")
+            .match_body(Matcher::PartialJson(json!({"assistant_id": "asst_1"})))
+            .with_body(run_body("queued"))
+            .create_async()
+            .await;
+        let poll_run = server
+            .mock("GET", "/threads/thread_1/runs/run_1")
+            .with_body(run_body("completed"))
+            .create_async()
+            .await;
+        let list = server
+            .mock("GET", Matcher::Regex("^/threads/thread_1/messages".to_string()))
+            .with_body(
+                json!({
+                    "object": "list",
+                    "data": [{
+                        "id": "msg_2",
+                        "object": "thread.message",
+                        "created_at": 1,
+                        "thread_id": "thread_1",
+                        "role": "assistant",
+                        "content": [{"type": "text", "text": {"value": "Hi there", "annotations": []}}]
+                    }],
+                    "has_more": false
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let response = assistant_provider(&server)
+            .complete(conversation(), config())
+            .await
+            .unwrap();
+
+        for mock in [assistant, create_run, poll_run, list].iter().chain(&thread) {
+            mock.assert_async().await;
+        }
+        assert_eq!(response.finish_reason, FinishReason::Stop);
+        match response.message {
+            Message::Assistant {
+                content: Some(ContentTypes::Text(text)),
+                tool_calls: None,
+            } => assert_eq!(text, "Hi there"),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_assistant_failed_run_is_error() {
+        let mut server = mockito::Server::new_async().await;
+        let _thread = mock_thread(&mut server).await;
+        server
+            .mock("POST", "/threads/thread_1/runs")
+            .with_body(run_body("failed"))
+            .create_async()
+            .await;
+
+        let provider = assistant_provider(&server);
+        let thread_id = provider.create_thread().await.unwrap();
+        provider.add_message(&thread_id, "Hello").await.unwrap();
+        let error = provider
+            .run(&AssistantId("asst_1".to_string()), &thread_id)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, ProviderError::OpenAIError(_)));
+    }
+}
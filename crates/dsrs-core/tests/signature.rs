@@ -1 +1,283 @@
-use dsrs_core::primatives::
\ No newline at end of file
+use serde::{Deserialize, Serialize};
+
+use dsrs_core::{
+    primatives::{
+        ChatHistory, DynamicSignature, Signature, SignatureSchema, SignatureSpec,
+        SignatureValidationError, ToolCallSet, ToolSet, check_special_fields, compose_signatures,
+    },
+    providers::models::{AvailableTool, Message, ToolCall},
+};
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+struct ChatInputs {
+    /// The user's question
+    question: String,
+    #[signature(history)]
+    history: Option<ChatHistory>,
+    #[signature(tools)]
+    tools: Option<ToolSet>,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize)]
+struct ChatOutputs {
+    /// The answer to the question
+    answer: String,
+    #[signature(tool_calls)]
+    tool_calls: Option<ToolCallSet>,
+}
+
+/// Answer the question, calling tools when needed
+#[derive(dsrs_core::primatives::Signature)]
+#[signature(name = "Chat", inputs = ChatInputs, outputs = ChatOutputs)]
+struct ChatSignature {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+struct PlainInputs {
+    question: String,
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize)]
+struct PlainOutputs {
+    answer: String,
+}
+
+#[derive(dsrs_core::primatives::Signature)]
+#[signature(inputs = PlainInputs, outputs = PlainOutputs, desc = "Plain question answering")]
+struct PlainSignature {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+fn chat_inputs() -> ChatInputs {
+    ChatInputs {
+        question: "What's the weather?".to_string(),
+        history: Some(ChatHistory::new(vec![
+            Message::user("Hi"),
+            Message::assistant(Some("Hello!"), None),
+        ])),
+        tools: Some(ToolSet {
+            tools: vec![AvailableTool {
+                name: "weather".to_string(),
+                desc: "Look up the weather".to_string(),
+                input_schema_json: None,
+            }],
+        }),
+    }
+}
+
+fn property_names(schema: &schemars::Schema) -> Vec<String> {
+    schema
+        .get("properties")
+        .and_then(|properties| properties.as_object())
+        .map(|properties| properties.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_derive_generates_name_desc_and_instructions() {
+    let mut signature = ChatSignature {
+        instructions: "Be brief.".to_string(),
+    };
+
+    assert_eq!(signature.name(), "Chat");
+    assert_eq!(
+        signature.desc(),
+        "Answer the question, calling tools when needed"
+    );
+    assert_eq!(signature.get_instructions(), "Be brief.");
+    signature.set_instructions("Be thorough.".to_string());
+    assert_eq!(signature.get_instructions(), "Be thorough.");
+
+    let plain = PlainSignature {
+        instructions: String::new(),
+    };
+    assert_eq!(plain.name(), "PlainSignature");
+    assert_eq!(plain.desc(), "Plain question answering");
+}
+
+#[test]
+fn test_prompt_schemas_exclude_special_fields() {
+    assert_eq!(
+        property_names(&ChatSignature::prompt_input_schema()),
+        vec!["question"]
+    );
+    assert_eq!(
+        property_names(&ChatSignature::prompt_output_schema()),
+        vec!["answer"]
+    );
+}
+
+#[test]
+fn test_extracts_and_filters_special_input_fields() {
+    let signature = ChatSignature {
+        instructions: String::new(),
+    };
+    let inputs = chat_inputs();
+
+    let history = signature.extract_history(&inputs).unwrap();
+    assert_eq!(history[0], Message::user("Hi"));
+    assert_eq!(history.len(), 2);
+    let tools = signature.extract_tools(&inputs).unwrap();
+    assert_eq!(tools[0].name, "weather");
+
+    let filtered = signature.filter_special_fields(&inputs);
+    assert_eq!(filtered.question, "What's the weather?");
+    assert!(filtered.history.is_none());
+    assert!(filtered.tools.is_none());
+}
+
+#[test]
+fn test_injects_tool_calls_into_outputs() {
+    let signature = ChatSignature {
+        instructions: String::new(),
+    };
+    let mut outputs = ChatOutputs {
+        answer: String::new(),
+        tool_calls: None,
+    };
+    let call = ToolCall {
+        id: "call_1".to_string(),
+        name: "weather".to_string(),
+        arguments: serde_json::json!({"city": "Paris"}),
+    };
+
+    signature
+        .inject_tool_calls(&mut outputs, vec![call.clone()])
+        .unwrap();
+
+    assert_eq!(outputs.tool_calls.unwrap().calls, vec![call]);
+}
+
+#[test]
+fn test_signature_without_special_fields_uses_defaults() {
+    let signature = PlainSignature {
+        instructions: String::new(),
+    };
+    let inputs = PlainInputs {
+        question: "Why?".to_string(),
+    };
+
+    assert!(signature.extract_history(&inputs).is_none());
+    assert!(signature.extract_tools(&inputs).is_none());
+    assert_eq!(signature.filter_special_fields(&inputs).question, "Why?");
+}
+
+#[derive(SignatureSchema, Serialize, Deserialize, Clone)]
+struct ToolInputs {
+    question: String,
+    #[signature(tools)]
+    tools: Option<ToolSet>,
+}
+
+/// Offers tools but has nowhere to put the calls
+#[derive(dsrs_core::primatives::Signature)]
+#[signature(inputs = ToolInputs, outputs = PlainOutputs)]
+struct DroppedCallsSignature {
+    #[signature(instruction)]
+    instructions: String,
+}
+
+#[test]
+fn test_derived_validate_checks_tools_and_tool_calls_match() {
+    let chat = ChatSignature {
+        instructions: String::new(),
+    };
+    let plain = PlainSignature {
+        instructions: String::new(),
+    };
+    let dropped = DroppedCallsSignature {
+        instructions: String::new(),
+    };
+
+    assert!(chat.validate().is_ok());
+    assert!(plain.validate().is_ok());
+    assert_eq!(
+        dropped.validate().unwrap_err(),
+        vec![SignatureValidationError::ToolsWithoutToolCalls]
+    );
+    assert_eq!(
+        check_special_fields::<PlainInputs, ChatOutputs>().unwrap_err(),
+        vec![SignatureValidationError::ToolCallsWithoutTools]
+    );
+}
+
+// Input and output field names in prompts
+fn prompt_fields<S: Signature>(_signature: &S) -> (Vec<String>, Vec<String>) {
+    (
+        property_names(&S::prompt_input_schema()),
+        property_names(&S::prompt_output_schema()),
+    )
+}
+
+#[test]
+fn test_composed_signature_takes_inputs_from_first_and_outputs_from_second() {
+    let chat = ChatSignature {
+        instructions: "Draft an answer.".to_string(),
+    };
+    let plain = PlainSignature {
+        instructions: "Polish the answer.".to_string(),
+    };
+    let composed = compose_signatures(chat, plain, |outputs: ChatOutputs| PlainInputs {
+        question: outputs.answer,
+    });
+
+    assert_eq!(composed.name(), "Chat -> PlainSignature");
+    assert_eq!(composed.get_instructions(), "Draft an answer.\nPolish the answer.");
+    assert_eq!(
+        prompt_fields(&composed),
+        (vec!["question".to_string()], vec!["answer".to_string()])
+    );
+    assert_eq!(composed.extract_history(&chat_inputs()).unwrap().len(), 2);
+    let next = composed.connect(ChatOutputs {
+        answer: "Sunny".to_string(),
+        tool_calls: None,
+    });
+    assert_eq!(next.question, "Sunny");
+}
+
+#[test]
+fn test_signature_spec_round_trips_through_json() {
+    let signature = ChatSignature {
+        instructions: "Answer briefly.".to_string(),
+    };
+    let spec = SignatureSpec::from_signature(&signature);
+
+    assert_eq!(spec.name, "Chat");
+    assert_eq!(spec.description, "Answer the question, calling tools when needed");
+    assert_eq!(spec.instructions, "Answer briefly.");
+    assert_eq!(spec.input_schema["properties"]["question"]["description"], "The user's question");
+    assert!(spec.input_schema["properties"].get("history").is_none());
+    assert_eq!(SignatureSpec::from_json(&spec.to_json()).unwrap(), spec);
+    assert!(SignatureSpec::from_json("{\"name\": \"Chat\"}").is_err());
+
+    let dynamic = DynamicSignature::from(spec.clone());
+    assert_eq!(SignatureSpec::from_signature(&dynamic), spec);
+}
+
+#[test]
+fn test_tool_set_add_and_builder() {
+    #[derive(schemars::JsonSchema)]
+    #[allow(dead_code)]
+    struct WeatherArgs {
+        city: String,
+    }
+
+    let mut tools = ToolSet::new();
+    assert!(tools.is_empty());
+    tools
+        .add::<WeatherArgs>("weather", "Look up the weather")
+        .add_tool(AvailableTool::for_args::<WeatherArgs>("forecast"));
+    assert_eq!(tools.len(), 2);
+    assert_eq!(tools.tools[0].desc, "Look up the weather");
+    let schema = tools.tools[0].input_schema_json.as_ref().unwrap();
+    assert_eq!(schema["properties"]["city"]["type"], "string");
+
+    let built = ToolSet::builder()
+        .tool::<WeatherArgs>("weather", "Look up the weather")
+        .build();
+    assert_eq!(built.len(), 1);
+    assert_eq!(built.tools[0].name, "weather");
+}
@@ -0,0 +1,169 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, DeriveInput, Fields, LitInt, LitStr, parse_macro_input};
+
+mod signature;
+mod tool;
+
+use signature::{SpecialField, expand_signature, expand_signature_fields, special_kind};
+use tool::{ToolArgs, expand_tool};
+
+/// Derive `schemars::JsonSchema` with support for `#[dsrs(...)]` field attributes.
+///
+/// `#[dsrs(field_order = N)]` adds an `"x-field-order": N` extension to the field's
+/// schema, which the adapters use to order fields in prompts. `serde`, `schemars` and
+/// doc attributes are honoured exactly as with `#[derive(JsonSchema)]`.
+///
+/// Fields marked `#[signature(history)]`, `#[signature(tools)]` or `#[signature(tool_calls)]`
+/// are left out of the schema and handled by the generated `SignatureFields` impl instead.
+#[proc_macro_derive(SignatureSchema, attributes(dsrs, signature))]
+pub fn derive_signature_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `Signature` for a struct holding the signature's instructions.
+///
+/// `#[signature(inputs = Type, outputs = Type)]` names the input and output types, which
+/// must derive `SignatureSchema`; `name = "..."` and `desc = "..."` default to the struct
+/// name and its doc comment. The `String` field marked `#[signature(instruction)]` stores
+/// the instructions. Special fields of the inputs and outputs are extracted, filtered and
+/// injected through their `SignatureFields` impls.
+#[proc_macro_derive(Signature, attributes(signature))]
+pub fn derive_signature(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_signature(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Generate an `AvailableTool` for an async function taking a single argument.
+///
+/// `#[tool(description = "...")]` on `async fn search(args: SearchArgs)` keeps the
+/// function and adds `search_tool()`, whose input schema comes from `SearchArgs` (which
+/// must implement `JsonSchema` and `DeserializeOwned`). `name = "..."` defaults to the
+/// function name, and the description to the function's doc comment, then the argument
+/// type's. Register it with `FunctionRegistry::register_tool(search_tool(), search)`.
+#[proc_macro_attribute]
+pub fn tool(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut tool_args = ToolArgs::default();
+    let parser = syn::meta::parser(|meta| tool_args.parse(meta));
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(input as syn::ItemFn);
+    expand_tool(tool_args, function)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "SignatureSchema only supports structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "SignatureSchema only supports structs with named fields",
+            ));
+        }
+    };
+
+    // The schema is generated by a mirror struct carrying the translated attributes
+    let mirror = format_ident!("__DsrsSchema{}", ident);
+    let name = LitStr::new(&ident.to_string(), Span::call_site());
+    let container_attrs = input.attrs.iter().filter(|attr| is_schema_attr(attr));
+
+    let mut mirror_fields = Vec::with_capacity(fields.len());
+    let mut specials = Vec::new();
+    for field in fields {
+        let field_ident = &field.ident;
+        let ty = &field.ty;
+        let attrs = field.attrs.iter().filter(|attr| is_schema_attr(attr));
+        let order = field_order(&field.attrs)?.map(|order| {
+            quote! { #[schemars(extend("x-field-order" = #order))] }
+        });
+        // Special fields never appear in prompts
+        let kind = special_kind(&field.attrs)?;
+        let skip = kind.map(|_| quote! { #[schemars(skip)] });
+        if let (Some(kind), Some(ident)) = (kind, field_ident) {
+            specials.push(SpecialField { kind, ident, ty });
+        }
+        mirror_fields.push(quote! {
+            #(#attrs)*
+            #order
+            #skip
+            #field_ident: #ty
+        });
+    }
+    let signature_fields = expand_signature_fields(&input, &specials)?;
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = &input.generics;
+    let mirror_where_clause = where_clause;
+    let mut where_clause = where_clause.cloned().unwrap_or_else(|| syn::parse_quote!(where));
+    where_clause
+        .predicates
+        .push(syn::parse_quote!(#mirror #ty_generics: ::schemars::JsonSchema));
+
+    Ok(quote! {
+        #[derive(::schemars::JsonSchema)]
+        #[schemars(rename = #name)]
+        #(#container_attrs)*
+        #[allow(dead_code, non_camel_case_types)]
+        #[doc(hidden)]
+        struct #mirror #generics #mirror_where_clause {
+            #(#mirror_fields,)*
+        }
+
+        impl #impl_generics ::schemars::JsonSchema for #ident #ty_generics #where_clause {
+            fn inline_schema() -> bool {
+                <#mirror #ty_generics as ::schemars::JsonSchema>::inline_schema()
+            }
+
+            fn schema_name() -> ::std::borrow::Cow<'static, str> {
+                <#mirror #ty_generics as ::schemars::JsonSchema>::schema_name()
+            }
+
+            fn schema_id() -> ::std::borrow::Cow<'static, str> {
+                <#mirror #ty_generics as ::schemars::JsonSchema>::schema_id()
+            }
+
+            fn json_schema(generator: &mut ::schemars::SchemaGenerator) -> ::schemars::Schema {
+                <#mirror #ty_generics as ::schemars::JsonSchema>::json_schema(generator)
+            }
+        }
+
+        #signature_fields
+    })
+}
+
+// Attributes that influence the generated schema and are copied onto the mirror struct
+fn is_schema_attr(attr: &Attribute) -> bool {
+    let path = attr.path();
+    path.is_ident("doc") || path.is_ident("serde") || path.is_ident("schemars")
+}
+
+fn field_order(attrs: &[Attribute]) -> syn::Result<Option<LitInt>> {
+    let mut order = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("dsrs")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("field_order") {
+                order = Some(meta.value()?.parse::<LitInt>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported dsrs attribute, expected `field_order`"))
+            }
+        })?;
+    }
+    Ok(order)
+}
@@ -0,0 +1,338 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    Attribute, Data, DeriveInput, Expr, ExprLit, Fields, GenericArgument, Ident, Lit, LitStr, Meta,
+    PathArguments, Type,
+};
+
+/// A field handled outside the prompt, marked with `#[signature(...)]`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Special {
+    History,
+    Tools,
+    ToolCalls,
+}
+
+pub(crate) struct SpecialField<'a> {
+    pub(crate) kind: Special,
+    pub(crate) ident: &'a Ident,
+    pub(crate) ty: &'a Type,
+}
+
+pub(crate) fn special_kind(attrs: &[Attribute]) -> syn::Result<Option<Special>> {
+    let mut kind = None;
+    for attr in attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("signature"))
+    {
+        attr.parse_nested_meta(|meta| {
+            let parsed = if meta.path.is_ident("history") {
+                Special::History
+            } else if meta.path.is_ident("tools") {
+                Special::Tools
+            } else if meta.path.is_ident("tool_calls") {
+                Special::ToolCalls
+            } else {
+                return Err(meta.error(
+                    "unsupported signature attribute, expected `history`, `tools` or `tool_calls`",
+                ));
+            };
+            if kind.replace(parsed).is_some() {
+                return Err(meta.error("a field can only be one kind of special field"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(kind)
+}
+
+// `T` for `Option<T>`, so optional special fields can be left empty
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
+/// `SignatureFields` impl for a `SignatureSchema` struct, from its special fields
+pub(crate) fn expand_signature_fields(
+    input: &DeriveInput,
+    specials: &[SpecialField],
+) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let find = |kind: Special| -> syn::Result<Option<&SpecialField>> {
+        let mut found = specials.iter().filter(|field| field.kind == kind);
+        let first = found.next();
+        if let Some(duplicate) = found.next() {
+            return Err(syn::Error::new_spanned(
+                duplicate.ident,
+                "only one field can be marked with each kind of signature attribute",
+            ));
+        }
+        Ok(first)
+    };
+
+    let history = find(Special::History)?.map(|field| {
+        let name = field.ident;
+        let value = match option_inner(field.ty) {
+            Some(_) => {
+                quote! { self.#name.as_ref().map(::dsrs_core::primatives::History::to_messages) }
+            }
+            None => quote! { Some(::dsrs_core::primatives::History::to_messages(&self.#name)) },
+        };
+        quote! {
+            fn extract_history(&self) -> Option<Vec<::dsrs_core::providers::models::Message>> {
+                #value
+            }
+        }
+    });
+
+    let tools = find(Special::Tools)?.map(|field| {
+        let name = field.ident;
+        let value = match option_inner(field.ty) {
+            Some(_) => quote! { self.#name.as_ref().map(::dsrs_core::primatives::Tools::to_available_tools) },
+            None => quote! { Some(::dsrs_core::primatives::Tools::to_available_tools(&self.#name)) },
+        };
+        quote! {
+            fn extract_tools(&self) -> Option<Vec<::dsrs_core::providers::models::AvailableTool>> {
+                #value
+            }
+        }
+    });
+
+    let has_tools = find(Special::Tools)?
+        .is_some()
+        .then(|| quote! { const HAS_TOOLS: bool = true; });
+    let has_tool_calls = find(Special::ToolCalls)?
+        .is_some()
+        .then(|| quote! { const HAS_TOOL_CALLS: bool = true; });
+
+    let tool_calls = find(Special::ToolCalls)?.map(|field| {
+        let name = field.ident;
+        let value = match option_inner(field.ty) {
+            Some(inner) => quote! {
+                Some(<#inner as ::dsrs_core::primatives::ToolCalls>::from_tool_calls(calls)?)
+            },
+            None => {
+                let ty = field.ty;
+                quote! { <#ty as ::dsrs_core::primatives::ToolCalls>::from_tool_calls(calls)? }
+            }
+        };
+        quote! {
+            fn inject_tool_calls(
+                &mut self,
+                calls: Vec<::dsrs_core::providers::models::ToolCall>,
+            ) -> ::anyhow::Result<()> {
+                self.#name = #value;
+                Ok(())
+            }
+        }
+    });
+
+    // Optional input special fields are emptied; required ones are already kept out of the
+    // schema. Outputs get no override, so they need not be `Clone`
+    let cleared: Vec<&Ident> = specials
+        .iter()
+        .filter(|field| field.kind != Special::ToolCalls && option_inner(field.ty).is_some())
+        .map(|field| field.ident)
+        .collect();
+    let filter = (!cleared.is_empty()).then(|| {
+        quote! {
+            fn filter_special_fields(&self) -> Self
+            where
+                Self: Clone,
+            {
+                Self {
+                    #(#cleared: None,)*
+                    ..self.clone()
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::dsrs_core::primatives::SignatureFields for #ident #ty_generics #where_clause {
+            #has_tools
+            #has_tool_calls
+            #history
+            #tools
+            #tool_calls
+            #filter
+        }
+    })
+}
+
+/// `Signature` impl for a struct holding a signature's instructions
+pub(crate) fn expand_signature(input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "Signature only supports structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "Signature only supports structs with named fields",
+            ));
+        }
+    };
+
+    let mut name = None;
+    let mut desc = None;
+    let mut inputs = None;
+    let mut outputs = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("signature"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("desc") {
+                desc = Some(meta.value()?.parse::<LitStr>()?);
+            } else if meta.path.is_ident("inputs") {
+                inputs = Some(meta.value()?.parse::<Type>()?);
+            } else if meta.path.is_ident("outputs") {
+                outputs = Some(meta.value()?.parse::<Type>()?);
+            } else {
+                return Err(meta.error(
+                    "unsupported signature attribute, expected `name`, `desc`, `inputs` or `outputs`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+    let missing = |what: &str| {
+        syn::Error::new_spanned(
+            ident,
+            format!("Signature requires `#[signature({} = Type)]`", what),
+        )
+    };
+    let inputs = inputs.ok_or_else(|| missing("inputs"))?;
+    let outputs = outputs.ok_or_else(|| missing("outputs"))?;
+    let name = name.unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+    let desc = desc.unwrap_or_else(|| LitStr::new(&doc_string(&input.attrs), ident.span()));
+
+    let mut instruction = None;
+    for field in fields {
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("signature"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if !meta.path.is_ident("instruction") {
+                    return Err(
+                        meta.error("unsupported signature attribute, expected `instruction`")
+                    );
+                }
+                if instruction.replace(field.ident.as_ref()).is_some() {
+                    return Err(meta.error("only one field can be marked `instruction`"));
+                }
+                Ok(())
+            })?;
+        }
+    }
+    let instruction = instruction.flatten().ok_or_else(|| {
+        syn::Error::new_spanned(
+            ident,
+            "Signature requires a `String` field marked `#[signature(instruction)]`",
+        )
+    })?;
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let fields_trait = quote! { ::dsrs_core::primatives::SignatureFields };
+
+    Ok(quote! {
+        impl #impl_generics ::dsrs_core::primatives::Signature for #ident #ty_generics #where_clause {
+            type Inputs = #inputs;
+            type Outputs = #outputs;
+
+            fn set_instructions(&mut self, instructions: String) {
+                self.#instruction = instructions;
+            }
+
+            fn get_instructions(&self) -> &str {
+                &self.#instruction
+            }
+
+            fn name(&self) -> &str {
+                #name
+            }
+
+            fn desc(&self) -> &str {
+                #desc
+            }
+
+            fn extract_history(
+                &self,
+                inputs: &Self::Inputs,
+            ) -> Option<Vec<::dsrs_core::providers::models::Message>> {
+                <#inputs as #fields_trait>::extract_history(inputs)
+            }
+
+            fn extract_tools(
+                &self,
+                inputs: &Self::Inputs,
+            ) -> Option<Vec<::dsrs_core::providers::models::AvailableTool>> {
+                <#inputs as #fields_trait>::extract_tools(inputs)
+            }
+
+            fn inject_tool_calls(
+                &self,
+                outputs: &mut Self::Outputs,
+                calls: Vec<::dsrs_core::providers::models::ToolCall>,
+            ) -> ::anyhow::Result<()> {
+                <#outputs as #fields_trait>::inject_tool_calls(outputs, calls)
+            }
+
+            fn filter_special_fields(&self, inputs: &Self::Inputs) -> Self::Inputs {
+                <#inputs as #fields_trait>::filter_special_fields(inputs)
+            }
+
+            fn validate(
+                &self,
+            ) -> ::std::result::Result<(), Vec<::dsrs_core::primatives::SignatureValidationError>> {
+                ::dsrs_core::primatives::check_special_fields::<#inputs, #outputs>()
+            }
+        }
+    })
+}
+
+// Doc comment lines joined with spaces, used as the description when `desc` is not given
+pub(crate) fn doc_string(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(meta) => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(doc), ..
+                }) => Some(doc.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
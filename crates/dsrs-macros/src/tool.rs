@@ -0,0 +1,77 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::meta::ParseNestedMeta;
+use syn::{FnArg, ItemFn, LitStr};
+
+use crate::signature::doc_string;
+
+#[derive(Default)]
+pub(crate) struct ToolArgs {
+    name: Option<LitStr>,
+    description: Option<LitStr>,
+}
+
+impl ToolArgs {
+    pub(crate) fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("description") {
+            self.description = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error("unsupported tool attribute, expected `name` or `description`"));
+        }
+        Ok(())
+    }
+}
+
+/// The function unchanged, plus `<name>_tool()` returning its `AvailableTool`
+pub(crate) fn expand_tool(args: ToolArgs, function: ItemFn) -> syn::Result<TokenStream> {
+    let ident = &function.sig.ident;
+    if function.sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            function.sig.fn_token,
+            "#[tool] only supports async functions",
+        ));
+    }
+    if !function.sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &function.sig.generics,
+            "#[tool] does not support generic functions",
+        ));
+    }
+    let mut inputs = function.sig.inputs.iter();
+    match (inputs.next(), inputs.next()) {
+        (Some(FnArg::Typed(_)), None) => {}
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &function.sig.inputs,
+                "#[tool] functions take exactly one argument, whose type gives the input schema",
+            ));
+        }
+    }
+
+    let vis = &function.vis;
+    let tool_ident = format_ident!("{}_tool", ident);
+    let name = args
+        .name
+        .unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+    // Without a description or doc comment, the argument type's doc comment is used
+    let description = args.description.or_else(|| {
+        let doc = doc_string(&function.attrs);
+        (!doc.is_empty()).then(|| LitStr::new(&doc, ident.span()))
+    });
+    let with_desc = description.map(|description| quote! { .with_desc(#description) });
+    let doc = LitStr::new(
+        &format!("`AvailableTool` describing [`{}`]", ident),
+        ident.span(),
+    );
+
+    Ok(quote! {
+        #function
+
+        #[doc = #doc]
+        #vis fn #tool_ident() -> ::dsrs_core::providers::models::AvailableTool {
+            ::dsrs_core::providers::models::AvailableTool::from_fn(#name, &#ident) #with_desc
+        }
+    })
+}